@@ -0,0 +1,65 @@
+//! Prints an alert whenever a watched market moves by more than a threshold.
+//!
+//! ```sh
+//! cargo run --example alert_bot [marketId]
+//! cargo run --example alert_bot -- marketId --live
+//! ```
+//!
+//! Against the mock, it stops after a few rounds; live, it runs until interrupted.
+
+mod common;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures_util::{StreamExt, TryStreamExt};
+use manifold_markets::error::Result;
+use manifold_markets::types::*;
+
+/// Change in probability, since the last alert, that triggers another.
+const THRESHOLD: f64 = 0.05;
+const MOCK_ROUNDS: usize = 3;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let client = common::client().await?;
+    let market_ids = vec![common::argument().unwrap_or_else(|| "fixtureBinaryId".to_owned())];
+
+    let (interval, rounds) = if common::is_live() {
+        (Duration::from_secs(60), usize::MAX)
+    } else {
+        (Duration::from_millis(100), MOCK_ROUNDS)
+    };
+
+    let mut last_alerted = HashMap::<String, f64>::new();
+    let mut markets = Box::pin(
+        client
+            .watch_markets(&market_ids, interval)
+            .take(rounds.saturating_mul(market_ids.len())),
+    );
+
+    while let Some(market) = markets.try_next().await? {
+        let Some(prob) = market.probability() else {
+            continue;
+        };
+
+        match last_alerted.get(market.id()) {
+            Some(last) if (prob - last).abs() < THRESHOLD => {}
+            last => {
+                let change = last.map_or(String::new(), |last| {
+                    format!(" ({:+.1} points)", (prob - last) * 100.0)
+                });
+                println!(
+                    "[{}] {} is at {:.1}%{change}",
+                    market.fetched_at.format("%H:%M:%S"),
+                    market.question(),
+                    prob * 100.0,
+                );
+
+                last_alerted.insert(market.id().to_owned(), prob);
+            }
+        }
+    }
+
+    Ok(())
+}
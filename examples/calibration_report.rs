@@ -0,0 +1,87 @@
+//! How well the last probabilities of resolved binary markets predicted their
+//! resolutions: the Brier score, and the YES rate per probability bucket.
+//!
+//! ```sh
+//! cargo run --example calibration_report [limit]
+//! cargo run --example calibration_report -- 1000 --live
+//! ```
+//!
+//! Looks at the latest `limit` markets (500 by default). A market's last
+//! probability is what it was left at when trading stopped, which is only
+//! an approximation of the forecast before the outcome was known.
+
+mod common;
+
+use futures_util::{StreamExt, TryStreamExt};
+use manifold_markets::error::Result;
+use manifold_markets::types::*;
+
+const BUCKETS: usize = 10;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let client = common::client().await?;
+    let limit = common::argument()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(500);
+
+    let markets: Vec<LiteMarket> = client.stream_markets().take(limit).try_collect().await?;
+
+    let forecasts = markets
+        .iter()
+        .filter(|m| m.outcome_type() == OutcomeType::Binary && m.is_resolved())
+        .filter_map(|m| {
+            let outcome = match m.resolution()? {
+                "YES" => 1.0,
+                "NO" => 0.0,
+                _ => return None,
+            };
+            Some((m.probability()?, outcome))
+        })
+        .collect::<Vec<_>>();
+
+    if forecasts.is_empty() {
+        println!("No resolved binary markets among the latest {limit}");
+        return Ok(());
+    }
+
+    let brier = forecasts
+        .iter()
+        .map(|(prob, outcome)| (prob - outcome).powi(2))
+        .sum::<f64>()
+        / forecasts.len() as f64;
+
+    println!(
+        "{} resolved binary markets, Brier score {brier:.3}",
+        forecasts.len()
+    );
+    println!("{:>9}  {:>7}  {:>7}", "bucket", "markets", "YES");
+
+    for bucket in 0..BUCKETS {
+        let (low, high) = (
+            bucket as f64 / BUCKETS as f64,
+            (bucket + 1) as f64 / BUCKETS as f64,
+        );
+        let in_bucket = forecasts
+            .iter()
+            .filter(|(prob, _)| low <= *prob && (*prob < high || bucket == BUCKETS - 1))
+            .collect::<Vec<_>>();
+
+        if in_bucket.is_empty() {
+            continue;
+        }
+
+        let yes_rate =
+            in_bucket.iter().map(|(_, outcome)| outcome).sum::<f64>() / in_bucket.len() as f64;
+
+        println!(
+            "{:>3.0}-{:>3.0}%  {:>7}  {:>6.0}%",
+            low * 100.0,
+            high * 100.0,
+            in_bucket.len(),
+            yes_rate * 100.0
+        );
+    }
+
+    Ok(())
+}
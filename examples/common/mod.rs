@@ -0,0 +1,79 @@
+//! What the examples share: a client for a local mock of the API, or for
+//! the real one with `--live`.
+//!
+//! The mock is [`fixtures::MockServer`], answering with the library's
+//! fixtures, so the examples run anywhere without an API key or network access.
+
+#![allow(dead_code)]
+
+use manifold_markets::error::Result;
+use manifold_markets::fixtures::{self, MockResponse, MockServer};
+use manifold_markets::ManifoldClient;
+use serde_json::{json, Value};
+
+/// Whether `--live` was passed, i.e. the example talks to the real API.
+pub fn is_live() -> bool {
+    std::env::args().any(|arg| arg == "--live")
+}
+
+/// Whether `flag` was passed.
+pub fn has_flag(flag: &str) -> bool {
+    std::env::args().any(|arg| arg == flag)
+}
+
+/// The first argument that isn't a flag, if any.
+pub fn argument() -> Option<String> {
+    std::env::args().skip(1).find(|arg| !arg.starts_with("--"))
+}
+
+/// A client configured from `MANIFOLD_*` variables (and `.env`) with
+/// `--live`, otherwise one talking to a mock of the API on localhost.
+pub async fn client() -> Result<ManifoldClient> {
+    if is_live() {
+        dotenv::dotenv().ok();
+        return ManifoldClient::from_env();
+    }
+
+    let server = MockServer::start(|request| {
+        if request.method == "GET"
+            && request.path == "/markets"
+            && !request.query.contains("before=")
+        {
+            return Some(MockResponse::json(json!([
+                fixtures::binary_market().0,
+                fixtures::multi_market().0,
+                fixtures::dpm_market().0,
+                resolved_market("resolvedA", 0.92, "YES"),
+                resolved_market("resolvedB", 0.85, "YES"),
+                resolved_market("resolvedC", 0.15, "NO"),
+                resolved_market("resolvedD", 0.35, "YES"),
+                resolved_market("resolvedE", 0.6, "NO"),
+            ])));
+        }
+
+        let target = match request.query.as_str() {
+            "" => request.path.clone(),
+            query => format!("{}?{query}", request.path),
+        };
+
+        fixtures::api_response(&request.method, &target).map(MockResponse::json)
+    })
+    .await?;
+
+    let client = server.client().build()?;
+
+    // Serve until the example exits.
+    std::mem::forget(server);
+
+    Ok(client)
+}
+
+/// A binary market that resolved while at `probability`.
+fn resolved_market(id: &str, probability: f64, resolution: &str) -> Value {
+    let mut market = fixtures::binary_market().0;
+    market["id"] = json!(id);
+    market["probability"] = json!(probability);
+    market["isResolved"] = json!(true);
+    market["resolution"] = json!(resolution);
+    market
+}
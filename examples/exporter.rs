@@ -0,0 +1,35 @@
+//! Writes a user's balance history as CSV, with realized profit per market.
+//!
+//! ```sh
+//! cargo run --example exporter [username] > ledger.csv
+//! cargo run --example exporter -- username --live > ledger.csv
+//! ```
+//!
+//! Without a username, exports the authorized user's history.
+
+mod common;
+
+use manifold_markets::error::Result;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let client = common::client().await?;
+
+    let user = match common::argument() {
+        Some(username) => client.get_user(&username).await?,
+        None => client.get_me().await?,
+    };
+
+    let ledger = client.ledger(user.id()).await?;
+    ledger.write_csv(std::io::stdout())?;
+
+    let mut pnl = ledger.realized_pnl().into_iter().collect::<Vec<_>>();
+    pnl.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    eprintln!("{} rows for {}", ledger.rows.len(), user.username());
+    for (market_id, pnl) in pnl.iter().take(5) {
+        eprintln!("  {market_id}: {pnl:+.2}");
+    }
+
+    Ok(())
+}
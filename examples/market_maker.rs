@@ -0,0 +1,76 @@
+//! Quotes both sides of a binary market with limit orders around its probability.
+//!
+//! ```sh
+//! cargo run --example market_maker [marketId]
+//! cargo run --example market_maker -- marketId --live [--trade]
+//! ```
+//!
+//! Orders are dry runs unless `--trade` is passed, and only go out if the
+//! market data is at most 10 seconds old.
+
+mod common;
+
+use std::time::Duration;
+
+use manifold_markets::error::Result;
+use manifold_markets::trading::BetRequest;
+use manifold_markets::types::*;
+
+/// Distance of each quote from the current probability.
+const HALF_SPREAD: f64 = 0.03;
+const QUOTE_AMOUNT: u64 = 10;
+const MAX_DATA_AGE: Duration = Duration::from_secs(10);
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let client = common::client().await?;
+    let market_id = common::argument().unwrap_or_else(|| "fixtureBinaryId".to_owned());
+
+    let market = client.get_market_fetched(&market_id).await?;
+    let Some(prob) = market.probability() else {
+        println!("{} is not a binary market", market.question());
+        return Ok(());
+    };
+
+    let book = client.get_order_book(&market_id).await?;
+    println!(
+        "{} at {:.1}%, {} YES and {} NO orders resting",
+        market.question(),
+        prob * 100.0,
+        book.yes.len(),
+        book.no.len()
+    );
+
+    let quotes = [
+        (Outcome::Yes, (prob - HALF_SPREAD).max(0.01)),
+        (Outcome::No, (prob + HALF_SPREAD).min(0.99)),
+    ];
+
+    for (outcome, limit) in quotes {
+        let order = BetRequest::new(QUOTE_AMOUNT, &market_id, outcome.clone())
+            .limit_prob((limit * 100.0).round() / 100.0)
+            .dry_run(!common::has_flag("--trade"))
+            .build()?;
+
+        let bet = client
+            .post_bet_if_fresh(&order, &market, MAX_DATA_AGE)
+            .await?;
+
+        if order.dry_run {
+            println!(
+                "{outcome} limit at {:?}: simulated, M${} would fill right away",
+                order.limit_prob,
+                bet.amount()
+            );
+        } else {
+            println!(
+                "{outcome} limit at {:?}: order {}, M${} filled right away",
+                order.limit_prob,
+                bet.id(),
+                bet.amount()
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! The `try_*` accessors must never panic, whatever JSON they're given.
+//!
+//! Run with `cargo +nightly fuzz run accessors` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use manifold_markets::answers::AnswerProbabilities;
+use manifold_markets::migrate::migrate_market;
+use manifold_markets::types::*;
+use serde_json::Value;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(value) = serde_json::from_slice::<Value>(data) else {
+        return;
+    };
+
+    let market = FullMarket(value.clone());
+    let _ = market.try_id();
+    let _ = market.try_question();
+    let _ = market.try_creator_id();
+    let _ = market.try_outcome_type();
+    let _ = market.try_pool();
+    let _ = market.try_close_time();
+    let _ = market.probability();
+    let _ = market.resolution();
+    let _ = market.group_slugs();
+    let _ = market.should_answers_sum_to_one();
+    let _ = AnswerProbabilities::from_market(&market);
+
+    for answer in market.answers() {
+        let _ = answer.try_id();
+        let _ = answer.try_text();
+        let _ = answer.probability();
+    }
+
+    let bet = Bet(value.clone());
+    let _ = bet.try_id();
+    let _ = bet.try_contract_id();
+    let _ = bet.try_user_id();
+    let _ = bet.try_outcome();
+    let _ = bet.try_created_time();
+    let _ = bet.try_amount();
+    let _ = bet.try_shares();
+    let _ = bet.try_prob_before();
+    let _ = bet.try_prob_after();
+    let _ = bet.fees();
+
+    let user = User(value.clone());
+    let _ = user.try_id();
+    let _ = user.try_name();
+    let _ = user.try_username();
+    let _ = user.try_balance();
+    let _ = user.try_created_time();
+
+    let group = Group(value.clone());
+    let _ = group.try_id();
+    let _ = group.try_slug();
+    let _ = group.try_name();
+
+    let txn = Txn(value.clone());
+    let _ = txn.try_id();
+    let _ = txn.try_created_time();
+    let _ = txn.try_from_id();
+    let _ = txn.try_to_id();
+    let _ = txn.try_amount();
+    let _ = txn.try_category();
+
+    let _ = migrate_market(value);
+});
@@ -0,0 +1,652 @@
+//! Analyzing comments, user activity and portfolio scenarios
+//!
+//! Comments often carry the news a market resolves on ("it was just
+//! announced", "the source says..."). A [`CommentAnalyzer`] looks at each new
+//! comment picked up by [`ManifoldClient::watch_comments`](crate::ManifoldClient::watch_comments)
+//! and decides whether it's worth a look. [`KeywordAnalyzer`] is a simple
+//! rules-based one; LLM summarizers and the like can implement the trait.
+//!
+//! [`activity`] sums up how a user trades and creates markets over time,
+//! for community stat pages.
+//!
+//! [`scenario`] values a portfolio as if some markets resolved a given way,
+//! e.g. "candidate A wins" across every market about the election, for
+//! checking the risk of a position before the event.
+//!
+//! [`depth_report`] estimates how much mana it takes to move a market, from
+//! its pool and limit orders, to tell liquid markets from thin ones.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+
+use chrono::{DateTime, Duration, NaiveDate};
+use futures_util::{try_join, TryStreamExt};
+use serde_json::Value;
+
+use crate::error::{ManifoldError, Result};
+use crate::math::CpmmPool;
+use crate::orders::OrderBook;
+use crate::text::fold;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// Words that suggest a comment is about how the market will resolve.
+pub const RESOLUTION_KEYWORDS: [&str; 10] = [
+    "resolve",
+    "resolves",
+    "resolved",
+    "resolution",
+    "announced",
+    "confirmed",
+    "official",
+    "source",
+    "cancelled",
+    "postponed",
+];
+
+/// A comment an analyzer flagged, yielded by [`ManifoldClient::watch_comments`](crate::ManifoldClient::watch_comments).
+#[derive(Debug, Clone)]
+pub struct CommentFlag {
+    pub market_id: String,
+    pub comment: Value,
+    /// Why the comment was flagged, or a summary of it.
+    pub note: String,
+}
+
+pub trait CommentAnalyzer {
+    /// A note on `comment` if it's worth flagging, `None` otherwise.
+    fn analyze(&self, comment: &Value) -> impl Future<Output = Result<Option<String>>> + Send;
+}
+
+/// The plain text of a comment, from its rich-text `content` (or legacy `text`).
+pub fn comment_text(comment: &Value) -> String {
+    fn collect(node: &Value, out: &mut Vec<String>) {
+        if let Some(text) = node["text"].as_str() {
+            out.push(text.to_owned());
+        }
+
+        if let Some(children) = node["content"].as_array() {
+            for child in children {
+                collect(child, out);
+            }
+        }
+    }
+
+    let mut parts = vec![];
+
+    match &comment["content"] {
+        Value::Null => collect(comment, &mut parts),
+        content => collect(content, &mut parts),
+    }
+
+    parts.join(" ")
+}
+
+/// Flags comments containing any of its keywords, ignoring case and punctuation.
+#[derive(Debug, Clone)]
+pub struct KeywordAnalyzer {
+    keywords: Vec<String>,
+}
+
+impl KeywordAnalyzer {
+    pub fn new<S: AsRef<str>>(keywords: &[S]) -> KeywordAnalyzer {
+        KeywordAnalyzer {
+            keywords: keywords
+                .iter()
+                .map(|k| fold(k.as_ref()))
+                .filter(|k| !k.is_empty())
+                .collect(),
+        }
+    }
+
+    /// The keywords found in `text`, in the order they were given.
+    pub fn matches(&self, text: &str) -> Vec<&str> {
+        let text = format!(" {} ", fold(text));
+
+        self.keywords
+            .iter()
+            .filter(|k| text.contains(&format!(" {k} ")))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+impl Default for KeywordAnalyzer {
+    /// Flags [`RESOLUTION_KEYWORDS`].
+    fn default() -> Self {
+        KeywordAnalyzer::new(&RESOLUTION_KEYWORDS)
+    }
+}
+
+impl CommentAnalyzer for KeywordAnalyzer {
+    async fn analyze(&self, comment: &Value) -> Result<Option<String>> {
+        let matches = self.matches(&comment_text(comment));
+
+        if matches.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(format!("mentions {}", matches.join(", "))))
+    }
+}
+
+/// How active a user is, see [`activity`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserActivity {
+    /// Days (in UTC) with at least one bet.
+    pub active_days: usize,
+    pub first_active: Option<NaiveDate>,
+    pub last_active: Option<NaiveDate>,
+    /// Consecutive active days up to today, or up to yesterday if there's
+    /// been no bet yet today.
+    pub current_streak: usize,
+    pub longest_streak: usize,
+    /// Mana traded per active day, counting sales as positive.
+    pub average_daily_volume: f64,
+    pub markets_created: usize,
+    /// The average time between two created markets.
+    pub creation_interval: Option<Duration>,
+}
+
+fn day(millis: i64) -> Option<NaiveDate> {
+    DateTime::from_timestamp_millis(millis).map(|t| t.date_naive())
+}
+
+/// Computes a user's [`UserActivity`] from their `bets` and market creation
+/// transactions (`CREATE_CONTRACT_ANTE`), as of `today`.
+pub fn activity(bets: &[Bet], creations: &[Txn], today: NaiveDate) -> UserActivity {
+    let mut volume_per_day = BTreeMap::<NaiveDate, f64>::new();
+
+    for bet in bets {
+        if let Some(day) = day(bet.created_time()) {
+            *volume_per_day.entry(day).or_default() += bet.amount().abs();
+        }
+    }
+
+    let days = volume_per_day.keys().copied().collect::<Vec<_>>();
+
+    let mut longest_streak = 0;
+    let mut streak = 0;
+    for (i, day) in days.iter().enumerate() {
+        if i > 0 && days[i - 1].succ_opt() == Some(*day) {
+            streak += 1;
+        } else {
+            streak = 1;
+        }
+        longest_streak = longest_streak.max(streak);
+    }
+
+    let current_streak = match days.last() {
+        Some(&last) if last == today || last.succ_opt() == Some(today) => streak,
+        _ => 0,
+    };
+
+    let mut created = creations
+        .iter()
+        .filter_map(|txn| DateTime::from_timestamp_millis(txn.created_time()))
+        .collect::<Vec<_>>();
+    created.sort();
+
+    let creation_interval = match (created.first(), created.last()) {
+        (Some(first), Some(last)) if created.len() > 1 => {
+            Some((*last - *first) / (created.len() as i32 - 1))
+        }
+        _ => None,
+    };
+
+    UserActivity {
+        active_days: days.len(),
+        first_active: days.first().copied(),
+        last_active: days.last().copied(),
+        current_streak,
+        longest_streak,
+        average_daily_volume: if days.is_empty() {
+            0.0
+        } else {
+            volume_per_day.values().sum::<f64>() / days.len() as f64
+        },
+        markets_created: created.len(),
+        creation_interval,
+    }
+}
+
+/// How a market resolves in a [`scenario`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScenarioResolution {
+    Yes,
+    No,
+    /// This answer of a multiple choice market wins, the others lose.
+    Answer(String),
+    /// `MKT` at this probability.
+    Prob(f64),
+    /// `CANCEL`: investments are refunded.
+    Cancel,
+}
+
+impl ScenarioResolution {
+    /// The resolution of a market that goes the other way, for [`MarketGroup::opposed`].
+    fn opposite(&self) -> ScenarioResolution {
+        match self {
+            ScenarioResolution::Yes => ScenarioResolution::No,
+            ScenarioResolution::No => ScenarioResolution::Yes,
+            ScenarioResolution::Prob(p) => ScenarioResolution::Prob(1.0 - p),
+            other => other.clone(),
+        }
+    }
+
+    /// What one share of `outcome` is worth, in a market or on its `answer_id`.
+    fn share_value(&self, outcome: &str, answer_id: Option<&str>) -> f64 {
+        let yes = match (self, answer_id) {
+            (ScenarioResolution::Yes, _) => 1.0,
+            (ScenarioResolution::No, _) => 0.0,
+            (ScenarioResolution::Prob(p), _) => *p,
+            (ScenarioResolution::Answer(winner), Some(answer_id)) => {
+                f64::from(u8::from(winner == answer_id))
+            }
+            // Shares of a legacy multiple choice market are keyed by answer.
+            (ScenarioResolution::Answer(winner), None) => {
+                return f64::from(u8::from(winner == outcome))
+            }
+            (ScenarioResolution::Cancel, _) => return 0.0,
+        };
+
+        match outcome {
+            "YES" => yes,
+            "NO" => 1.0 - yes,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Markets the user considers correlated: when the group's event happens,
+/// the `aligned` ones resolve one way and the `opposed` ones the other.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MarketGroup {
+    pub name: String,
+    pub aligned: Vec<String>,
+    /// Resolve YES when the aligned ones resolve NO, and the other way around.
+    pub opposed: Vec<String>,
+}
+
+/// One assumption of a [`scenario`]; later ones override earlier ones for the same market.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Assumption {
+    Market {
+        market_id: String,
+        resolution: ScenarioResolution,
+    },
+    /// Every market of the group resolves according to `resolution` (of the aligned ones).
+    Group {
+        group: MarketGroup,
+        resolution: ScenarioResolution,
+    },
+}
+
+/// What a portfolio is worth in a [`scenario`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScenarioValue {
+    pub value: f64,
+    pub invested: f64,
+    /// Value per market.
+    pub markets: BTreeMap<String, f64>,
+    /// Markets no assumption covers, valued at their current payout.
+    pub unassumed: BTreeSet<String>,
+}
+
+impl ScenarioValue {
+    pub fn profit(&self) -> f64 {
+        self.value - self.invested
+    }
+}
+
+/// Values the positions of a `portfolio` as if their markets resolved as `assumptions` say.
+///
+/// Positions in markets without an assumption keep their current payout.
+pub fn scenario(portfolio: &[Position], assumptions: &[Assumption]) -> ScenarioValue {
+    let mut resolutions = BTreeMap::new();
+
+    for assumption in assumptions {
+        match assumption {
+            Assumption::Market {
+                market_id,
+                resolution,
+            } => {
+                resolutions.insert(market_id.as_str(), resolution.clone());
+            }
+            Assumption::Group { group, resolution } => {
+                for market_id in &group.aligned {
+                    resolutions.insert(market_id, resolution.clone());
+                }
+                for market_id in &group.opposed {
+                    resolutions.insert(market_id, resolution.opposite());
+                }
+            }
+        }
+    }
+
+    let mut result = ScenarioValue::default();
+
+    for position in portfolio {
+        let market_id = position.contract_id();
+
+        let value = match resolutions.get(market_id) {
+            Some(ScenarioResolution::Cancel) => position.invested(),
+            Some(resolution) => position
+                .total_shares()
+                .iter()
+                .map(|(outcome, shares)| {
+                    shares * resolution.share_value(outcome, position.answer_id())
+                })
+                .sum(),
+            None => {
+                result.unassumed.insert(market_id.to_owned());
+                position.payout()
+            }
+        };
+
+        result.value += value;
+        result.invested += position.invested();
+        *result.markets.entry(market_id.to_owned()).or_default() += value;
+    }
+
+    result
+}
+
+/// Moves [`depth_report`] prices, in percentage points.
+pub const DEPTH_POINTS: [u8; 3] = [1, 5, 10];
+
+/// How much mana moves a market by some points, see [`depth_report`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthLevel {
+    pub points: u8,
+    /// Mana of YES to raise the probability by `points`; `None` if that's beyond 99.9%.
+    pub up: Option<f64>,
+    /// Mana of NO to lower it by `points`; `None` if that's below 0.1%.
+    pub down: Option<f64>,
+}
+
+impl DepthLevel {
+    /// The cheaper direction: how easily the market is pushed around by `points`.
+    pub fn min(&self) -> Option<f64> {
+        match (self.up, self.down) {
+            (Some(up), Some(down)) => Some(up.min(down)),
+            (up, down) => up.or(down),
+        }
+    }
+}
+
+/// The liquidity of a binary market, see [`depth_report`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthReport {
+    pub probability: f64,
+    pub pool: CpmmPool,
+    /// Mana resting in limit orders on either side.
+    pub orders_yes: f64,
+    pub orders_no: f64,
+    /// One per [`DEPTH_POINTS`], in the same order.
+    pub levels: Vec<DepthLevel>,
+}
+
+impl DepthReport {
+    pub fn level(&self, points: u8) -> Option<&DepthLevel> {
+        self.levels.iter().find(|level| level.points == points)
+    }
+}
+
+/// Mana of `outcome` that moves `pool` to `target`, eating through the
+/// opposite limit orders it passes on the way. Ignores fees.
+fn cost_to_move(pool: &CpmmPool, book: &OrderBook, outcome: &Outcome, target: f64) -> Result<f64> {
+    // A limit order at `target` too big to run out before getting there.
+    let order = PlaceBetArgs {
+        limit_prob: Some(target),
+        ..PlaceBetArgs::new(u64::MAX, "", outcome.clone())
+    };
+
+    Ok(book.simulate(pool, &order, "")?.total())
+}
+
+/// How much mana it takes to move a binary market by each of [`DEPTH_POINTS`]
+/// in either direction, from its pool, `p` and the limit orders in `book`.
+///
+/// Screeners use it to avoid markets where a small bet swings the price.
+/// See [`ManifoldClient::get_depth_report`] to fetch both.
+pub fn depth_report(market: &impl Market, book: &OrderBook) -> Result<DepthReport> {
+    let pool = CpmmPool::from_market(market).ok_or_else(|| {
+        ManifoldError::Validation(format!("market {} has no CPMM pool", market.id()))
+    })?;
+    let probability = pool.probability();
+
+    let within = |prob: f64| (0.001..=0.999).contains(&prob).then_some(prob);
+
+    let levels = DEPTH_POINTS
+        .iter()
+        .map(|&points| {
+            let delta = f64::from(points) / 100.0;
+
+            Ok(DepthLevel {
+                points,
+                up: within(probability + delta)
+                    .map(|target| cost_to_move(&pool, book, &Outcome::Yes, target))
+                    .transpose()?,
+                down: within(probability - delta)
+                    .map(|target| cost_to_move(&pool, book, &Outcome::No, target))
+                    .transpose()?,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(DepthReport {
+        probability,
+        pool,
+        orders_yes: book.yes.iter().map(|order| order.remaining).sum(),
+        orders_no: book.no.iter().map(|order| order.remaining).sum(),
+        levels,
+    })
+}
+
+impl ManifoldClient {
+    /// [`depth_report`] of a binary market, fetching it and its order book.
+    pub async fn get_depth_report(&self, market_id: &str) -> Result<DepthReport> {
+        let (market, book) = try_join!(self.get_market(market_id), self.get_order_book(market_id))?;
+
+        depth_report(&market, &book)
+    }
+
+    /// [`activity`] of `user_id`, from their whole betting and market creation history.
+    pub async fn user_activity(&self, user_id: &str) -> Result<UserActivity> {
+        let bets: Vec<Bet> = self
+            .stream_bets(Some(user_id), None, None, None)
+            .try_collect()
+            .await?;
+        let creations: Vec<Txn> = self
+            .stream_txns(Some(user_id), None, Some("CREATE_CONTRACT_ANTE"))
+            .try_collect()
+            .await?;
+
+        Ok(activity(&bets, &creations, self.now().date_naive()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::fixtures;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn flags_keywords() -> Result<()> {
+        let analyzer = KeywordAnalyzer::default();
+
+        assert_eq!(comment_text(&fixtures::comment().0), "Fixture comment");
+        assert_eq!(analyzer.analyze(&fixtures::comment().0).await?, None);
+
+        let comment = json!({
+            "content": {
+                "type": "doc",
+                "content": [
+                    {"type": "paragraph", "content": [{"type": "text", "text": "It's OFFICIAL:"}]},
+                    {"type": "paragraph", "content": [{"type": "text", "text": "the event was postponed."}]},
+                ],
+            },
+        });
+        assert_eq!(
+            analyzer.analyze(&comment).await?.as_deref(),
+            Some("mentions official, postponed")
+        );
+
+        let analyzer = KeywordAnalyzer::new(&["press release"]);
+        assert_eq!(
+            analyzer.matches("Read the Press-Release!"),
+            ["press release"]
+        );
+        assert!(analyzer.matches("press releases").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn computes_activity() {
+        let day = |d: u32| {
+            NaiveDate::from_ymd_opt(2024, 1, d)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp_millis()
+        };
+        let bet = |d: u32, amount: f64| Bet(json!({"createdTime": day(d), "amount": amount}));
+        let txn = |d: u32| Txn(json!({"createdTime": day(d)}));
+
+        let bets = [
+            bet(1, 10.0),
+            bet(2, 10.0),
+            bet(3, -10.0),
+            bet(3, 30.0),
+            bet(10, 20.0),
+            bet(11, 20.0),
+        ];
+        let today = NaiveDate::from_ymd_opt(2024, 1, 12).unwrap();
+
+        let activity = activity(&bets, &[txn(1), txn(5), txn(11)], today);
+        assert_eq!(activity.active_days, 5);
+        assert_eq!(activity.longest_streak, 3);
+        assert_eq!(activity.current_streak, 2);
+        assert_eq!(activity.average_daily_volume, 20.0);
+        assert_eq!(activity.markets_created, 3);
+        assert_eq!(activity.creation_interval, Some(Duration::days(5)));
+
+        let later = NaiveDate::from_ymd_opt(2024, 1, 13).unwrap();
+        assert_eq!(super::activity(&bets, &[], later).current_streak, 0);
+    }
+
+    #[test]
+    fn values_scenarios() {
+        let position = |market_id: &str, answer_id: Option<&str>, shares: Value| {
+            Position(json!({
+                "contractId": market_id,
+                "answerId": answer_id,
+                "totalShares": shares,
+                "invested": 10.0,
+                "payout": 15.0,
+            }))
+        };
+        let portfolio = [
+            position("a-wins", None, json!({"YES": 30.0})),
+            position("b-wins", None, json!({"NO": 20.0, "YES": 5.0})),
+            position("winner", Some("a"), json!({"YES": 40.0})),
+            position("legacy", None, json!({"a": 10.0, "b": 50.0})),
+            position("other", None, json!({"YES": 100.0})),
+        ];
+
+        let election = MarketGroup {
+            name: "election".to_owned(),
+            aligned: vec!["a-wins".to_owned()],
+            opposed: vec!["b-wins".to_owned()],
+        };
+        let a_wins = [
+            Assumption::Group {
+                group: election.clone(),
+                resolution: ScenarioResolution::Yes,
+            },
+            Assumption::Market {
+                market_id: "winner".to_owned(),
+                resolution: ScenarioResolution::Answer("a".to_owned()),
+            },
+            Assumption::Market {
+                market_id: "legacy".to_owned(),
+                resolution: ScenarioResolution::Answer("a".to_owned()),
+            },
+        ];
+
+        let value = scenario(&portfolio, &a_wins);
+        assert_eq!(value.markets["a-wins"], 30.0);
+        assert_eq!(value.markets["b-wins"], 20.0);
+        assert_eq!(value.markets["winner"], 40.0);
+        assert_eq!(value.markets["legacy"], 10.0);
+        assert_eq!(value.unassumed, BTreeSet::from(["other".to_owned()]));
+        assert_eq!(value.value, 30.0 + 20.0 + 40.0 + 10.0 + 15.0);
+        assert_eq!(value.profit(), value.value - 50.0);
+
+        let b_wins = [
+            Assumption::Group {
+                group: election,
+                resolution: ScenarioResolution::No,
+            },
+            Assumption::Market {
+                market_id: "winner".to_owned(),
+                resolution: ScenarioResolution::Answer("b".to_owned()),
+            },
+            Assumption::Market {
+                market_id: "other".to_owned(),
+                resolution: ScenarioResolution::Cancel,
+            },
+        ];
+
+        let value = scenario(&portfolio, &b_wins);
+        assert_eq!(value.markets["a-wins"], 0.0);
+        assert_eq!(value.markets["b-wins"], 5.0);
+        assert_eq!(value.markets["winner"], 0.0);
+        assert_eq!(value.markets["other"], 10.0);
+    }
+
+    #[test]
+    fn reports_depth() -> Result<()> {
+        let market = fixtures::cpmm_market("m", CpmmPool::new(100.0, 100.0, 0.5));
+        let empty = depth_report(&market, &OrderBook::default())?;
+
+        assert_eq!(empty.probability, 0.5);
+        assert_eq!(empty.levels.len(), DEPTH_POINTS.len());
+
+        let five = empty.level(5).unwrap();
+        let (up, down) = (five.up.unwrap(), five.down.unwrap());
+        assert!((up - down).abs() < 1e-6);
+        assert!(empty.level(1).unwrap().min().unwrap() < up);
+        assert!(up < empty.level(10).unwrap().min().unwrap());
+
+        // A NO order at 53% has to be filled before YES gets to 55%, not to 51%.
+        let book = OrderBook {
+            no: vec![crate::orders::LimitOrder {
+                bet_id: "b".to_owned(),
+                user_id: "u".to_owned(),
+                yes: false,
+                limit_prob: 0.53,
+                remaining: 47.0,
+            }],
+            ..Default::default()
+        };
+        let report = depth_report(&market, &book)?;
+
+        assert_eq!(report.orders_no, 47.0);
+        assert_eq!(report.level(1), empty.level(1));
+        assert!((report.level(5).unwrap().up.unwrap() - up - 53.0).abs() < 1e-6);
+        assert_eq!(report.level(5).unwrap().down, five.down);
+
+        let edge = fixtures::cpmm_market("edge", CpmmPool::new(1.0, 1000.0, 0.5));
+        assert!(depth_report(&edge, &book)?.level(10).unwrap().up.is_none());
+        assert!(depth_report(&fixtures::multi_market(), &book).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+//! Spotting unusual betting activity
+//!
+//! An [`AnomalyDetector`] is fed bets in the order they were placed and
+//! reports sudden large bets, large bets from new accounts and users rapidly
+//! flipping a market back and forth, according to its [`AnomalyThresholds`].
+//! [`ManifoldClient::watch_bet_anomalies`] runs one over the new bets on a
+//! set of markets, for moderator bots.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use futures_util::Stream;
+
+use crate::error::Result;
+use crate::polling::{poll_stream, Polled, Seen};
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// When [`AnomalyDetector`] reports a bet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnomalyThresholds {
+    /// Bets (or sales) of at least this much mana are [`Anomaly::LargeBet`]s.
+    pub large_bet: f64,
+    /// Bets moving the probability by at least this much are [`Anomaly::LargeBet`]s too.
+    pub large_move: f64,
+    /// Bets of at least this much mana from accounts younger than
+    /// `new_account_age` are [`Anomaly::NewAccountWhale`]s.
+    pub whale_bet: f64,
+    pub new_account_age: Duration,
+    /// This many changes of direction by one user on one market within
+    /// `flip_window` are [`Anomaly::RapidFlipping`].
+    pub flips: usize,
+    pub flip_window: Duration,
+}
+
+impl Default for AnomalyThresholds {
+    fn default() -> Self {
+        AnomalyThresholds {
+            large_bet: 1000.0,
+            large_move: 0.2,
+            whale_bet: 250.0,
+            new_account_age: Duration::from_secs(7 * 24 * 60 * 60),
+            flips: 4,
+            flip_window: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Anomaly {
+    LargeBet {
+        bet: Bet,
+    },
+    NewAccountWhale {
+        bet: Bet,
+        /// How old the account was when the bet was placed.
+        account_age: Duration,
+    },
+    RapidFlipping {
+        market_id: String,
+        user_id: String,
+        /// The bets that changed direction, oldest first.
+        bets: Vec<Bet>,
+    },
+}
+
+/// Checks bets against [`AnomalyThresholds`], remembering recent bets to spot flipping.
+#[derive(Debug, Clone, Default)]
+pub struct AnomalyDetector {
+    pub thresholds: AnomalyThresholds,
+    /// Per market, answer and user, the bets that changed direction within the flip window.
+    flips: HashMap<(String, Option<String>, String), VecDeque<Bet>>,
+}
+
+/// Whether a bet moved the probability up, down or not at all.
+fn direction(bet: &Bet) -> Option<bool> {
+    let change = bet.prob_after() - bet.prob_before();
+
+    (change != 0.0).then_some(change > 0.0)
+}
+
+impl AnomalyDetector {
+    pub fn new(thresholds: AnomalyThresholds) -> AnomalyDetector {
+        AnomalyDetector {
+            thresholds,
+            flips: HashMap::new(),
+        }
+    }
+
+    /// Whether [`check`](Self::check) needs the bettor's profile for `bet`.
+    pub fn needs_bettor(&self, bet: &Bet) -> bool {
+        bet.amount().abs() >= self.thresholds.whale_bet
+    }
+
+    /// Reports the anomalies of `bet`, which must be newer than the bets checked before.
+    ///
+    /// New-account whales are only reported when `bettor` is given.
+    pub fn check(&mut self, bet: &Bet, bettor: Option<&User>) -> Vec<Anomaly> {
+        let thresholds = &self.thresholds;
+        let mut anomalies = vec![];
+
+        if bet.amount().abs() >= thresholds.large_bet
+            || (bet.prob_after() - bet.prob_before()).abs() >= thresholds.large_move
+        {
+            anomalies.push(Anomaly::LargeBet { bet: bet.clone() });
+        }
+
+        if let Some(bettor) = bettor.filter(|_| self.needs_bettor(bet)) {
+            let age = bet.created_time() - bettor.created_time();
+            let account_age = Duration::from_millis(age.max(0) as u64);
+
+            if account_age < thresholds.new_account_age {
+                anomalies.push(Anomaly::NewAccountWhale {
+                    bet: bet.clone(),
+                    account_age,
+                });
+            }
+        }
+
+        let Some(up) = direction(bet) else {
+            return anomalies;
+        };
+
+        let key = (
+            bet.contract_id().to_owned(),
+            bet.answer_id().map(str::to_owned),
+            bet.user_id().to_owned(),
+        );
+        let window = self.thresholds.flip_window.as_millis() as i64;
+        let flips = self.flips.entry(key).or_default();
+
+        while flips
+            .front()
+            .is_some_and(|b| bet.created_time() - b.created_time() > window)
+        {
+            flips.pop_front();
+        }
+
+        // Only bets that go the other way than the previous one count.
+        if flips.back().and_then(direction) == Some(up) {
+            return anomalies;
+        }
+        flips.push_back(bet.clone());
+
+        // The first bet sets the direction; every one after it is a flip.
+        if flips.len() > self.thresholds.flips {
+            anomalies.push(Anomaly::RapidFlipping {
+                market_id: bet.contract_id().to_owned(),
+                user_id: bet.user_id().to_owned(),
+                bets: flips.drain(..).collect(),
+            });
+        }
+
+        anomalies
+    }
+}
+
+impl ManifoldClient {
+    /// Polls the bets on `market_ids` every `interval`, yielding the
+    /// anomalies in the new ones, see [`AnomalyDetector`].
+    ///
+    /// Bets already placed when a market is first fetched aren't reported, and only
+    /// the latest 1000 bets of each market are looked at per round. Bettors'
+    /// profiles are fetched once, when needed. Never ends; a failed fetch
+    /// yields an error and polling continues.
+    pub fn watch_bet_anomalies<'a>(
+        &'a self,
+        market_ids: &'a [String],
+        interval: Duration,
+        thresholds: AnomalyThresholds,
+    ) -> impl Stream<Item = Result<Anomaly>> + 'a {
+        struct State {
+            detector: AnomalyDetector,
+            seen: Seen,
+            bettors: HashMap<String, User>,
+        }
+
+        let state = State {
+            detector: AnomalyDetector::new(thresholds),
+            seen: Seen::default(),
+            bettors: HashMap::new(),
+        };
+
+        poll_stream(self, interval, state, move |mut state| async move {
+            let mut items = vec![];
+
+            for market_id in market_ids {
+                let bets = match self
+                    .get_bets_with(&BetsQuery::new().contract_id(market_id).limit(1000))
+                    .await
+                {
+                    Ok(bets) => bets,
+                    Err(e) => {
+                        items.push(Err(e));
+                        continue;
+                    }
+                };
+
+                let bets = bets.into_iter().rev().collect();
+
+                let new = match state.seen.diff(market_id, bets, |bet: &Bet| bet.id()) {
+                    Polled::Baseline(bets) => {
+                        // Only remembered, for the flips of later bets.
+                        for bet in &bets {
+                            state.detector.check(bet, None);
+                        }
+                        continue;
+                    }
+                    Polled::New(bets) => bets,
+                };
+
+                for bet in new {
+                    if state.detector.needs_bettor(&bet)
+                        && !state.bettors.contains_key(bet.user_id())
+                    {
+                        match self.get_user_by_id(bet.user_id()).await {
+                            Ok(user) => {
+                                state.bettors.insert(bet.user_id().to_owned(), user);
+                            }
+                            Err(e) => items.push(Err(e)),
+                        }
+                    }
+
+                    let bettor = state.bettors.get(bet.user_id());
+                    let anomalies = state.detector.check(&bet, bettor);
+
+                    items.extend(anomalies.into_iter().map(Ok));
+                }
+            }
+
+            Some((state, items))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn bet(id: &str, minute: i64, amount: f64, prob_before: f64, prob_after: f64) -> Bet {
+        Bet(json!({
+            "id": id,
+            "contractId": "market",
+            "userId": "user",
+            "outcome": if prob_after > prob_before { "YES" } else { "NO" },
+            "createdTime": minute * 60_000,
+            "amount": amount,
+            "shares": amount,
+            "probBefore": prob_before,
+            "probAfter": prob_after,
+        }))
+    }
+
+    #[test]
+    fn detects_anomalies() {
+        let mut detector = AnomalyDetector::default();
+
+        let large = detector.check(&bet("a", 0, 5000.0, 0.5, 0.6), None);
+        assert!(matches!(large[..], [Anomaly::LargeBet { .. }]));
+
+        let user = User(json!({"id": "user", "createdTime": 0}));
+        let whale = detector.check(&bet("b", 1000, 300.0, 0.6, 0.61), Some(&user));
+        assert!(matches!(
+            whale[..],
+            [Anomaly::NewAccountWhale { account_age, .. }] if account_age == Duration::from_secs(1000 * 60)
+        ));
+
+        // Up (b), then down, up, down, up within the hour.
+        assert!(detector
+            .check(&bet("c", 1001, 10.0, 0.61, 0.6), None)
+            .is_empty());
+        assert!(detector
+            .check(&bet("d", 1002, 10.0, 0.6, 0.61), None)
+            .is_empty());
+        assert!(detector
+            .check(&bet("e", 1003, 10.0, 0.61, 0.6), None)
+            .is_empty());
+        let flips = detector.check(&bet("f", 1004, 10.0, 0.6, 0.61), None);
+        assert!(matches!(
+            &flips[..],
+            [Anomaly::RapidFlipping { bets, .. }] if bets.len() == 5
+        ));
+
+        // Spread out over hours, flipping is fine.
+        for i in 0..10 {
+            let (before, after) = if i % 2 == 0 { (0.6, 0.5) } else { (0.5, 0.6) };
+            assert!(detector
+                .check(&bet("g", 2000 + i * 120, 10.0, before, after), None)
+                .is_empty());
+        }
+    }
+}
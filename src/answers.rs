@@ -0,0 +1,387 @@
+//! Keeping a market's answers in sync with an external list of options
+//!
+//! Bots that mirror option lists (sports fixtures, candidates, releases)
+//! into multiple choice markets only ever need to *add* the missing answers;
+//! [`ManifoldClient::reconcile_answers`] does that while respecting an
+//! answer cap and pacing its requests.
+//!
+//! Markets with independent answers are resolved answer by answer, see
+//! [`ManifoldClient::post_resolve_answers`].
+//!
+//! Answer probabilities mean different things depending on
+//! [`Market::should_answers_sum_to_one`]; [`AnswerProbabilities`] keeps track of which.
+//!
+//! Manifold's limits on answers ([`MAX_ANSWERS`], [`MAX_ANSWER_LENGTH`] and
+//! the market's [`AddAnswersMode`]) are checked locally by [`check_new_answer`]
+//! and [`ManifoldClient::add_answer`], which explain what's wrong instead of
+//! getting a bare 400.
+//!
+//! Free-text input from humans (e.g. "Man United" in a chat command) is
+//! matched to existing answers with [`match_answer`].
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use futures_util::try_join;
+
+use serde_json::Value;
+
+use crate::error::{ManifoldError, Result};
+use crate::text::{normalize, similarity};
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// Options for [`ManifoldClient::reconcile_answers`].
+#[derive(Debug, Clone)]
+pub struct ReconcileOptions {
+    /// Never let the market have more answers than this. [`MAX_ANSWERS`] applies in any case.
+    pub max_answers: Option<usize>,
+    /// Pause between consecutive answer additions.
+    pub delay: Duration,
+}
+
+impl Default for ReconcileOptions {
+    fn default() -> Self {
+        ReconcileOptions {
+            max_answers: None,
+            delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// What [`ManifoldClient::reconcile_answers`] did.
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    /// Desired answers that already existed, with their answer IDs.
+    pub present: Vec<(String, String)>,
+    /// Desired answers that were added, with their new answer IDs.
+    pub added: Vec<(String, String)>,
+    /// Desired answers that weren't added because of `max_answers`.
+    pub skipped: Vec<String>,
+    /// Desired answers whose addition failed.
+    pub failed: Vec<(String, ManifoldError)>,
+    /// Existing answers that aren't in the desired list. These are never removed.
+    pub extra: Vec<Answer>,
+}
+
+impl ReconcileReport {
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty() && self.failed.is_empty()
+    }
+}
+
+/// The probabilities of a multi market's answers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnswerProbabilities {
+    /// Whether the answers are mutually exclusive. If not, each answer is
+    /// an independent yes/no question and the probabilities can sum to anything.
+    pub sums_to_one: bool,
+    /// `(answer id, probability)`, in the market's order.
+    pub answers: Vec<(String, f64)>,
+}
+
+impl AnswerProbabilities {
+    /// The answers' probabilities, rescaled to sum to exactly one if the market's answers should.
+    ///
+    /// Fetched probabilities of exclusive answers are rounded and updated
+    /// separately, so they rarely sum to one exactly. Answers without a
+    /// probability are left out.
+    pub fn from_market(market: &impl Market) -> AnswerProbabilities {
+        let sums_to_one = market.should_answers_sum_to_one();
+
+        let mut answers = market
+            .answers()
+            .iter()
+            .filter_map(|a| Some((a.try_id().ok()?.to_owned(), a.probability()?)))
+            .collect::<Vec<_>>();
+
+        let total = answers.iter().map(|(_, p)| p).sum::<f64>();
+
+        if sums_to_one && total > 0.0 {
+            for (_, p) in &mut answers {
+                *p /= total;
+            }
+        }
+
+        AnswerProbabilities {
+            sums_to_one,
+            answers,
+        }
+    }
+
+    pub fn get(&self, answer_id: &str) -> Option<f64> {
+        self.answers
+            .iter()
+            .find(|(id, _)| id == answer_id)
+            .map(|(_, p)| *p)
+    }
+
+    pub fn total(&self) -> f64 {
+        self.answers.iter().map(|(_, p)| p).sum()
+    }
+}
+
+/// Checks the text of a new answer: not empty, at most [`MAX_ANSWER_LENGTH`] characters.
+pub fn check_answer_text(text: &str) -> Result<()> {
+    if text.trim().is_empty() {
+        return Err(ManifoldError::Validation(
+            "answers can't be empty".to_owned(),
+        ));
+    }
+
+    let length = text.chars().count();
+    if length > MAX_ANSWER_LENGTH {
+        return Err(ManifoldError::Validation(format!(
+            "answer is {length} characters long, more than the {MAX_ANSWER_LENGTH} allowed"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Whether `user_id` may add an answer with `text` to `market`.
+///
+/// Fails with [`ManifoldError::Validation`] explaining why not: the market
+/// doesn't take new answers, or only from its creator, is full, or already
+/// has the answer (compared after [normalization](crate::text::normalize)).
+pub fn check_new_answer(market: &impl Market, user_id: &str, text: &str) -> Result<()> {
+    let invalid = |message: String| Err(ManifoldError::Validation(message));
+
+    check_answer_text(text)?;
+
+    match market.add_answers_mode() {
+        AddAnswersMode::Anyone => {}
+        AddAnswersMode::OnlyCreator if market.creator_id() == user_id => {}
+        AddAnswersMode::OnlyCreator => {
+            return invalid("only the market's creator can add answers".to_owned())
+        }
+        AddAnswersMode::Disabled => {
+            return invalid("the market doesn't take new answers".to_owned())
+        }
+    }
+
+    let answers = market.answers();
+
+    if answers.len() >= MAX_ANSWERS {
+        return invalid(format!(
+            "the market already has the maximum of {MAX_ANSWERS} answers"
+        ));
+    }
+
+    if let Some(existing) = answers
+        .iter()
+        .find(|a| normalize(a.text()) == normalize(text))
+    {
+        return invalid(format!(
+            "the market already has the answer {:?}",
+            existing.text()
+        ));
+    }
+
+    Ok(())
+}
+
+/// An answer that matches some free-text input, see [`match_answer`].
+#[derive(Debug, Clone)]
+pub struct AnswerMatch {
+    pub answer_id: String,
+    pub text: String,
+    /// Similarity between the input and the answer text, in `[0, 1]`.
+    pub confidence: f64,
+}
+
+/// All answers ranked by how well they match `input`, best first.
+pub fn rank_answers(answers: &[Answer], input: &str) -> Vec<AnswerMatch> {
+    let mut matches = answers
+        .iter()
+        .map(|a| AnswerMatch {
+            answer_id: a.id().to_owned(),
+            text: a.text().to_owned(),
+            confidence: similarity(input, a.text()),
+        })
+        .collect::<Vec<_>>();
+
+    matches.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    matches
+}
+
+/// The answer that best matches `input`, if any matches with at least `min_confidence`.
+///
+/// See [`similarity`] for how texts are compared. Around `0.8` is a good
+/// threshold for chat input; callers should ask for confirmation below that.
+pub fn match_answer(answers: &[Answer], input: &str, min_confidence: f64) -> Option<AnswerMatch> {
+    rank_answers(answers, input)
+        .into_iter()
+        .next()
+        .filter(|m| m.confidence >= min_confidence)
+}
+
+/// The id of the answer a `POST /market/[marketId]/answer` response added.
+fn new_answer_id(response: Value) -> Result<String> {
+    response["newAnswerId"]
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            ManifoldError::SchemaError("newAnswerId is not a string".to_owned(), Some(response))
+        })
+}
+
+impl ManifoldClient {
+    /// Adds an answer to a market, after checking that it would be accepted
+    /// (see [`check_new_answer`]). Returns the new answer's ID.
+    pub async fn add_answer(&self, market_id: &str, text: &str) -> Result<String> {
+        let (market, me) = try_join!(self.get_market(market_id), self.get_me())?;
+
+        check_new_answer(&market, me.id(), text)?;
+
+        new_answer_id(self.post_answer(market_id, text).await?)
+    }
+
+    /// Adds the answers from `desired` that the market doesn't have yet.
+    ///
+    /// Answers are compared after [normalization](crate::text::normalize), so
+    /// differences in case and whitespace don't create duplicates. A failure to
+    /// add one answer doesn't stop the others; see [`ReconcileReport::failed`].
+    pub async fn reconcile_answers(
+        &self,
+        market_id: &str,
+        desired: &[&str],
+        options: &ReconcileOptions,
+    ) -> Result<ReconcileReport> {
+        let market = self.get_market(market_id).await?;
+        let answers = market.answers();
+
+        let mut report = ReconcileReport::default();
+        let mut seen = HashSet::new();
+        let mut count = answers.len();
+
+        for text in desired {
+            let key = normalize(text);
+
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+
+            if let Some(answer) = answers.iter().find(|a| normalize(a.text()) == key) {
+                report
+                    .present
+                    .push((text.to_string(), answer.id().to_owned()));
+                continue;
+            }
+
+            if count >= options.max_answers.unwrap_or(MAX_ANSWERS).min(MAX_ANSWERS) {
+                report.skipped.push(text.to_string());
+                continue;
+            }
+
+            if !report.added.is_empty() || !report.failed.is_empty() {
+                self.clock().sleep(options.delay).await;
+            }
+
+            match self.post_answer(market_id, text).await {
+                Ok(response) => {
+                    // The answer was added even if its id can't be read.
+                    count += 1;
+
+                    match new_answer_id(response) {
+                        Ok(id) => report.added.push((text.to_string(), id)),
+                        Err(e) => report.failed.push((text.to_string(), e)),
+                    }
+                }
+                Err(e) => report.failed.push((text.to_string(), e)),
+            }
+        }
+
+        report.extra = answers
+            .into_iter()
+            .filter(|a| !seen.contains(&normalize(a.text())))
+            .collect();
+
+        Ok(report)
+    }
+
+    /// Resolves several answers of a market whose answers don't sum to one.
+    ///
+    /// Answers are resolved one after another; a failure doesn't stop the rest.
+    /// Returns every answer ID with the result of its resolution.
+    pub async fn post_resolve_answers(
+        &self,
+        market_id: &str,
+        resolutions: &[(&str, AnswerResolution)],
+    ) -> Vec<(String, Result<Value>)> {
+        let mut results = vec![];
+
+        for (answer_id, outcome) in resolutions {
+            let result = self
+                .post_resolve_answer(market_id, answer_id, *outcome)
+                .await;
+
+            results.push((answer_id.to_string(), result));
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn normalizes_exclusive_answers_only() {
+        let answers = json!([
+            {"id": "a", "text": "A", "probability": 0.5},
+            {"id": "b", "text": "B", "probability": 0.3},
+            {"id": "c", "text": "C"},
+        ]);
+
+        let exclusive = FullMarket(json!({"answers": answers, "shouldAnswersSumToOne": true}));
+        let probs = AnswerProbabilities::from_market(&exclusive);
+        assert!(probs.sums_to_one);
+        assert_eq!(probs.answers.len(), 2);
+        assert!((probs.total() - 1.0).abs() < 1e-9);
+        assert_eq!(probs.get("a"), Some(0.625));
+
+        let independent = FullMarket(json!({"answers": answers, "shouldAnswersSumToOne": false}));
+        let probs = AnswerProbabilities::from_market(&independent);
+        assert!(!probs.sums_to_one);
+        assert_eq!(probs.get("a"), Some(0.5));
+        assert_eq!(probs.get("c"), None);
+    }
+
+    #[test]
+    fn requires_the_new_answer_id() {
+        assert_eq!(new_answer_id(json!({"newAnswerId": "a1"})).unwrap(), "a1");
+        assert!(matches!(
+            new_answer_id(json!({})),
+            Err(ManifoldError::SchemaError(_, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn checks_new_answers_locally() {
+        let mut market = crate::fixtures::multi_market();
+        let creator = market.creator_id().to_owned();
+
+        assert!(check_new_answer(&market, "someone", "Purple").is_err());
+
+        market.0["addAnswersMode"] = json!("ONLY_CREATOR");
+        check_new_answer(&market, &creator, "Purple").unwrap();
+        assert!(check_new_answer(&market, "someone", "Purple").is_err());
+        assert!(check_new_answer(&market, &creator, " green ").is_err());
+        assert!(check_new_answer(&market, &creator, &"x".repeat(MAX_ANSWER_LENGTH + 1)).is_err());
+
+        market.0["addAnswersMode"] = json!("ANYONE");
+        check_new_answer(&market, "someone", "Purple").unwrap();
+
+        market.0["answers"] = json!((0..MAX_ANSWERS)
+            .map(|i| json!({"id": i.to_string(), "text": i.to_string()}))
+            .collect::<Vec<_>>());
+        assert!(matches!(
+            check_new_answer(&market, "someone", "Purple"),
+            Err(ManifoldError::Validation(_))
+        ));
+    }
+}
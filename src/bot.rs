@@ -0,0 +1,970 @@
+//! A small runtime for bots that run several strategies
+//!
+//! Strategies place orders through [`BotRuntime::place_bet`] under their
+//! strategy id. The runtime writes every order to an [`AuditLog`] and every
+//! fill to a persisted [`StrategyLedger`], so operators can see which
+//! strategy does what, and which ones make money ([`BotRuntime::report`]).
+//!
+//! Strategies that implement [`StatePersistence`] can have their state saved
+//! on shutdown and restored on start, in the same [`Storage`] as the ledger.
+//!
+//! Strategies running concurrently take a [`MarketLocks`] lock on a market
+//! before acting on it, so two of them (or a retry of the same action) never
+//! adjust the same position at once.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{ManifoldError, Result};
+use crate::freshness::Fetched;
+use crate::math::CpmmPool;
+use crate::secret::redact;
+use crate::storage::Storage;
+use crate::types::*;
+use crate::ManifoldClient;
+
+const LEDGER_KEY: &str = "bot-ledger";
+
+/// Something the bot runtime did.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum AuditKind {
+    #[serde(rename_all = "camelCase")]
+    OrderPlaced { order: PlaceBetArgs, bet_id: String },
+    #[serde(rename_all = "camelCase")]
+    OrderFailed { order: PlaceBetArgs, error: String },
+    /// A [dry run](PlaceBetArgs::dry_run) went through; nothing was placed.
+    #[serde(rename_all = "camelCase")]
+    OrderSimulated { order: PlaceBetArgs, shares: f64 },
+    /// A limit order was cancelled by the runtime, e.g. by [`SelfTradePolicy::CancelOwn`].
+    #[serde(rename_all = "camelCase")]
+    OrderCancelled { bet_id: String, reason: String },
+    /// A parameter changed while running, e.g. `strategies.momentum.spread`.
+    #[serde(rename_all = "camelCase")]
+    ConfigChanged { key: String, old: Value, new: Value },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub time: DateTime<Utc>,
+    pub strategy_id: Option<String>,
+    #[serde(flatten)]
+    pub kind: AuditKind,
+}
+
+/// Events of a bot run, optionally appended to a JSON-lines file as they happen.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    events: Vec<AuditEvent>,
+    file: Option<PathBuf>,
+}
+
+impl AuditLog {
+    /// A log kept in memory only.
+    pub fn new() -> AuditLog {
+        Default::default()
+    }
+
+    /// A log that also appends each event to `path`, one JSON object per line.
+    pub fn to_file(path: impl Into<PathBuf>) -> AuditLog {
+        AuditLog {
+            events: vec![],
+            file: Some(path.into()),
+        }
+    }
+
+    pub fn events(&self) -> &[AuditEvent] {
+        &self.events
+    }
+
+    pub fn record(&mut self, strategy_id: Option<&str>, kind: AuditKind) -> Result<()> {
+        let event = AuditEvent {
+            time: Utc::now(),
+            strategy_id: strategy_id.map(str::to_owned),
+            kind,
+        };
+
+        if let Some(path) = &self.file {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+
+            writeln!(file, "{}", serde_json::to_string(&event)?)?;
+        }
+
+        self.events.push(event);
+
+        Ok(())
+    }
+}
+
+/// A filled bet, attributed to the strategy that placed it.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrategyFill {
+    pub strategy_id: String,
+    pub bet_id: String,
+    pub market_id: String,
+    /// The answer of a `cpmm-multi-1` market the bet was on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer_id: Option<String>,
+    pub outcome: String,
+    /// Mana spent, negative for sales.
+    pub amount: f64,
+    pub shares: f64,
+    pub created_time: i64,
+}
+
+/// Performance of one strategy, see [`StrategyLedger::report`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StrategyReport {
+    pub fills: usize,
+    /// Mana traded, counting sales as positive.
+    pub turnover: f64,
+    /// Profit on resolved markets.
+    pub realized_pnl: f64,
+    /// Mark-to-market profit on open markets, at their current probability.
+    pub unrealized_pnl: f64,
+    pub resolved_markets: usize,
+    /// Fraction of resolved markets the strategy made money on; cancelled markets don't count.
+    pub hit_rate: Option<f64>,
+}
+
+/// The fills of each strategy.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct StrategyLedger {
+    pub fills: Vec<StrategyFill>,
+}
+
+impl StrategyLedger {
+    pub fn record(&mut self, strategy_id: &str, bet: &Bet) {
+        self.fills.push(StrategyFill {
+            strategy_id: strategy_id.to_owned(),
+            bet_id: bet.id().to_owned(),
+            market_id: bet.contract_id().to_owned(),
+            answer_id: bet.answer_id().map(str::to_owned),
+            outcome: bet.outcome().to_owned(),
+            amount: bet.amount(),
+            shares: bet.shares(),
+            created_time: bet.created_time(),
+        });
+    }
+
+    pub fn market_ids(&self) -> HashSet<&str> {
+        self.fills.iter().map(|f| f.market_id.as_str()).collect()
+    }
+
+    /// Per-strategy performance, valuing positions with the current state of `markets`.
+    ///
+    /// Only YES/NO positions are valued, on the market or on each of its
+    /// answers separately; fills on markets missing from `markets` only count
+    /// towards turnover. Every resolved market or answer counts towards the
+    /// hit rate.
+    pub fn report<M: Market>(&self, markets: &[M]) -> BTreeMap<String, StrategyReport> {
+        let markets: HashMap<&str, &M> = markets.iter().map(|m| (m.id(), m)).collect();
+
+        // (strategy, market, answer) -> (cost, YES shares, NO shares)
+        let mut positions = BTreeMap::<(&str, &str, Option<&str>), (f64, f64, f64)>::new();
+        let mut reports = BTreeMap::<String, StrategyReport>::new();
+
+        for fill in &self.fills {
+            let report = reports.entry(fill.strategy_id.clone()).or_default();
+            report.fills += 1;
+            report.turnover += fill.amount.abs();
+
+            let position = positions
+                .entry((
+                    &fill.strategy_id,
+                    &fill.market_id,
+                    fill.answer_id.as_deref(),
+                ))
+                .or_default();
+            position.0 += fill.amount;
+
+            match fill.outcome.as_str() {
+                "YES" => position.1 += fill.shares,
+                "NO" => position.2 += fill.shares,
+                _ => {}
+            }
+        }
+
+        let mut hits = HashMap::<&str, usize>::new();
+
+        for ((strategy_id, market_id, answer_id), (cost, yes, no)) in positions {
+            let Some(market) = markets.get(market_id) else {
+                continue;
+            };
+
+            let report = reports.get_mut(strategy_id).expect("strategy has fills");
+
+            // Cancelled markets refund everything, so they don't count.
+            let Some((resolved, yes_value)) = yes_value(*market, answer_id) else {
+                continue;
+            };
+
+            let pnl = yes * yes_value + no * (1.0 - yes_value) - cost;
+
+            if resolved {
+                report.realized_pnl += pnl;
+                report.resolved_markets += 1;
+
+                if pnl > 0.0 {
+                    *hits.entry(strategy_id).or_default() += 1;
+                }
+            } else {
+                report.unrealized_pnl += pnl;
+            }
+        }
+
+        for (strategy_id, report) in reports.iter_mut() {
+            if report.resolved_markets > 0 {
+                let hits = hits.get(strategy_id.as_str()).copied().unwrap_or(0);
+                report.hit_rate = Some(hits as f64 / report.resolved_markets as f64);
+            }
+        }
+
+        reports
+    }
+}
+
+/// Whether the market, or its answer, is resolved, and what a YES share of it is
+/// worth: its payout if resolved, else its probability. `None` if cancelled.
+fn yes_value(market: &impl Market, answer_id: Option<&str>) -> Option<(bool, f64)> {
+    let Some(answer_id) = answer_id else {
+        return match (market.is_resolved(), market.resolution()) {
+            (true, Some("YES")) => Some((true, 1.0)),
+            (true, Some("NO")) => Some((true, 0.0)),
+            (true, Some("MKT")) => Some((
+                true,
+                number_f64(&market.data()["resolutionProbability"]).unwrap_or_default(),
+            )),
+            (true, _) => None,
+            (false, _) => Some((false, market.probability().unwrap_or(0.5))),
+        };
+    };
+
+    let answer = market.answers().into_iter().find(|a| a.id() == answer_id);
+    let data = answer.as_ref().map_or(&Value::Null, |a| &a.0);
+
+    // Answers of markets whose answers don't sum to one resolve on their own.
+    match data["resolution"].as_str() {
+        Some("YES") => return Some((true, 1.0)),
+        Some("NO") => return Some((true, 0.0)),
+        Some("MKT") => {
+            return Some((
+                true,
+                number_f64(&data["resolutionProbability"]).unwrap_or_default(),
+            ))
+        }
+        Some(_) => return None,
+        None => {}
+    }
+
+    match (market.is_resolved(), market.resolution()) {
+        (true, Some("MKT")) => Some((
+            true,
+            number_f64(&market.data()["resolutions"][answer_id]).unwrap_or_default() / 100.0,
+        )),
+        (true, Some("CANCEL") | None) => None,
+        (true, Some(resolution)) => Some((true, f64::from(u8::from(resolution == answer_id)))),
+        (false, _) => Some((false, answer.and_then(|a| a.probability()).unwrap_or(0.5))),
+    }
+}
+
+/// Limits the runtime enforces on every order, whatever the strategies ask for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafetyRails {
+    /// Orders on markets closing sooner than this are refused,
+    /// see [`ManifoldClient::post_bet_before_close`].
+    pub close_margin: Duration,
+    /// Largest amount of a single order.
+    pub max_bet: Option<u64>,
+    /// Orders decided on market data older than this are refused,
+    /// see [`BotRuntime::place_bet_on`].
+    pub max_data_age: Option<Duration>,
+}
+
+impl Default for SafetyRails {
+    fn default() -> Self {
+        SafetyRails {
+            close_margin: Duration::from_secs(5),
+            max_bet: None,
+            max_data_age: None,
+        }
+    }
+}
+
+impl SafetyRails {
+    pub fn check(&self, order: &PlaceBetArgs) -> Result<()> {
+        match self.max_bet {
+            Some(max) if order.amount > max => Err(ManifoldError::Validation(format!(
+                "order of M${} is over the M${max} limit",
+                order.amount
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Per-market async locks, shared by cloning.
+///
+/// Hold a market's lock for the whole of an action, e.g. cancelling a limit
+/// order and placing its replacement, so that nothing else acts on the
+/// market in between and crosses or duplicates it. Locks aren't reentrant:
+/// locking a market again while holding its guard waits forever.
+#[derive(Debug, Clone, Default)]
+pub struct MarketLocks {
+    locks: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+/// Holds a market's lock until dropped, see [`MarketLocks::lock`].
+#[derive(Debug)]
+pub struct MarketGuard {
+    pub market_id: String,
+    _guard: tokio::sync::OwnedMutexGuard<()>,
+}
+
+impl MarketLocks {
+    pub fn new() -> MarketLocks {
+        Default::default()
+    }
+
+    fn mutex(&self, market_id: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.locks.lock().unwrap();
+
+        // Forget the locks nobody holds or waits for.
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+
+        locks.entry(market_id.to_owned()).or_default().clone()
+    }
+
+    /// Waits until no one else holds `market_id`'s lock, and takes it.
+    pub async fn lock(&self, market_id: &str) -> MarketGuard {
+        MarketGuard {
+            market_id: market_id.to_owned(),
+            _guard: self.mutex(market_id).lock_owned().await,
+        }
+    }
+
+    /// Takes `market_id`'s lock if it's free, for strategies that would rather skip a busy market.
+    pub fn try_lock(&self, market_id: &str) -> Option<MarketGuard> {
+        Some(MarketGuard {
+            market_id: market_id.to_owned(),
+            _guard: self.mutex(market_id).try_lock_owned().ok()?,
+        })
+    }
+}
+
+/// An order is a self-trade when more than this fraction of it would be
+/// matched against the user's own limit orders.
+pub const SELF_TRADE_FRACTION: f64 = 0.5;
+
+/// What [`BotRuntime::place_bet`] does with a strategy's self-trades, see
+/// [`OrderBook::simulate`](crate::orders::OrderBook::simulate).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SelfTradePolicy {
+    /// Place the order anyway.
+    #[default]
+    Allow,
+    /// Cancel the strategy's orders it would be matched against, then place it.
+    ///
+    /// Orders of other strategies (or placed outside the runtime) are never
+    /// cancelled; if the order would be matched against those, it's refused
+    /// like with [`Abort`](Self::Abort). Cancelled orders stay cancelled even
+    /// if placing the order then fails.
+    CancelOwn,
+    /// Refuse the order with [`ManifoldError::SelfTrade`].
+    Abort,
+}
+
+/// A strategy whose state survives restarts, see [`BotRuntime::save_state`].
+pub trait StatePersistence {
+    /// Whatever the strategy needs to resume: targeted positions, last acted timestamps, etc.
+    type State: Serialize + DeserializeOwned;
+
+    fn strategy_id(&self) -> &str;
+
+    fn snapshot(&self) -> Self::State;
+
+    fn restore(&mut self, state: Self::State);
+}
+
+/// A saved [`StatePersistence::State`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSnapshot<T> {
+    pub saved_at: DateTime<Utc>,
+    pub state: T,
+}
+
+/// Routes strategies' orders through the client, recording who placed what.
+#[derive(Debug)]
+pub struct BotRuntime<S: Storage> {
+    pub client: ManifoldClient,
+    pub audit: AuditLog,
+    pub rails: SafetyRails,
+    /// Markets the strategies should trade.
+    pub markets: Vec<String>,
+    /// Parameters of each strategy, by strategy id.
+    pub strategy_params: BTreeMap<String, Value>,
+    /// Clone these into concurrently running strategies, or share them between runtimes.
+    pub locks: MarketLocks,
+    /// By strategy id; strategies not in here are [allowed](SelfTradePolicy::Allow) to self-trade.
+    pub self_trade: BTreeMap<String, SelfTradePolicy>,
+    storage: S,
+    ledger: StrategyLedger,
+    /// The authorized user, fetched for the first self-trade check.
+    user_id: Option<String>,
+}
+
+impl<S: Storage> BotRuntime<S> {
+    /// A runtime that keeps its [`StrategyLedger`] in `storage`, loading it if it exists.
+    pub fn new(client: ManifoldClient, storage: S, audit: AuditLog) -> Result<BotRuntime<S>> {
+        let ledger = storage.load_as(LEDGER_KEY)?.unwrap_or_default();
+
+        Ok(BotRuntime {
+            client,
+            audit,
+            rails: SafetyRails::default(),
+            markets: vec![],
+            strategy_params: BTreeMap::new(),
+            locks: MarketLocks::new(),
+            self_trade: BTreeMap::new(),
+            storage,
+            ledger,
+            user_id: None,
+        })
+    }
+
+    pub fn ledger(&self) -> &StrategyLedger {
+        &self.ledger
+    }
+
+    /// Takes the lock of `market_id`, see [`MarketLocks`].
+    pub async fn lock_market(&self, market_id: &str) -> MarketGuard {
+        self.locks.lock(market_id).await
+    }
+
+    /// Places a bet for `strategy_id`, and records it in the audit log and the ledger.
+    ///
+    /// Orders that break the [`SafetyRails`] fail without being sent, and
+    /// self-trades are handled according to the strategy's [`SelfTradePolicy`].
+    /// Doesn't take the market's lock itself; see [`lock_market`](Self::lock_market).
+    ///
+    /// [Dry runs](PlaceBetArgs::dry_run) are audited as simulated, stay out of
+    /// the ledger, and never cancel orders to avoid a self-trade.
+    ///
+    /// Once the bet is placed, this returns it even if recording it fails;
+    /// the failure is logged, and the fill stays in the in-memory ledger to be
+    /// saved with the next one.
+    pub async fn place_bet(&mut self, strategy_id: &str, order: &PlaceBetArgs) -> Result<Bet> {
+        let result = match self.rails.check(order) {
+            Ok(()) => match self.prevent_self_trade(strategy_id, order).await {
+                Ok(()) => {
+                    self.client
+                        .post_bet_before_close(order, self.rails.close_margin)
+                        .await
+                }
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(bet) if order.dry_run => {
+                self.audit.record(
+                    Some(strategy_id),
+                    AuditKind::OrderSimulated {
+                        order: order.clone(),
+                        shares: bet.shares(),
+                    },
+                )?;
+
+                Ok(bet)
+            }
+            Ok(bet) => {
+                self.ledger.record(strategy_id, &bet);
+
+                if let Err(e) = self.storage.save_as(LEDGER_KEY, &self.ledger) {
+                    log::error!("Bet {} placed, but saving the ledger failed: {e}", bet.id());
+                }
+
+                if let Err(e) = self.audit.record(
+                    Some(strategy_id),
+                    AuditKind::OrderPlaced {
+                        order: order.clone(),
+                        bet_id: bet.id().to_owned(),
+                    },
+                ) {
+                    log::error!("Bet {} placed, but auditing it failed: {e}", bet.id());
+                }
+
+                Ok(bet)
+            }
+            Err(e) => {
+                self.audit.record(
+                    Some(strategy_id),
+                    AuditKind::OrderFailed {
+                        order: order.clone(),
+                        error: redact(&e.to_string()),
+                    },
+                )?;
+
+                Err(e)
+            }
+        }
+    }
+
+    /// [`place_bet`](Self::place_bet) for an order decided on `market`.
+    ///
+    /// With [`SafetyRails::max_data_age`] set, fails with
+    /// [`ManifoldError::StaleData`] if `market` was fetched longer ago than
+    /// that; the refusal is recorded in the audit log like any other.
+    pub async fn place_bet_on<M: Market>(
+        &mut self,
+        strategy_id: &str,
+        order: &PlaceBetArgs,
+        market: &Fetched<M>,
+    ) -> Result<Bet> {
+        if let Some(max_age) = self.rails.max_data_age {
+            if let Err(e) = self.client.check_fresh(market, max_age) {
+                self.audit.record(
+                    Some(strategy_id),
+                    AuditKind::OrderFailed {
+                        order: order.clone(),
+                        error: redact(&e.to_string()),
+                    },
+                )?;
+
+                return Err(e);
+            }
+        }
+
+        self.place_bet(strategy_id, order).await
+    }
+
+    async fn prevent_self_trade(&mut self, strategy_id: &str, order: &PlaceBetArgs) -> Result<()> {
+        let policy = self
+            .self_trade
+            .get(strategy_id)
+            .copied()
+            .unwrap_or_default();
+
+        if policy == SelfTradePolicy::Allow || !matches!(order.outcome, Outcome::Yes | Outcome::No)
+        {
+            return Ok(());
+        }
+
+        let user_id = match &self.user_id {
+            Some(user_id) => user_id.clone(),
+            None => {
+                let user_id = self.client.get_me().await?.id().to_owned();
+                self.user_id = Some(user_id.clone());
+                user_id
+            }
+        };
+
+        // The read cache may hand back an older market, whose pool is only as good as its age.
+        let market = self.client.get_market_fetched(&order.contract_id).await?;
+        if let Some(max_age) = self.rails.max_data_age {
+            self.client.check_fresh(&market, max_age)?;
+        }
+        let Some(pool) = CpmmPool::from_market(&*market) else {
+            return Ok(());
+        };
+
+        let book = self.client.get_order_book(&order.contract_id).await?;
+        let estimate = book.simulate(&pool, order, &user_id)?;
+
+        if estimate.own_fraction() <= SELF_TRADE_FRACTION {
+            return Ok(());
+        }
+
+        let strategy_orders = self
+            .ledger
+            .fills
+            .iter()
+            .filter(|fill| fill.strategy_id == strategy_id)
+            .map(|fill| fill.bet_id.as_str())
+            .collect::<HashSet<_>>();
+        let others = estimate
+            .own
+            .iter()
+            .filter(|(bet_id, _)| !strategy_orders.contains(bet_id.as_str()))
+            .count();
+
+        if policy == SelfTradePolicy::Abort || others > 0 {
+            return Err(ManifoldError::SelfTrade(format!(
+                "M${:.2} of the M${} order would be matched against {} of your own orders, \
+                 {others} of them not placed by {strategy_id}",
+                estimate.own_amount(),
+                order.amount,
+                estimate.own.len()
+            )));
+        }
+
+        if order.dry_run {
+            return Ok(());
+        }
+
+        // Don't cancel anything for an order that would be refused for closing anyway.
+        self.client.time_to_bet(&*market, self.rails.close_margin)?;
+
+        for (bet_id, _) in &estimate.own {
+            self.client.cancel_bet(bet_id).await?;
+            self.audit.record(
+                Some(strategy_id),
+                AuditKind::OrderCancelled {
+                    bet_id: bet_id.clone(),
+                    reason: "self-trade".to_owned(),
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the markets the strategies traded and reports their performance.
+    pub async fn report(&self) -> Result<BTreeMap<String, StrategyReport>> {
+        let mut markets = vec![];
+
+        for id in self.ledger.market_ids() {
+            markets.push(self.client.get_market(id).await?);
+        }
+
+        Ok(self.ledger.report(&markets))
+    }
+
+    fn state_key(strategy_id: &str) -> String {
+        format!("bot-state-{strategy_id}")
+    }
+
+    /// Saves the strategy's state, replacing the previous snapshot atomically
+    /// (as far as the [`Storage`] supports it).
+    pub fn save_state<P: StatePersistence>(&self, strategy: &P) -> Result<()> {
+        self.storage.save_as(
+            &Self::state_key(strategy.strategy_id()),
+            &StateSnapshot {
+                saved_at: Utc::now(),
+                state: strategy.snapshot(),
+            },
+        )
+    }
+
+    /// Restores the strategy's last saved state, if there is one.
+    ///
+    /// Returns when the state was saved, or `None` (leaving the strategy as is) if it never was.
+    pub fn restore_state<P: StatePersistence>(
+        &self,
+        strategy: &mut P,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let snapshot: Option<StateSnapshot<P::State>> = self
+            .storage
+            .load_as(&Self::state_key(strategy.strategy_id()))?;
+
+        Ok(snapshot.map(|snapshot| {
+            strategy.restore(snapshot.state);
+            snapshot.saved_at
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn fill(strategy: &str, market: &str, outcome: &str, amount: f64, shares: f64) -> Bet {
+        Bet(json!({
+            "id": format!("{strategy}-{market}-{amount}"),
+            "contractId": market,
+            "outcome": outcome,
+            "amount": amount,
+            "shares": shares,
+            "createdTime": 0,
+        }))
+    }
+
+    #[derive(Default)]
+    struct Follower {
+        last_acted: BTreeMap<String, i64>,
+    }
+
+    impl StatePersistence for Follower {
+        type State = BTreeMap<String, i64>;
+
+        fn strategy_id(&self) -> &str {
+            "follower"
+        }
+
+        fn snapshot(&self) -> Self::State {
+            self.last_acted.clone()
+        }
+
+        fn restore(&mut self, state: Self::State) {
+            self.last_acted = state;
+        }
+    }
+
+    #[tokio::test]
+    async fn locks_markets() {
+        let locks = MarketLocks::new();
+
+        let guard = locks.lock("a").await;
+        assert!(locks.try_lock("a").is_none());
+        assert!(locks.try_lock("b").is_some());
+
+        let waiting = tokio::spawn({
+            let locks = locks.clone();
+            async move { locks.lock("a").await.market_id }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        drop(guard);
+        assert_eq!(waiting.await.unwrap(), "a");
+        assert!(locks.try_lock("a").is_some());
+    }
+
+    #[test]
+    fn persists_strategy_state() -> Result<()> {
+        let client = ManifoldClient::new(crate::ManifoldAuthorization::NoAuthorization)?;
+        let runtime = BotRuntime::new(client, MemoryStorage::new(), AuditLog::new())?;
+
+        let mut strategy = Follower::default();
+        assert_eq!(runtime.restore_state(&mut strategy)?, None);
+
+        strategy.last_acted.insert("market".to_owned(), 1234);
+        runtime.save_state(&strategy)?;
+
+        let mut restarted = Follower::default();
+        assert!(runtime.restore_state(&mut restarted)?.is_some());
+        assert_eq!(restarted.last_acted, strategy.last_acted);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_per_strategy() {
+        let mut ledger = StrategyLedger::default();
+
+        ledger.record("momentum", &fill("momentum", "won", "YES", 10.0, 20.0));
+        ledger.record("momentum", &fill("momentum", "lost", "YES", 5.0, 8.0));
+        ledger.record("momentum", &fill("momentum", "open", "NO", 10.0, 15.0));
+        ledger.record("arb", &fill("arb", "won", "NO", 4.0, 6.0));
+
+        let markets = [
+            FullMarket(json!({"id": "won", "isResolved": true, "resolution": "YES"})),
+            FullMarket(json!({"id": "lost", "isResolved": true, "resolution": "NO"})),
+            FullMarket(json!({"id": "open", "isResolved": false, "probability": 0.4})),
+        ];
+
+        let reports = ledger.report(&markets);
+
+        let momentum = &reports["momentum"];
+        assert_eq!(momentum.fills, 3);
+        assert_eq!(momentum.turnover, 25.0);
+        assert_eq!(momentum.realized_pnl, 10.0 - 5.0);
+        assert!((momentum.unrealized_pnl - (15.0 * 0.6 - 10.0)).abs() < 1e-9);
+        assert_eq!(momentum.hit_rate, Some(0.5));
+
+        assert_eq!(reports["arb"].realized_pnl, -4.0);
+        assert_eq!(reports["arb"].hit_rate, Some(0.0));
+    }
+
+    #[tokio::test]
+    async fn keeps_placed_bets_when_auditing_fails() -> Result<()> {
+        let server = crate::fixtures::MockServer::api().await?;
+        let dir = std::env::temp_dir();
+        // A directory can't be appended to, so every audit fails.
+        let mut runtime = BotRuntime::new(
+            server.client().build()?,
+            MemoryStorage::new(),
+            AuditLog::to_file(&dir),
+        )?;
+
+        let order = PlaceBetArgs::new(10, "fixtureBinaryId", Outcome::Yes);
+        let bet = runtime.place_bet("momentum", &order).await?;
+
+        assert_eq!(runtime.ledger().fills.len(), 1);
+        assert_eq!(runtime.ledger().fills[0].bet_id, bet.id());
+        assert!(runtime.audit.events().is_empty());
+
+        let saved: StrategyLedger = runtime.storage.load_as(LEDGER_KEY)?.unwrap();
+        assert_eq!(&saved, runtime.ledger());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keeps_dry_runs_out_of_the_ledger() -> Result<()> {
+        let server = crate::fixtures::MockServer::api().await?;
+        let mut runtime = BotRuntime::new(
+            server.client().build()?,
+            MemoryStorage::new(),
+            AuditLog::new(),
+        )?;
+
+        let mut order = PlaceBetArgs::new(10, "fixtureBinaryId", Outcome::Yes);
+        order.dry_run = true;
+        runtime.place_bet("momentum", &order).await?;
+
+        assert!(runtime.ledger().fills.is_empty());
+        assert!(matches!(
+            runtime.audit.events()[0].kind,
+            AuditKind::OrderSimulated { .. }
+        ));
+
+        let bet = server.requests().pop().unwrap();
+        assert_eq!(bet.path, "/bet");
+        assert_eq!(bet.body["dryRun"], true);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn audits_refused_orders_with_the_limit() -> Result<()> {
+        let server = crate::fixtures::MockServer::api().await?;
+        let mut runtime = BotRuntime::new(
+            server.client().build()?,
+            MemoryStorage::new(),
+            AuditLog::new(),
+        )?;
+        runtime.rails.max_bet = Some(50);
+
+        let order = PlaceBetArgs::new(100, "fixtureBinaryId", Outcome::Yes);
+        let result = runtime.place_bet("momentum", &order).await;
+        assert!(matches!(result, Err(ManifoldError::Validation(_))));
+        assert!(server.requests().is_empty());
+
+        match &runtime.audit.events()[0].kind {
+            AuditKind::OrderFailed { error, .. } => {
+                assert!(error.contains("over the M$50 limit"), "{error}")
+            }
+            kind => panic!("unexpected {kind:?}"),
+        }
+
+        Ok(())
+    }
+
+    /// A market whose book has a NO limit order of the fixture user at 45%.
+    async fn self_trade_server() -> Result<crate::fixtures::MockServer> {
+        use crate::fixtures::{self, MockResponse, MockServer};
+
+        MockServer::start(|request| {
+            let response = match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/bets") => json!([{
+                    "id": "resting",
+                    "userId": fixtures::USER_ID,
+                    "contractId": "fixtureBinaryId",
+                    "outcome": "NO",
+                    "limitProb": 0.45,
+                    "orderAmount": 100,
+                    "amount": 0,
+                    "shares": 0,
+                    "isFilled": false,
+                    "isCancelled": false,
+                    "createdTime": 0,
+                }]),
+                ("POST", path) if path.starts_with("/bet/cancel/") => {
+                    json!({
+                        "id": "resting",
+                        "userId": fixtures::USER_ID,
+                        "contractId": "fixtureBinaryId",
+                        "isCancelled": true,
+                    })
+                }
+                _ => {
+                    let target = format!("{}?{}", request.path, request.query);
+                    return fixtures::api_response(&request.method, &target)
+                        .map(MockResponse::json);
+                }
+            };
+
+            Some(MockResponse::json(response))
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn cancels_only_the_strategys_own_orders() -> Result<()> {
+        let server = self_trade_server().await?;
+        let mut runtime = BotRuntime::new(
+            server.client().build()?,
+            MemoryStorage::new(),
+            AuditLog::new(),
+        )?;
+        runtime
+            .self_trade
+            .insert("momentum".to_owned(), SelfTradePolicy::CancelOwn);
+
+        let order = PlaceBetArgs::new(100, "fixtureBinaryId", Outcome::Yes);
+        let cancels = |server: &crate::fixtures::MockServer| {
+            server
+                .requests()
+                .iter()
+                .filter(|r| r.path.starts_with("/bet/cancel/"))
+                .count()
+        };
+
+        // Another strategy's order is never cancelled.
+        let result = runtime.place_bet("momentum", &order).await;
+        assert!(matches!(result, Err(ManifoldError::SelfTrade(_))));
+        assert_eq!(cancels(&server), 0);
+
+        runtime.ledger.record(
+            "momentum",
+            &fill("momentum", "fixtureBinaryId", "NO", 0.0, 0.0),
+        );
+        runtime.ledger.fills[0].bet_id = "resting".to_owned();
+
+        runtime.place_bet("momentum", &order).await?;
+        assert_eq!(cancels(&server), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_answers_separately() {
+        let mut ledger = StrategyLedger::default();
+
+        for (answer, amount, shares) in [("a", 10.0, 25.0), ("b", 10.0, 12.0)] {
+            let mut bet = fill("multi", "race", "YES", amount, shares);
+            bet.0["id"] = json!(format!("bet-{answer}"));
+            bet.0["answerId"] = json!(answer);
+            ledger.record("multi", &bet);
+        }
+
+        let race = |resolution: Value| {
+            FullMarket(json!({
+                "id": "race",
+                "isResolved": !resolution.is_null(),
+                "resolution": resolution,
+                "answers": [
+                    {"id": "a", "probability": 0.6},
+                    {"id": "b", "probability": 0.4},
+                ],
+            }))
+        };
+
+        let open = &ledger.report(&[race(Value::Null)])["multi"];
+        assert!((open.unrealized_pnl - (25.0 * 0.6 - 10.0 + 12.0 * 0.4 - 10.0)).abs() < 1e-9);
+
+        let resolved = &ledger.report(&[race(json!("a"))])["multi"];
+        assert_eq!(resolved.realized_pnl, 25.0 - 10.0 - 10.0);
+        assert_eq!(resolved.resolved_markets, 2);
+        assert_eq!(resolved.hit_rate, Some(0.5));
+    }
+}
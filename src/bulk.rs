@@ -0,0 +1,362 @@
+//! Bulk operations with progress reporting
+//!
+//! Operations that issue many requests (fetching hundreds of markets,
+//! liquidating a portfolio, backfilling history) return a future together
+//! with a [`watch::Receiver<Progress>`](tokio::sync::watch::Receiver), so
+//! CLIs and dashboards can render progress without instrumenting the internals.
+//!
+//! The work is driven only by the returned future: dropping it cancels
+//! everything that is still in flight.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use tokio::sync::watch;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::Result;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// A snapshot of a bulk operation's progress.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    /// Items that were processed, successfully or not.
+    pub completed: usize,
+    /// Total number of items, if known in advance.
+    pub total: Option<usize>,
+    /// How many of the completed items have failed.
+    pub errors: usize,
+    /// The message of the most recent error, if any.
+    pub last_error: Option<String>,
+
+    started: DateTime<Utc>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Progress {
+    pub fn new(total: Option<usize>) -> Progress {
+        Progress::with_clock(total, Arc::new(SystemClock))
+    }
+
+    /// Timed by `clock` instead of the system clock, e.g. a client's.
+    pub fn with_clock(total: Option<usize>, clock: Arc<dyn Clock>) -> Progress {
+        Progress {
+            completed: 0,
+            total,
+            errors: 0,
+            last_error: None,
+            started: clock.now(),
+            clock,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        (self.clock.now() - self.started)
+            .to_std()
+            .unwrap_or_default()
+    }
+
+    /// Completed fraction in `[0, 1]`, if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total? {
+            0 => Some(1.0),
+            total => Some(self.completed as f64 / total as f64),
+        }
+    }
+
+    /// Estimated time left, extrapolated from the average time per item so far.
+    pub fn eta(&self) -> Option<Duration> {
+        let total = self.total?;
+
+        if self.completed == 0 {
+            return None;
+        }
+
+        let remaining = total.saturating_sub(self.completed) as u32;
+
+        Some(self.elapsed() / self.completed as u32 * remaining)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.total.map(|t| self.completed >= t).unwrap_or(false)
+    }
+}
+
+/// The sending half of a progress channel.
+///
+/// Cheap to use from inside the bulk operation; updates are dropped silently
+/// when nobody is listening.
+#[derive(Debug)]
+pub struct ProgressReporter {
+    tx: watch::Sender<Progress>,
+}
+
+impl ProgressReporter {
+    pub fn new(total: Option<usize>) -> (ProgressReporter, watch::Receiver<Progress>) {
+        ProgressReporter::with_clock(total, Arc::new(SystemClock))
+    }
+
+    /// See [`Progress::with_clock`].
+    pub fn with_clock(
+        total: Option<usize>,
+        clock: Arc<dyn Clock>,
+    ) -> (ProgressReporter, watch::Receiver<Progress>) {
+        let (tx, rx) = watch::channel(Progress::with_clock(total, clock));
+
+        (ProgressReporter { tx }, rx)
+    }
+
+    pub fn set_total(&self, total: usize) {
+        self.tx.send_modify(|p| p.total = Some(total));
+    }
+
+    pub fn success(&self) {
+        self.tx.send_modify(|p| p.completed += 1);
+    }
+
+    pub fn failure(&self, error: &impl std::fmt::Display) {
+        self.tx.send_modify(|p| {
+            p.completed += 1;
+            p.errors += 1;
+            p.last_error = Some(error.to_string());
+        });
+    }
+
+    /// Records the outcome of one item, passing the result through.
+    pub fn record<T>(&self, result: Result<T>) -> Result<T> {
+        match &result {
+            Ok(_) => self.success(),
+            Err(e) => self.failure(e),
+        }
+
+        result
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Progress> {
+        self.tx.subscribe()
+    }
+}
+
+/// Runs `f` over every item with at most `concurrency` calls in flight.
+///
+/// Results are returned in the same order as the items. Individual failures
+/// don't stop the operation; they are counted in the [`Progress`] and returned
+/// in place.
+pub fn run_bulk<'a, I, T, F, Fut>(
+    items: I,
+    concurrency: usize,
+    f: F,
+) -> (
+    impl Future<Output = Vec<Result<T>>> + 'a,
+    watch::Receiver<Progress>,
+)
+where
+    I: IntoIterator + 'a,
+    I::IntoIter: 'a,
+    F: FnMut(I::Item) -> Fut + 'a,
+    Fut: Future<Output = Result<T>> + 'a,
+    T: 'a,
+{
+    run_bulk_with_clock(items, concurrency, Arc::new(SystemClock), f)
+}
+
+/// [`run_bulk`], with the [`Progress`] timed by `clock`.
+pub fn run_bulk_with_clock<'a, I, T, F, Fut>(
+    items: I,
+    concurrency: usize,
+    clock: Arc<dyn Clock>,
+    f: F,
+) -> (
+    impl Future<Output = Vec<Result<T>>> + 'a,
+    watch::Receiver<Progress>,
+)
+where
+    I: IntoIterator + 'a,
+    I::IntoIter: 'a,
+    F: FnMut(I::Item) -> Fut + 'a,
+    Fut: Future<Output = Result<T>> + 'a,
+    T: 'a,
+{
+    let items = items.into_iter().collect::<Vec<_>>();
+    let (reporter, rx) = ProgressReporter::with_clock(Some(items.len()), clock);
+
+    let fut = async move {
+        stream::iter(items)
+            .map(f)
+            .buffered(concurrency.max(1))
+            .map(|r| reporter.record(r))
+            .collect::<Vec<_>>()
+            .await
+    };
+
+    (fut, rx)
+}
+
+impl ManifoldClient {
+    /// Fetches many markets by ID concurrently.
+    ///
+    /// See [`run_bulk`] for the semantics of the returned future and progress channel.
+    pub fn get_markets_bulk<'a>(
+        &'a self,
+        market_ids: &'a [&'a str],
+        concurrency: usize,
+    ) -> (
+        impl Future<Output = Vec<Result<FullMarket>>> + 'a,
+        watch::Receiver<Progress>,
+    ) {
+        run_bulk_with_clock(
+            market_ids.iter(),
+            concurrency,
+            self.clock.clone(),
+            move |id| self.get_market(id),
+        )
+    }
+
+    /// Fetches many users by ID concurrently.
+    ///
+    /// See [`run_bulk`] for the semantics of the returned future and progress channel.
+    pub fn get_users_bulk<'a>(
+        &'a self,
+        user_ids: &'a [&'a str],
+        concurrency: usize,
+    ) -> (
+        impl Future<Output = Vec<Result<User>>> + 'a,
+        watch::Receiver<Progress>,
+    ) {
+        run_bulk_with_clock(
+            user_ids.iter(),
+            concurrency,
+            self.clock.clone(),
+            move |id| self.get_user_by_id(id),
+        )
+    }
+
+    /// Sells all shares the user owns in each of many markets concurrently,
+    /// see [`sell_shares`](Self::sell_shares).
+    ///
+    /// Multiple choice markets are sold per answer, so they need `sell_shares`
+    /// with an `answer_id` instead.
+    ///
+    /// See [`run_bulk`] for the semantics of the returned future and progress channel.
+    pub fn liquidate_bulk<'a>(
+        &'a self,
+        market_ids: &'a [&'a str],
+        concurrency: usize,
+    ) -> (
+        impl Future<Output = Vec<Result<Bet>>> + 'a,
+        watch::Receiver<Progress>,
+    ) {
+        run_bulk_with_clock(
+            market_ids.iter(),
+            concurrency,
+            self.clock.clone(),
+            move |id| self.sell_shares(id, None, None, None),
+        )
+    }
+
+    /// Fetches the whole bet history of many markets concurrently, newest
+    /// first in each, see [`stream_bets_with`](Self::stream_bets_with).
+    ///
+    /// See [`run_bulk`] for the semantics of the returned future and progress channel.
+    pub fn backfill_bets_bulk<'a>(
+        &'a self,
+        market_ids: &'a [&'a str],
+        concurrency: usize,
+    ) -> (
+        impl Future<Output = Vec<Result<Vec<Bet>>>> + 'a,
+        watch::Receiver<Progress>,
+    ) {
+        run_bulk_with_clock(
+            market_ids.iter(),
+            concurrency,
+            self.clock.clone(),
+            move |id| {
+                let query = BetsQuery::new().contract_id(id);
+
+                async move { self.stream_bets_with(&query).try_collect().await }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ManifoldError;
+
+    #[tokio::test]
+    async fn run_bulk_reports_progress() {
+        let (fut, rx) = run_bulk(0..10, 3, |i| async move {
+            if i % 4 == 0 {
+                Err(ManifoldError::Other(format!("item {i}")))
+            } else {
+                Ok(i * 2)
+            }
+        });
+
+        let results = fut.await;
+
+        assert_eq!(results.len(), 10);
+        assert_eq!(*results[3].as_ref().unwrap(), 6);
+
+        let progress = rx.borrow().clone();
+
+        assert!(progress.is_finished());
+        assert_eq!(progress.completed, 10);
+        assert_eq!(progress.errors, 3);
+        assert_eq!(progress.last_error.as_deref(), Some("item 8"));
+    }
+
+    #[tokio::test]
+    async fn liquidates_and_backfills_on_the_client_clock() -> Result<()> {
+        use chrono::TimeZone;
+        use serde_json::json;
+
+        use crate::clock::MockClock;
+        use crate::fixtures::{MockRequest, MockResponse, MockServer};
+
+        let server = MockServer::start(|request: &MockRequest| {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("POST", path) => Some(MockResponse::json(json!({
+                    "id": format!("sale-{}", path.split('/').nth(2)?),
+                    "userId": "u",
+                    "shares": -10,
+                }))),
+                ("GET", "/bets") if request.query.contains("before=") => {
+                    Some(MockResponse::json(json!([])))
+                }
+                ("GET", "/bets") => Some(MockResponse::json(json!([
+                    {"id": "b2", "createdTime": 2},
+                    {"id": "b1", "createdTime": 1},
+                ]))),
+                _ => None,
+            }
+        })
+        .await?;
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        let client = server.client().clock(clock.clone()).build()?;
+
+        let (sales, rx) = client.liquidate_bulk(&["a", "b"], 2);
+        let sales = sales.await;
+        assert_eq!(sales[1].as_ref().unwrap().id(), "sale-b");
+        assert!(rx.borrow().is_finished());
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(rx.borrow().elapsed(), Duration::from_secs(10));
+
+        let (histories, rx) = client.backfill_bets_bulk(&["a"], 2);
+        let histories = histories.await;
+        assert_eq!(histories[0].as_ref().unwrap().len(), 2);
+        assert_eq!(rx.borrow().errors, 0);
+        assert!(server
+            .requests()
+            .iter()
+            .any(|r| r.query == "contractId=a&before=b1"));
+
+        Ok(())
+    }
+}
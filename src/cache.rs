@@ -0,0 +1,257 @@
+//! Sharing reads between clients
+//!
+//! A bot running several accounts in one process would otherwise fetch the
+//! same markets and users once per account. A [`ReadCache`] keeps public
+//! market and user responses for a while, and clients built with
+//! [`ManifoldClientBuilder::share_reads_with`](crate::ManifoldClientBuilder::share_reads_with)
+//! use the same cache and the same
+//! [rate limit budgets](crate::ManifoldClientBuilder::rate_limit)
+//! as another client. Everything else, and all mutating calls, stay with
+//! each client's own account.
+//!
+//! Only [`get_market`](ManifoldClient::get_market),
+//! [`get_market_by_slug`](ManifoldClient::get_market_by_slug),
+//! [`get_user`](ManifoldClient::get_user) and
+//! [`get_user_by_id`](ManifoldClient::get_user_by_id) are cached, since
+//! they're the same for every account. Entries expire by the client's clock,
+//! and keep the time they were fetched, so
+//! [`get_market_fetched`](ManifoldClient::get_market_fetched) reports a cached
+//! market as old as it is.
+//!
+//! Writes drop what they changed, whether it was fetched by id, slug or
+//! username: bets, sells and cancels drop the market and the bettor,
+//! resolutions drop the market and every user (all holders are paid out),
+//! closes and other market updates drop the market, and answers, liquidity
+//! and managrams drop every user, since the client doesn't know which one
+//! it's acting as. Writes by other clients that don't share the cache are
+//! only noticed when entries expire.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::freshness::Fetched;
+use crate::ManifoldClient;
+
+/// Entries kept before expired ones are dropped.
+const PURGE_AT: usize = 10_000;
+
+/// Responses by URL, see the [module documentation](self).
+#[derive(Debug)]
+pub struct ReadCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Fetched<Value>>>,
+}
+
+impl ReadCache {
+    /// Keeps responses for `ttl`.
+    pub fn new(ttl: Duration) -> ReadCache {
+        ReadCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The response for `url`, if it's at most `ttl` old at `now`.
+    pub fn get(&self, url: &str, now: DateTime<Utc>) -> Option<Fetched<Value>> {
+        let entries = self.entries.lock().unwrap();
+
+        entries
+            .get(url)
+            .filter(|entry| entry.check_fresh(self.ttl, now).is_ok())
+            .cloned()
+    }
+
+    pub fn insert(&self, url: &str, response: Fetched<Value>, now: DateTime<Utc>) {
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= PURGE_AT {
+            entries.retain(|_, entry| entry.check_fresh(self.ttl, now).is_ok());
+        }
+
+        entries.insert(url.to_owned(), response);
+    }
+
+    /// Forgets the responses whose URL contains `part`, e.g. `/user/` for every user.
+    pub fn invalidate(&self, part: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|url, _| !url.contains(part));
+    }
+
+    /// Forgets the market or user with `id`, including responses fetched by
+    /// its slug or username.
+    pub fn invalidate_id(&self, id: &str) {
+        self.entries.lock().unwrap().retain(|url, entry| {
+            !url.split('/').any(|segment| segment == id)
+                && entry.value.get("id").and_then(Value::as_str) != Some(id)
+        });
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ManifoldClient {
+    /// [`execute`](Self::execute) for a GET of `path` that's the same for
+    /// every account, going through the [`ReadCache`] if the client has one.
+    pub(crate) async fn execute_shared<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        Ok(self.execute_shared_fetched::<T>(path).await?.into_inner())
+    }
+
+    /// [`execute_shared`](Self::execute_shared), with the time the response
+    /// was fetched, which is older than now for a cached one.
+    pub(crate) async fn execute_shared_fetched<T: DeserializeOwned>(
+        &self,
+        path: &str,
+    ) -> Result<Fetched<T>> {
+        let Some(cache) = self.read_cache() else {
+            return self.fetch(self.execute(self.http_get(path))).await;
+        };
+
+        let url = self.url(path);
+
+        let response = match cache.get(&url, self.now()) {
+            Some(response) => response,
+            None => {
+                let response: Fetched<Value> =
+                    self.fetch(self.execute(self.http_get(path))).await?;
+                cache.insert(&url, response.clone(), self.now());
+                response
+            }
+        };
+
+        let fetched_at = response.fetched_at;
+
+        Ok(Fetched::new(
+            serde_json::from_value(response.into_inner())?,
+            fetched_at,
+        ))
+    }
+
+    /// Drops `market_id` from the read cache after a write to it.
+    pub(crate) fn forget_market(&self, market_id: &str) {
+        if let Some(cache) = self.read_cache() {
+            cache.invalidate_id(market_id);
+        }
+    }
+
+    /// Drops the user `user_id` from the read cache after a write that
+    /// changed their balance, or every user if it's `None`.
+    pub(crate) fn forget_users(&self, user_id: Option<&str>) {
+        if let Some(cache) = self.read_cache() {
+            match user_id {
+                Some(user_id) => cache.invalidate_id(user_id),
+                None => cache.invalidate("/user/"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+    use crate::fixtures::{self, MockRequest, MockResponse, MockServer};
+    use crate::types::Market;
+
+    #[test]
+    fn expires_and_invalidates_entries() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let later = |secs: i64| start + chrono::Duration::seconds(secs);
+
+        let cache = ReadCache::new(Duration::from_secs(30));
+        cache.insert(
+            "/v0/market/a",
+            Fetched::new(json!({"id": "a"}), start),
+            start,
+        );
+        cache.insert("/v0/user/b", Fetched::new(json!({"id": "b"}), start), start);
+
+        assert_eq!(
+            cache.get("/v0/market/a", later(30)).unwrap().value,
+            json!({"id": "a"})
+        );
+        assert!(cache.get("/v0/market/a", later(31)).is_none());
+        assert!(cache.get("/v0/market/c", start).is_none());
+
+        cache.invalidate("/market/a");
+        assert!(cache.get("/v0/market/a", start).is_none());
+        assert_eq!(cache.len(), 1);
+
+        cache.insert(
+            "/v0/slug/will-it-rain",
+            Fetched::new(json!({"id": "a"}), start),
+            start,
+        );
+        cache.insert(
+            "/v0/user/bob",
+            Fetched::new(json!({"id": "b"}), start),
+            start,
+        );
+        cache.invalidate_id("a");
+        assert!(cache.get("/v0/slug/will-it-rain", start).is_none());
+        cache.invalidate_id("b");
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn keeps_fetch_times_and_forgets_written_markets() -> Result<()> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let clock = MockClock::new(start);
+        let server =
+            MockServer::start(
+                |req: &MockRequest| match (req.method.as_str(), req.path.as_str()) {
+                    ("GET", "/slug/rain") => Some(MockResponse::json(fixtures::binary_market().0)),
+                    ("POST", path) if path.ends_with("/close") => {
+                        Some(MockResponse::json(json!({})))
+                    }
+                    (method, path) => fixtures::api_response(method, path).map(MockResponse::json),
+                },
+            )
+            .await?;
+        let client = server
+            .client()
+            .read_cache(Duration::from_secs(60))
+            .clock(clock.clone())
+            .build()?;
+
+        let market = client.get_market_by_slug("rain").await?;
+        client.get_market_fetched(market.id()).await?;
+        clock.advance(Duration::from_secs(20));
+
+        let cached = client.get_market_fetched(market.id()).await?;
+        assert_eq!(cached.fetched_at, start);
+        assert_eq!(server.requests().len(), 2);
+
+        client.close_market(market.id(), None).await?;
+        assert!(client.read_cache().unwrap().is_empty());
+
+        let refetched = client.get_market_fetched(market.id()).await?;
+        assert_eq!(refetched.fetched_at, clock.now());
+
+        Ok(())
+    }
+}
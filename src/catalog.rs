@@ -0,0 +1,1105 @@
+//! A machine-readable catalog of the endpoints the client implements
+//!
+//! CLIs, RPC services and LLM tool definitions wrapping the client can be
+//! generated from [`ENDPOINTS`] (or the OpenAPI-style [`openapi`] document)
+//! instead of each keeping their own list. A test checks that every endpoint
+//! documented on a client method is in the catalog.
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use self::Location::{Body, Path, Query};
+
+/// Whether an endpoint needs the client to be authenticated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Auth {
+    None,
+    /// Works without, but shows more to authorized users (e.g. private groups).
+    Optional,
+    Required,
+}
+
+/// Where a parameter goes in the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Location {
+    Path,
+    Query,
+    Body,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Param {
+    pub name: &'static str,
+    pub location: Location,
+    pub required: bool,
+    /// JSON schema type: `string`, `number`, `integer`, `boolean`, `array` or `object`.
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub description: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoint {
+    /// `GET` or `POST`.
+    pub method: &'static str,
+    /// Without the API version, with `{name}` for path parameters, e.g. `/market/{marketId}`.
+    pub path: &'static str,
+    pub summary: &'static str,
+    pub auth: Auth,
+    pub params: &'static [Param],
+    /// The type the client returns for the response, e.g. `FullMarket` or `Vec<Bet>`.
+    pub response: &'static str,
+    /// The [`ManifoldClient`](crate::ManifoldClient) methods calling the endpoint.
+    pub client_methods: &'static [&'static str],
+    /// Whether the client pages through the endpoint, see [`crate::streams`].
+    pub paginated: bool,
+}
+
+const fn param(
+    name: &'static str,
+    location: Location,
+    required: bool,
+    kind: &'static str,
+    description: &'static str,
+) -> Param {
+    Param {
+        name,
+        location,
+        required,
+        kind,
+        description,
+    }
+}
+
+const MARKET_ID: Param = param("marketId", Path, true, "string", "The market's ID.");
+
+pub const ENDPOINTS: &[Endpoint] = &[
+    Endpoint {
+        method: "GET",
+        path: "/user/{username}",
+        summary: "Gets a user by their username.",
+        auth: Auth::None,
+        params: &[param(
+            "username",
+            Path,
+            true,
+            "string",
+            "The user's username.",
+        )],
+        response: "User",
+        client_methods: &["get_user"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/user/by-id/{id}",
+        summary: "Gets a user by their ID.",
+        auth: Auth::None,
+        params: &[param("id", Path, true, "string", "The user's ID.")],
+        response: "User",
+        client_methods: &["get_user_by_id"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/me",
+        summary: "Gets the authenticated user.",
+        auth: Auth::Required,
+        params: &[],
+        response: "User",
+        client_methods: &["get_me"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/users",
+        summary: "Lists users, newest first.",
+        auth: Auth::None,
+        params: &[
+            param(
+                "limit",
+                Query,
+                false,
+                "integer",
+                "At most 1000, the default.",
+            ),
+            param(
+                "before",
+                Query,
+                false,
+                "string",
+                "ID of the user to list from.",
+            ),
+        ],
+        response: "Vec<User>",
+        client_methods: &["get_users", "stream_users"],
+        paginated: true,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/groups",
+        summary: "Lists all groups.",
+        auth: Auth::None,
+        params: &[param(
+            "availableToUserId",
+            Query,
+            false,
+            "string",
+            "Only groups this user can join or has joined.",
+        )],
+        response: "Vec<Group>",
+        client_methods: &["get_groups"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/group/{slug}",
+        summary: "Gets a group by its slug.",
+        auth: Auth::None,
+        params: &[param("slug", Path, true, "string", "The group's slug.")],
+        response: "Group",
+        client_methods: &["get_group"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/group/by-id/{id}",
+        summary: "Gets a group by its ID.",
+        auth: Auth::None,
+        params: &[param("id", Path, true, "string", "The group's ID.")],
+        response: "Group",
+        client_methods: &["get_group_by_id"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/group/by-id/{id}/markets",
+        summary: "Lists a group's markets.",
+        auth: Auth::Optional,
+        params: &[param("id", Path, true, "string", "The group's ID.")],
+        response: "Vec<LiteMarket>",
+        client_methods: &["get_group_markets"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/group/by-id/{id}/members",
+        summary: "Lists a group's members with their roles.",
+        auth: Auth::None,
+        params: &[param("id", Path, true, "string", "The group's ID.")],
+        response: "Vec<GroupMember>",
+        client_methods: &["get_group_members", "ensure_group_role"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/markets",
+        summary: "Lists markets, newest first.",
+        auth: Auth::None,
+        params: &[
+            param(
+                "limit",
+                Query,
+                false,
+                "integer",
+                "At most 1000; 500 by default.",
+            ),
+            param(
+                "before",
+                Query,
+                false,
+                "string",
+                "ID of the market to list from.",
+            ),
+            param(
+                "userId",
+                Query,
+                false,
+                "string",
+                "Only markets created by this user.",
+            ),
+        ],
+        response: "Vec<LiteMarket>",
+        client_methods: &[
+            "get_markets",
+            "stream_markets",
+            "stream_markets_chunked",
+            "stream_public_markets",
+        ],
+        paginated: true,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/market/{marketId}",
+        summary: "Gets a market with its answers.",
+        auth: Auth::None,
+        params: &[MARKET_ID],
+        response: "FullMarket",
+        client_methods: &["get_market"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/market/{marketId}/positions",
+        summary: "Lists the positions of a market's holders.",
+        auth: Auth::None,
+        params: &[
+            MARKET_ID,
+            param(
+                "order",
+                Query,
+                false,
+                "string",
+                "`profit` (the default) or `shares`.",
+            ),
+            param(
+                "top",
+                Query,
+                false,
+                "integer",
+                "How many of the largest positions to return.",
+            ),
+            param(
+                "bottom",
+                Query,
+                false,
+                "integer",
+                "How many of the smallest positions to return.",
+            ),
+            param(
+                "userId",
+                Query,
+                false,
+                "string",
+                "Only this user's position.",
+            ),
+        ],
+        response: "Vec<ContractMetric>",
+        client_methods: &["get_market_positions"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/slug/{marketSlug}",
+        summary: "Gets a market by its slug.",
+        auth: Auth::None,
+        params: &[param(
+            "marketSlug",
+            Path,
+            true,
+            "string",
+            "The market's slug.",
+        )],
+        response: "FullMarket",
+        client_methods: &["get_market_by_slug"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/search-markets",
+        summary: "Searches markets.",
+        auth: Auth::None,
+        params: &[
+            param("term", Query, true, "string", "The search terms."),
+            param(
+                "filter",
+                Query,
+                false,
+                "string",
+                "`all`, `open`, `closed`, `resolved`, ...",
+            ),
+            param(
+                "sort",
+                Query,
+                false,
+                "string",
+                "`score`, `newest`, `liquidity`, ...",
+            ),
+            param(
+                "contractType",
+                Query,
+                false,
+                "string",
+                "`ALL`, `BINARY`, `MULTIPLE_CHOICE`, ...",
+            ),
+            param(
+                "limit",
+                Query,
+                false,
+                "integer",
+                "How many markets to return.",
+            ),
+            param(
+                "offset",
+                Query,
+                false,
+                "integer",
+                "How many markets to skip.",
+            ),
+        ],
+        response: "Vec<LiteMarket>",
+        client_methods: &["search_markets", "stream_search_markets"],
+        paginated: true,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/get-related-markets",
+        summary: "Lists markets related to a market, most related first.",
+        auth: Auth::None,
+        params: &[
+            param("contractId", Query, true, "string", "The market's ID."),
+            param(
+                "limit",
+                Query,
+                false,
+                "integer",
+                "How many markets to return.",
+            ),
+        ],
+        response: "Vec<LiteMarket>",
+        client_methods: &["get_related_markets"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/bets",
+        summary: "Lists bets, newest first.",
+        auth: Auth::None,
+        params: &[
+            param("userId", Query, false, "string", "Only bets of this user."),
+            param(
+                "username",
+                Query,
+                false,
+                "string",
+                "Only bets of this user.",
+            ),
+            param(
+                "contractId",
+                Query,
+                false,
+                "string",
+                "Only bets on this market.",
+            ),
+            param(
+                "contractSlug",
+                Query,
+                false,
+                "string",
+                "Only bets on this market.",
+            ),
+            param(
+                "kinds",
+                Query,
+                false,
+                "string",
+                "`open-limit` for open limit orders only.",
+            ),
+            param(
+                "limit",
+                Query,
+                false,
+                "integer",
+                "At most 1000, the default.",
+            ),
+            param(
+                "before",
+                Query,
+                false,
+                "string",
+                "ID of the bet to list from.",
+            ),
+            param(
+                "afterTime",
+                Query,
+                false,
+                "integer",
+                "Only bets created after this, in milliseconds since the epoch.",
+            ),
+            param(
+                "beforeTime",
+                Query,
+                false,
+                "integer",
+                "Only bets created before this, in milliseconds since the epoch.",
+            ),
+        ],
+        response: "Vec<Bet>",
+        client_methods: &[
+            "get_bets",
+            "get_bets_with",
+            "stream_bets",
+            "stream_bets_with",
+            "get_order_book",
+        ],
+        paginated: true,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/comments",
+        summary: "Lists a market's comments, newest first.",
+        auth: Auth::None,
+        params: &[
+            param("contractId", Query, false, "string", "The market's ID."),
+            param("contractSlug", Query, false, "string", "The market's slug."),
+            param(
+                "limit",
+                Query,
+                false,
+                "integer",
+                "How many comments to return.",
+            ),
+            param(
+                "afterTime",
+                Query,
+                false,
+                "integer",
+                "Only comments created after this, in milliseconds since the epoch.",
+            ),
+            param(
+                "beforeTime",
+                Query,
+                false,
+                "integer",
+                "Only comments created before this, in milliseconds since the epoch.",
+            ),
+        ],
+        response: "Value",
+        client_methods: &["get_comments", "stream_comments"],
+        paginated: true,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/txns",
+        summary: "Lists mana transactions, newest first.",
+        auth: Auth::None,
+        params: &[
+            param(
+                "fromId",
+                Query,
+                false,
+                "string",
+                "Only transactions from this ID.",
+            ),
+            param(
+                "toId",
+                Query,
+                false,
+                "string",
+                "Only transactions to this ID.",
+            ),
+            param(
+                "category",
+                Query,
+                false,
+                "string",
+                "Only this category, e.g. `MANA_PAYMENT`.",
+            ),
+            param(
+                "limit",
+                Query,
+                false,
+                "integer",
+                "How many transactions to return.",
+            ),
+            param(
+                "offset",
+                Query,
+                false,
+                "integer",
+                "How many transactions to skip.",
+            ),
+        ],
+        response: "Vec<Txn>",
+        client_methods: &["stream_txns"],
+        paginated: true,
+    },
+    Endpoint {
+        method: "GET",
+        path: "/managrams",
+        summary: "Lists managrams, newest first.",
+        auth: Auth::None,
+        params: &[
+            param(
+                "toId",
+                Query,
+                false,
+                "string",
+                "Only managrams to this user.",
+            ),
+            param(
+                "fromId",
+                Query,
+                false,
+                "string",
+                "Only managrams from this user.",
+            ),
+            param(
+                "limit",
+                Query,
+                false,
+                "integer",
+                "How many managrams to return, at most 100.",
+            ),
+            param(
+                "before",
+                Query,
+                false,
+                "integer",
+                "Only managrams sent before this, in milliseconds since the epoch.",
+            ),
+            param(
+                "after",
+                Query,
+                false,
+                "integer",
+                "Only managrams sent after this, in milliseconds since the epoch.",
+            ),
+        ],
+        response: "Vec<Txn>",
+        client_methods: &["get_managrams", "stream_managrams"],
+        paginated: true,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/managram",
+        summary: "Sends mana to other users.",
+        auth: Auth::Required,
+        params: &[
+            param("toIds", Body, true, "array", "The IDs of the recipients."),
+            param(
+                "amount",
+                Body,
+                true,
+                "number",
+                "Mana for each recipient, at least 10.",
+            ),
+            param(
+                "message",
+                Body,
+                false,
+                "string",
+                "A note sent with the mana.",
+            ),
+        ],
+        response: "Value",
+        client_methods: &["send_managram"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/bet",
+        summary: "Places a bet or a limit order.",
+        auth: Auth::Required,
+        params: &[
+            param("amount", Body, true, "integer", "Mana to bet, before fees."),
+            param("contractId", Body, true, "string", "The market's ID."),
+            param(
+                "outcome",
+                Body,
+                true,
+                "string",
+                "`YES`, `NO`, or an answer.",
+            ),
+            param(
+                "limitProb",
+                Body,
+                false,
+                "number",
+                "Limit probability, 0.001 to 0.999.",
+            ),
+            param(
+                "value",
+                Body,
+                false,
+                "number",
+                "Target value of numeric markets.",
+            ),
+            param(
+                "answerId",
+                Body,
+                false,
+                "string",
+                "The answer of a `cpmm-multi-1` market to bet `YES` or `NO` on.",
+            ),
+            param(
+                "expiresAt",
+                Body,
+                false,
+                "integer",
+                "When an unfilled limit order is cancelled, in milliseconds since the epoch.",
+            ),
+            param(
+                "dryRun",
+                Body,
+                false,
+                "boolean",
+                "Simulate the bet without placing it.",
+            ),
+        ],
+        response: "Bet",
+        client_methods: &[
+            "post_bet",
+            "place_bet",
+            "bet_yes",
+            "bet_no",
+            "bet_answer",
+            "buy_shares",
+        ],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/bet/cancel/{betId}",
+        summary: "Cancels a limit order, returning it.",
+        auth: Auth::Required,
+        params: &[param(
+            "betId",
+            Path,
+            true,
+            "string",
+            "The limit order's ID.",
+        )],
+        response: "Bet",
+        client_methods: &["cancel_bet", "post_bet_cancel"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/market",
+        summary: "Creates a market. Costs mana.",
+        auth: Auth::Required,
+        params: &[
+            param(
+                "outcomeType",
+                Body,
+                true,
+                "string",
+                "`BINARY`, `MULTIPLE_CHOICE`, ...",
+            ),
+            param("question", Body, true, "string", "The headline question."),
+            param(
+                "description",
+                Body,
+                false,
+                "string",
+                "The resolution criteria.",
+            ),
+            param(
+                "closeTime",
+                Body,
+                false,
+                "integer",
+                "Milliseconds since the epoch.",
+            ),
+            param(
+                "visibility",
+                Body,
+                false,
+                "string",
+                "`public` or `unlisted`.",
+            ),
+            param(
+                "groupIds",
+                Body,
+                false,
+                "array",
+                "Groups to create the market in.",
+            ),
+            param(
+                "initialProb",
+                Body,
+                false,
+                "integer",
+                "Binary markets: 1 to 99.",
+            ),
+            param(
+                "answers",
+                Body,
+                false,
+                "array",
+                "Multiple choice markets: the answers.",
+            ),
+            param(
+                "coverImageUrl",
+                Body,
+                false,
+                "string",
+                "The image on the market's card.",
+            ),
+        ],
+        response: "FullMarket",
+        client_methods: &["post_market", "create_market"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/market/{marketId}/group",
+        summary: "Adds a market to a group, or removes it.",
+        auth: Auth::Required,
+        params: &[
+            MARKET_ID,
+            param("groupId", Body, true, "string", "The group's ID."),
+            param(
+                "remove",
+                Body,
+                false,
+                "boolean",
+                "Remove instead of adding.",
+            ),
+        ],
+        response: "Value",
+        client_methods: &["post_market_group"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/market/{marketId}/add-liquidity",
+        summary: "Adds liquidity to a market.",
+        auth: Auth::Required,
+        params: &[
+            MARKET_ID,
+            param("amount", Body, true, "integer", "Mana to add."),
+        ],
+        response: "Value",
+        client_methods: &["post_market_add_liquidity", "add_liquidity"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/market/{marketId}/update",
+        summary: "Updates a market's question, description, cover image, close time or visibility.",
+        auth: Auth::Required,
+        params: &[
+            MARKET_ID,
+            param("question", Body, false, "string", "The new question."),
+            param(
+                "descriptionMarkdown",
+                Body,
+                false,
+                "string",
+                "The new description.",
+            ),
+            param(
+                "coverImageUrl",
+                Body,
+                false,
+                "string",
+                "The image on the market's card.",
+            ),
+            param(
+                "closeTime",
+                Body,
+                false,
+                "integer",
+                "Milliseconds since the epoch.",
+            ),
+            param(
+                "visibility",
+                Body,
+                false,
+                "string",
+                "`public` or `unlisted`.",
+            ),
+        ],
+        response: "Value",
+        client_methods: &["post_market_update", "post_market_cover_image"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/market/{marketId}/close",
+        summary: "Closes a market, now or at a time.",
+        auth: Auth::Required,
+        params: &[
+            MARKET_ID,
+            param(
+                "closeTime",
+                Body,
+                false,
+                "integer",
+                "Milliseconds since the epoch.",
+            ),
+        ],
+        response: "Value",
+        client_methods: &["post_market_close", "close_market"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/market/{marketId}/resolve",
+        summary: "Resolves a market, or one answer of it.",
+        auth: Auth::Required,
+        params: &[
+            MARKET_ID,
+            param(
+                "outcome",
+                Body,
+                true,
+                "string",
+                "`YES`, `NO`, `MKT`, `CANCEL` or an answer.",
+            ),
+            param(
+                "probabilityInt",
+                Body,
+                false,
+                "integer",
+                "The probability of `MKT`.",
+            ),
+            param(
+                "answerId",
+                Body,
+                false,
+                "string",
+                "Resolve only this answer.",
+            ),
+            param(
+                "resolutions",
+                Body,
+                false,
+                "array",
+                "Answer weights of `MKT`.",
+            ),
+            param(
+                "value",
+                Body,
+                false,
+                "number",
+                "Numeric markets: the value.",
+            ),
+        ],
+        response: "Value",
+        client_methods: &[
+            "post_market_resolve",
+            "resolve_market",
+            "post_resolve_answer",
+        ],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/market/{marketId}/sell",
+        summary: "Sells shares.",
+        auth: Auth::Required,
+        params: &[
+            MARKET_ID,
+            param("outcome", Body, false, "string", "`YES` or `NO`."),
+            param("shares", Body, false, "number", "How many; all by default."),
+            param(
+                "answerId",
+                Body,
+                false,
+                "string",
+                "Multiple choice markets: whose shares to sell.",
+            ),
+        ],
+        response: "Bet",
+        client_methods: &["sell_shares", "sell_fraction", "post_market_sell"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/market/{marketId}/answer",
+        summary: "Adds an answer to a multiple choice market.",
+        auth: Auth::Required,
+        params: &[
+            MARKET_ID,
+            param("text", Body, true, "string", "The answer."),
+        ],
+        response: "Value",
+        client_methods: &["post_answer", "add_answer"],
+        paginated: false,
+    },
+    Endpoint {
+        method: "POST",
+        path: "/comment",
+        summary: "Comments on a market.",
+        auth: Auth::Required,
+        params: &[
+            param("contractId", Body, true, "string", "The market's ID."),
+            param(
+                "content",
+                Body,
+                false,
+                "object",
+                "The comment as TipTap JSON.",
+            ),
+            param("html", Body, false, "string", "The comment as HTML."),
+            param(
+                "markdown",
+                Body,
+                false,
+                "string",
+                "The comment as markdown.",
+            ),
+        ],
+        response: "Value",
+        client_methods: &["post_comment"],
+        paginated: false,
+    },
+];
+
+/// The catalog entry of `method` and `path` (e.g. `GET` and `/market/{marketId}`).
+pub fn endpoint(method: &str, path: &str) -> Option<&'static Endpoint> {
+    ENDPOINTS
+        .iter()
+        .find(|e| e.method.eq_ignore_ascii_case(method) && e.path == path)
+}
+
+/// The endpoints a client method calls.
+pub fn endpoints_of(client_method: &str) -> Vec<&'static Endpoint> {
+    ENDPOINTS
+        .iter()
+        .filter(|e| e.client_methods.contains(&client_method))
+        .collect()
+}
+
+/// [`ENDPOINTS`] as an OpenAPI 3 document, with paths under `/v0`.
+///
+/// Responses are described by the client's type names only
+/// (`x-client-response`), since the client itself is shallowly typed.
+pub fn openapi() -> Value {
+    let mut paths = Map::new();
+
+    for endpoint in ENDPOINTS {
+        let parameters = endpoint
+            .params
+            .iter()
+            .filter(|p| p.location != Location::Body)
+            .map(|p| {
+                json!({
+                    "name": p.name,
+                    "in": p.location,
+                    "required": p.required,
+                    "description": p.description,
+                    "schema": {"type": p.kind},
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let body = endpoint
+            .params
+            .iter()
+            .filter(|p| p.location == Location::Body)
+            .collect::<Vec<_>>();
+
+        let mut operation = json!({
+            "summary": endpoint.summary,
+            "operationId": endpoint.client_methods.first(),
+            "parameters": parameters,
+            "security": match endpoint.auth {
+                Auth::None => json!([]),
+                Auth::Optional => json!([{}, {"apiKey": []}]),
+                Auth::Required => json!([{"apiKey": []}]),
+            },
+            "responses": {"200": {"description": "OK"}},
+            "x-client-response": endpoint.response,
+            "x-client-methods": endpoint.client_methods,
+        });
+
+        if !body.is_empty() {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": {"application/json": {"schema": {
+                    "type": "object",
+                    "properties": body
+                        .iter()
+                        .map(|p| (p.name.to_owned(), json!({"type": p.kind, "description": p.description})))
+                        .collect::<Map<_, _>>(),
+                    "required": body.iter().filter(|p| p.required).map(|p| p.name).collect::<Vec<_>>(),
+                }}},
+            });
+        }
+
+        let path = paths
+            .entry(format!("/v0{}", endpoint.path))
+            .or_insert_with(|| json!({}));
+        path[endpoint.method.to_lowercase()] = operation;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {"title": "Manifold Markets API", "version": "v0"},
+        "servers": [{"url": "https://manifold.markets/api"}],
+        "components": {"securitySchemes": {"apiKey": {
+            "type": "apiKey",
+            "in": "header",
+            "name": "Authorization",
+            "description": "`Key <api key>`",
+        }}},
+        "paths": paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Endpoints in the `` `GET /v0/...` `` lines opening client methods' docs.
+    fn documented_endpoints() -> Vec<(String, String)> {
+        let sources = [
+            include_str!("client.rs"),
+            include_str!("streams.rs"),
+            include_str!("context.rs"),
+            include_str!("orders.rs"),
+        ];
+
+        sources
+            .iter()
+            .flat_map(|source| source.lines())
+            .filter_map(|line| {
+                let doc = line.trim().strip_prefix("/// `")?;
+                let (method, rest) = doc.split_once(" /v0")?;
+                let path = rest.split(['`', '?']).next()?;
+
+                Some((method.to_owned(), path.replace('[', "{").replace(']', "}")))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn catalogs_every_documented_endpoint() {
+        let documented = documented_endpoints();
+        assert!(documented.len() > 20);
+
+        for (method, path) in documented {
+            assert!(
+                endpoint(&method, &path).is_some(),
+                "{method} {path} is missing from the catalog"
+            );
+        }
+    }
+
+    #[test]
+    fn describes_endpoints() {
+        for endpoint in ENDPOINTS {
+            for name in endpoint.path.split('{').skip(1) {
+                let name = name.split('}').next().unwrap();
+
+                assert!(
+                    endpoint
+                        .params
+                        .iter()
+                        .any(|p| p.name == name && p.location == Location::Path),
+                    "{} has no parameter for {{{name}}}",
+                    endpoint.path
+                );
+            }
+        }
+
+        assert_eq!(endpoints_of("place_bet")[0].path, "/bet");
+
+        let openapi = openapi();
+        let get_market = &openapi["paths"]["/v0/market/{marketId}"]["get"];
+        assert_eq!(get_market["operationId"], "get_market");
+        assert_eq!(get_market["parameters"][0]["in"], "path");
+        assert_eq!(
+            openapi["paths"]["/v0/bet"]["post"]["requestBody"]["content"]["application/json"]
+                ["schema"]["required"],
+            json!(["amount", "contractId", "outcome"])
+        );
+    }
+}
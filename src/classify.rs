@@ -0,0 +1,194 @@
+//! Assigning markets to user-defined categories
+//!
+//! A [`Classifier`] maps a market to a category name, so reports and dashboards
+//! can break activity down by topic (see [`activity_by_category`](crate::reports::activity_by_category)).
+//! [`RulesClassifier`] is a keyword- and group-based implementation that
+//! covers the common cases without any model.
+
+use crate::text::fold;
+use crate::types::*;
+
+/// Category of markets that no rule matched.
+pub const UNCLASSIFIED: &str = "other";
+
+/// Something that assigns markets to categories.
+pub trait Classifier {
+    /// The category of `market`, or `None` if it doesn't fit any.
+    fn classify(&self, market: &dyn Market) -> Option<String>;
+}
+
+impl<F: Fn(&dyn Market) -> Option<String>> Classifier for F {
+    fn classify(&self, market: &dyn Market) -> Option<String> {
+        self(market)
+    }
+}
+
+/// A category with the keywords and groups that put markets into it.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub category: String,
+    /// Words or phrases matched against whole words of the question, ignoring
+    /// case and punctuation; stored [folded](crate::text::fold).
+    pub keywords: Vec<String>,
+    /// Group slugs; a market in any of them matches.
+    pub group_slugs: Vec<String>,
+}
+
+impl Rule {
+    pub fn new(category: &str) -> Rule {
+        Rule {
+            category: category.to_owned(),
+            keywords: vec![],
+            group_slugs: vec![],
+        }
+    }
+
+    pub fn keywords(mut self, keywords: &[&str]) -> Self {
+        self.keywords.extend(keywords.iter().map(|k| fold(k)));
+        self
+    }
+
+    pub fn groups(mut self, group_slugs: &[&str]) -> Self {
+        self.group_slugs
+            .extend(group_slugs.iter().map(|s| s.to_string()));
+        self
+    }
+
+    fn matches(&self, question: &str, group_slugs: &[&str]) -> bool {
+        self.group_slugs
+            .iter()
+            .any(|g| group_slugs.contains(&g.as_str()))
+            || self
+                .keywords
+                .iter()
+                .any(|k| question.contains(&format!(" {k} ")))
+    }
+}
+
+/// Classifies markets by the first [`Rule`] that matches.
+#[derive(Debug, Clone, Default)]
+pub struct RulesClassifier {
+    pub rules: Vec<Rule>,
+}
+
+impl RulesClassifier {
+    /// A classifier without rules; add some with [`rule`](Self::rule).
+    pub fn empty() -> RulesClassifier {
+        RulesClassifier::default()
+    }
+
+    /// Starter rules for `politics`, `sports`, `ai` and `personal`.
+    pub fn with_default_rules() -> RulesClassifier {
+        RulesClassifier::empty()
+            .rule(
+                Rule::new("politics")
+                    .keywords(&[
+                        "election",
+                        "president",
+                        "presidential",
+                        "senate",
+                        "congress",
+                        "parliament",
+                        "prime minister",
+                        "vote",
+                        "democrat",
+                        "republican",
+                        "governor",
+                        "poll",
+                        "referendum",
+                    ])
+                    .groups(&["politics-default", "us-politics", "world-politics"]),
+            )
+            .rule(
+                Rule::new("sports")
+                    .keywords(&[
+                        "nba",
+                        "nfl",
+                        "mlb",
+                        "nhl",
+                        "fifa",
+                        "world cup",
+                        "super bowl",
+                        "olympics",
+                        "championship",
+                        "premier league",
+                        "match",
+                        "tournament",
+                    ])
+                    .groups(&["sports-default", "football", "basketball", "soccer"]),
+            )
+            .rule(
+                Rule::new("ai")
+                    .keywords(&[
+                        "ai",
+                        "agi",
+                        "gpt",
+                        "llm",
+                        "openai",
+                        "anthropic",
+                        "deepmind",
+                        "machine learning",
+                        "neural network",
+                    ])
+                    .groups(&["ai", "technology-default"]),
+            )
+            .rule(
+                Rule::new("personal")
+                    .keywords(&["i", "my", "me", "i'll", "will i"])
+                    .groups(&["personal-goals"]),
+            )
+    }
+
+    /// Appends a rule; earlier rules take precedence.
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+}
+
+impl Classifier for RulesClassifier {
+    fn classify(&self, market: &dyn Market) -> Option<String> {
+        let question = format!(" {} ", fold(market.question()));
+        let group_slugs = market.group_slugs();
+
+        self.rules
+            .iter()
+            .find(|r| r.matches(&question, &group_slugs))
+            .map(|r| r.category.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn classify(question: &str, groups: &[&str]) -> Option<String> {
+        let market = LiteMarket(json!({"question": question, "groupSlugs": groups}));
+
+        RulesClassifier::with_default_rules().classify(&market)
+    }
+
+    #[test]
+    fn classifies_by_rules() {
+        assert_eq!(
+            classify("Who will win the 2028 US presidential election?", &[]).as_deref(),
+            Some("politics")
+        );
+        assert_eq!(
+            classify("Will OpenAI release GPT-6 in 2027?", &[]).as_deref(),
+            Some("ai")
+        );
+        assert_eq!(
+            classify("Will I finish my thesis this year?", &[]).as_deref(),
+            Some("personal")
+        );
+        assert_eq!(
+            classify("Will the Lakers win?", &["basketball"]).as_deref(),
+            Some("sports")
+        );
+        // "ai" is matched on whole words only
+        assert_eq!(classify("Will it rain in Thailand?", &[]), None);
+    }
+}
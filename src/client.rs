@@ -1,36 +1,466 @@
-use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, DATE};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
 use serde_json::{json, Value};
 
-use crate::error::Result;
+use crate::cache::ReadCache;
+use crate::clock::{Clock, SystemClock};
+use crate::comments::{CommentContent, CommentGuard, CommentLimits};
+use crate::config::Config;
+use crate::error::{ManifoldError, Result};
+use crate::ratelimit::{RateLimitConfig, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::secret::REDACTED;
+use crate::timing::{LatencyStats, LatencyTracker};
+use crate::trading::{BetEncoding, BetRequest};
 use crate::types::*;
 
 const DEFAULT_BASE: &str = "https://manifold.markets/api/v0";
 
-#[derive(Clone, Debug)]
+/// The largest cover image [`ManifoldClient::get_cover_image`] downloads, 10 MB.
+pub const MAX_COVER_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Version of the Manifold API, the path segment between the API root and the endpoint.
+///
+/// All endpoint methods are written against `v0`, which the default base URL
+/// ends with. A custom version can be used to try out a new API revision
+/// whose endpoints are compatible, see [`ManifoldClientBuilder::version`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    #[default]
+    V0,
+    Custom(String),
+}
+
+impl ApiVersion {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ApiVersion::V0 => "v0",
+            ApiVersion::Custom(version) => version,
+        }
+    }
+
+    /// `base` ending with this version instead of the one it ends with, if any.
+    fn apply(&self, base: &str) -> String {
+        let is_version = |segment: &str| {
+            segment
+                .strip_prefix('v')
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        };
+
+        let root = match base.rsplit_once('/') {
+            Some((root, last)) if is_version(last) => root,
+            _ => base,
+        };
+
+        format!("{root}/{}", self.as_str())
+    }
+}
+
+/// How the client authenticates. `Debug` never shows the key or token.
+#[derive(Clone)]
 pub enum ManifoldAuthorization {
     ApiKey(String),
     JWT(String),
     NoAuthorization,
 }
 
-impl Into<Option<HeaderValue>> for ManifoldAuthorization {
-    fn into(self) -> Option<HeaderValue> {
-        let st = match self {
+impl fmt::Debug for ManifoldAuthorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifoldAuthorization::ApiKey(_) => f.debug_tuple("ApiKey").field(&REDACTED).finish(),
+            ManifoldAuthorization::JWT(_) => f.debug_tuple("JWT").field(&REDACTED).finish(),
+            ManifoldAuthorization::NoAuthorization => f.write_str("NoAuthorization"),
+        }
+    }
+}
+
+impl From<ManifoldAuthorization> for Option<HeaderValue> {
+    fn from(auth: ManifoldAuthorization) -> Self {
+        let st = match auth {
             ManifoldAuthorization::ApiKey(key) => format!("Key {key}"),
             ManifoldAuthorization::JWT(token) => format!("Bearer {token}"),
             ManifoldAuthorization::NoAuthorization => return Option::None,
         };
 
-        Some(HeaderValue::from_str(&st).expect("Failure creating authorization header"))
+        let mut header = HeaderValue::from_str(&st).expect("Failure creating authorization header");
+        // Keeps it out of the `Debug` output of the HTTP client and its requests.
+        header.set_sensitive(true);
+
+        Some(header)
     }
 }
 
-#[derive(Clone, Debug)]
+/// A reference to a group (a.k.a. topic), by ID or by slug.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GroupRef {
+    Id(String),
+    Slug(String),
+}
+
+#[derive(Clone)]
 pub struct ManifoldClient {
     // pub auth: ManifoldAuthorization,
+    /// Base URL that endpoint paths are appended to, e.g. `https://manifold.markets/api/v0`.
     pub base: String,
 
-    pub http: reqwest::Client,
+    /// The version set with [`ManifoldClientBuilder::version`], already part of `base`.
+    pub version: Option<ApiVersion>,
+
+    /// Without the API key, which [`send_request`](Self::send_request) adds to
+    /// each request after the production guard, so raw requests can't change data.
+    pub(crate) http: reqwest::Client,
+
+    /// Sent with every request that goes through the client.
+    authorization: Option<HeaderValue>,
+
+    /// For files hosted elsewhere, like cover images: no API key or other default headers.
+    downloads: reqwest::Client,
+
+    /// Groups that every market created by this client is put in.
+    ///
+    /// See [`ManifoldClientBuilder::default_group_ids`].
+    pub default_groups: Vec<GroupRef>,
+
+    authenticated: bool,
+
+    /// Refuse mutating requests, see [`ManifoldClientBuilder::allow_real_money`].
+    production_guard: bool,
+
+    latency: Arc<LatencyTracker>,
+
+    comments: Arc<CommentGuard>,
+
+    rate_limit: Option<Arc<RateLimiter>>,
+
+    read_cache: Option<Arc<ReadCache>>,
+
+    retry_policy: Option<RetryPolicy>,
+
+    pub(crate) clock: Arc<dyn Clock>,
+
+    /// See [`ManifoldClientBuilder::lenient_accessors`].
+    lenient_accessors: bool,
+
+    /// By market id, see [`bet_encoding`](Self::bet_encoding).
+    pub(crate) bet_encodings: Arc<Mutex<HashMap<String, BetEncoding>>>,
+}
+
+impl fmt::Debug for ManifoldClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManifoldClient")
+            .field("base", &self.base)
+            .field("version", &self.version)
+            .field("default_groups", &self.default_groups)
+            .field("authenticated", &self.authenticated)
+            .field("production_guard", &self.production_guard)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builder for [`ManifoldClient`] with non-default settings.
+#[derive(Clone, Debug)]
+pub struct ManifoldClientBuilder {
+    auth: ManifoldAuthorization,
+    base: String,
+    version: Option<ApiVersion>,
+    default_groups: Vec<GroupRef>,
+    comment_limits: CommentLimits,
+    allow_real_money: bool,
+    timeout: Option<Duration>,
+    requests_per_minute: Option<u32>,
+    rate_limit: Option<RateLimitConfig>,
+    shared_rate_limit: Option<Arc<RateLimiter>>,
+    read_cache: Option<Arc<ReadCache>>,
+    retry_policy: Option<RetryPolicy>,
+    clock: Arc<dyn Clock>,
+    lenient_accessors: bool,
+}
+
+/// Whether `base` points at the production instance.
+fn is_production(base: &str) -> bool {
+    let host = base
+        .split("://")
+        .last()
+        .unwrap_or_default()
+        .split(['/', ':'])
+        .next()
+        .unwrap_or_default();
+
+    host == "manifold.markets" || host == "api.manifold.markets" || host == "www.manifold.markets"
+}
+
+/// Reads `response`'s body, failing once it's over `max_bytes`.
+async fn read_capped(mut response: reqwest::Response, max_bytes: usize) -> Result<Vec<u8>> {
+    let too_large = || ManifoldError::Other(format!("response body is over {max_bytes} bytes"));
+
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_bytes as u64)
+    {
+        return Err(too_large());
+    }
+
+    let mut body = vec![];
+
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(too_large());
+        }
+
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+}
+
+impl ManifoldClientBuilder {
+    pub fn new(auth: ManifoldAuthorization) -> ManifoldClientBuilder {
+        ManifoldClientBuilder {
+            auth,
+            base: DEFAULT_BASE.to_owned(),
+            version: None,
+            default_groups: vec![],
+            comment_limits: CommentLimits::default(),
+            allow_real_money: false,
+            timeout: None,
+            requests_per_minute: None,
+            rate_limit: Some(RateLimitConfig::default()),
+            shared_rate_limit: None,
+            read_cache: None,
+            retry_policy: None,
+            clock: Arc::new(SystemClock),
+            lenient_accessors: false,
+        }
+    }
+
+    /// A builder configured from `MANIFOLD_*` environment variables, which
+    /// must include `MANIFOLD_API_KEY`.
+    ///
+    /// The variables are read by [`Config::apply_vars`](crate::config::Config::apply_vars):
+    /// the API key, base URL, timeout, rate limit and real money switch.
+    /// Invalid values fail with [`ManifoldError::ConfigError`] naming the variable.
+    pub fn from_env() -> Result<ManifoldClientBuilder> {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// [`from_env`](Self::from_env) with the given variables instead of the environment.
+    pub fn from_vars(
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<ManifoldClientBuilder> {
+        let mut config = Config::default();
+        config.apply_vars(vars)?;
+
+        if config.api_key.is_none() {
+            return Err(ManifoldError::ConfigError(
+                "MANIFOLD_API_KEY is not set".to_owned(),
+            ));
+        }
+
+        config.builder()
+    }
+
+    /// Base URL of the API, which endpoint paths are appended to as they are,
+    /// `https://manifold.markets/api/v0` by default.
+    pub fn base(mut self, base: &str) -> Self {
+        self.base = base.to_owned();
+        self
+    }
+
+    /// Replaces the version the [base URL](Self::base) ends with (`/v0`),
+    /// or appends it if the base doesn't end with one.
+    pub fn version(mut self, version: ApiVersion) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Put every market created with [`ManifoldClient::post_market`] in these groups.
+    ///
+    /// After creation, the client checks that the market actually ended up
+    /// in the groups and adds it to the missing ones.
+    pub fn default_group_ids(mut self, ids: &[&str]) -> Self {
+        self.default_groups
+            .extend(ids.iter().map(|id| GroupRef::Id(id.to_string())));
+        self
+    }
+
+    /// Same as [`default_group_ids`](Self::default_group_ids), with topics given by their slugs.
+    pub fn default_topic_slugs(mut self, slugs: &[&str]) -> Self {
+        self.default_groups
+            .extend(slugs.iter().map(|slug| GroupRef::Slug(slug.to_string())));
+        self
+    }
+
+    /// Spacing and deduplication of comments, see [`CommentLimits`].
+    pub fn comment_limits(mut self, limits: CommentLimits) -> Self {
+        self.comment_limits = limits;
+        self
+    }
+
+    /// Allow bets, market creation, comments and other mutating requests on production.
+    ///
+    /// Without this, a client whose base URL is `manifold.markets` fails those requests
+    /// with [`ManifoldError::ProductionGuard`], so a bot under test can't spend real mana
+    /// by accident. Clients for the dev server or a local instance aren't affected.
+    pub fn allow_real_money(mut self) -> Self {
+        self.allow_real_money = true;
+        self
+    }
+
+    /// Fail requests that take longer than this, from connecting to reading the whole response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Send at most this many requests per minute, waiting before requests that would go over.
+    ///
+    /// Replaces the separate read and bet budgets of [`rate_limit`](Self::rate_limit)
+    /// with this one. The whole budget may be used in a burst. Clones of a client share it.
+    ///
+    /// [`build`](Self::build) fails with [`ManifoldError::ConfigError`] if `requests` is 0.
+    pub fn requests_per_minute(mut self, requests: u32) -> Self {
+        self.requests_per_minute = Some(requests);
+        self.rate_limit = None;
+        self
+    }
+
+    /// Separate budgets for reads and bets, waiting before requests that would go over;
+    /// see [`crate::ratelimit`].
+    ///
+    /// On by default with [`RateLimitConfig::default`], which keeps to
+    /// Manifold's published limit. Clones of a client share the budgets, but
+    /// other clients get their own, while Manifold counts per IP address; use
+    /// [`share_reads_with`](Self::share_reads_with) for several clients in one process.
+    ///
+    /// [`build`](Self::build) fails with [`ManifoldError::ConfigError`] if a budget is 0.
+    pub fn rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.rate_limit = Some(config);
+        self.requests_per_minute = None;
+        self
+    }
+
+    /// Send requests as fast as they come, e.g. for a local instance.
+    pub fn without_rate_limit(mut self) -> Self {
+        self.rate_limit = None;
+        self.requests_per_minute = None;
+        self
+    }
+
+    /// Keep market and user responses for `ttl`, see [`crate::cache`].
+    ///
+    /// Clones of a client share the cache.
+    pub fn read_cache(mut self, ttl: Duration) -> Self {
+        self.read_cache = Some(Arc::new(ReadCache::new(ttl)));
+        self
+    }
+
+    /// Use `client`'s [read cache](Self::read_cache) and [rate limit
+    /// budgets](Self::rate_limit), e.g. for another account of the
+    /// same bot; see [`crate::cache`].
+    ///
+    /// Replaces this builder's own settings for both, including turning them
+    /// off if `client` has none.
+    pub fn share_reads_with(mut self, client: &ManifoldClient) -> Self {
+        self.read_cache = client.read_cache.clone();
+        self.shared_rate_limit = client.rate_limit.clone();
+        self.requests_per_minute = None;
+        self.rate_limit = None;
+        self
+    }
+
+    /// Retry reads that fail with a rate limit, a server error or a network
+    /// failure; see [`crate::retry`]. Off by default.
+    ///
+    /// Bets and other requests that change data are never retried.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Where the client gets the time from, the system clock by default; see [`crate::clock`].
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Make [`ManifoldClient::with_accessors`] lenient: accessors like
+    /// `User::balance` called in it warn and return a default instead of
+    /// panicking on malformed data. Other clients aren't affected; see
+    /// [`set_lenient_accessors`](crate::types::set_lenient_accessors) for the
+    /// whole process.
+    pub fn lenient_accessors(mut self) -> Self {
+        self.lenient_accessors = true;
+        self
+    }
+
+    pub fn build(self) -> Result<ManifoldClient> {
+        if self.requests_per_minute == Some(0) {
+            return Err(ManifoldError::ConfigError(
+                "requests_per_minute is 0; a rate limit budget must be at least 1".to_owned(),
+            ));
+        }
+
+        if let Some(config) = &self.rate_limit {
+            config.validate()?;
+        }
+
+        let authenticated = !matches!(self.auth, ManifoldAuthorization::NoAuthorization);
+
+        let mut headers = HeaderMap::new();
+
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+
+        let base = self.base.trim_end_matches('/');
+        let base = match &self.version {
+            Some(version) => version.apply(base),
+            None => base.to_owned(),
+        };
+
+        let mut http = reqwest::Client::builder()
+            .user_agent("manifold-markets.rs/0.1.0")
+            .default_headers(headers);
+        let mut downloads = reqwest::Client::builder().user_agent("manifold-markets.rs/0.1.0");
+
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+            downloads = downloads.timeout(timeout);
+        }
+
+        Ok(ManifoldClient {
+            // auth,
+            production_guard: !self.allow_real_money && is_production(&base),
+            base,
+            version: self.version,
+            http: http.build()?,
+            authorization: self.auth.into(),
+            downloads: downloads.build()?,
+            default_groups: self.default_groups,
+            authenticated,
+            latency: Default::default(),
+            comments: Arc::new(CommentGuard::new(self.comment_limits)),
+            rate_limit: self.shared_rate_limit.or_else(|| {
+                let limiter = match (self.requests_per_minute, self.rate_limit) {
+                    (Some(requests), _) => RateLimiter::shared(requests),
+                    (None, Some(config)) => RateLimiter::new(config),
+                    (None, None) => return None,
+                };
+
+                Some(Arc::new(limiter))
+            }),
+            read_cache: self.read_cache,
+            retry_policy: self.retry_policy,
+            clock: self.clock,
+            lenient_accessors: self.lenient_accessors,
+            bet_encodings: Default::default(),
+        })
+    }
 }
 
 impl ManifoldClient {
@@ -47,30 +477,170 @@ impl ManifoldClient {
     }
 
     pub fn new_custom_base(auth: ManifoldAuthorization, base: &str) -> Result<ManifoldClient> {
-        let mut headers = HeaderMap::new();
+        Self::builder(auth).base(base).build()
+    }
 
-        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    pub fn builder(auth: ManifoldAuthorization) -> ManifoldClientBuilder {
+        ManifoldClientBuilder::new(auth)
+    }
 
-        if let Some(header) = auth.into() {
-            headers.insert(AUTHORIZATION, header);
-        }
+    /// A client configured from environment variables, see [`ManifoldClientBuilder::from_env`].
+    pub fn from_env() -> Result<ManifoldClient> {
+        ManifoldClientBuilder::from_env()?.build()
+    }
 
-        Ok(ManifoldClient {
-            // auth,
-            base: base.trim_end_matches('/').to_owned(),
-            http: reqwest::Client::builder()
-                .user_agent("manifold-markets.rs/0.1.0")
-                .default_headers(headers)
-                .build()?,
-        })
+    /// Whether the client sends an authorization header.
+    pub fn is_authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    /// Full URL of an endpoint, e.g. `/markets` → `https://manifold.markets/api/v0/markets`.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base, path)
     }
 
+    /// A raw GET request to an endpoint.
+    ///
+    /// This is an escape hatch for endpoints the client doesn't cover yet.
+    /// Send it with [`send_managed`](Self::send_managed) to get the same
+    /// handling as the first-class endpoints, including the API key, which
+    /// isn't part of the request until then.
+    #[doc(hidden)]
     pub fn http_get(&self, path: &str) -> reqwest::RequestBuilder {
-        self.http.get(format!("{}{}", self.base, path))
+        self.http.get(self.url(path))
     }
 
+    /// A raw POST request to an endpoint. See [`http_get`](Self::http_get).
+    #[doc(hidden)]
     pub fn http_post(&self, path: &str) -> reqwest::RequestBuilder {
-        self.http.post(format!("{}{}", self.base, path))
+        self.http.post(self.url(path))
+    }
+
+    /// Sends a request built with [`http_get`](Self::http_get) or [`http_post`](Self::http_post)
+    /// through the same path as the first-class endpoints, and parses the JSON response.
+    pub async fn send_managed<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T> {
+        self.execute(request).await
+    }
+
+    /// Latency of this client's requests and the estimated server clock skew.
+    ///
+    /// Clones of a client share their statistics.
+    pub fn latency_stats(&self) -> LatencyStats {
+        self.latency.stats()
+    }
+
+    /// The client's clock, see [`crate::clock`].
+    pub fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    /// The current time according to the client's clock.
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Runs `f` with [lenient](ManifoldClientBuilder::lenient_accessors)
+    /// accessors if the client was built with them, on the current thread.
+    ///
+    /// E.g. `client.with_accessors(|| user.balance())`.
+    pub fn with_accessors<R>(&self, f: impl FnOnce() -> R) -> R {
+        if self.lenient_accessors {
+            crate::types::with_lenient_accessors(true, f)
+        } else {
+            f()
+        }
+    }
+
+    /// The cache of market and user responses, if enabled; see [`crate::cache`].
+    pub fn read_cache(&self) -> Option<&ReadCache> {
+        self.read_cache.as_deref()
+    }
+
+    /// Every request of the client goes through here.
+    pub(crate) async fn send_request(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let mut request = request.build()?;
+
+        if self.production_guard && request.method() != Method::GET {
+            return Err(ManifoldError::ProductionGuard(format!(
+                "{} {} would change data on production; build the client with \
+                 ManifoldClientBuilder::allow_real_money() if that's intended",
+                request.method(),
+                request.url().path()
+            )));
+        }
+
+        if let Some(authorization) = &self.authorization {
+            request
+                .headers_mut()
+                .insert(AUTHORIZATION, authorization.clone());
+        }
+
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.acquire(request.method(), self.clock()).await;
+        }
+
+        let sent_at = Utc::now();
+        let started = Instant::now();
+
+        let response = self.http.execute(request).await?;
+
+        self.latency
+            .record(sent_at, started.elapsed(), response.headers().get(DATE));
+
+        Ok(response)
+    }
+
+    /// [`send_request`](Self::send_request), failing on non-success statuses with
+    /// the matching error, see [`ManifoldError::from_status`].
+    ///
+    /// Reads are retried according to the client's [`RetryPolicy`].
+    pub(crate) async fn execute_response(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let mut request = request.build()?;
+        let policy = self
+            .retry_policy
+            .as_ref()
+            .filter(|_| request.method() == Method::GET);
+
+        let mut retry = 0;
+
+        loop {
+            let next = policy.and_then(|_| request.try_clone());
+            let builder = reqwest::RequestBuilder::from_parts(self.http.clone(), request);
+
+            let error = match self.send_request(builder).await {
+                Ok(response) => match ManifoldError::check(response).await {
+                    Ok(response) => return Ok(response),
+                    Err(error) => error,
+                },
+                Err(error) => error,
+            };
+
+            let (Some(next), Some(delay)) = (next, policy.and_then(|p| p.delay(retry, &error)))
+            else {
+                return Err(error);
+            };
+
+            self.clock.sleep(delay).await;
+            request = next;
+            retry += 1;
+        }
+    }
+
+    /// [`execute_response`](Self::execute_response), parsing the body as JSON.
+    pub(crate) async fn execute<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T> {
+        Ok(self.execute_response(request).await?.json().await?)
     }
 
     /// `GET /v0/user/[username]`
@@ -78,13 +648,7 @@ impl ManifoldClient {
     /// Gets a user by their username. Remember that usernames may change.
     /// Requires no authorization.
     pub async fn get_user(&self, username: &str) -> Result<User> {
-        Ok(self
-            .http_get(&format!("/user/{username}"))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+        self.execute_shared(&format!("/user/{username}")).await
     }
 
     /// `GET /v0/user/by-id/[id]`
@@ -93,26 +657,14 @@ impl ManifoldClient {
     ///
     /// Requires no authorization.
     pub async fn get_user_by_id(&self, id: &str) -> Result<User> {
-        Ok(self
-            .http_get(&format!("/user/by-id/{id}"))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+        self.execute_shared(&format!("/user/by-id/{id}")).await
     }
 
     /// `GET /v0/me`
     ///
     /// Gets the currently authenticated user.
     pub async fn get_me(&self) -> Result<User> {
-        Ok(self
-            .http_get("/me")
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+        self.execute(self.http_get("/me")).await
     }
 
     /// `GET /v0/groups`
@@ -131,7 +683,7 @@ impl ManifoldClient {
             req = req.query(&[("availableToUserId", id)]);
         }
 
-        Ok(req.send().await?.error_for_status()?.json().await?)
+        self.execute(req).await
     }
 
     /// `GET /v0/group/[slug]`
@@ -140,13 +692,7 @@ impl ManifoldClient {
     ///
     /// Requires no authorization. Note: group is singular in the URL.
     pub async fn get_group(&self, slug: &str) -> Result<Group> {
-        Ok(self
-            .http_get(&format!("/group/{slug}"))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+        self.execute(self.http_get(&format!("/group/{slug}"))).await
     }
 
     /// `GET /v0/group/by-id/[id]`
@@ -155,28 +701,68 @@ impl ManifoldClient {
     ///
     /// Requires no authorization. Note: group is singular in the URL.
     pub async fn get_group_by_id(&self, id: &str) -> Result<Group> {
-        Ok(self
-            .http_get(&format!("/group/by-id/{id}"))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+        self.execute(self.http_get(&format!("/group/by-id/{id}")))
+            .await
     }
 
     /// `GET /v0/group/by-id/[id]/markets`
     ///
     /// Gets a group's markets by its unique ID.
     ///
-    /// Requires no authorization. Note: group is singular in the URL.
+    /// Requires no authorization, except for private groups. Note: group is singular in the URL.
+    ///
+    /// Asking for a private group's markets without authorization fails with
+    /// [`ManifoldError::AuthenticationRequired`].
     pub async fn get_group_markets(&self, id: &str) -> Result<Vec<LiteMarket>> {
-        Ok(self
-            .http_get(&format!("/group/by-id/{id}/markets"))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+        match self
+            .execute(self.http_get(&format!("/group/by-id/{id}/markets")))
+            .await
+        {
+            Err(e) if !self.is_authenticated() && matches!(e.status(), Some(401 | 403 | 404)) => {
+                Err(ManifoldError::AuthenticationRequired(format!(
+                    "group {id} is private or doesn't exist; its markets are only visible to authorized members"
+                )))
+            }
+            result => result,
+        }
+    }
+
+    /// `GET /v0/group/by-id/[id]/members`
+    ///
+    /// Gets the members of a group by its unique ID, with their roles.
+    ///
+    /// Requires no authorization. Note: group is singular in the URL.
+    pub async fn get_group_members(&self, id: &str) -> Result<Vec<GroupMember>> {
+        self.execute(self.http_get(&format!("/group/by-id/{id}/members")))
+            .await
+    }
+
+    /// Checks that a user has at least the `required` role in a group.
+    ///
+    /// Meant to be called before admin-only actions, to fail with a readable
+    /// [`ManifoldError::PermissionError`] instead of an HTTP 403.
+    pub async fn ensure_group_role(
+        &self,
+        group_id: &str,
+        user_id: &str,
+        required: GroupRole,
+    ) -> Result<GroupRole> {
+        let members = self.get_group_members(group_id).await?;
+
+        let role = members
+            .iter()
+            .find(|m| m.user_id() == user_id)
+            .map(|m| m.role());
+
+        match role {
+            Some(role) if role >= required => Ok(role),
+            Some(role) => Err(ManifoldError::PermissionError(format!(
+                "user {user_id} is {role:?} in group {group_id}, but {required:?} is required"
+            ))),
+            None => Err(ManifoldError::PermissionError(format!(
+                "user {user_id} is not a member of group {group_id}"
+            ))),
+        }
     }
 
     /// `GET /v0/markets`
@@ -204,22 +790,99 @@ impl ManifoldClient {
             req = req.query(&[("before", before)]);
         }
 
-        Ok(req.send().await?.error_for_status()?.json().await?)
+        self.execute(req).await
+    }
+
+    /// `GET /v0/search-markets`
+    ///
+    /// Searches markets by keyword, returning one page of results.
+    ///
+    /// Parameters:
+    ///
+    /// - `term`: Required. The search terms; an empty term lists markets without filtering by text.
+    /// - `filter`: Optional. One of `all` (the default), `open`, `closed`, `resolved`, `closing-this-month` or `closing-next-month`.
+    /// - `sort`: Optional. One of `score` (the default), `newest`, `liquidity`, `24-hour-vol`, `close-date`, `resolve-date`, `most-popular`, ...
+    /// - `contractType`: Optional. One of `ALL` (the default), `BINARY`, `MULTIPLE_CHOICE`, `PSEUDO_NUMERIC`, ...
+    /// - `limit`: Optional. How many markets to return. The maximum is 1000 and the default is 100.
+    ///
+    /// Requires no authorization. Use [`stream_search_markets`](Self::stream_search_markets)
+    /// for all results.
+    pub async fn search_markets(
+        &self,
+        term: &str,
+        filter: Option<&str>,
+        sort: Option<&str>,
+        contract_type: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<Vec<LiteMarket>> {
+        let mut query = vec![("term", term.to_owned())];
+
+        if let Some(filter) = filter {
+            query.push(("filter", filter.to_owned()));
+        }
+        if let Some(sort) = sort {
+            query.push(("sort", sort.to_owned()));
+        }
+        if let Some(contract_type) = contract_type {
+            query.push(("contractType", contract_type.to_owned()));
+        }
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+
+        self.execute(self.http_get("/search-markets").query(&query))
+            .await
     }
 
     /// `GET /v0/market/[marketId]`
     ///
     /// Gets information about a single market by ID. Includes answers, but not bets and comments. Use /bets or /comments with a market ID to retrieve bets or comments.
-
+    ///
     /// Requires no authorization.
     pub async fn get_market(&self, market_id: &str) -> Result<FullMarket> {
-        Ok(self
-            .http_get(&format!("/market/{market_id}"))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+        self.execute_shared(&format!("/market/{market_id}")).await
+    }
+
+    /// `GET /v0/market/[marketId]/positions`
+    ///
+    /// Gets the positions of the market's holders, or only of `user_id`.
+    ///
+    /// Parameters:
+    ///
+    /// * `order`: Optional. `profit` (the default) or `shares`.
+    /// * `top`: Optional. How many of the largest positions to return.
+    /// * `bottom`: Optional. How many of the smallest positions to return.
+    /// * `userId`: Optional. Only this user's position.
+    ///
+    /// Requires no authorization.
+    pub async fn get_market_positions(
+        &self,
+        market_id: &str,
+        order: Option<&str>,
+        top: Option<u32>,
+        bottom: Option<u32>,
+        user_id: Option<&str>,
+    ) -> Result<Vec<ContractMetric>> {
+        let mut query = vec![];
+
+        if let Some(order) = order {
+            query.push(("order", order.to_owned()));
+        }
+        if let Some(top) = top {
+            query.push(("top", top.to_string()));
+        }
+        if let Some(bottom) = bottom {
+            query.push(("bottom", bottom.to_string()));
+        }
+        if let Some(user_id) = user_id {
+            query.push(("userId", user_id.to_owned()));
+        }
+
+        self.execute(
+            self.http_get(&format!("/market/{market_id}/positions"))
+                .query(&query),
+        )
+        .await
     }
 
     /// `GET /v0/slug/[marketSlug]`
@@ -228,13 +891,24 @@ impl ManifoldClient {
     ///
     /// Requires no authorization.
     pub async fn get_market_by_slug(&self, market_slug: &str) -> Result<FullMarket> {
-        Ok(self
-            .http_get(&format!("/slug/{market_slug}"))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+        self.execute_shared(&format!("/slug/{market_slug}")).await
+    }
+
+    /// Downloads the market's [cover image](Market::cover_image_url), if it has one.
+    ///
+    /// Images are hosted elsewhere, so the request goes out without the API key
+    /// or the client's other headers, and isn't rate limited, retried or counted
+    /// in [`latency_stats`](Self::latency_stats); only the client's timeout applies.
+    /// The URL is up to the market's creator; images over
+    /// [`MAX_COVER_IMAGE_BYTES`] fail with [`ManifoldError::Other`].
+    pub async fn get_cover_image(&self, market: &impl Market) -> Result<Option<Vec<u8>>> {
+        let Some(url) = market.cover_image_url() else {
+            return Ok(None);
+        };
+
+        let response = ManifoldError::check(self.downloads.get(url).send().await?).await?;
+
+        Ok(Some(read_capped(response, MAX_COVER_IMAGE_BYTES).await?))
     }
 
     /// `GET /v0/users`
@@ -256,13 +930,7 @@ impl ManifoldClient {
             query.push(("before", before.to_string()));
         }
 
-        let response = self
-            .http_get("/users")
-            .query(&query)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+        self.execute(self.http_get("/users").query(&query)).await
     }
 
     /// `POST /v0/bet`
@@ -275,30 +943,39 @@ impl ManifoldClient {
     /// - `contractId`: Required. The ID of the contract to bet on.
     /// - `outcome`: Required. The outcome to bet on. For binary markets, this is YES or NO. For free response markets, this is the ID of the free response answer. For numeric markets, this is a string representing the target bucket, and an additional value parameter is required which is a number representing the target value. (Bet on numeric markets at your own peril.)
     /// - `limitProb`: Optional. A number between 0.001 and 0.999 inclusive representing the limit probability for your bet (i.e. 0.1% to 99.9% — multiply by 100 for the probability percentage). The bet will execute immediately in the direction of outcome, but not beyond this specified limit. If not all the bet is filled, the bet will remain as an open offer that can later be matched against an opposite direction bet.
+    /// - `expiresAt`: Optional. When the unfilled part of a limit order is cancelled, in milliseconds since the epoch.
+    /// - `answerId`: Optional. The answer of a `cpmm-multi-1` market to bet YES or NO on.
+    /// - `dryRun`: Optional. If true, the bet is simulated and returned without being placed.
     ///
     /// For example, if the current market probability is 50%:
     ///
     /// A M$10 bet on YES with limitProb=0.4 would not be filled until the market probability moves down to 40% and someone bets M$15 of NO to match your bet odds.
     /// A M$100 bet on YES with limitProb=0.6 would fill partially or completely depending on current unfilled limit bets and the AMM's liquidity. Any remaining portion of the bet not filled would remain to be matched against in the future.
     /// An unfilled limit order bet can be cancelled using the cancel API.
-    pub async fn post_bet(
-        &self,
-        amount: u64,
-        contract_id: &str,
-        outcome: Outcome,
-        limit_prob: Option<f64>,
-    ) -> Result<Bet> {
+    ///
+    /// The request is checked locally first, see [`BetRequest::build`].
+    pub async fn post_bet(&self, request: BetRequest) -> Result<Bet> {
+        self.place_bet(&request.build()?).await
+    }
+
+    /// [`post_bet`](Self::post_bet) with the parameters in a [`PlaceBetArgs`], sent as they are.
+    ///
+    /// Also bets on answers of `cpmm-multi-1` markets, through
+    /// [`answer_id`](PlaceBetArgs::answer_id); see [`bet_answer`](Self::bet_answer).
+    ///
+    /// The market and the bettor are dropped from the [read cache](crate::cache).
+    pub async fn place_bet(&self, args: &PlaceBetArgs) -> Result<Bet> {
         let mut body = json!(
             {
-                "amount": amount,
-                "contractId": contract_id,
+                "amount": args.amount,
+                "contractId": args.contract_id,
             }
         );
 
         {
             let body = body.as_object_mut().unwrap();
 
-            let outcome = match outcome {
+            let outcome = match &args.outcome {
                 Outcome::Yes => json!("YES"),
                 Outcome::No => json!("NO"),
                 Outcome::FreeResponse(id) => json!(id),
@@ -310,30 +987,53 @@ impl ManifoldClient {
 
             body.insert("outcome".to_owned(), outcome);
 
-            if let Some(limit_prob) = limit_prob {
+            if let Some(limit_prob) = args.limit_prob {
                 body.insert("limitProb".to_owned(), json!(limit_prob));
             }
+
+            if let Some(answer_id) = &args.answer_id {
+                body.insert("answerId".to_owned(), json!(answer_id));
+            }
+
+            if let Some(expires_at) = args.expires_at {
+                body.insert("expiresAt".to_owned(), json!(expires_at));
+            }
+
+            if args.dry_run {
+                body.insert("dryRun".to_owned(), json!(true));
+            }
         }
 
-        let response = self
-            .http_post("/bet")
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+        let bet: Result<Bet> = self.execute(self.http_post("/bet").json(&body)).await;
+
+        self.forget_market(&args.contract_id);
+        self.forget_users(bet.as_ref().ok().map(|bet| bet.user_id()));
+
+        bet
     }
 
-    /// `POST /v0/bet/[betId]/cancel`
-    ///
-    /// Cancel the limit order of a bet with the specified id. If the bet was unfilled, it will be cancelled so that no other bets will match with it. This action is irreversible.
+    /// Same as [`cancel_bet`](Self::cancel_bet), returning the order as JSON.
+    #[deprecated(note = "use `cancel_bet`")]
     pub async fn post_bet_cancel(&self, bet_id: &str) -> Result<Value> {
-        let response = self
-            .http_post(&format!("/bet/{bet_id}/cancel"))
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+        Ok(self.cancel_bet(bet_id).await?.0)
+    }
+
+    /// `POST /v0/bet/cancel/[betId]`
+    ///
+    /// Cancels an unfilled limit order, returning it with `isCancelled` set,
+    /// so that no other bets will match with it. Whatever was filled before
+    /// stays filled. This action is irreversible.
+    pub async fn cancel_bet(&self, bet_id: &str) -> Result<Bet> {
+        let bet: Bet = self
+            .execute(self.http_post(&format!("/bet/cancel/{bet_id}")))
+            .await?;
+
+        if let Ok(market_id) = bet.try_contract_id() {
+            self.forget_market(market_id);
+        }
+        self.forget_users(bet.try_user_id().ok());
+
+        Ok(bet)
     }
 
     /// `POST /v0/market`
@@ -349,6 +1049,13 @@ impl ManifoldClient {
     /// closeTime: Optional. The time at which the market will close, represented as milliseconds since the epoch. Defaults to 7 days from now.
     /// visibility: Optional. One of public (default) or unlisted. Controls whether the market can be shown on homepage and in search results.
     /// groupId: Optional. A group to create this market under.
+    /// groupIds: Optional. An array of groups to create this market under.
+    ///
+    /// The client's [default groups](ManifoldClientBuilder::default_group_ids) are added to `groupIds`,
+    /// and the created market is then added to any of them it didn't end up in.
+    /// That's best-effort: the market exists by then, so a group that can't be
+    /// added is logged as a warning, and the market is returned all the same.
+    ///
     /// For binary markets, you must also provide:
     ///
     /// initialProb: An initial probability for the market, between 1 and 99.
@@ -362,13 +1069,82 @@ impl ManifoldClient {
     ///
     /// answers: An array of strings, each of which will be a valid answer for the market.
     pub async fn post_market(&self, value: &Value) -> Result<FullMarket> {
-        let response = self
-            .http_post("/market")
-            .json(&value)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+        let default_groups = self.resolve_default_groups().await?;
+
+        let mut value = value.clone();
+
+        if !default_groups.is_empty() {
+            let mut ids = value["groupIds"].as_array().cloned().unwrap_or_default();
+
+            for group in &default_groups {
+                if !ids.iter().any(|id| id == group.id()) {
+                    ids.push(json!(group.id()));
+                }
+            }
+
+            if let Some(body) = value.as_object_mut() {
+                body.insert("groupIds".to_owned(), json!(ids));
+            }
+        }
+
+        let market: Result<FullMarket> = self.execute(self.http_post("/market").json(&value)).await;
+        self.forget_users(None);
+        let market = market?;
+
+        let slugs = market.group_slugs();
+
+        for group in &default_groups {
+            if !slugs.contains(&group.slug()) {
+                if let Err(e) = self.post_market_group(market.id(), group.id(), false).await {
+                    log::warn!(
+                        "created market {} but couldn't add it to group {}: {e}",
+                        market.id(),
+                        group.slug()
+                    );
+                }
+            }
+        }
+
+        Ok(market)
+    }
+
+    async fn resolve_default_groups(&self) -> Result<Vec<Group>> {
+        let mut groups = vec![];
+
+        for group in &self.default_groups {
+            groups.push(match group {
+                GroupRef::Id(id) => self.get_group_by_id(id).await?,
+                GroupRef::Slug(slug) => self.get_group(slug).await?,
+            });
+        }
+
+        Ok(groups)
+    }
+
+    /// `POST /v0/market/[marketId]/group`
+    ///
+    /// Adds or removes a market to/from a group.
+    ///
+    /// Parameters:
+    ///
+    /// groupId: Required. The ID of the group.
+    /// remove: Optional. If true, removes the market from the group instead.
+    pub async fn post_market_group(
+        &self,
+        market_id: &str,
+        group_id: &str,
+        remove: bool,
+    ) -> Result<Value> {
+        let result = self
+            .execute(
+                self.http_post(&format!("/market/{market_id}/group"))
+                    .json(&json!({ "groupId": group_id, "remove": remove })),
+            )
+            .await;
+
+        self.forget_market(market_id);
+
+        result
     }
 
     /// `POST /v0/market/[marketId]/add-liquidity`
@@ -377,13 +1153,143 @@ impl ManifoldClient {
     ///
     /// amount: Required. The amount of liquidity to add, in M$.
     pub async fn post_market_add_liquidity(&self, market_id: &str, amount: u64) -> Result<Value> {
-        let response = self
-            .http_post(&format!("/market/{market_id}/add-liquidity"))
-            .json(&json!({ "amount": amount }))
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+        let result = self
+            .execute(
+                self.http_post(&format!("/market/{market_id}/add-liquidity"))
+                    .json(&json!({ "amount": amount })),
+            )
+            .await;
+
+        self.forget_market(market_id);
+        self.forget_users(None);
+
+        result
+    }
+
+    /// Subsidizes a market with `amount` mana, see [`post_market_add_liquidity`](Self::post_market_add_liquidity).
+    pub async fn add_liquidity(&self, market_id: &str, amount: u64) -> Result<Value> {
+        if amount == 0 {
+            return Err(ManifoldError::Validation(
+                "liquidity to add must be positive".to_owned(),
+            ));
+        }
+
+        self.post_market_add_liquidity(market_id, amount).await
+    }
+
+    /// `POST /v0/managram`
+    ///
+    /// Sends mana to other users.
+    ///
+    /// Parameters:
+    ///
+    /// - `toIds`: Required. The IDs of the users to send mana to.
+    /// - `amount`: Required. How much mana each of them gets; at least [`MIN_MANAGRAM`].
+    /// - `message`: Optional. A note sent along with the mana.
+    ///
+    /// The total, `amount` times the number of recipients, leaves the authorized user's balance.
+    pub async fn send_managram(
+        &self,
+        to_ids: &[&str],
+        amount: u64,
+        message: &str,
+    ) -> Result<Value> {
+        if to_ids.is_empty() {
+            return Err(ManifoldError::Validation(
+                "a managram needs at least one recipient".to_owned(),
+            ));
+        }
+
+        if amount < MIN_MANAGRAM {
+            return Err(ManifoldError::Validation(format!(
+                "managrams are at least M${MIN_MANAGRAM}, not M${amount}"
+            )));
+        }
+
+        let result = self
+            .execute(self.http_post("/managram").json(&json!({
+                "toIds": to_ids,
+                "amount": amount,
+                "message": message,
+            })))
+            .await;
+
+        self.forget_users(None);
+
+        result
+    }
+
+    /// `GET /v0/managrams`
+    ///
+    /// Gets managrams, ordered by creation date descending.
+    ///
+    /// Parameters:
+    ///
+    /// - `toId`: Optional. Only managrams sent to this user.
+    /// - `fromId`: Optional. Only managrams sent by this user.
+    /// - `limit`: Optional. How many managrams to return. The maximum and the default are 100.
+    /// - `before`: Optional. Only managrams sent before this time.
+    /// - `after`: Optional. Only managrams sent after this time.
+    ///
+    /// Requires no authorization. See [`stream_managrams`](Self::stream_managrams) for all of them.
+    pub async fn get_managrams(
+        &self,
+        to_id: Option<&str>,
+        from_id: Option<&str>,
+        limit: Option<u32>,
+        before: Option<DateTime<Utc>>,
+        after: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Txn>> {
+        let mut query = vec![];
+
+        if let Some(to_id) = to_id {
+            query.push(("toId", to_id.to_owned()));
+        }
+        if let Some(from_id) = from_id {
+            query.push(("fromId", from_id.to_owned()));
+        }
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+        if let Some(before) = before {
+            query.push(("before", before.timestamp_millis().to_string()));
+        }
+        if let Some(after) = after {
+            query.push(("after", after.timestamp_millis().to_string()));
+        }
+
+        self.execute(self.http_get("/managrams").query(&query))
+            .await
+    }
+
+    /// `POST /v0/market/[marketId]/update`
+    ///
+    /// Updates a market on behalf of its creator. Only the given fields change.
+    ///
+    /// Parameters:
+    ///
+    /// - `question`: Optional. The new question.
+    /// - `descriptionMarkdown`: Optional. The new description, as markdown.
+    /// - `coverImageUrl`: Optional. The URL of the image shown on the market's card.
+    /// - `closeTime`: Optional. Milliseconds since the epoch to close the market at.
+    /// - `visibility`: Optional. `public` or `unlisted`.
+    pub async fn post_market_update(&self, market_id: &str, value: &Value) -> Result<Value> {
+        let result = self
+            .execute(
+                self.http_post(&format!("/market/{market_id}/update"))
+                    .json(&value),
+            )
+            .await;
+
+        self.forget_market(market_id);
+
+        result
+    }
+
+    /// Sets the image shown on a market's card, see [`post_market_update`](Self::post_market_update).
+    pub async fn post_market_cover_image(&self, market_id: &str, url: &str) -> Result<Value> {
+        self.post_market_update(market_id, &json!({ "coverImageUrl": url }))
+            .await
     }
 
     /// `POST /v0/market/[marketId]/close`
@@ -400,16 +1306,40 @@ impl ManifoldClient {
         if let Some(close_time) = close_time {
             body.as_object_mut()
                 .unwrap()
-                .insert("closeTime".to_owned(), json!(close_time.to_string()));
+                .insert("closeTime".to_owned(), json!(close_time));
         }
 
-        let response = self
-            .http_post(&format!("/market/{market_id}/close"))
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+        let result = self
+            .execute(
+                self.http_post(&format!("/market/{market_id}/close"))
+                    .json(&body),
+            )
+            .await;
+
+        self.forget_market(market_id);
+
+        result
+    }
+
+    /// Closes a market now, or at `close_time`, see [`post_market_close`](Self::post_market_close).
+    ///
+    /// A close time in the past (by the client's [clock](Self::clock)) is refused
+    /// here rather than by the server.
+    pub async fn close_market(
+        &self,
+        market_id: &str,
+        close_time: Option<DateTime<Utc>>,
+    ) -> Result<Value> {
+        if let Some(close_time) = close_time {
+            if close_time <= self.now() {
+                return Err(ManifoldError::Validation(format!(
+                    "close time {close_time} is in the past"
+                )));
+            }
+        }
+
+        self.post_market_close(market_id, close_time.map(|t| t.timestamp_millis() as u64))
+            .await
     }
 
     /// `POST /v0/market/[marketId]/resolve`
@@ -433,50 +1363,144 @@ impl ManifoldClient {
     /// If log scale: log10(value - min + 1) / log10(max - min + 1)
     /// Otherwise: (value - min) / (max - min)
     pub async fn post_market_resolve(&self, market_id: &str, value: &Value) -> Result<Value> {
-        let response = self
-            .http_post(&format!("/market/{market_id}/resolve"))
-            .json(&value)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+        let result = self
+            .execute(
+                self.http_post(&format!("/market/{market_id}/resolve"))
+                    .json(&value),
+            )
+            .await;
+
+        // Every holder is paid out.
+        self.forget_market(market_id);
+        self.forget_users(None);
+
+        result
     }
 
-    /// `POST /v0/market/[marketId]/sell`
+    /// `POST /v0/market/[marketId]/resolve` with a typed [`Resolution`]
     ///
-    /// Sells some quantity of shares in a binary market on behalf of the authorized user.
+    /// Checks the resolution before sending it, see [`Resolution::body`].
+    pub async fn resolve_market(&self, market_id: &str, resolution: &Resolution) -> Result<Value> {
+        self.post_market_resolve(market_id, &resolution.body()?)
+            .await
+    }
+
+    /// `POST /v0/market/[marketId]/resolve` with an `answerId`
     ///
-    /// Parameters:
+    /// Resolves one answer of a multiple choice market whose answers don't sum to one
+    /// (each answer is an independent binary market).
+    ///
+    /// Checks the resolution before sending it, see [`AnswerResolution::body`].
     ///
-    /// outcome: Optional. One of YES, or NO. If you leave it off, and you only own one kind of shares, you will sell that kind of shares.
-    /// shares: Optional. The amount of shares to sell of the outcome given above. If not provided, all the shares you own will be sold.
+    /// See [`ManifoldClient::post_resolve_answers`] to resolve several answers at once.
+    pub async fn post_resolve_answer(
+        &self,
+        market_id: &str,
+        answer_id: &str,
+        outcome: AnswerResolution,
+    ) -> Result<Value> {
+        self.post_market_resolve(market_id, &outcome.body(answer_id)?)
+            .await
+    }
+
+    /// Same as [`sell_shares`](Self::sell_shares) with whole shares and no
+    /// answer, returning the sale as JSON.
+    #[deprecated(note = "use `sell_shares`")]
     pub async fn post_market_sell(
         &self,
         market_id: &str,
         outcome: Option<Outcome>,
         shares: Option<u64>,
     ) -> Result<Value> {
+        let shares = shares.map(|shares| shares as f64);
+
+        Ok(self.sell_shares(market_id, outcome, shares, None).await?.0)
+    }
+
+    /// `POST /v0/market/[marketId]/sell`
+    ///
+    /// Sells `shares` of `outcome` (`YES` or `NO`), fractions included, or
+    /// all of them if `shares` is `None`.
+    /// Without an `outcome`, sells whichever kind of shares the user owns.
+    /// In multiple choice markets, `answer_id` picks the answer whose shares are sold.
+    ///
+    /// Returns the sale, as a bet with negative amount and shares.
+    pub async fn sell_shares(
+        &self,
+        market_id: &str,
+        outcome: Option<Outcome>,
+        shares: Option<f64>,
+        answer_id: Option<&str>,
+    ) -> Result<Bet> {
         let mut body = json!({});
 
-        if let Some(outcome) = outcome {
-            body.as_object_mut()
-                .unwrap()
-                .insert("outcome".to_owned(), json!(outcome));
-        }
+        {
+            let body = body.as_object_mut().unwrap();
 
-        if let Some(shares) = shares {
-            body.as_object_mut()
-                .unwrap()
-                .insert("shares".to_owned(), json!(shares));
+            if let Some(outcome) = outcome {
+                let outcome = match outcome {
+                    Outcome::Yes => "YES",
+                    Outcome::No => "NO",
+                    other => {
+                        return Err(ManifoldError::Validation(format!(
+                            "only YES and NO shares can be sold, not {other:?}"
+                        )))
+                    }
+                };
+                body.insert("outcome".to_owned(), json!(outcome));
+            }
+
+            if let Some(shares) = shares {
+                if shares.is_nan() || shares <= 0.0 {
+                    return Err(ManifoldError::Validation(format!(
+                        "can't sell {shares} shares"
+                    )));
+                }
+                body.insert("shares".to_owned(), json!(shares));
+            }
+
+            if let Some(answer_id) = answer_id {
+                body.insert("answerId".to_owned(), json!(answer_id));
+            }
         }
 
-        let response = self
-            .http_post(&format!("/market/{market_id}/sell"))
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+        let sale: Result<Bet> = self
+            .execute(
+                self.http_post(&format!("/market/{market_id}/sell"))
+                    .json(&body),
+            )
+            .await;
+
+        self.forget_market(market_id);
+        self.forget_users(sale.as_ref().ok().map(|sale| sale.user_id()));
+
+        sale
+    }
+
+    /// `POST /v0/market/[marketId]/answer`
+    ///
+    /// Adds a new answer to a free response or multiple choice market on behalf of the authorized user.
+    ///
+    /// Parameters:
+    ///
+    /// text: Required. The answer text.
+    ///
+    /// Returns `{"newAnswerId": ...}`. Empty and too long answers fail
+    /// locally; [`add_answer`](Self::add_answer) also checks the market.
+    pub async fn post_answer(&self, market_id: &str, text: &str) -> Result<Value> {
+        crate::answers::check_answer_text(text)?;
+
+        let result = self
+            .execute(
+                self.http_post(&format!("/market/{market_id}/answer"))
+                    .json(&json!({ "text": text })),
+            )
+            .await;
+
+        self.forget_market(market_id);
+        self.forget_users(None);
+
+        result
     }
 
     /// `POST /v0/comment`
@@ -488,14 +1512,28 @@ impl ManifoldClient {
     /// content: The comment to post, formatted as TipTap json, OR
     /// html: The comment to post, formatted as an HTML string, OR
     /// markdown: The comment to post, formatted as a markdown string.
-    pub async fn post_comment(&self, value: &Value) -> Result<Value> {
-        let response = self
-            .http_post("/comment")
-            .json(&value)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+    ///
+    /// Comments are spaced out and deduplicated according to the client's
+    /// [`CommentLimits`](ManifoldClientBuilder::comment_limits). A comment that
+    /// failed in a way that leaves it possibly posted (see
+    /// [`ManifoldError::is_not_applied`]) still counts as a duplicate.
+    pub async fn post_comment(
+        &self,
+        contract_id: &str,
+        content: CommentContent,
+    ) -> Result<Comment> {
+        let body = content.body(contract_id);
+        let hash = self.comments.reserve(&body, self.clock()).await?;
+
+        let result = self.execute(self.http_post("/comment").json(&body)).await;
+
+        // After a timeout or a server error the comment may be up already,
+        // and posting it again is exactly what the guard is for.
+        if result.as_ref().is_err_and(ManifoldError::is_not_applied) {
+            self.comments.release(hash);
+        }
+
+        result
     }
 
     /// `GET /v0/comments`
@@ -504,11 +1542,14 @@ impl ManifoldClient {
     /// contractId: Optional. Which contract to read comments for. Either an ID or slug must be specified.
     /// contractSlug: Optional.
     /// Requires no authorization.
+    ///
+    /// Returns a single page; use [`stream_comments`](Self::stream_comments) for
+    /// all comments of busy markets, or only those of a time window.
     pub async fn get_comments(
         &self,
         contract_id: Option<&str>,
         contract_slug: Option<&str>,
-    ) -> Result<Value> {
+    ) -> Result<Vec<Comment>> {
         let mut url = "/comments".to_owned();
         if let Some(contract_id) = contract_id {
             url.push_str(&format!("?contractId={contract_id}"));
@@ -516,8 +1557,7 @@ impl ManifoldClient {
             url.push_str(&format!("?contractSlug={contract_slug}"));
         }
 
-        let response = self.http_get(&url).send().await?.error_for_status()?;
-        Ok(response.json().await?)
+        self.execute(self.http_get(&url)).await
     }
 
     /// `GET /v0/bets`
@@ -532,6 +1572,8 @@ impl ManifoldClient {
     /// limit: Optional. How many bets to return. The maximum and the default are 1000.
     /// before: Optional. The ID of the bet before which the list will start. For example, if you ask for the most recent 10 bets, and then perform a second query for 10 more bets with before=[the id of the 10th bet], you will get bets 11 through 20.
     /// Requires no authorization.
+    ///
+    /// See [`get_bets_with`](Self::get_bets_with) for the other filters.
     pub async fn get_bets(
         &self,
         user_id: Option<&str>,
@@ -541,40 +1583,140 @@ impl ManifoldClient {
         limit: Option<u64>,
         before: Option<&str>,
     ) -> Result<Vec<Bet>> {
-        let mut query: Vec<(&str, &str)> = vec![];
+        self.get_bets_with(&BetsQuery {
+            user_id: user_id.map(str::to_owned),
+            username: username.map(str::to_owned),
+            contract_id: contract_id.map(str::to_owned),
+            contract_slug: contract_slug.map(str::to_owned),
+            limit,
+            before: before.map(str::to_owned),
+            ..BetsQuery::default()
+        })
+        .await
+    }
 
-        if let Some(user_id) = user_id {
-            query.push(("userId", user_id));
-        }
+    /// `GET /v0/bets` with any of its filters, including the creation time.
+    pub async fn get_bets_with(&self, query: &BetsQuery) -> Result<Vec<Bet>> {
+        self.execute(self.http_get("/bets").query(&query.params()))
+            .await
+    }
+}
 
-        if let Some(username) = username {
-            query.push(("username", username));
-        }
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
 
-        if let Some(contract_id) = contract_id {
-            query.push(("contractId", contract_id));
-        }
+    use super::*;
+    use crate::fixtures::{MockRequest, MockResponse, MockServer};
 
-        if let Some(contract_slug) = contract_slug {
-            query.push(("contractSlug", contract_slug));
-        }
+    #[tokio::test]
+    async fn downloads_cover_images_up_to_a_cap() -> Result<()> {
+        let image = "x".repeat(100);
+        let server = MockServer::start({
+            let image = image.clone();
+            move |req: &MockRequest| {
+                (req.path == "/cover.png").then(|| MockResponse::json(json!(image)))
+            }
+        })
+        .await?;
+        let client = server.client().build()?;
+        assert!(client.is_authenticated());
 
-        let limit_s = limit.map(|l| l.to_string());
+        let market = LiteMarket(json!({"coverImageUrl": format!("{}/cover.png", server.base())}));
+        let downloaded = client.get_cover_image(&market).await?.unwrap();
+        assert_eq!(downloaded, json!(image).to_string().into_bytes());
 
-        if let Some(limit) = limit_s.as_deref() {
-            query.push(("limit", limit));
-        }
+        // The key stays with the API, and the image host's clock out of the skew estimate.
+        assert_eq!(server.requests()[0].header("authorization"), None);
+        assert_eq!(client.latency_stats().requests, 0);
 
-        if let Some(before) = before {
-            query.push(("before", before));
+        let response = client
+            .downloads
+            .get(format!("{}/cover.png", server.base()))
+            .send()
+            .await?;
+        assert!(matches!(
+            read_capped(response, 50).await,
+            Err(ManifoldError::Other(_))
+        ));
+
+        assert_eq!(client.get_cover_image(&LiteMarket(json!({}))).await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keeps_comments_that_may_have_been_posted() -> Result<()> {
+        let server =
+            MockServer::start(|req: &MockRequest| match req.body["markdown"].as_str()? {
+                "slow" => Some(
+                    MockResponse::json(crate::fixtures::comment().0).delay(Duration::from_secs(2)),
+                ),
+                _ => Some(MockResponse::status(
+                    400,
+                    json!({"message": "Comment is too long"}),
+                )),
+            })
+            .await?;
+        let client = server
+            .client()
+            .timeout(Duration::from_millis(200))
+            .clock(crate::clock::MockClock::new(Utc::now()))
+            .build()?;
+        let comment = |text: &str| CommentContent::Markdown(text.to_owned());
+
+        // Timed out, so it may be up; posting it again would double it.
+        let timeout = client.post_comment("m", comment("slow")).await.unwrap_err();
+        assert!(matches!(&timeout, ManifoldError::HttpError(e) if e.is_timeout()));
+        assert!(matches!(
+            client.post_comment("m", comment("slow")).await,
+            Err(ManifoldError::DuplicateComment(_))
+        ));
+
+        // Refused, so it can be fixed and sent again.
+        for _ in 0..2 {
+            assert!(matches!(
+                client.post_comment("m", comment("rejected")).await,
+                Err(ManifoldError::ApiError { status: 400, .. })
+            ));
         }
+        assert_eq!(server.requests().len(), 3);
 
-        let response = self
-            .http_get("/bets")
-            .query(&query)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(response.json().await?)
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn adds_the_key_only_to_requests_sent_through_the_client() -> Result<()> {
+        let server =
+            MockServer::start(|_: &MockRequest| Some(MockResponse::json(json!({})))).await?;
+        let client = server.client().build()?;
+
+        client.http_post("/bet").json(&json!({})).send().await?;
+        client.send_managed::<Value>(client.http_get("/me")).await?;
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("authorization"), None);
+        assert_eq!(requests[1].header("authorization"), Some("Key mock"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sends_close_times_as_numbers() -> Result<()> {
+        let server =
+            MockServer::start(|_: &MockRequest| Some(MockResponse::json(json!({})))).await?;
+        let client = server.client().build()?;
+        let close_time = client.now() + chrono::Duration::hours(1);
+
+        client
+            .post_market_close("m", Some(close_time.timestamp_millis() as u64))
+            .await?;
+        client.close_market("m", Some(close_time)).await?;
+
+        let requests = server.requests();
+        assert_eq!(requests[0].body["closeTime"], close_time.timestamp_millis());
+        assert_eq!(requests[1].body, requests[0].body);
+
+        Ok(())
     }
 }
@@ -0,0 +1,150 @@
+//! Where the client gets the time from
+//!
+//! Everything time-dependent in the client (close-time checks, countdowns,
+//! polling watchers, trigger runs, TWAP slices, rate limits, comment spacing)
+//! asks the client's [`Clock`] for the time and for sleeps instead of the
+//! system. Tests give the client a [`MockClock`] (see
+//! [`ManifoldClientBuilder::clock`](crate::ManifoldClientBuilder::clock))
+//! to run them deterministically and without waiting.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Waits for `duration`.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+
+    /// Waits until `time`, not at all if it's passed.
+    fn sleep_until(&self, time: DateTime<Utc>) -> BoxFuture<'static, ()> {
+        self.sleep((time - self.now()).to_std().unwrap_or_default())
+    }
+}
+
+/// The system clock and tokio timers, the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Sleeping moves it forward by the duration right away, so code that sleeps
+/// runs at full speed while seeing time pass. Clones share their time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug)]
+struct MockState {
+    now: DateTime<Utc>,
+    sleeps: Vec<Duration>,
+}
+
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> MockClock {
+        MockClock {
+            state: Arc::new(Mutex::new(MockState {
+                now,
+                sleeps: vec![],
+            })),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.state.lock().unwrap().now = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.state.lock().unwrap().now += chrono::Duration::from_std(duration).unwrap_or_default();
+    }
+
+    /// Every sleep so far, in order.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.state.lock().unwrap().sleeps.clone()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.state.lock().unwrap().now
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.state.lock().unwrap().sleeps.push(duration);
+        self.advance(duration);
+
+        Box::pin(tokio::task::yield_now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+    use crate::error::ManifoldError;
+    use crate::types::*;
+    use crate::{ManifoldAuthorization, ManifoldClient};
+
+    #[tokio::test]
+    async fn mock_clock_drives_close_time_checks() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let clock = MockClock::new(start);
+        let client = ManifoldClient::builder(ManifoldAuthorization::NoAuthorization)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        let market = LiteMarket(json!({
+            "id": "m",
+            "closeTime": (start + chrono::Duration::minutes(10)).timestamp_millis(),
+        }));
+
+        assert!(market.is_active_at(client.now()));
+        assert_eq!(
+            client
+                .time_to_bet(&market, Duration::from_secs(60))
+                .unwrap(),
+            Some(Duration::from_secs(9 * 60))
+        );
+
+        client.clock().sleep(Duration::from_secs(9 * 60 + 30)).await;
+
+        assert!(matches!(
+            client.time_to_bet(&market, Duration::from_secs(60)),
+            Err(ManifoldError::MarketClosing(_))
+        ));
+
+        clock
+            .sleep_until(start + chrono::Duration::minutes(20))
+            .await;
+
+        assert!(!market.is_active_at(client.now()));
+        assert!(matches!(
+            client
+                .close_market("m", Some(start + chrono::Duration::minutes(15)))
+                .await,
+            Err(ManifoldError::Validation(_))
+        ));
+        assert_eq!(
+            clock.sleeps(),
+            [Duration::from_secs(570), Duration::from_secs(630)]
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Comment posting safeguards
+//!
+//! Manifold throttles comment posting, and bots that retry after a timeout
+//! tend to post the same report twice. [`ManifoldClient::post_comment`](crate::ManifoldClient::post_comment)
+//! therefore spaces comments out and refuses to post identical content twice
+//! within a window; see [`CommentLimits`].
+//!
+//! Comments can be written as markdown, HTML or TipTap JSON; see [`CommentContent`].
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::clock::Clock;
+use crate::error::{ManifoldError, Result};
+
+/// The body of a comment, in one of the formats `POST /v0/comment` accepts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommentContent {
+    Markdown(String),
+    Html(String),
+    /// Rich text as TipTap JSON, the format comments are stored in.
+    TipTap(Value),
+}
+
+impl CommentContent {
+    /// The request body for commenting on `contract_id`.
+    pub fn body(&self, contract_id: &str) -> Value {
+        let (field, content) = match self {
+            CommentContent::Markdown(markdown) => ("markdown", json!(markdown)),
+            CommentContent::Html(html) => ("html", json!(html)),
+            CommentContent::TipTap(content) => ("content", content.clone()),
+        };
+
+        json!({ "contractId": contract_id, field: content })
+    }
+}
+
+/// Limits applied to comments posted by a client.
+#[derive(Debug, Clone)]
+pub struct CommentLimits {
+    /// Minimum time between two comments; posting sooner waits.
+    pub min_interval: Duration,
+    /// Posting the same content again within this window fails
+    /// with [`ManifoldError::DuplicateComment`].
+    pub dedupe_window: Duration,
+}
+
+impl Default for CommentLimits {
+    fn default() -> Self {
+        CommentLimits {
+            min_interval: Duration::from_secs(2),
+            dedupe_window: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct GuardState {
+    next_slot: Option<DateTime<Utc>>,
+    recent: HashMap<u64, DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct CommentGuard {
+    pub(crate) limits: CommentLimits,
+    state: Mutex<GuardState>,
+}
+
+fn content_hash(comment: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    comment.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+impl CommentGuard {
+    pub(crate) fn new(limits: CommentLimits) -> CommentGuard {
+        CommentGuard {
+            limits,
+            state: Default::default(),
+        }
+    }
+
+    /// Reserves a slot for posting `comment`, waiting for it on `clock` if needed.
+    ///
+    /// Returns the content hash, to be passed to [`release`](Self::release) if posting fails.
+    pub(crate) async fn reserve(&self, comment: &Value, clock: &dyn Clock) -> Result<u64> {
+        let hash = content_hash(comment);
+        let now = clock.now();
+
+        let slot = {
+            let mut state = self.state.lock().unwrap();
+
+            let window = self.limits.dedupe_window;
+            state
+                .recent
+                .retain(|_, posted| (now - *posted).to_std().unwrap_or_default() < window);
+
+            if state.recent.contains_key(&hash) {
+                return Err(ManifoldError::DuplicateComment(format!(
+                    "the same comment was already posted in the last {window:?}"
+                )));
+            }
+
+            let slot = state.next_slot.map_or(now, |next| next.max(now));
+
+            state.next_slot = Some(
+                slot + chrono::Duration::from_std(self.limits.min_interval).unwrap_or_default(),
+            );
+            state.recent.insert(hash, slot);
+
+            slot
+        };
+
+        if slot > now {
+            clock.sleep_until(slot).await;
+        }
+
+        Ok(hash)
+    }
+
+    /// Forgets a reserved comment that surely wasn't posted, so it can be retried.
+    pub(crate) fn release(&self, hash: u64) {
+        self.state.lock().unwrap().recent.remove(&hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[tokio::test]
+    async fn spaces_and_dedupes_comments_by_the_clock() -> Result<()> {
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        let guard = CommentGuard::default();
+        let comment = |text: &str| CommentContent::Markdown(text.to_owned()).body("m");
+
+        guard.reserve(&comment("one"), &clock).await?;
+        guard.reserve(&comment("two"), &clock).await?;
+        assert_eq!(clock.sleeps(), [Duration::from_secs(2)]);
+
+        assert!(matches!(
+            guard.reserve(&comment("one"), &clock).await,
+            Err(ManifoldError::DuplicateComment(_))
+        ));
+
+        clock.advance(Duration::from_secs(60 * 60));
+        guard.reserve(&comment("one"), &clock).await?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn content_goes_in_its_own_field() {
+        assert_eq!(
+            CommentContent::Markdown("**hi**".to_owned()).body("m"),
+            json!({"contractId": "m", "markdown": "**hi**"})
+        );
+        assert_eq!(
+            CommentContent::Html("<b>hi</b>".to_owned()).body("m"),
+            json!({"contractId": "m", "html": "<b>hi</b>"})
+        );
+
+        let doc = json!({"type": "doc", "content": []});
+        assert_eq!(
+            CommentContent::TipTap(doc.clone()).body("m"),
+            json!({"contractId": "m", "content": doc})
+        );
+    }
+}
@@ -0,0 +1,630 @@
+//! Typed configuration for bots and tools built on the client
+//!
+//! Deployments keep credentials, safety limits, strategy parameters and the
+//! markets to watch in a file instead of in code:
+//!
+//! ```toml
+//! api_key = "..."
+//! markets = ["market-id-1", "market-id-2"]
+//!
+//! [safety]
+//! close_margin_secs = 30
+//! max_bet = 100
+//! max_data_age_secs = 60
+//!
+//! [comments]
+//! min_interval_secs = 5
+//!
+//! [format]
+//! thousands_separator = ","
+//! utc_offset = "+02:00"
+//! relative_times = true
+//!
+//! [rate_limit]
+//! reads_per_minute = 300
+//! bets_per_minute = 200
+//!
+//! [strategies.momentum]
+//! threshold = 0.05
+//! bankroll_fraction = 0.1
+//! ```
+//!
+//! Any value can be overridden from the environment, see [`Config::apply_env`].
+//! [`ManifoldClientBuilder::from_env`](crate::ManifoldClientBuilder::from_env)
+//! builds a client from the environment alone the same way.
+//!
+//! A running [`BotRuntime`] can pick up edits to the file without restarting,
+//! see `ConfigWatcher` and [`BotRuntime::apply_config`].
+//!
+//! Reading files requires the `config` feature.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "config")]
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::bot::{AuditKind, BotRuntime, SafetyRails};
+use crate::comments::CommentLimits;
+use crate::error::{ManifoldError, Result};
+use crate::format::FormatOptions;
+use crate::ratelimit::RateLimitConfig;
+use crate::secret::SecretString;
+use crate::storage::Storage;
+use crate::{ManifoldAuthorization, ManifoldClient, ManifoldClientBuilder};
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SafetyConfig {
+    pub close_margin_secs: Option<u64>,
+    pub max_bet: Option<u64>,
+    pub max_data_age_secs: Option<u64>,
+}
+
+impl SafetyConfig {
+    pub fn rails(&self) -> SafetyRails {
+        let defaults = SafetyRails::default();
+
+        SafetyRails {
+            close_margin: self
+                .close_margin_secs
+                .map_or(defaults.close_margin, Duration::from_secs),
+            max_bet: self.max_bet.or(defaults.max_bet),
+            max_data_age: self
+                .max_data_age_secs
+                .map(Duration::from_secs)
+                .or(defaults.max_data_age),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CommentConfig {
+    pub min_interval_secs: Option<u64>,
+    pub dedupe_window_secs: Option<u64>,
+}
+
+impl CommentConfig {
+    pub fn limits(&self) -> CommentLimits {
+        let defaults = CommentLimits::default();
+
+        CommentLimits {
+            min_interval: self
+                .min_interval_secs
+                .map_or(defaults.min_interval, Duration::from_secs),
+            dedupe_window: self
+                .dedupe_window_secs
+                .map_or(defaults.dedupe_window, Duration::from_secs),
+        }
+    }
+}
+
+/// How reports and messages show mana and times, see [`FormatOptions`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FormatConfig {
+    pub thousands_separator: Option<char>,
+    pub decimal_separator: Option<char>,
+    /// `+02:00`, `-05:30`, ...; UTC if not set.
+    pub utc_offset: Option<String>,
+    pub relative_times: bool,
+}
+
+impl FormatConfig {
+    pub fn options(&self) -> Result<FormatOptions> {
+        let defaults = FormatOptions::default();
+
+        let timezone = match &self.utc_offset {
+            Some(offset) => offset
+                .parse()
+                .map_err(|e| config_error(format!("format.utc_offset {offset:?}: {e}")))?,
+            None => defaults.timezone,
+        };
+
+        Ok(FormatOptions {
+            thousands_separator: self.thousands_separator,
+            decimal_separator: self.decimal_separator.unwrap_or(defaults.decimal_separator),
+            timezone,
+            relative_times: self.relative_times,
+        })
+    }
+}
+
+/// Everything a deployment configures, see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Never printed, and left out when the config is serialized, so a
+    /// config written back out doesn't read back with `[redacted]` as the key.
+    #[serde(skip_serializing)]
+    pub api_key: Option<SecretString>,
+    pub base_url: Option<String>,
+    /// See [`ManifoldClientBuilder::timeout`]; may be fractional.
+    pub timeout_secs: Option<f64>,
+    /// See [`ManifoldClientBuilder::requests_per_minute`]; overrides `rate_limit`.
+    pub requests_per_minute: Option<u32>,
+    /// See [`ManifoldClientBuilder::allow_real_money`].
+    pub allow_real_money: bool,
+    pub safety: SafetyConfig,
+    pub comments: CommentConfig,
+    pub format: FormatConfig,
+    /// See [`ManifoldClientBuilder::rate_limit`]; the default budgets if not set.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Markets the bot watches.
+    pub markets: Vec<String>,
+    /// Parameters of each strategy, by strategy id; see [`strategy`](Self::strategy).
+    pub strategies: BTreeMap<String, Value>,
+}
+
+fn config_error(e: impl std::fmt::Display) -> ManifoldError {
+    ManifoldError::ConfigError(e.to_string())
+}
+
+impl Config {
+    #[cfg(feature = "config")]
+    pub fn from_toml_str(text: &str) -> Result<Config> {
+        toml::from_str(text).map_err(config_error)
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(text: &str) -> Result<Config> {
+        serde_yaml::from_str(text).map_err(config_error)
+    }
+
+    /// Reads a config file, TOML or (with the `yaml` feature) YAML depending
+    /// on its extension, and applies [environment overrides](Self::apply_env).
+    #[cfg(feature = "config")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+
+        let mut config = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Config::from_toml_str(&text)?,
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => Config::from_yaml_str(&text)?,
+            _ => {
+                return Err(config_error(format!(
+                    "unsupported config file type: {}",
+                    path.display()
+                )))
+            }
+        };
+
+        config.apply_env()?;
+
+        Ok(config)
+    }
+
+    /// Overrides values from `MANIFOLD_*` environment variables.
+    ///
+    /// | Variable | Value |
+    /// |---|---|
+    /// | `MANIFOLD_API_KEY` | `api_key`, unset if blank |
+    /// | `MANIFOLD_BASE_URL` | `base_url` |
+    /// | `MANIFOLD_TIMEOUT_SECS` | `timeout_secs`, a positive number |
+    /// | `MANIFOLD_REQUESTS_PER_MINUTE` | `requests_per_minute`, at least 1 |
+    /// | `MANIFOLD_ALLOW_REAL_MONEY` | `allow_real_money`, `true` or `false` |
+    /// | `MANIFOLD_MARKETS` | `markets`, comma-separated |
+    /// | `MANIFOLD_SAFETY_CLOSE_MARGIN_SECS` | `safety.close_margin_secs` |
+    /// | `MANIFOLD_SAFETY_MAX_BET` | `safety.max_bet` |
+    /// | `MANIFOLD_SAFETY_MAX_DATA_AGE_SECS` | `safety.max_data_age_secs` |
+    pub fn apply_env(&mut self) -> Result<()> {
+        self.apply_vars(std::env::vars())
+    }
+
+    /// [`apply_env`](Self::apply_env) with the given variables instead of the environment.
+    ///
+    /// Invalid values fail with [`ManifoldError::ConfigError`] naming the variable.
+    pub fn apply_vars(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<()> {
+        fn invalid(name: &str, value: &str, reason: impl std::fmt::Display) -> ManifoldError {
+            config_error(format!("{name}={value:?}: {reason}"))
+        }
+
+        fn parse<T: std::str::FromStr>(name: &str, value: &str) -> Result<T>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value.trim().parse().map_err(|e| invalid(name, value, e))
+        }
+
+        let number = parse::<u64>;
+
+        for (name, value) in vars {
+            match name.as_str() {
+                "MANIFOLD_API_KEY" => {
+                    let key = value.trim();
+                    self.api_key = (!key.is_empty()).then(|| key.into());
+                }
+                "MANIFOLD_BASE_URL" => self.base_url = Some(value.trim().to_owned()),
+                "MANIFOLD_TIMEOUT_SECS" => {
+                    let secs: f64 = parse(&name, &value)?;
+
+                    if !(secs.is_finite() && secs > 0.0) {
+                        return Err(invalid(&name, &value, "not a positive number of seconds"));
+                    }
+
+                    self.timeout_secs = Some(secs);
+                }
+                "MANIFOLD_REQUESTS_PER_MINUTE" => {
+                    let requests: u32 = parse(&name, &value)?;
+
+                    if requests == 0 {
+                        return Err(invalid(&name, &value, "must be at least 1"));
+                    }
+
+                    self.requests_per_minute = Some(requests);
+                }
+                "MANIFOLD_ALLOW_REAL_MONEY" => self.allow_real_money = parse(&name, &value)?,
+                "MANIFOLD_MARKETS" => {
+                    self.markets = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|m| !m.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                }
+                "MANIFOLD_SAFETY_CLOSE_MARGIN_SECS" => {
+                    self.safety.close_margin_secs = Some(number(&name, &value)?)
+                }
+                "MANIFOLD_SAFETY_MAX_BET" => self.safety.max_bet = Some(number(&name, &value)?),
+                "MANIFOLD_SAFETY_MAX_DATA_AGE_SECS" => {
+                    self.safety.max_data_age_secs = Some(number(&name, &value)?)
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A client with the configured credentials, base URL, timeout, comment and rate limits.
+    pub fn client(&self) -> Result<ManifoldClient> {
+        self.builder()?.build()
+    }
+
+    /// A builder with the settings of [`client`](Self::client), to change some before building.
+    ///
+    /// Fails with [`ManifoldError::ConfigError`] on a timeout that isn't a
+    /// positive number of seconds.
+    pub fn builder(&self) -> Result<ManifoldClientBuilder> {
+        let auth = match &self.api_key {
+            Some(key) => ManifoldAuthorization::ApiKey(key.expose_secret().to_owned()),
+            None => ManifoldAuthorization::NoAuthorization,
+        };
+
+        let mut builder = ManifoldClient::builder(auth).comment_limits(self.comments.limits());
+
+        if let Some(base) = &self.base_url {
+            builder = builder.base(base);
+        }
+
+        if let Some(secs) = self.timeout_secs {
+            let timeout = Duration::try_from_secs_f64(secs)
+                .ok()
+                .filter(|timeout| !timeout.is_zero())
+                .ok_or_else(|| {
+                    config_error(format!("timeout_secs {secs} is not a positive number"))
+                })?;
+
+            builder = builder.timeout(timeout);
+        }
+
+        if self.allow_real_money {
+            builder = builder.allow_real_money();
+        }
+
+        if let Some(rate_limit) = self.rate_limit {
+            builder = builder.rate_limit(rate_limit);
+        }
+
+        if let Some(requests) = self.requests_per_minute {
+            builder = builder.requests_per_minute(requests);
+        }
+
+        Ok(builder)
+    }
+
+    /// The parameters of strategy `id`, deserialized into the strategy's own type.
+    pub fn strategy<T: DeserializeOwned>(&self, id: &str) -> Result<T> {
+        let params = self
+            .strategies
+            .get(id)
+            .ok_or_else(|| config_error(format!("no parameters for strategy {id:?}")))?;
+
+        serde_json::from_value(params.clone())
+            .map_err(|e| config_error(format!("strategy {id:?}: {e}")))
+    }
+}
+
+/// Reloads a config file when its contents change.
+#[cfg(feature = "config")]
+#[derive(Debug, Clone)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    last: Option<String>,
+}
+
+#[cfg(feature = "config")]
+impl ConfigWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> ConfigWatcher {
+        ConfigWatcher {
+            path: path.into(),
+            last: None,
+        }
+    }
+
+    /// The config, if the file changed since the last call (or this is the first call).
+    ///
+    /// A file that fails to parse is reported once and then ignored until it changes again,
+    /// so a half-saved edit doesn't stop a running bot.
+    pub fn poll(&mut self) -> Result<Option<Config>> {
+        let text = std::fs::read_to_string(&self.path)?;
+
+        if self.last.as_deref() == Some(text.as_str()) {
+            return Ok(None);
+        }
+
+        self.last = Some(text);
+
+        Config::load(&self.path).map(Some)
+    }
+}
+
+/// `{"a": {"b": 1}}` → `{"a.b": 1}`; arrays and scalars are leaves.
+fn flatten(prefix: &str, value: &Value, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+
+                flatten(&key, value, out);
+            }
+        }
+        _ => {
+            out.insert(prefix.to_owned(), value.clone());
+        }
+    }
+}
+
+/// The values a runtime uses, flattened to dotted keys; unset values are shown with their defaults.
+fn runtime_settings(
+    rails: &SafetyRails,
+    markets: &[String],
+    strategies: &BTreeMap<String, Value>,
+) -> BTreeMap<String, Value> {
+    let mut settings = BTreeMap::new();
+
+    settings.insert(
+        "safety.close_margin_secs".to_owned(),
+        rails.close_margin.as_secs().into(),
+    );
+    settings.insert("safety.max_bet".to_owned(), rails.max_bet.into());
+    settings.insert(
+        "safety.max_data_age_secs".to_owned(),
+        rails.max_data_age.map(|age| age.as_secs()).into(),
+    );
+    settings.insert("markets".to_owned(), markets.into());
+
+    for (id, params) in strategies {
+        flatten(&format!("strategies.{id}"), params, &mut settings);
+    }
+
+    settings
+}
+
+impl<S: Storage> BotRuntime<S> {
+    /// Applies the safety rails, markets and strategy parameters of `config`,
+    /// recording an [`AuditKind::ConfigChanged`] event for each value that changed.
+    ///
+    /// Credentials and other client settings need a new client and are not applied.
+    /// Returns the number of changed values.
+    pub fn apply_config(&mut self, config: &Config) -> Result<usize> {
+        let rails = config.safety.rails();
+
+        let old = runtime_settings(&self.rails, &self.markets, &self.strategy_params);
+        let new = runtime_settings(&rails, &config.markets, &config.strategies);
+
+        let mut changes = 0;
+
+        for key in old
+            .keys()
+            .chain(new.keys())
+            .collect::<std::collections::BTreeSet<_>>()
+        {
+            let (old, new) = (
+                old.get(key).cloned().unwrap_or(Value::Null),
+                new.get(key).cloned().unwrap_or(Value::Null),
+            );
+
+            if old != new {
+                self.audit.record(
+                    None,
+                    AuditKind::ConfigChanged {
+                        key: key.clone(),
+                        old,
+                        new,
+                    },
+                )?;
+                changes += 1;
+            }
+        }
+
+        self.rails = rails;
+        self.markets = config.markets.clone();
+        self.strategy_params = config.strategies.clone();
+
+        Ok(changes)
+    }
+
+    /// Applies the watched file's config if it changed; see [`apply_config`](Self::apply_config).
+    #[cfg(feature = "config")]
+    pub fn reload_config(&mut self, watcher: &mut ConfigWatcher) -> Result<usize> {
+        match watcher.poll()? {
+            Some(config) => self.apply_config(&config),
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "config")]
+    const EXAMPLE: &str = r#"
+        api_key = "from-file"
+        markets = ["a", "b"]
+
+        [safety]
+        max_bet = 100
+
+        [format]
+        decimal_separator = ","
+        utc_offset = "-05:30"
+
+        [rate_limit]
+        bets_per_minute = 50
+
+        [strategies.momentum]
+        threshold = 0.05
+    "#;
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn reloads_into_runtime() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("config-{}.toml", std::process::id()));
+        std::fs::write(&path, EXAMPLE)?;
+
+        let client = ManifoldClient::new(ManifoldAuthorization::NoAuthorization)?;
+        let mut runtime = BotRuntime::new(
+            client,
+            crate::storage::MemoryStorage::new(),
+            crate::bot::AuditLog::new(),
+        )?;
+        let mut watcher = ConfigWatcher::new(&path);
+
+        assert!(runtime.reload_config(&mut watcher)? > 0);
+        assert_eq!(runtime.rails.max_bet, Some(100));
+        assert_eq!(runtime.reload_config(&mut watcher)?, 0);
+
+        std::fs::write(&path, EXAMPLE.replace("0.05", "0.07"))?;
+        let events = runtime.audit.events().len();
+
+        assert_eq!(runtime.reload_config(&mut watcher)?, 1);
+        assert_eq!(
+            runtime.audit.events()[events].kind,
+            AuditKind::ConfigChanged {
+                key: "strategies.momentum.threshold".to_owned(),
+                old: 0.05.into(),
+                new: 0.07.into(),
+            }
+        );
+
+        std::fs::remove_file(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_client_settings_from_vars() -> Result<()> {
+        let mut config = Config::default();
+        config.apply_vars([
+            ("MANIFOLD_API_KEY".to_owned(), " ".to_owned()),
+            ("MANIFOLD_TIMEOUT_SECS".to_owned(), "2.5".to_owned()),
+            ("MANIFOLD_REQUESTS_PER_MINUTE".to_owned(), "100".to_owned()),
+            ("HOME".to_owned(), "/root".to_owned()),
+        ])?;
+
+        assert_eq!(config.api_key, None);
+        assert_eq!(config.timeout_secs, Some(2.5));
+        assert_eq!(config.requests_per_minute, Some(100));
+        assert!(!config.client()?.is_authenticated());
+
+        for (name, value) in [
+            ("MANIFOLD_TIMEOUT_SECS", "0"),
+            ("MANIFOLD_TIMEOUT_SECS", "soon"),
+            ("MANIFOLD_REQUESTS_PER_MINUTE", "0"),
+        ] {
+            assert!(matches!(
+                config.apply_vars([(name.to_owned(), value.to_owned())]),
+                Err(ManifoldError::ConfigError(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "config")]
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Momentum {
+        threshold: f64,
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn parses_and_overrides() -> Result<()> {
+        let mut config = Config::from_toml_str(EXAMPLE)?;
+
+        assert_eq!(config.markets, vec!["a", "b"]);
+        assert_eq!(config.safety.rails().max_bet, Some(100));
+        assert_eq!(
+            config.strategy::<Momentum>("momentum")?,
+            Momentum { threshold: 0.05 }
+        );
+        assert!(config.strategy::<Momentum>("missing").is_err());
+
+        let format = config.format.options()?;
+        assert_eq!(format.mana(3.5), "M$3,50");
+        assert_eq!(format.timezone.local_minus_utc(), -(5 * 3600 + 30 * 60));
+
+        assert_eq!(
+            config.rate_limit,
+            Some(RateLimitConfig {
+                reads_per_minute: 400,
+                bets_per_minute: 50,
+            })
+        );
+
+        config.apply_vars([
+            ("MANIFOLD_API_KEY".to_owned(), "from-env".to_owned()),
+            ("MANIFOLD_MARKETS".to_owned(), "c, d".to_owned()),
+            (
+                "MANIFOLD_SAFETY_CLOSE_MARGIN_SECS".to_owned(),
+                "30".to_owned(),
+            ),
+            (
+                "MANIFOLD_SAFETY_MAX_DATA_AGE_SECS".to_owned(),
+                "90".to_owned(),
+            ),
+        ])?;
+
+        assert_eq!(
+            config.api_key.as_ref().map(SecretString::expose_secret),
+            Some("from-env")
+        );
+        assert_eq!(config.markets, vec!["c", "d"]);
+        assert_eq!(config.safety.rails().close_margin, Duration::from_secs(30));
+        assert_eq!(
+            config.safety.rails().max_data_age,
+            Some(Duration::from_secs(90))
+        );
+
+        assert!(config
+            .apply_vars([("MANIFOLD_SAFETY_MAX_BET".to_owned(), "lots".to_owned())])
+            .is_err());
+
+        let written = serde_json::to_value(&config)?;
+        assert!(written.get("api_key").is_none());
+        assert_eq!(serde_json::from_value::<Config>(written)?.api_key, None);
+
+        Ok(())
+    }
+}
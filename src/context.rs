@@ -0,0 +1,189 @@
+//! Everything a bot looks at before trading on a market, in one call
+//!
+//! [`ManifoldClient::get_market_context`] fetches the market, its comments,
+//! recent bets, positions and the creator's profile concurrently.
+//! [`ManifoldClient::get_related_markets`] finds markets on the same topic.
+
+use futures_util::{try_join, StreamExt, TryStreamExt};
+use serde_json::Value;
+
+use crate::error::{ManifoldError, Result};
+use crate::text::{fold, similarity};
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// How many of the most recent bets a [`MarketContext`] includes.
+pub const CONTEXT_BETS: u64 = 100;
+
+/// Words left out of the searches for related markets.
+const STOP_WORDS: [&str; 24] = [
+    "the", "and", "for", "will", "before", "after", "end", "with", "than", "more", "less", "any",
+    "does", "what", "who", "which", "when", "how", "this", "that", "there", "from", "into", "have",
+];
+
+/// The words of `question` worth searching for: at most `max_words`, in order,
+/// leaving out short words, numbers and [`STOP_WORDS`].
+pub fn search_terms(question: &str, max_words: usize) -> String {
+    let mut words: Vec<String> = vec![];
+
+    for word in fold(question).split(' ') {
+        if word.len() < 3
+            || word.chars().all(|c| c.is_ascii_digit())
+            || STOP_WORDS.contains(&word)
+            || words.iter().any(|w| w == word)
+        {
+            continue;
+        }
+
+        words.push(word.to_owned());
+        if words.len() == max_words {
+            break;
+        }
+    }
+
+    words.join(" ")
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketContext {
+    pub market: FullMarket,
+    pub creator: User,
+    pub comments: Vec<Value>,
+    /// The most recent bets, newest first; at most [`CONTEXT_BETS`].
+    pub bets: Vec<Bet>,
+    pub positions: Vec<Position>,
+}
+
+impl ManifoldClient {
+    /// Fetches a market with its comments, recent bets, positions and creator.
+    ///
+    /// All requests are made concurrently (the creator right after the market);
+    /// fails if any of them fails.
+    pub async fn get_market_context(&self, market_id: &str) -> Result<MarketContext> {
+        let market_and_creator = async {
+            let market = self.get_market(market_id).await?;
+            let creator = self.get_user_by_id(market.creator_id()).await?;
+
+            Result::Ok((market, creator))
+        };
+
+        let comments = async {
+            let comments = self.get_comments(Some(market_id), None).await?;
+
+            Result::Ok(comments.into_iter().map(|c| c.0).collect::<Vec<_>>())
+        };
+
+        let bets = BetsQuery::new().contract_id(market_id).limit(CONTEXT_BETS);
+
+        let ((market, creator), comments, bets, positions) = try_join!(
+            market_and_creator,
+            comments,
+            self.get_bets_with(&bets),
+            self.get_market_positions(market_id, None, None, None, None),
+        )?;
+
+        Ok(MarketContext {
+            market,
+            creator,
+            comments,
+            bets,
+            positions,
+        })
+    }
+
+    /// `GET /v0/get-related-markets`
+    ///
+    /// Markets on the same topic as `market_id`, like the site's "related
+    /// questions", most related first.
+    ///
+    /// Where that route isn't available, falls back to
+    /// [searching](Self::stream_search_markets) for the main words of the
+    /// question (see [`search_terms`]) and ranking the results by how
+    /// [similar](crate::text::similarity) their questions are.
+    pub async fn get_related_markets(
+        &self,
+        market_id: &str,
+        limit: usize,
+    ) -> Result<Vec<LiteMarket>> {
+        let response = self
+            .execute::<Value>(
+                self.http_get("/get-related-markets")
+                    .query(&[("contractId", market_id), ("limit", &limit.to_string())]),
+            )
+            .await;
+
+        let body = match response {
+            Ok(body) => Some(body),
+            Err(e) if e.is_not_found() => None,
+            Err(e) => return Err(e),
+        };
+
+        if let Some(body) = body {
+            let markets = match body {
+                Value::Array(markets) => markets,
+                Value::Object(mut body) => match body.remove("marketsFromEmbeddings") {
+                    Some(Value::Array(markets)) => markets,
+                    _ => {
+                        return Err(ManifoldError::SchemaError(
+                            "Related markets are missing".to_owned(),
+                            Some(Value::Object(body)),
+                        ))
+                    }
+                },
+                other => {
+                    return Err(ManifoldError::SchemaError(
+                        "Related markets are not an array".to_owned(),
+                        Some(other),
+                    ))
+                }
+            };
+
+            return Ok(markets
+                .into_iter()
+                .map(LiteMarket)
+                .filter(|m| m.try_id().is_ok_and(|id| id != market_id))
+                .take(limit)
+                .collect());
+        }
+
+        let market = self.get_market(market_id).await?;
+        let question = market.question();
+
+        let terms = match search_terms(question, 4) {
+            terms if terms.is_empty() => question.to_owned(),
+            terms => terms,
+        };
+
+        let mut candidates: Vec<LiteMarket> = self
+            .stream_search_markets(&terms, None, None, None)
+            .try_filter(|m| std::future::ready(m.try_id().is_ok_and(|id| id != market_id)))
+            .take(limit * 3)
+            .try_collect()
+            .await?;
+
+        candidates.sort_by(|a, b| {
+            similarity(question, b.question()).total_cmp(&similarity(question, a.question()))
+        });
+        candidates.truncate(limit);
+
+        Ok(candidates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_search_terms() {
+        assert_eq!(
+            search_terms("Will the Fed cut interest rates before June 2025?", 4),
+            "fed cut interest rates"
+        );
+        assert_eq!(
+            search_terms("Will SpaceX's Starship reach orbit?", 2),
+            "spacex starship"
+        );
+        assert_eq!(search_terms("Will it be?", 4), "");
+    }
+}
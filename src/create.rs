@@ -0,0 +1,568 @@
+//! Market creation helpers on top of `POST /v0/market`
+//!
+//! - [`ManifoldClient::create_market`] with a [`CreateMarketBuilder`], which
+//!   checks the parameters of each market type before sending them
+//! - [`ManifoldClient::create_market_if_absent`] for restartable creation jobs
+//! - [`ManifoldClient::clone_market`] for weekly-series style markets
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::answers::check_answer_text;
+use crate::error::{ManifoldError, Result};
+use crate::text::{normalize, normalize_question};
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// The result of [`ManifoldClient::create_market_if_absent`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum CreatedOrExisting {
+    Created(FullMarket),
+    Existing(FullMarket),
+}
+
+impl CreatedOrExisting {
+    pub fn market(&self) -> &FullMarket {
+        match self {
+            CreatedOrExisting::Created(m) | CreatedOrExisting::Existing(m) => m,
+        }
+    }
+
+    pub fn into_market(self) -> FullMarket {
+        match self {
+            CreatedOrExisting::Created(m) | CreatedOrExisting::Existing(m) => m,
+        }
+    }
+
+    pub fn was_created(&self) -> bool {
+        matches!(self, CreatedOrExisting::Created(_))
+    }
+}
+
+/// Parameters of a new market, checked against its type by [`build`](Self::build).
+///
+/// ```
+/// # use manifold_markets::create::CreateMarketBuilder;
+/// let args = CreateMarketBuilder::binary("Will it rain tomorrow?")
+///     .initial_prob(30)
+///     .group_id("weather")
+///     .build()?;
+/// # Ok::<(), manifold_markets::error::ManifoldError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct CreateMarketBuilder {
+    args: CreateMarketArgs,
+    max_cost: Option<u64>,
+}
+
+impl CreateMarketBuilder {
+    pub fn binary(question: &str) -> CreateMarketBuilder {
+        CreateMarketBuilder::new(OutcomeType::Binary, question)
+    }
+
+    pub fn pseudo_numeric(question: &str, min: f64, max: f64) -> CreateMarketBuilder {
+        CreateMarketBuilder::new(OutcomeType::PseudoNumeric, question)
+            .min(min)
+            .max(max)
+    }
+
+    /// A market anyone can add answers to.
+    pub fn free_response(question: &str) -> CreateMarketBuilder {
+        CreateMarketBuilder::new(OutcomeType::FreeResponse, question)
+    }
+
+    pub fn multiple_choice(question: &str, answers: &[&str]) -> CreateMarketBuilder {
+        CreateMarketBuilder::new(OutcomeType::MultipleChoice, question).answers(answers)
+    }
+
+    /// A market of any type; the type's required parameters still have to be set.
+    pub fn new(outcome_type: OutcomeType, question: &str) -> CreateMarketBuilder {
+        CreateMarketBuilder {
+            args: CreateMarketArgs::new(outcome_type, question),
+            max_cost: None,
+        }
+    }
+
+    /// A plain string or TipTap JSON.
+    pub fn description(mut self, description: impl Into<Value>) -> Self {
+        self.args.description = Some(description.into());
+        self
+    }
+
+    pub fn description_markdown(mut self, markdown: &str) -> Self {
+        self.args.description_markdown = Some(markdown.to_owned());
+        self
+    }
+
+    pub fn close_time(mut self, close_time: DateTime<Utc>) -> Self {
+        self.args.close_time = Some(close_time.timestamp_millis());
+        self
+    }
+
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.args.visibility = Some(visibility);
+        self
+    }
+
+    /// Adds the market to a group; can be called several times.
+    pub fn group_id(mut self, group_id: &str) -> Self {
+        self.args.group_ids.push(group_id.to_owned());
+        self
+    }
+
+    pub fn cover_image_url(mut self, url: &str) -> Self {
+        self.args.cover_image_url = Some(url.to_owned());
+        self
+    }
+
+    /// Binary markets: the starting probability in percent, 1 to 99.
+    pub fn initial_prob(mut self, percent: u8) -> Self {
+        self.args.initial_prob = Some(percent);
+        self
+    }
+
+    /// Numeric markets.
+    pub fn min(mut self, min: f64) -> Self {
+        self.args.min = Some(min);
+        self
+    }
+
+    /// Numeric markets.
+    pub fn max(mut self, max: f64) -> Self {
+        self.args.max = Some(max);
+        self
+    }
+
+    /// Numeric markets: whether values grow exponentially from min to max.
+    pub fn is_log_scale(mut self, log_scale: bool) -> Self {
+        self.args.is_log_scale = Some(log_scale);
+        self
+    }
+
+    /// Numeric markets: the starting value, strictly between min and max.
+    pub fn initial_value(mut self, value: f64) -> Self {
+        self.args.initial_value = Some(value);
+        self
+    }
+
+    /// Multiple choice markets; free response markets can start with answers too.
+    pub fn answers(mut self, answers: &[&str]) -> Self {
+        self.args.answers = Some(answers.iter().map(|a| a.to_string()).collect());
+        self
+    }
+
+    /// Multiple choice markets: who can add answers later.
+    pub fn add_answers_mode(mut self, mode: AddAnswersMode) -> Self {
+        self.args.add_answers_mode = Some(mode);
+        self
+    }
+
+    /// Refuse to build a market that would cost more than `amount` mana to
+    /// create, see [`CreateMarketArgs::creation_cost`].
+    pub fn max_cost(mut self, amount: u64) -> Self {
+        self.max_cost = Some(amount);
+        self
+    }
+
+    /// The parameters, if they fit the market's type.
+    ///
+    /// Fails with [`ManifoldError::Validation`] on missing or out-of-range parameters,
+    /// on parameters of another market type, which the API would ignore, and
+    /// on answers Manifold would reject: too many, too long, or duplicated.
+    pub fn build(self) -> Result<CreateMarketArgs> {
+        let args = self.args;
+        let invalid = |message: String| Err(ManifoldError::Validation(message));
+
+        if args.question.trim().is_empty() {
+            return invalid("a market needs a question".to_owned());
+        }
+
+        let numeric = args.min.is_some()
+            || args.max.is_some()
+            || args.is_log_scale.is_some()
+            || args.initial_value.is_some();
+
+        match args.outcome_type {
+            OutcomeType::Binary => {
+                if let Some(prob) = args.initial_prob.filter(|p| !(1..=99).contains(p)) {
+                    return invalid(format!(
+                        "initial probability {prob}% is not between 1 and 99"
+                    ));
+                }
+            }
+            OutcomeType::PseudoNumeric => {
+                let (Some(min), Some(max)) = (args.min, args.max) else {
+                    return invalid("numeric markets need a min and a max".to_owned());
+                };
+
+                if min >= max {
+                    return invalid(format!("min {min} is not below max {max}"));
+                }
+
+                if args.is_log_scale == Some(true) && min < 0.0 {
+                    return invalid(format!("log scale markets can't go below 0, min is {min}"));
+                }
+
+                if let Some(value) = args.initial_value.filter(|v| !(min < *v && *v < max)) {
+                    return invalid(format!(
+                        "initial value {value} is not strictly between {min} and {max}"
+                    ));
+                }
+            }
+            OutcomeType::MultipleChoice | OutcomeType::FreeResponse => {
+                let answers = args.answers.as_deref().unwrap_or_default();
+
+                if args.outcome_type == OutcomeType::MultipleChoice && answers.len() < 2 {
+                    return invalid("multiple choice markets need at least two answers".to_owned());
+                }
+
+                if answers.len() > MAX_ANSWERS {
+                    return invalid(format!(
+                        "{} answers is more than the {MAX_ANSWERS} a market can have",
+                        answers.len()
+                    ));
+                }
+
+                let mut seen = HashSet::new();
+
+                for answer in answers {
+                    check_answer_text(answer)?;
+
+                    if !seen.insert(normalize(answer)) {
+                        return invalid(format!("answer {answer:?} is given twice"));
+                    }
+                }
+            }
+            OutcomeType::Poll
+            | OutcomeType::BountiedQuestion
+            | OutcomeType::Number
+            | OutcomeType::Stonk
+            | OutcomeType::Unknown(_) => {
+                return invalid(format!(
+                    "creating {:?} markets isn't supported",
+                    args.outcome_type
+                ));
+            }
+        }
+
+        let answers = args.answers.is_some() || args.add_answers_mode.is_some();
+        let misplaced = match args.outcome_type {
+            OutcomeType::Binary => numeric || answers,
+            OutcomeType::PseudoNumeric => args.initial_prob.is_some() || answers,
+            OutcomeType::FreeResponse | OutcomeType::MultipleChoice => {
+                numeric || args.initial_prob.is_some()
+            }
+            OutcomeType::Poll
+            | OutcomeType::BountiedQuestion
+            | OutcomeType::Number
+            | OutcomeType::Stonk
+            | OutcomeType::Unknown(_) => true,
+        };
+
+        if misplaced {
+            return invalid(format!(
+                "{:?} markets don't take some of the given parameters",
+                args.outcome_type
+            ));
+        }
+
+        if let Some(max_cost) = self.max_cost {
+            let cost = args.creation_cost();
+
+            if cost > max_cost {
+                return invalid(format!(
+                    "creating this market costs M${cost}, more than the maximum of M${max_cost}"
+                ));
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+impl CreateMarketArgs {
+    /// Creation parameters that would recreate `market`: same type, question,
+    /// description, close time, visibility, cover image and answers.
+    ///
+    /// Groups are not copied, since markets only list their group slugs;
+    /// [`ManifoldClient::clone_market`] resolves those.
+    ///
+    /// Binary markets start at 50%, numeric markets in the middle of their range.
+    pub fn from_market(market: &impl Market) -> CreateMarketArgs {
+        let data = market.data();
+        let outcome_type = market.outcome_type();
+
+        let mut args = CreateMarketArgs::new(outcome_type.clone(), market.question());
+
+        args.description = Some(data["description"].clone()).filter(|d| !d.is_null());
+        args.close_time = data["closeTime"].as_i64();
+        args.visibility = Some(market.visibility());
+        args.cover_image_url = market.cover_image_url().map(str::to_owned);
+
+        match outcome_type {
+            OutcomeType::Binary => args.initial_prob = Some(50),
+            OutcomeType::PseudoNumeric => {
+                args.min = number_f64(&data["min"]);
+                args.max = number_f64(&data["max"]);
+                args.is_log_scale = data["isLogScale"].as_bool();
+                args.initial_value = args.min.zip(args.max).map(|(min, max)| (min + max) / 2.0);
+            }
+            OutcomeType::FreeResponse | OutcomeType::MultipleChoice => {
+                args.answers = Some(
+                    market
+                        .answers()
+                        .iter()
+                        .map(|a| a.text().to_owned())
+                        .collect(),
+                )
+                .filter(|answers: &Vec<String>| !answers.is_empty());
+
+                if outcome_type == OutcomeType::MultipleChoice {
+                    args.add_answers_mode = Some(market.add_answers_mode());
+                }
+            }
+            // Not creatable, so `build` rejects these anyway.
+            OutcomeType::Poll
+            | OutcomeType::BountiedQuestion
+            | OutcomeType::Number
+            | OutcomeType::Stonk
+            | OutcomeType::Unknown(_) => {}
+        }
+
+        args
+    }
+}
+
+impl ManifoldClient {
+    /// Creates a market, after checking its parameters (see [`CreateMarketBuilder::build`]).
+    ///
+    /// The client's [default groups](crate::ManifoldClientBuilder::default_group_ids)
+    /// are added as with [`post_market`](Self::post_market).
+    pub async fn create_market(&self, market: CreateMarketBuilder) -> Result<FullMarket> {
+        let args = market.build()?;
+
+        self.post_market(&serde_json::to_value(&args)?).await
+    }
+
+    /// Creates a copy of an existing market, e.g. the next one of a weekly series.
+    ///
+    /// The parameters from [`CreateMarketArgs::from_market`], including the
+    /// source market's groups, are passed to `overrides` before creation;
+    /// that's where the new close time or question goes:
+    ///
+    /// ```no_run
+    /// # async fn example(client: &manifold_markets::ManifoldClient) -> manifold_markets::error::Result<()> {
+    /// let week = chrono::Duration::weeks(1).num_milliseconds();
+    ///
+    /// client
+    ///     .clone_market("source-market-id", |args| {
+    ///         args.question = args.question.replace("week 1", "week 2");
+    ///         args.close_time = args.close_time.map(|t| t + week);
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn clone_market(
+        &self,
+        source_market_id: &str,
+        overrides: impl FnOnce(&mut CreateMarketArgs),
+    ) -> Result<FullMarket> {
+        let source = self.get_market(source_market_id).await?;
+
+        let mut args = CreateMarketArgs::from_market(&source);
+
+        for slug in source.group_slugs() {
+            args.group_ids
+                .push(self.get_group(slug).await?.id().to_owned());
+        }
+
+        overrides(&mut args);
+
+        self.post_market(&serde_json::to_value(&args)?).await
+    }
+
+    /// Creates a market, unless the authorized user already has one with the same question.
+    ///
+    /// Questions are compared with [`normalize_question`], so a job that crashed
+    /// after creating a market can be restarted without creating a duplicate.
+    ///
+    /// Lookup goes through `GET /v0/search-markets`, so a market created
+    /// moments ago might not be found yet.
+    pub async fn create_market_if_absent(
+        &self,
+        args: &CreateMarketArgs,
+    ) -> Result<CreatedOrExisting> {
+        let me = self.get_me().await?;
+        let question = normalize_question(&args.question);
+
+        let candidates: Vec<LiteMarket> = self
+            .execute(self.http_get("/search-markets").query(&[
+                ("term", args.question.as_str()),
+                ("creatorId", me.id()),
+                ("filter", "all"),
+                ("limit", "100"),
+            ]))
+            .await?;
+
+        let existing = candidates
+            .iter()
+            .find(|m| m.creator_id() == me.id() && normalize_question(m.question()) == question);
+
+        if let Some(existing) = existing {
+            return Ok(CreatedOrExisting::Existing(
+                self.get_market(existing.id()).await?,
+            ));
+        }
+
+        let market = self.post_market(&serde_json::to_value(args)?).await?;
+
+        Ok(CreatedOrExisting::Created(market))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::{self, MockRequest, MockResponse, MockServer};
+
+    #[test]
+    fn builds_checked_parameters() -> Result<()> {
+        let close = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+
+        let binary = CreateMarketBuilder::binary("Will it?")
+            .initial_prob(30)
+            .close_time(close)
+            .group_id("g1")
+            .group_id("g2")
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(&binary)?,
+            serde_json::json!({
+                "outcomeType": "BINARY",
+                "question": "Will it?",
+                "closeTime": close.timestamp_millis(),
+                "groupIds": ["g1", "g2"],
+                "initialProb": 30,
+            })
+        );
+
+        let numeric = CreateMarketBuilder::pseudo_numeric("How many?", 1.0, 1000.0)
+            .is_log_scale(true)
+            .initial_value(10.0)
+            .build()?;
+        assert_eq!(numeric.initial_value, Some(10.0));
+
+        let multi = CreateMarketBuilder::multiple_choice("Which?", &["A", "B"])
+            .add_answers_mode(AddAnswersMode::OnlyCreator)
+            .max_cost(120)
+            .build()?;
+        assert_eq!(multi.creation_cost(), 120);
+        assert_eq!(
+            serde_json::to_value(&multi)?["addAnswersMode"],
+            "ONLY_CREATOR"
+        );
+        CreateMarketBuilder::free_response("What?").build()?;
+
+        let invalid = [
+            CreateMarketBuilder::binary("Will it?").initial_prob(100),
+            CreateMarketBuilder::binary("  "),
+            CreateMarketBuilder::binary("Will it?").answers(&["A", "B"]),
+            CreateMarketBuilder::pseudo_numeric("How many?", 5.0, 5.0),
+            CreateMarketBuilder::pseudo_numeric("How many?", 0.0, 10.0).initial_value(10.0),
+            CreateMarketBuilder::pseudo_numeric("How many?", -1.0, 10.0).is_log_scale(true),
+            CreateMarketBuilder::new(OutcomeType::PseudoNumeric, "How many?"),
+            CreateMarketBuilder::multiple_choice("Which?", &["A"]),
+            CreateMarketBuilder::multiple_choice("Which?", &["A", ""]),
+            CreateMarketBuilder::free_response("What?").initial_prob(50),
+            CreateMarketBuilder::multiple_choice("Which?", &["A", "a "]),
+            CreateMarketBuilder::multiple_choice(
+                "Which?",
+                &[&"A".repeat(MAX_ANSWER_LENGTH + 1), "B"],
+            ),
+            CreateMarketBuilder::multiple_choice("Which?", &["A"; MAX_ANSWERS + 1]),
+            CreateMarketBuilder::multiple_choice("Which?", &["A", "B", "C"]).max_cost(120),
+            CreateMarketBuilder::binary("Will it?").add_answers_mode(AddAnswersMode::Anyone),
+            CreateMarketBuilder::new(OutcomeType::Poll, "Which day?"),
+            CreateMarketBuilder::new(OutcomeType::Stonk, "Forecaster stock"),
+        ];
+
+        for builder in invalid {
+            assert!(
+                matches!(builder.clone().build(), Err(ManifoldError::Validation(_))),
+                "{builder:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn finds_existing_markets_by_normalized_question() -> Result<()> {
+        let server = MockServer::start(|request: &MockRequest| {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/me") => Some(MockResponse::json(fixtures::user().0)),
+                ("GET", "/search-markets") => Some(MockResponse::json(json!([
+                    // The same question, from someone else.
+                    {
+                        "id": "theirs",
+                        "creatorId": "someoneElse",
+                        "question": "Will it rain tomorrow?",
+                    },
+                    // Ours, but a different question.
+                    {
+                        "id": "longer",
+                        "creatorId": fixtures::USER_ID,
+                        "question": "Will it rain tomorrow in Paris?",
+                    },
+                    {
+                        "id": "mine",
+                        "creatorId": fixtures::USER_ID,
+                        "question": "  will it RAIN   tomorrow",
+                    },
+                ]))),
+                ("GET", "/market/mine") => {
+                    let mut market = fixtures::binary_market().0;
+                    market["id"] = json!("mine");
+                    Some(MockResponse::json(market))
+                }
+                ("POST", "/market") => Some(MockResponse::json(fixtures::binary_market().0)),
+                _ => None,
+            }
+        })
+        .await?;
+        let client = server.client().build()?;
+
+        let duplicate = CreateMarketBuilder::binary("Will it rain tomorrow?").build()?;
+        let existing = client.create_market_if_absent(&duplicate).await?;
+
+        assert!(!existing.was_created());
+        assert_eq!(existing.market().id(), "mine");
+        assert!(server.requests().iter().all(|r| r.method == "GET"));
+
+        let search = &server.requests()[1];
+        assert!(search
+            .query
+            .contains(&format!("creatorId={}", fixtures::USER_ID)));
+
+        let near_miss = CreateMarketBuilder::binary("Will it rain tomorrow in London?").build()?;
+        let created = client.create_market_if_absent(&near_miss).await?;
+
+        assert!(created.was_created());
+        assert_eq!(created.market().id(), fixtures::binary_market().id());
+
+        let posted = server.requests().pop().unwrap();
+        assert_eq!(
+            (posted.method.as_str(), posted.body["question"].as_str()),
+            ("POST", Some("Will it rain tomorrow in London?"))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+//! Loading API keys from somewhere safer than a plaintext `.env` file
+//!
+//! - [`api_key_from_keyring`] reads the OS credential store (macOS Keychain,
+//!   Windows Credential Manager, Secret Service on Linux), with the `keyring` feature.
+//! - [`api_key_from_systemd_credential`] reads a credential passed to a
+//!   service with systemd's `LoadCredential=`.
+//! - [`api_key_from_file`] reads a file, refusing it if other users can read it.
+//!
+//! ```no_run
+//! # use manifold_markets::credentials::api_key_from_file;
+//! # use manifold_markets::ManifoldClient;
+//! let key = api_key_from_file("/home/bot/.config/manifold/key")?;
+//! let client = ManifoldClient::from_api_key(key.expose_secret())?;
+//! # Ok::<(), manifold_markets::error::ManifoldError>(())
+//! ```
+
+use std::path::Path;
+
+use crate::error::{ManifoldError, Result};
+use crate::secret::SecretString;
+
+/// Service name the keys are stored under in the OS keyring.
+pub const KEYRING_SERVICE: &str = "manifold-markets";
+
+/// Reads an API key from a file, which must not be accessible by other users (e.g. mode `0600`).
+///
+/// Surrounding whitespace is trimmed. The permission check only exists on Unix.
+pub fn api_key_from_file(path: impl AsRef<Path>) -> Result<SecretString> {
+    let path = path.as_ref();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = std::fs::metadata(path)?.permissions().mode();
+
+        if mode & 0o077 != 0 {
+            return Err(ManifoldError::CredentialError(format!(
+                "{} is accessible by other users (mode {:o}); chmod 600 it",
+                path.display(),
+                mode & 0o777
+            )));
+        }
+    }
+
+    let key = std::fs::read_to_string(path)?;
+    let key = key.trim();
+
+    if key.is_empty() {
+        return Err(ManifoldError::CredentialError(format!(
+            "{} is empty",
+            path.display()
+        )));
+    }
+
+    Ok(SecretString::new(key))
+}
+
+/// Reads a credential that systemd passed to the service, see `LoadCredential=` in `systemd.exec(5)`.
+pub fn api_key_from_systemd_credential(name: &str) -> Result<SecretString> {
+    let dir = std::env::var_os("CREDENTIALS_DIRECTORY").ok_or_else(|| {
+        ManifoldError::CredentialError(
+            "CREDENTIALS_DIRECTORY is not set; is this running as a systemd service with LoadCredential=?"
+                .to_owned(),
+        )
+    })?;
+
+    api_key_from_file(Path::new(&dir).join(name))
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_entry(user: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, user)
+        .map_err(|e| ManifoldError::CredentialError(e.to_string()))
+}
+
+/// Reads the API key stored for `user` (any label, e.g. the Manifold username) in the OS keyring.
+#[cfg(feature = "keyring")]
+pub fn api_key_from_keyring(user: &str) -> Result<SecretString> {
+    keyring_entry(user)?
+        .get_password()
+        .map(SecretString::new)
+        .map_err(|e| ManifoldError::CredentialError(e.to_string()))
+}
+
+/// Stores an API key for `user` in the OS keyring, e.g. from a CLI's `login` command.
+#[cfg(feature = "keyring")]
+pub fn store_api_key_in_keyring(user: &str, key: &SecretString) -> Result<()> {
+    keyring_entry(user)?
+        .set_password(key.expose_secret())
+        .map_err(|e| ManifoldError::CredentialError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn checks_key_file_permissions() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("manifold-key-{}", std::process::id()));
+        std::fs::write(&path, "secret-key\n")?;
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+        assert!(matches!(
+            api_key_from_file(&path),
+            Err(ManifoldError::CredentialError(_))
+        ));
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        assert_eq!(api_key_from_file(&path)?.expose_secret(), "secret-key");
+
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
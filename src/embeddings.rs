@@ -0,0 +1,445 @@
+//! Question embeddings, cached by market id
+//!
+//! Computing embeddings for tens of thousands of questions is slow and costs
+//! money, so [`embed_markets`] only asks the [`Embedder`] for markets that are
+//! missing from an [`EmbeddingCache`], or whose question changed since.
+//!
+//! Caches:
+//!
+//! - [`MemoryEmbeddingCache`] for a single run
+//! - [`FileEmbeddingCache`], a JSON file
+//! - `SqliteEmbeddingCache`, with the `sqlite` feature
+//!
+//! [`find_duplicates`] uses the embeddings to find markets asking the same question.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ManifoldError, Result};
+use crate::types::*;
+
+pub type Embedding = Vec<f32>;
+
+/// Something that turns texts into embeddings, e.g. a model API.
+pub trait Embedder {
+    /// Returns one embedding per text, in the same order.
+    fn embed(&self, texts: &[String]) -> impl Future<Output = Result<Vec<Embedding>>> + Send;
+}
+
+/// Storage for embeddings, keyed by market id.
+///
+/// Entries remember a fingerprint of the text they were computed from,
+/// so edited questions are embedded again.
+pub trait EmbeddingCache {
+    /// The cached embedding for `market_id`, if it was computed from `text`.
+    fn get(&self, market_id: &str, text: &str) -> Result<Option<Embedding>>;
+
+    fn put(&mut self, market_id: &str, text: &str, embedding: Embedding) -> Result<()>;
+
+    /// Makes previous writes durable.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across runs and Rust versions.
+fn fingerprint(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct CacheEntry {
+    fingerprint: u64,
+    embedding: Embedding,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryEmbeddingCache {
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl MemoryEmbeddingCache {
+    pub fn new() -> MemoryEmbeddingCache {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl EmbeddingCache for MemoryEmbeddingCache {
+    fn get(&self, market_id: &str, text: &str) -> Result<Option<Embedding>> {
+        Ok(self
+            .entries
+            .get(market_id)
+            .filter(|e| e.fingerprint == fingerprint(text))
+            .map(|e| e.embedding.clone()))
+    }
+
+    fn put(&mut self, market_id: &str, text: &str, embedding: Embedding) -> Result<()> {
+        self.entries.insert(
+            market_id.to_owned(),
+            CacheEntry {
+                fingerprint: fingerprint(text),
+                embedding,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+/// A cache kept in memory and saved to a JSON file on [`flush`](EmbeddingCache::flush).
+#[derive(Debug, Clone)]
+pub struct FileEmbeddingCache {
+    path: PathBuf,
+    memory: MemoryEmbeddingCache,
+}
+
+impl FileEmbeddingCache {
+    /// Loads the cache from `path`, or starts an empty one if the file doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<FileEmbeddingCache> {
+        let path = path.as_ref().to_owned();
+
+        let entries = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(FileEmbeddingCache {
+            path,
+            memory: MemoryEmbeddingCache { entries },
+        })
+    }
+}
+
+impl EmbeddingCache for FileEmbeddingCache {
+    fn get(&self, market_id: &str, text: &str) -> Result<Option<Embedding>> {
+        self.memory.get(market_id, text)
+    }
+
+    fn put(&mut self, market_id: &str, text: &str, embedding: Embedding) -> Result<()> {
+        self.memory.put(market_id, text, embedding)
+    }
+
+    /// Writes the whole cache to a temporary file and moves it over the old one,
+    /// so a crash never leaves a truncated cache behind.
+    fn flush(&mut self) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+
+        std::fs::write(&tmp, serde_json::to_vec(&self.memory.entries)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteEmbeddingCache;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::path::Path;
+
+    use rusqlite::{params, Connection, OptionalExtension};
+
+    use super::{fingerprint, Embedding, EmbeddingCache};
+    use crate::error::Result;
+
+    /// A cache in an SQLite database, written through on every [`put`](EmbeddingCache::put).
+    #[derive(Debug)]
+    pub struct SqliteEmbeddingCache {
+        connection: Connection,
+    }
+
+    impl SqliteEmbeddingCache {
+        pub fn open(path: impl AsRef<Path>) -> Result<SqliteEmbeddingCache> {
+            Self::from_connection(Connection::open(path)?)
+        }
+
+        /// Uses an existing connection, creating the `market_embeddings` table if needed.
+        pub fn from_connection(connection: Connection) -> Result<SqliteEmbeddingCache> {
+            connection.execute(
+                "CREATE TABLE IF NOT EXISTS market_embeddings (
+                    market_id   TEXT PRIMARY KEY,
+                    fingerprint INTEGER NOT NULL,
+                    embedding   BLOB NOT NULL
+                )",
+                [],
+            )?;
+
+            Ok(SqliteEmbeddingCache { connection })
+        }
+    }
+
+    impl EmbeddingCache for SqliteEmbeddingCache {
+        fn get(&self, market_id: &str, text: &str) -> Result<Option<Embedding>> {
+            let blob: Option<Vec<u8>> = self
+                .connection
+                .query_row(
+                    "SELECT embedding FROM market_embeddings WHERE market_id = ?1 AND fingerprint = ?2",
+                    params![market_id, fingerprint(text) as i64],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            Ok(blob.map(|blob| {
+                blob.chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect()
+            }))
+        }
+
+        fn put(&mut self, market_id: &str, text: &str, embedding: Embedding) -> Result<()> {
+            let blob = embedding
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect::<Vec<u8>>();
+
+            self.connection.execute(
+                "INSERT OR REPLACE INTO market_embeddings (market_id, fingerprint, embedding) VALUES (?1, ?2, ?3)",
+                params![market_id, fingerprint(text) as i64, blob],
+            )?;
+
+            Ok(())
+        }
+    }
+}
+
+/// Embeddings of the markets' questions, in order, computing only the ones missing from `cache`.
+///
+/// Missing embeddings are requested from `embedder` in a single call, then
+/// stored and flushed. Fails, storing nothing, if `embedder` returns a
+/// different number of embeddings than it was given texts.
+pub async fn embed_markets<M: Market>(
+    embedder: &impl Embedder,
+    cache: &mut impl EmbeddingCache,
+    markets: &[M],
+) -> Result<Vec<Embedding>> {
+    let mut embeddings = Vec::with_capacity(markets.len());
+    let mut missing = vec![];
+
+    for (i, market) in markets.iter().enumerate() {
+        let cached = cache.get(market.id(), market.question())?;
+
+        if cached.is_none() {
+            missing.push(i);
+        }
+
+        embeddings.push(cached.unwrap_or_default());
+    }
+
+    if missing.is_empty() {
+        return Ok(embeddings);
+    }
+
+    let texts = missing
+        .iter()
+        .map(|&i| markets[i].question().to_owned())
+        .collect::<Vec<_>>();
+
+    let computed = embedder.embed(&texts).await?;
+
+    if computed.len() != texts.len() {
+        return Err(ManifoldError::Other(format!(
+            "the embedder returned {} embeddings for {} texts",
+            computed.len(),
+            texts.len()
+        )));
+    }
+
+    for (i, embedding) in missing.into_iter().zip(computed) {
+        cache.put(markets[i].id(), markets[i].question(), embedding.clone())?;
+        embeddings[i] = embedding;
+    }
+
+    cache.flush()?;
+
+    Ok(embeddings)
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norms =
+        a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+/// Two markets that likely ask the same question, see [`find_duplicates`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicatePair {
+    pub first: String,
+    pub second: String,
+    pub similarity: f32,
+}
+
+/// Pairs of markets whose embeddings have a cosine similarity of at least `threshold`,
+/// most similar first.
+///
+/// `embeddings` are in the same order as `markets`, e.g. from [`embed_markets`];
+/// if one is longer than the other, the extra entries are ignored.
+/// Compares every pair, so it's quadratic in the number of markets.
+pub fn find_duplicates<M: Market>(
+    markets: &[M],
+    embeddings: &[Embedding],
+    threshold: f32,
+) -> Vec<DuplicatePair> {
+    let mut pairs = vec![];
+
+    let embedded = markets.iter().zip(embeddings).collect::<Vec<_>>();
+
+    for (i, (first, a)) in embedded.iter().enumerate() {
+        for (second, b) in &embedded[i + 1..] {
+            let similarity = cosine_similarity(a, b);
+
+            if similarity >= threshold {
+                pairs.push(DuplicatePair {
+                    first: first.id().to_owned(),
+                    second: second.id().to_owned(),
+                    similarity,
+                });
+            }
+        }
+    }
+
+    pairs.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// Letter counts, which is enough to tell questions apart.
+    #[derive(Default)]
+    struct LetterEmbedder {
+        texts_embedded: AtomicUsize,
+    }
+
+    impl Embedder for LetterEmbedder {
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>> {
+            self.texts_embedded.fetch_add(texts.len(), Ordering::SeqCst);
+
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let mut counts = vec![0.0; 26];
+
+                    for c in text.to_lowercase().bytes().filter(u8::is_ascii_lowercase) {
+                        counts[usize::from(c - b'a')] += 1.0;
+                    }
+
+                    counts
+                })
+                .collect())
+        }
+    }
+
+    fn market(id: &str, question: &str) -> LiteMarket {
+        LiteMarket(json!({"id": id, "question": question}))
+    }
+
+    #[tokio::test]
+    async fn embeds_only_missing_markets() -> Result<()> {
+        let embedder = LetterEmbedder::default();
+        let mut cache = MemoryEmbeddingCache::new();
+
+        let mut markets = vec![
+            market("a", "Will it rain tomorrow?"),
+            market("b", "Will it rain tomorrow"),
+            market("c", "Who wins the election?"),
+        ];
+
+        embed_markets(&embedder, &mut cache, &markets).await?;
+        assert_eq!(embedder.texts_embedded.load(Ordering::SeqCst), 3);
+
+        markets[2] = market("c", "Who wins the 2028 election?");
+        let embeddings = embed_markets(&embedder, &mut cache, &markets).await?;
+        assert_eq!(embedder.texts_embedded.load(Ordering::SeqCst), 4);
+
+        let duplicates = find_duplicates(&markets, &embeddings, 0.99);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(
+            (duplicates[0].first.as_str(), duplicates[0].second.as_str()),
+            ("a", "b")
+        );
+
+        Ok(())
+    }
+
+    /// Drops the last embedding.
+    struct ShortEmbedder;
+
+    impl Embedder for ShortEmbedder {
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Embedding>> {
+            Ok(vec![vec![1.0]; texts.len() - 1])
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_embeddings() -> Result<()> {
+        let mut cache = MemoryEmbeddingCache::new();
+        let markets = vec![market("a", "Will it rain?"), market("b", "Will it snow?")];
+
+        assert!(embed_markets(&ShortEmbedder, &mut cache, &markets)
+            .await
+            .is_err());
+        assert_eq!(cache.get("a", "Will it rain?")?, None);
+
+        let duplicates = find_duplicates(&markets, &[vec![1.0]], 0.5);
+        assert!(duplicates.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn file_cache_persists() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("embeddings-{}.json", std::process::id()));
+
+        let mut cache = FileEmbeddingCache::open(&path)?;
+        cache.put("a", "question", vec![1.0, 2.0])?;
+        cache.flush()?;
+
+        let cache = FileEmbeddingCache::open(&path)?;
+        assert_eq!(cache.get("a", "question")?, Some(vec![1.0, 2.0]));
+        assert_eq!(cache.get("a", "edited question")?, None);
+
+        std::fs::remove_file(path)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_cache_round_trip() -> Result<()> {
+        let mut cache =
+            SqliteEmbeddingCache::from_connection(rusqlite::Connection::open_in_memory()?)?;
+
+        cache.put("a", "question", vec![1.5, -2.0])?;
+
+        assert_eq!(cache.get("a", "question")?, Some(vec![1.5, -2.0]));
+        assert_eq!(cache.get("a", "other")?, None);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,22 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{DATE, RETRY_AFTER};
 use serde_json::Value;
 use thiserror::Error;
 
+/// What the API says, lowercased and without the final period, when a trade
+/// is refused because the market or answer is closed or resolved.
+const MARKET_CLOSED_MESSAGES: &[&str] = &[
+    "trading is closed",
+    "market is closed",
+    "market is resolved",
+    "contract is closed",
+    "contract is resolved",
+    "answer is resolved",
+    "cannot bet on a resolved market",
+];
+
 #[derive(Error, Debug)]
 pub enum ManifoldError {
     #[error("JSON parse error")]
@@ -9,11 +25,335 @@ pub enum ManifoldError {
     #[error("HTTP error")]
     HttpError(#[from] reqwest::Error),
 
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// The API key is missing or invalid (HTTP 401).
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// The request failed a check before it was sent.
+    #[error("Invalid request: {0}")]
+    Validation(String),
+
+    /// The API key isn't allowed to do this (HTTP 403), e.g. resolving someone
+    /// else's market.
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// The API rejected the request (HTTP 400, and other 4xx statuses
+    /// without their own variant), or refused a 403 for an insufficient
+    /// balance or a closed market.
+    ///
+    /// `message` is the `message` of Manifold's error response, and `body`
+    /// the whole response: JSON if it parses, else a string.
+    #[error("API error {status}: {message}")]
+    ApiError {
+        status: u16,
+        message: String,
+        body: Value,
+    },
+
+    /// Too many requests (HTTP 429), with how long the server asked to wait.
+    #[error("Rate limited: {0}")]
+    RateLimited(String, Option<Duration>),
+
+    /// The server failed (HTTP 5xx), with the status.
+    #[error("Server error {0}: {1}")]
+    Server(u16, String),
+
     #[error("Unexpected schema error: {0} {1:?}")]
     SchemaError(String, Option<Value>),
 
-    #[error("Other error")]
+    #[error("Authentication required: {0}")]
+    AuthenticationRequired(String),
+
+    #[error("Permission error: {0}")]
+    PermissionError(String),
+
+    #[error("Market closes too soon: {0}")]
+    MarketClosing(String),
+
+    /// Market data is older than allowed, see [`crate::freshness`].
+    #[error("Stale data: {0}")]
+    StaleData(String),
+
+    #[error("Duplicate comment: {0}")]
+    DuplicateComment(String),
+
+    #[error("Self-trade: {0}")]
+    SelfTrade(String),
+
+    #[error("Refusing to use real money: {0}")]
+    ProductionGuard(String),
+
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Credential error: {0}")]
+    CredentialError(String),
+
+    #[cfg(feature = "tools")]
+    #[error("Invalid tool call: {0}")]
+    InvalidToolCall(String),
+
+    /// Boxed, since it's much larger than the other variants.
+    #[cfg(feature = "realtime")]
+    #[error("WebSocket error")]
+    WebSocketError(#[source] Box<tokio_tungstenite::tungstenite::Error>),
+
+    #[error("IO error")]
+    IoError(#[from] std::io::Error),
+
+    #[cfg(feature = "csv")]
+    #[error("CSV error")]
+    CsvError(#[from] csv::Error),
+
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error")]
+    SqliteError(#[from] rusqlite::Error),
+
+    #[error("{0}")]
     Other(String),
 }
 
 pub type Result<T> = core::result::Result<T, ManifoldError>;
+
+#[cfg(feature = "realtime")]
+impl From<tokio_tungstenite::tungstenite::Error> for ManifoldError {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> ManifoldError {
+        ManifoldError::WebSocketError(Box::new(error))
+    }
+}
+
+impl ManifoldError {
+    /// The error for a response with a non-success status and the given body.
+    ///
+    /// The message is the body's `message` field if it's JSON, else the body itself.
+    pub fn from_status(status: u16, body: &str, retry_after: Option<Duration>) -> ManifoldError {
+        let body = serde_json::from_str::<Value>(body)
+            .unwrap_or_else(|_| Value::String(body.trim().to_owned()));
+        let message = match &body {
+            Value::String(text) => text.clone(),
+            json => json["message"]
+                .as_str()
+                .map_or_else(|| json.to_string(), str::to_owned),
+        };
+
+        match status {
+            401 => ManifoldError::Unauthorized(message),
+            403 if !is_balance_message(&message) && !is_market_closed_message(&message) => {
+                ManifoldError::Forbidden(message)
+            }
+            404 => ManifoldError::NotFound(message),
+            429 => ManifoldError::RateLimited(message, retry_after),
+            500.. => ManifoldError::Server(status, message),
+            _ => ManifoldError::ApiError {
+                status,
+                message,
+                body,
+            },
+        }
+    }
+
+    /// Passes successful responses through, turning others into errors with [`from_status`](Self::from_status).
+    pub(crate) async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let header = |name| response.headers().get(name).and_then(|v| v.to_str().ok());
+        let retry_after = header(RETRY_AFTER).and_then(|v| retry_after(v, header(DATE)));
+        let body = response.text().await.unwrap_or_default();
+
+        Err(ManifoldError::from_status(
+            status.as_u16(),
+            &body,
+            retry_after,
+        ))
+    }
+
+    /// The HTTP status the error came with, if any.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ManifoldError::NotFound(_) => Some(404),
+            ManifoldError::Unauthorized(_) => Some(401),
+            ManifoldError::Forbidden(_) => Some(403),
+            ManifoldError::ApiError { status, .. } => Some(*status),
+            ManifoldError::RateLimited(..) => Some(429),
+            ManifoldError::Server(status, _) => Some(*status),
+            ManifoldError::HttpError(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+
+    /// Whether sending the same request again later may succeed: rate limits,
+    /// server errors, timeouts and connection failures.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ManifoldError::RateLimited(..) | ManifoldError::Server(..) => true,
+            ManifoldError::HttpError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status()
+                        .is_some_and(|s| s.is_server_error() || s.as_u16() == 429)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the request surely wasn't carried out: it failed before being
+    /// sent, couldn't connect, or was refused with a 4xx status.
+    ///
+    /// Timeouts, server errors and dropped connections say nothing either way,
+    /// so a bet or comment that failed with one may still have gone through.
+    pub fn is_not_applied(&self) -> bool {
+        match self {
+            ManifoldError::Validation(_)
+            | ManifoldError::DuplicateComment(_)
+            | ManifoldError::ProductionGuard(_) => true,
+            ManifoldError::HttpError(e) if e.status().is_none() => e.is_connect() || e.is_builder(),
+            error => error
+                .status()
+                .is_some_and(|status| (400..500).contains(&status)),
+        }
+    }
+
+    /// Whether the error is about authentication or permissions.
+    pub fn is_auth(&self) -> bool {
+        matches!(
+            self,
+            ManifoldError::Unauthorized(_)
+                | ManifoldError::Forbidden(_)
+                | ManifoldError::AuthenticationRequired(_)
+                | ManifoldError::PermissionError(_)
+                | ManifoldError::CredentialError(_)
+        )
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, ManifoldError::NotFound(_))
+    }
+
+    /// The server's message for an [`ApiError`](ManifoldError::ApiError).
+    pub fn api_message(&self) -> Option<&str> {
+        match self {
+            ManifoldError::ApiError { message, .. } => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Whether the API rejected a bet or other payment for lack of mana.
+    pub fn is_insufficient_balance(&self) -> bool {
+        self.api_message().is_some_and(is_balance_message)
+    }
+
+    /// Whether the API rejected a trade because the market is closed or resolved.
+    pub fn is_market_closed(&self) -> bool {
+        self.api_message().is_some_and(is_market_closed_message)
+    }
+}
+
+fn is_balance_message(message: &str) -> bool {
+    message.to_lowercase().contains("insufficient balance")
+}
+
+fn is_market_closed_message(message: &str) -> bool {
+    let message = message.trim().trim_end_matches('.').to_lowercase();
+
+    MARKET_CLOSED_MESSAGES.contains(&message.as_str())
+}
+
+/// How long a `Retry-After` header asks to wait: a number of seconds, or an
+/// HTTP date, counted from the response's `Date` if it has one.
+fn retry_after(value: &str, date: Option<&str>) -> Option<Duration> {
+    if let Ok(secs) = value.trim().parse() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    let now = date
+        .and_then(|date| DateTime::parse_from_rfc2822(date.trim()).ok())
+        .map_or_else(Utc::now, |date| date.with_timezone(&Utc));
+
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_statuses() {
+        let error = ManifoldError::from_status(404, r#"{"message": "Contract not found"}"#, None);
+        assert!(matches!(&error, ManifoldError::NotFound(m) if m == "Contract not found"));
+        assert!(error.is_not_found() && !error.is_retryable());
+
+        let error = ManifoldError::from_status(429, "slow down", Some(Duration::from_secs(3)));
+        assert!(error.is_retryable());
+        assert_eq!(error.status(), Some(429));
+
+        assert!(ManifoldError::from_status(401, "", None).is_auth());
+
+        let error = ManifoldError::from_status(400, r#"{"message": "Invalid outcome"}"#, None);
+        assert!(
+            matches!(&error, ManifoldError::ApiError { status: 400, message, .. } if message == "Invalid outcome")
+        );
+        assert!(!error.is_retryable() && !error.is_insufficient_balance());
+        assert!(error.is_not_applied());
+        assert!(!ManifoldError::from_status(502, "Bad gateway", None).is_not_applied());
+
+        let error = ManifoldError::from_status(
+            403,
+            r#"{"message": "Insufficient balance.", "details": {"balance": 3}}"#,
+            None,
+        );
+        assert!(error.is_insufficient_balance() && !error.is_market_closed());
+        assert_eq!(error.status(), Some(403));
+        assert!(
+            matches!(&error, ManifoldError::ApiError { body, .. } if body["details"]["balance"] == 3)
+        );
+
+        let error = ManifoldError::from_status(403, r#"{"message": "Trading is closed."}"#, None);
+        assert!(error.is_market_closed() && !error.is_auth());
+
+        let error = ManifoldError::from_status(
+            403,
+            r#"{"message": "Only the creator can resolve a market that was closed early"}"#,
+            None,
+        );
+        assert!(matches!(error, ManifoldError::Forbidden(_)));
+        assert!(error.is_auth() && !error.is_market_closed());
+        assert_eq!(error.status(), Some(403));
+
+        let error = ManifoldError::from_status(400, "Answer was resolved differently", None);
+        assert!(!error.is_market_closed());
+
+        let error = ManifoldError::from_status(409, "Conflict", None);
+        assert!(
+            matches!(&error, ManifoldError::ApiError { message, body, .. } if message == "Conflict" && body == "Conflict")
+        );
+
+        let error = ManifoldError::from_status(503, "<html>", None);
+        assert!(matches!(error, ManifoldError::Server(503, _)));
+        assert!(error.is_retryable() && !error.is_auth());
+    }
+
+    #[test]
+    fn reads_retry_after_as_seconds_or_a_date() {
+        let date = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+        assert_eq!(retry_after("120", None), Some(Duration::from_secs(120)));
+        assert_eq!(
+            retry_after("Wed, 21 Oct 2015 07:28:30 GMT", Some(date)),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            retry_after("Wed, 21 Oct 2015 07:27:00 GMT", Some(date)),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after("soon", Some(date)), None);
+    }
+}
@@ -0,0 +1,364 @@
+//! Execution algorithms for orders too large to place at once
+//!
+//! A single large bet moves the price against itself and shows everyone
+//! what you're doing. [`ManifoldClient::execute_twap`] splits it into
+//! slices placed over time instead, and [`plan`] splits it into what the
+//! market can absorb now and limit orders to rest for the rest.
+
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::error::{ManifoldError, Result};
+use crate::math::CpmmPool;
+use crate::trading::BasketReport;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// Parameters of [`ManifoldClient::execute_twap`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwapConfig {
+    pub slices: u32,
+    /// Average time between slices.
+    pub interval: Duration,
+    /// Randomizes each wait by up to this fraction of `interval`, in either
+    /// direction. Taken as 1 if it's above, and as 0 if it's below or NaN.
+    pub jitter: f64,
+    /// Stops once the market's probability is beyond this: above it when
+    /// buying YES, below it when buying NO.
+    pub abort_beyond_prob: Option<f64>,
+    /// Stops after this many failed slices.
+    pub max_failures: u32,
+}
+
+impl TwapConfig {
+    pub fn new(slices: u32, interval: Duration) -> TwapConfig {
+        TwapConfig {
+            slices,
+            interval,
+            jitter: 0.2,
+            abort_beyond_prob: None,
+            max_failures: 3,
+        }
+    }
+
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn abort_beyond_prob(mut self, prob: f64) -> Self {
+        self.abort_beyond_prob = Some(prob);
+        self
+    }
+
+    pub fn max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = max_failures;
+        self
+    }
+}
+
+/// What [`ManifoldClient::execute_twap`] did.
+#[derive(Debug, Default)]
+pub struct TwapReport {
+    pub fills: Vec<Bet>,
+    pub failures: Vec<ManifoldError>,
+    /// Why execution stopped early, if it did.
+    pub aborted: Option<String>,
+    /// Mana of the order that wasn't placed.
+    pub remaining: u64,
+}
+
+impl TwapReport {
+    /// Mana spent across all fills.
+    pub fn filled_amount(&self) -> f64 {
+        self.fills.iter().map(|b| b.amount()).sum()
+    }
+}
+
+/// Splits `amount` into `slices` whole amounts that differ by at most 1.
+fn split_amount(amount: u64, slices: u32) -> Vec<u64> {
+    let slices = u64::from(slices.max(1)).min(amount.max(1));
+
+    (0..slices)
+        .map(|i| amount / slices + u64::from(i < amount % slices))
+        .collect()
+}
+
+fn jittered(interval: Duration, jitter: f64, rng: &mut impl Rng) -> Duration {
+    if jitter.is_nan() || jitter <= 0.0 {
+        return interval;
+    }
+
+    let jitter = jitter.min(1.0);
+    interval.mul_f64(1.0 + rng.gen_range(-jitter..=jitter))
+}
+
+/// How to place an order without moving the market too much, see [`plan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionPlan {
+    /// A market order to place right away, if the market can absorb any of the order.
+    pub immediate: Option<PlaceBetArgs>,
+    /// Limit orders for the rest, from the best price to the worst.
+    pub resting: Vec<PlaceBetArgs>,
+    /// The probability the immediate order is expected to move the market to.
+    pub expected_prob: f64,
+}
+
+impl ExecutionPlan {
+    /// All orders of the plan, immediate one first.
+    pub fn orders(&self) -> Vec<PlaceBetArgs> {
+        self.immediate
+            .iter()
+            .chain(&self.resting)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Rounds a limit probability to a whole percent, towards the better price.
+fn round_limit(prob: f64, buying_yes: bool) -> f64 {
+    let percent = prob * 100.0;
+    let percent = if buying_yes {
+        percent.floor()
+    } else {
+        percent.ceil()
+    };
+
+    (percent / 100.0).clamp(0.01, 0.99)
+}
+
+/// Splits `order` into what can be bought from `pool` now without moving the
+/// probability by more than `max_impact`, and limit orders for the rest.
+///
+/// The immediate part moves the market at most to the impact limit (or the
+/// order's own `limit_prob`, if that's closer). The rest is split evenly into
+/// `levels` limit orders between the current probability and that limit,
+/// which fill as other traders move the market back.
+///
+/// Only YES and NO orders are supported. Uses [`CpmmPool`] math, which ignores fees.
+pub fn plan(
+    order: &PlaceBetArgs,
+    pool: &CpmmPool,
+    max_impact: f64,
+    levels: u32,
+) -> Result<ExecutionPlan> {
+    let buying_yes = match order.outcome {
+        Outcome::Yes => true,
+        Outcome::No => false,
+        _ => {
+            return Err(ManifoldError::Other(
+                "only YES and NO orders can be planned".to_owned(),
+            ))
+        }
+    };
+
+    let current = pool.probability();
+    let mut limit = if buying_yes {
+        (current + max_impact).min(0.99)
+    } else {
+        (current - max_impact).max(0.01)
+    };
+
+    if let Some(order_limit) = order.limit_prob {
+        limit = if buying_yes {
+            limit.min(order_limit)
+        } else {
+            limit.max(order_limit)
+        };
+    }
+
+    let absorbable = pool.amount_to_probability(&order.outcome, limit)?.floor() as u64;
+    let immediate_amount = absorbable.min(order.amount);
+    let rest = order.amount - immediate_amount;
+
+    let immediate = (immediate_amount > 0).then(|| PlaceBetArgs {
+        amount: immediate_amount,
+        limit_prob: Some(round_limit(limit, buying_yes)),
+        ..order.clone()
+    });
+
+    let expected_prob = pool.probability_after(&order.outcome, immediate_amount as f64)?;
+
+    let resting = if rest == 0 {
+        vec![]
+    } else {
+        let amounts = split_amount(rest, levels);
+        let count = amounts.len() as f64;
+
+        amounts
+            .into_iter()
+            .enumerate()
+            .map(|(i, amount)| {
+                // Best price first: the level closest to the current probability.
+                let prob = current + (limit - current) * (i + 1) as f64 / count;
+
+                PlaceBetArgs {
+                    amount,
+                    limit_prob: Some(round_limit(prob, buying_yes)),
+                    ..order.clone()
+                }
+            })
+            .collect()
+    };
+
+    Ok(ExecutionPlan {
+        immediate,
+        resting,
+        expected_prob,
+    })
+}
+
+impl ManifoldClient {
+    /// Places all orders of a [`plan`] at once, see [`post_basket`](Self::post_basket).
+    pub async fn execute_plan(&self, plan: &ExecutionPlan) -> BasketReport {
+        self.post_basket(plan.orders()).await
+    }
+
+    /// Places `order` in slices spread over time (time-weighted average price).
+    ///
+    /// Before each slice the market is fetched again; execution stops if it's
+    /// about to close (see [`time_to_bet`](Self::time_to_bet)), if the probability
+    /// is past [`abort_beyond_prob`](TwapConfig::abort_beyond_prob), or after
+    /// [`max_failures`](TwapConfig::max_failures) failed slices. Each slice keeps
+    /// the order's `limit_prob`.
+    ///
+    /// Dropping the future stops execution between slices.
+    pub async fn execute_twap(&self, order: &PlaceBetArgs, config: &TwapConfig) -> TwapReport {
+        let slices = split_amount(order.amount, config.slices);
+        let mut report = TwapReport {
+            remaining: order.amount,
+            ..Default::default()
+        };
+
+        for (i, amount) in slices.into_iter().enumerate() {
+            if i > 0 {
+                let wait = jittered(config.interval, config.jitter, &mut rand::thread_rng());
+                self.clock().sleep(wait).await;
+            }
+
+            let market = match self.get_market(&order.contract_id).await {
+                Ok(market) => market,
+                Err(e) => {
+                    report.failures.push(e);
+
+                    if report.failures.len() >= config.max_failures as usize {
+                        report.aborted = Some("too many failures".to_owned());
+                        break;
+                    }
+
+                    continue;
+                }
+            };
+
+            if let Err(e) = self.time_to_bet(&market, Duration::ZERO) {
+                report.aborted = Some(e.to_string());
+                break;
+            }
+
+            let beyond = match (
+                &order.outcome,
+                config.abort_beyond_prob,
+                market.probability(),
+            ) {
+                (Outcome::Yes, Some(limit), Some(prob)) => prob > limit,
+                (Outcome::No, Some(limit), Some(prob)) => prob < limit,
+                _ => false,
+            };
+
+            if beyond {
+                report.aborted = Some(format!(
+                    "probability {:?} is beyond {:?}",
+                    market.probability(),
+                    config.abort_beyond_prob
+                ));
+                break;
+            }
+
+            let slice = PlaceBetArgs {
+                amount,
+                ..order.clone()
+            };
+
+            match self.place_bet(&slice).await {
+                Ok(bet) => {
+                    report.remaining -= amount;
+                    report.fills.push(bet);
+                }
+                Err(e) => {
+                    report.failures.push(e);
+
+                    if report.failures.len() >= config.max_failures as usize {
+                        report.aborted = Some("too many failures".to_owned());
+                        break;
+                    }
+                }
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn splits_amounts() {
+        assert_eq!(split_amount(100, 3), vec![34, 33, 33]);
+        assert_eq!(split_amount(2, 5), vec![1, 1]);
+        assert_eq!(split_amount(10, 0), vec![10]);
+        assert_eq!(split_amount(1000, 7).iter().sum::<u64>(), 1000);
+    }
+
+    #[test]
+    fn plans_within_impact() -> Result<()> {
+        let pool = CpmmPool::new(100.0, 100.0, 0.5);
+        let order = PlaceBetArgs::new(1000, "market", Outcome::Yes);
+
+        let plan = plan(&order, &pool, 0.1, 3)?;
+
+        let immediate = plan.immediate.as_ref().unwrap();
+        assert!(plan.expected_prob <= 0.6 && plan.expected_prob > 0.59);
+        assert_eq!(immediate.limit_prob, Some(0.6));
+
+        assert_eq!(plan.resting.len(), 3);
+        assert_eq!(
+            plan.orders().iter().map(|o| o.amount).sum::<u64>(),
+            order.amount
+        );
+        assert_eq!(
+            plan.resting
+                .iter()
+                .map(|o| o.limit_prob.unwrap())
+                .collect::<Vec<_>>(),
+            vec![0.53, 0.56, 0.6]
+        );
+
+        let small = PlaceBetArgs::new(5, "market", Outcome::No);
+        let plan = super::plan(&small, &pool, 0.1, 3)?;
+        assert_eq!(plan.immediate.unwrap().amount, 5);
+        assert!(plan.resting.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn jitters_within_bounds() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let interval = Duration::from_secs(60);
+
+        for _ in 0..100 {
+            let wait = jittered(interval, 0.25, &mut rng);
+
+            assert!(wait >= Duration::from_secs(45) && wait <= Duration::from_secs(75));
+        }
+
+        assert_eq!(jittered(interval, 0.0, &mut rng), interval);
+        assert_eq!(jittered(interval, f64::NAN, &mut rng), interval);
+        assert!(jittered(interval, 5.0, &mut rng) <= Duration::from_secs(120));
+    }
+}
@@ -0,0 +1,372 @@
+//! Accounting exports of a user's bet and transaction history
+//!
+//! [`ManifoldClient::ledger`] collects everything that changed a user's balance
+//! into a [`Ledger`], with fees and the realized profit of each resolution.
+//! With the `csv` feature, ledgers can be written to CSV and read back in a
+//! later run to [`diff`](Ledger::diff) against fresh data.
+//!
+//! Amounts are kept in the API's textual form, which is exact with the
+//! `arbitrary-precision` feature.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::types::*;
+use crate::ManifoldClient;
+
+const PAYOUT: &str = "CONTRACT_RESOLUTION_PAYOUT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RowKind {
+    Bet,
+    Txn,
+    /// A market resolved without paying the user anything.
+    Resolution,
+}
+
+/// One balance change in a [`Ledger`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LedgerRow {
+    pub time: DateTime<Utc>,
+    pub kind: RowKind,
+    /// Bet or transaction id; the market id for resolutions.
+    pub id: String,
+    pub market_id: Option<String>,
+    /// Bet outcome, or transaction category.
+    pub detail: String,
+    /// Change of the user's balance: negative for bets, positive for sales and payouts.
+    pub amount: String,
+    pub shares: Option<String>,
+    pub fees: Option<f64>,
+    /// On resolution payouts (and [`RowKind::Resolution`] rows): the payout
+    /// minus the mana the user had put into the market.
+    pub realized_pnl: Option<f64>,
+}
+
+/// Rows that differ between two ledgers, see [`Ledger::diff`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LedgerDiff {
+    pub added: Vec<LedgerRow>,
+    pub removed: Vec<LedgerRow>,
+    /// `(previous, current)` pairs of rows with the same kind and id.
+    pub changed: Vec<(LedgerRow, LedgerRow)>,
+}
+
+impl LedgerDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A user's balance history, oldest first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ledger {
+    pub rows: Vec<LedgerRow>,
+}
+
+fn negate(amount: &str) -> String {
+    match amount.strip_prefix('-') {
+        Some(positive) => positive.to_owned(),
+        None => format!("-{amount}"),
+    }
+}
+
+fn timestamp(ms: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(ms).unwrap_or_default()
+}
+
+impl Ledger {
+    /// Builds the ledger of `user_id` from their bets and the transactions they sent or received.
+    ///
+    /// Losing positions don't produce a payout transaction, so markets the user bet on
+    /// that have resolved are passed in `resolved` to realize their losses.
+    pub fn from_history<M: Market>(
+        user_id: &str,
+        bets: &[Bet],
+        txns: &[Txn],
+        resolved: &[M],
+    ) -> Ledger {
+        let mut rows = vec![];
+
+        for bet in bets {
+            rows.push(LedgerRow {
+                time: timestamp(bet.created_time()),
+                kind: RowKind::Bet,
+                id: bet.id().to_owned(),
+                market_id: Some(bet.contract_id().to_owned()),
+                detail: bet.outcome().to_owned(),
+                amount: negate(&bet.amount_exact()),
+                shares: Some(bet.shares_exact()),
+                fees: Some(bet.fees()),
+                realized_pnl: None,
+            });
+        }
+
+        let mut paid = HashSet::new();
+
+        for txn in txns {
+            let market_id = if txn.to_type() == "CONTRACT" {
+                Some(txn.to_id())
+            } else if txn.from_type() == "CONTRACT" {
+                Some(txn.from_id())
+            } else {
+                None
+            };
+
+            if txn.category() == PAYOUT {
+                paid.extend(market_id);
+            }
+
+            rows.push(LedgerRow {
+                time: timestamp(txn.created_time()),
+                kind: RowKind::Txn,
+                id: txn.id().to_owned(),
+                market_id: market_id.map(str::to_owned),
+                detail: txn.category().to_owned(),
+                amount: if txn.from_id() == user_id {
+                    negate(&txn.amount_exact())
+                } else {
+                    txn.amount_exact()
+                },
+                shares: None,
+                fees: None,
+                realized_pnl: None,
+            });
+        }
+
+        for market in resolved {
+            if paid.contains(market.id()) {
+                continue;
+            }
+
+            let Some(time) = market.resolution_time() else {
+                continue;
+            };
+
+            rows.push(LedgerRow {
+                time,
+                kind: RowKind::Resolution,
+                id: market.id().to_owned(),
+                market_id: Some(market.id().to_owned()),
+                detail: market.resolution().unwrap_or_default().to_owned(),
+                amount: "0".to_owned(),
+                shares: None,
+                fees: None,
+                realized_pnl: None,
+            });
+        }
+
+        rows.sort_by(|a, b| a.time.cmp(&b.time).then(a.kind.cmp(&b.kind)));
+
+        let mut invested: HashMap<String, f64> = HashMap::new();
+
+        for row in &mut rows {
+            let Some(market_id) = &row.market_id else {
+                continue;
+            };
+
+            let amount = row.amount.parse::<f64>().unwrap_or(0.0);
+
+            match row.kind {
+                RowKind::Bet => *invested.entry(market_id.clone()).or_default() -= amount,
+                RowKind::Txn if row.detail != PAYOUT => {}
+                RowKind::Txn | RowKind::Resolution => {
+                    let invested = invested.remove(market_id).unwrap_or(0.0);
+
+                    row.realized_pnl = Some(amount - invested);
+                }
+            }
+        }
+
+        Ledger { rows }
+    }
+
+    /// Realized profit per market, from the rows that have one.
+    pub fn realized_pnl(&self) -> BTreeMap<&str, f64> {
+        let mut pnl = BTreeMap::new();
+
+        for row in &self.rows {
+            if let (Some(market_id), Some(realized)) = (&row.market_id, row.realized_pnl) {
+                *pnl.entry(market_id.as_str()).or_default() += realized;
+            }
+        }
+
+        pnl
+    }
+
+    /// What changed from `previous` to this ledger, matching rows by kind and id.
+    pub fn diff(&self, previous: &Ledger) -> LedgerDiff {
+        let key = |row: &LedgerRow| (row.kind, row.id.clone());
+
+        let old: HashMap<_, _> = previous.rows.iter().map(|r| (key(r), r)).collect();
+        let new: HashMap<_, _> = self.rows.iter().map(|r| (key(r), r)).collect();
+
+        let mut diff = LedgerDiff::default();
+
+        for row in &self.rows {
+            match old.get(&key(row)) {
+                None => diff.added.push(row.clone()),
+                Some(&old) if old != row => diff.changed.push((old.clone(), row.clone())),
+                Some(_) => {}
+            }
+        }
+
+        diff.removed = previous
+            .rows
+            .iter()
+            .filter(|r| !new.contains_key(&key(r)))
+            .cloned()
+            .collect();
+
+        diff
+    }
+
+    #[cfg(feature = "csv")]
+    pub fn write_csv<W: std::io::Write>(&self, writer: W) -> Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+
+        for row in &self.rows {
+            writer.serialize(row)?;
+        }
+
+        writer.flush().map_err(csv::Error::from)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "csv")]
+    pub fn read_csv<R: std::io::Read>(reader: R) -> Result<Ledger> {
+        let rows = csv::Reader::from_reader(reader)
+            .deserialize()
+            .try_collect::<Vec<LedgerRow>>()?;
+
+        Ok(Ledger { rows })
+    }
+}
+
+impl ManifoldClient {
+    /// Fetches the complete [`Ledger`] of `user_id`: all bets, all transactions,
+    /// and the markets they bet on that resolved without a payout.
+    ///
+    /// This scans the user's whole history, so it takes a while for active users.
+    pub async fn ledger(&self, user_id: &str) -> Result<Ledger> {
+        let bets: Vec<Bet> = self
+            .stream_bets(Some(user_id), None, None, None)
+            .try_collect()
+            .await?;
+
+        let mut txns: Vec<Txn> = self
+            .stream_txns(Some(user_id), None, None)
+            .try_collect()
+            .await?;
+        let received: Vec<Txn> = self
+            .stream_txns(None, Some(user_id), None)
+            .try_collect()
+            .await?;
+        txns.extend(received);
+
+        let paid: HashSet<&str> = txns
+            .iter()
+            .filter(|t| t.category() == PAYOUT)
+            .map(|t| t.from_id())
+            .collect();
+
+        let market_ids: HashSet<&str> = bets
+            .iter()
+            .map(|b| b.contract_id())
+            .filter(|id| !paid.contains(id))
+            .collect();
+
+        let mut resolved = vec![];
+
+        for id in market_ids {
+            let market = self.get_market(id).await?;
+
+            if market.is_resolved() {
+                resolved.push(market);
+            }
+        }
+
+        Ok(Ledger::from_history(user_id, &bets, &txns, &resolved))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn ledger() -> Ledger {
+        let bets = [
+            json!({"id": "b1", "contractId": "won", "outcome": "YES", "createdTime": 1000,
+                   "amount": 10, "shares": 20, "fees": {"creatorFee": 0.5, "platformFee": 0.25}}),
+            json!({"id": "b2", "contractId": "won", "outcome": "YES", "createdTime": 2000,
+                   "amount": -4, "shares": -5}),
+            json!({"id": "b3", "contractId": "lost", "outcome": "NO", "createdTime": 3000,
+                   "amount": 7, "shares": 9}),
+        ]
+        .map(Bet);
+
+        let txns = [json!({"id": "t1", "category": PAYOUT, "createdTime": 5000,
+                "fromId": "won", "fromType": "CONTRACT", "toId": "me", "toType": "USER",
+                "amount": 15})]
+        .map(Txn);
+
+        let resolved = [FullMarket(
+            json!({"id": "lost", "isResolved": true, "resolution": "YES", "resolutionTime": 4000}),
+        )];
+
+        Ledger::from_history("me", &bets, &txns, &resolved)
+    }
+
+    #[test]
+    fn realizes_pnl_on_resolution() {
+        let ledger = ledger();
+
+        assert_eq!(ledger.rows.len(), 5);
+        assert_eq!(ledger.rows[0].amount, "-10");
+        assert_eq!(ledger.rows[0].fees, Some(0.75));
+        assert_eq!(ledger.rows[1].amount, "4");
+        assert_eq!(ledger.rows[3].kind, RowKind::Resolution);
+
+        let pnl = ledger.realized_pnl();
+        assert_eq!(pnl["won"], 9.0);
+        assert_eq!(pnl["lost"], -7.0);
+    }
+
+    #[test]
+    fn diffs_ledgers() {
+        let previous = ledger();
+        let mut current = previous.clone();
+
+        current.rows.remove(0);
+        current.rows[0].fees = Some(1.0);
+
+        let diff = current.diff(&previous);
+
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.changed.len(), 1);
+        assert!(diff.added.is_empty());
+        assert!(previous.diff(&previous).is_empty());
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn csv_round_trip() -> Result<()> {
+        let ledger = ledger();
+
+        let mut buffer = vec![];
+        ledger.write_csv(&mut buffer)?;
+
+        assert!(String::from_utf8_lossy(&buffer).starts_with("time,kind,id,market_id"));
+        assert_eq!(Ledger::read_csv(buffer.as_slice())?, ledger);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,516 @@
+//! Sample data for tests, behind the `test-util` feature
+//!
+//! Every function returns a fresh, realistic copy of what the API returns
+//! for that entity, with fixed ids and timestamps so tests are deterministic.
+//! Tweak the returned JSON (`market.0["closeTime"] = ...`) for edge cases,
+//! or build markets with specific pools with [`cpmm_market`] and
+//! [`multi_market_with`].
+//!
+//! [`MockServer`] serves them over HTTP on localhost, for tests that go
+//! through a real [`ManifoldClient`]:
+//!
+//! ```
+//! # use manifold_markets::fixtures::{self, MockServer};
+//! # tokio_test_block_on(async {
+//! let server = MockServer::api().await?;
+//! let client = server.client().build()?;
+//!
+//! assert_eq!(client.get_me().await?.id(), fixtures::USER_ID);
+//! assert_eq!(server.requests()[0].path, "/me");
+//! # Ok::<(), manifold_markets::error::ManifoldError>(())
+//! # });
+//! # fn tokio_test_block_on<F: std::future::Future>(f: F) -> F::Output {
+//! #     tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(f)
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::error::Result;
+use crate::math::CpmmPool;
+use crate::types::*;
+use crate::{ManifoldAuthorization, ManifoldClient, ManifoldClientBuilder};
+
+/// 2023-01-01T00:00:00Z, the creation time of all fixtures.
+pub const CREATED_TIME: i64 = 1_672_531_200_000;
+/// 2030-01-01T00:00:00Z, the close time of all fixture markets.
+pub const CLOSE_TIME: i64 = 1_893_456_000_000;
+
+pub const USER_ID: &str = "fixtureUserId";
+pub const CREATOR_ID: &str = "fixtureCreatorId";
+
+pub fn user() -> User {
+    User(json!({
+        "id": USER_ID,
+        "createdTime": CREATED_TIME,
+        "name": "Fixture User",
+        "username": "FixtureUser",
+        "url": "https://manifold.markets/FixtureUser",
+        "avatarUrl": "https://example.com/avatar.png",
+        "balance": 1234.5,
+        "totalDeposits": 1000,
+        "profitCached": {"daily": 1.5, "weekly": -3.0, "monthly": 20.25, "allTime": 234.5},
+    }))
+}
+
+pub fn group() -> Group {
+    Group(json!({
+        "id": "fixtureGroupId",
+        "slug": "fixture-group",
+        "name": "Fixture Group",
+        "creatorId": CREATOR_ID,
+        "createdTime": CREATED_TIME,
+        "totalMembers": 12,
+        "privacyStatus": "public",
+    }))
+}
+
+/// A binary market with its CPMM pool; see [`cpmm_market`] for the fields.
+pub fn binary_market() -> FullMarket {
+    cpmm_market("fixtureBinaryId", CpmmPool::new(120.0, 80.0, 0.5))
+}
+
+/// An open binary `cpmm-1` market with the given pool and a matching probability.
+pub fn cpmm_market(id: &str, pool: CpmmPool) -> FullMarket {
+    FullMarket(json!({
+        "id": id,
+        "creatorId": CREATOR_ID,
+        "creatorUsername": "FixtureCreator",
+        "creatorName": "Fixture Creator",
+        "createdTime": CREATED_TIME,
+        "closeTime": CLOSE_TIME,
+        "question": "Will the fixture market resolve YES?",
+        "slug": "will-the-fixture-market-resolve-yes",
+        "url": "https://manifold.markets/FixtureCreator/will-the-fixture-market-resolve-yes",
+        "coverImageUrl": "https://storage.googleapis.com/fixture/cover.png",
+        "outcomeType": "BINARY",
+        "mechanism": "cpmm-1",
+        "pool": {"YES": pool.yes, "NO": pool.no},
+        "p": pool.p,
+        "totalLiquidity": 100,
+        "probability": pool.probability(),
+        "volume": 250.0,
+        "volume24Hours": 10.0,
+        "isResolved": false,
+        "visibility": "public",
+        "groupSlugs": ["fixture-group"],
+        "description": {"type": "doc", "content": []},
+        "textDescription": "",
+    }))
+}
+
+/// A `cpmm-multi-1` multiple choice market with three answers that sum to one.
+pub fn multi_market() -> FullMarket {
+    multi_market_with(&[("Red", 0.5), ("Green", 0.3), ("Blue", 0.2)], true)
+}
+
+/// A multiple choice market with the given `(text, probability)` answers.
+///
+/// Answer ids are `answer0`, `answer1`, ...
+pub fn multi_market_with(answers: &[(&str, f64)], sums_to_one: bool) -> FullMarket {
+    let answers = answers
+        .iter()
+        .enumerate()
+        .map(|(i, (text, prob))| {
+            json!({
+                "id": format!("answer{i}"),
+                "index": i,
+                "contractId": "fixtureMultiId",
+                "userId": CREATOR_ID,
+                "createdTime": CREATED_TIME,
+                "text": text,
+                "probability": prob,
+                "pool": {"YES": 100.0 * (1.0 - prob), "NO": 100.0 * prob},
+            })
+        })
+        .collect::<Vec<_>>();
+
+    FullMarket(json!({
+        "id": "fixtureMultiId",
+        "creatorId": CREATOR_ID,
+        "creatorUsername": "FixtureCreator",
+        "createdTime": CREATED_TIME,
+        "closeTime": CLOSE_TIME,
+        "question": "Which color will the fixture be?",
+        "slug": "which-color-will-the-fixture-be",
+        "outcomeType": "MULTIPLE_CHOICE",
+        "mechanism": "cpmm-multi-1",
+        "shouldAnswersSumToOne": sums_to_one,
+        "answers": answers,
+        "volume": 500.0,
+        "isResolved": false,
+        "visibility": "public",
+        "groupSlugs": ["fixture-group"],
+    }))
+}
+
+/// A resolved free response market from the `dpm-2` era.
+pub fn dpm_market() -> FullMarket {
+    FullMarket(json!({
+        "id": "fixtureDpmId",
+        "creatorId": CREATOR_ID,
+        "createdTime": CREATED_TIME,
+        "closeTime": CREATED_TIME + 86_400_000,
+        "question": "What will the fixture answer be?",
+        "outcomeType": "FREE_RESPONSE",
+        "mechanism": "dpm-2",
+        "pool": {"0": 0.0, "1": 150.0, "2": 50.0},
+        "totalShares": {"0": 0.0, "1": 300.0, "2": 100.0},
+        "answers": [
+            {"id": "1", "number": 1, "text": "Forty-two", "userId": USER_ID, "createdTime": CREATED_TIME, "probability": 0.9},
+            {"id": "2", "number": 2, "text": "Seven", "userId": CREATOR_ID, "createdTime": CREATED_TIME, "probability": 0.1},
+        ],
+        "isResolved": true,
+        "resolution": "1",
+        "resolutionTime": CREATED_TIME + 2 * 86_400_000,
+        "visibility": "public",
+        "groupSlugs": [],
+    }))
+}
+
+/// A partially filled YES limit order on [`binary_market`].
+pub fn bet_with_fills() -> Bet {
+    Bet(json!({
+        "id": "fixtureBetId",
+        "userId": USER_ID,
+        "contractId": "fixtureBinaryId",
+        "createdTime": CREATED_TIME + 60_000,
+        "outcome": "YES",
+        "orderAmount": 100,
+        "amount": 60,
+        "shares": 98.5,
+        "limitProb": 0.62,
+        "probBefore": 0.4,
+        "probAfter": 0.62,
+        "isFilled": false,
+        "isCancelled": false,
+        "fees": {"creatorFee": 0.5, "platformFee": 0.5, "liquidityFee": 0},
+        "fills": [
+            {"amount": 40, "shares": 70.0, "timestamp": CREATED_TIME + 60_000, "matchedBetId": null},
+            {"amount": 20, "shares": 28.5, "timestamp": CREATED_TIME + 120_000, "matchedBetId": "fixtureMakerBetId"},
+        ],
+    }))
+}
+
+/// A comment on [`binary_market`], as returned by `GET /v0/comments`.
+pub fn comment() -> Comment {
+    Comment(json!({
+        "id": "fixtureCommentId",
+        "contractId": "fixtureBinaryId",
+        "userId": USER_ID,
+        "userName": "Fixture User",
+        "userUsername": "FixtureUser",
+        "createdTime": CREATED_TIME + 180_000,
+        "commentType": "contract",
+        "content": {
+            "type": "doc",
+            "content": [{"type": "paragraph", "content": [{"type": "text", "text": "Fixture comment"}]}],
+        },
+    }))
+}
+
+/// What the mock API answers with for `method` and `target`, a path below
+/// `/v0` with an optional query; `None` for requests it doesn't know.
+///
+/// Markets and users come from the fixtures above; cursor-paginated
+/// endpoints return one page, and an empty one for any `before=` query.
+pub fn api_response(method: &str, target: &str) -> Option<Value> {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    if query.split('&').any(|param| param.starts_with("before=")) {
+        return Some(json!([]));
+    }
+
+    let response = match (method, path) {
+        ("GET", "/me") => user().0,
+        ("GET", path) if path.starts_with("/user/") => user().0,
+        ("GET", "/markets" | "/search-markets") => {
+            json!([binary_market().0, multi_market().0, dpm_market().0])
+        }
+        ("GET", path) if path.starts_with("/market/") => {
+            let mut market = binary_market().0;
+            market["id"] = json!(path.trim_start_matches("/market/"));
+            market
+        }
+        ("GET", "/bets") => json!([bet_with_fills().0]),
+        ("GET", "/comments") => json!([comment().0]),
+        ("GET", "/groups") => json!([group().0]),
+        ("GET", "/txns") => json!([]),
+        ("POST", "/bet") => bet_with_fills().0,
+        _ => return None,
+    };
+
+    Some(response)
+}
+
+/// A request [`MockServer`] received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    pub method: String,
+    /// Below `/v0`, e.g. `/market/abc`.
+    pub path: String,
+    /// Without the `?`, empty if there's none.
+    pub query: String,
+    /// Names in lowercase, in the order they were sent.
+    pub headers: Vec<(String, String)>,
+    /// `Null` for requests without a JSON body.
+    pub body: Value,
+}
+
+impl MockRequest {
+    /// The value of the header `name`, given in lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// What [`MockServer`] answers a request with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Value,
+    /// How long to wait before answering.
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    /// `200 OK` with `body`.
+    pub fn json(body: Value) -> MockResponse {
+        MockResponse::status(200, body)
+    }
+
+    pub fn status(status: u16, body: Value) -> MockResponse {
+        MockResponse {
+            status,
+            headers: vec![],
+            body,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Answer only after `delay`, e.g. to make requests time out.
+    pub fn delay(mut self, delay: Duration) -> MockResponse {
+        self.delay = delay;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> MockResponse {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&MockRequest) -> Option<MockResponse> + Send + Sync;
+
+/// A mock of the API on localhost, answering every request with a handler.
+///
+/// Requests the handler returns `None` for get a `404` with a Manifold-style
+/// error. The server stops when dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    base: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer {
+    /// A server answering with [`api_response`].
+    pub async fn api() -> Result<MockServer> {
+        MockServer::start(|request| {
+            let target = match request.query.as_str() {
+                "" => request.path.clone(),
+                query => format!("{}?{query}", request.path),
+            };
+
+            api_response(&request.method, &target).map(MockResponse::json)
+        })
+        .await
+    }
+
+    pub async fn start(
+        handler: impl Fn(&MockRequest) -> Option<MockResponse> + Send + Sync + 'static,
+    ) -> Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let base = format!("http://{}/api/v0", listener.local_addr()?);
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let task = tokio::spawn({
+            let requests = requests.clone();
+
+            async move {
+                while let Ok((connection, _)) = listener.accept().await {
+                    tokio::spawn(serve(connection, handler.clone(), requests.clone()));
+                }
+            }
+        });
+
+        Ok(MockServer {
+            base,
+            requests,
+            task,
+        })
+    }
+
+    /// Base URL for [`ManifoldClientBuilder::base`], e.g. `http://127.0.0.1:1234/api/v0`.
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// A builder for a client of this server, authorized with a dummy API key
+    /// and without a rate limit.
+    pub fn client(&self) -> ManifoldClientBuilder {
+        ManifoldClient::builder(ManifoldAuthorization::ApiKey("mock".to_owned()))
+            .base(&self.base)
+            .without_rate_limit()
+    }
+
+    /// Requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answers one request; every response closes the connection.
+async fn serve(
+    connection: TcpStream,
+    handler: Arc<Handler>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(connection);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    let mut content_length = 0;
+    let mut headers = vec![];
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).await?;
+
+        if header.trim().is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+
+            headers.push((name.trim().to_lowercase(), value.trim().to_owned()));
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let request = MockRequest {
+        method: method.to_owned(),
+        path: path.strip_prefix("/api/v0").unwrap_or(path).to_owned(),
+        query: query.to_owned(),
+        headers,
+        body: serde_json::from_slice(&body).unwrap_or_default(),
+    };
+    requests.lock().unwrap().push(request.clone());
+
+    let response = handler(&request).unwrap_or_else(|| {
+        MockResponse::status(404, json!({"message": format!("{target} is not mocked")}))
+    });
+
+    if !response.delay.is_zero() {
+        tokio::time::sleep(response.delay).await;
+    }
+
+    let body = response.body.to_string();
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n",
+        response.status,
+        body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str("\r\n");
+
+    let mut connection = reader.into_inner();
+    connection.write_all(head.as_bytes()).await?;
+    connection.write_all(body.as_bytes()).await?;
+    connection.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures_parse() {
+        let user = user();
+        assert_eq!(user.id(), USER_ID);
+        assert_eq!(user.balance(), 1234.5);
+        assert_eq!(group().slug(), "fixture-group");
+
+        let market = binary_market();
+        assert_eq!(market.pool().len(), 2);
+        assert_eq!(
+            CpmmPool::from_market(&market),
+            Some(CpmmPool::new(120.0, 80.0, 0.5))
+        );
+        assert_eq!(market.probability(), Some(0.4));
+
+        let multi = multi_market_with(&[("A", 0.7), ("B", 0.6)], false);
+        assert!(!multi.should_answers_sum_to_one());
+        assert_eq!(multi.answers()[1].id(), "answer1");
+
+        let dpm = dpm_market();
+        assert!(dpm.is_resolved());
+        assert_eq!(dpm.pool().len(), 3);
+
+        let bet = bet_with_fills();
+        assert!(!bet.is_filled());
+        assert_eq!(bet.fees(), 1.0);
+        assert_eq!(bet.limit_prob(), Some(0.62));
+
+        assert_eq!(comment().contract_id(), market.id());
+        assert_eq!(comment().text(), "Fixture comment");
+    }
+
+    #[tokio::test]
+    async fn serves_the_mock_api() -> Result<()> {
+        let server = MockServer::api().await?;
+        let client = server.client().build()?;
+
+        assert_eq!(client.get_market("abc").await?.id(), "abc");
+        assert!(client
+            .get_market_by_slug("nope")
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/market/abc");
+        assert_eq!(requests[0].header("authorization"), Some("Key mock"));
+
+        Ok(())
+    }
+}
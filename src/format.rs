@@ -0,0 +1,576 @@
+//! Formatting and parsing helpers for probabilities and mana
+//!
+//! Kept in one place so that every consumer (CLIs, reports, notifiers)
+//! displays the same number the same way. The one-line [`Display`](fmt::Display)
+//! impls of [`LiteMarket`], [`FullMarket`], [`Bet`], [`User`] and [`Outcome`]
+//! are here too.
+//!
+//! The plain functions use Manifold's own conventions: `M$1234.50`, times in
+//! UTC. Bots posting to communities elsewhere can pass [`FormatOptions`] to
+//! the `*_with` variants for thousands separators, another timezone, or
+//! relative times like "closes in 3h".
+
+use std::fmt;
+
+use chrono::{DateTime, FixedOffset, Utc};
+use serde_json::Value;
+
+use crate::error::{ManifoldError, Result};
+use crate::types::{number_f64, Bet, FullMarket, LiteMarket, Market, Outcome, User};
+
+/// Formats a probability as a whole percentage, the way Manifold shows it.
+///
+/// `0.237` becomes `"24%"`. Probabilities that would round to 0% or 100%
+/// without being exactly that are shown as `"<1%"` and `">99%"`.
+pub fn format_prob(prob: f64) -> String {
+    let pct = (prob * 100.0).round();
+
+    if pct <= 0.0 && prob > 0.0 {
+        "<1%".to_owned()
+    } else if pct >= 100.0 && prob < 1.0 {
+        ">99%".to_owned()
+    } else {
+        format!("{pct}%")
+    }
+}
+
+/// Formats a probability move, e.g. `"34%→41%"`.
+pub fn format_prob_move(from: f64, to: f64) -> String {
+    format!("{}→{}", format_prob(from), format_prob(to))
+}
+
+/// Parses a probability typed by a human.
+///
+/// Accepts percentages (`"65%"`, `"65 %"`) and fractions (`"0.65"`).
+/// A bare number above 1 is read as a percentage, so `"65"` is `0.65` too.
+pub fn parse_prob(input: &str) -> Result<f64> {
+    let input = input.trim();
+
+    let invalid = || ManifoldError::Validation(format!("{input:?} is not a probability"));
+
+    let (number, is_pct) = match input.strip_suffix('%') {
+        Some(number) => (number.trim_end(), true),
+        None => (input, false),
+    };
+
+    let value: f64 = number.parse().map_err(|_| invalid())?;
+
+    let prob = if is_pct || value > 1.0 {
+        value / 100.0
+    } else {
+        value
+    };
+
+    if !(0.0..=1.0).contains(&prob) {
+        return Err(invalid());
+    }
+
+    Ok(prob)
+}
+
+/// A probability in basis points (1% = 100bps).
+pub fn to_bps(prob: f64) -> i64 {
+    (prob * 10_000.0).round() as i64
+}
+
+/// The move from `from` to `to`, in basis points.
+pub fn delta_bps(from: f64, to: f64) -> i64 {
+    to_bps(to) - to_bps(from)
+}
+
+/// Formats a move in basis points with an explicit sign, e.g. `"+120bps"`.
+pub fn format_delta_bps(from: f64, to: f64) -> String {
+    format!("{:+}bps", delta_bps(from, to))
+}
+
+/// Formats an amount of mana, e.g. `"M$25"` or `"-M$3.50"`.
+///
+/// Whole amounts are shown without decimals.
+pub fn format_mana(amount: f64) -> String {
+    FormatOptions::default().mana(amount)
+}
+
+/// A time relative to `now`, e.g. `"in 3h"`, `"2d ago"` or `"now"`.
+///
+/// Uses the largest whole unit out of days, hours and minutes.
+pub fn format_relative_time(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (time - now).num_seconds();
+
+    let minutes = secs.abs() / 60;
+    let amount = match minutes {
+        0 => return "now".to_owned(),
+        1..=59 => format!("{minutes}m"),
+        60..=1439 => format!("{}h", minutes / 60),
+        _ => format!("{}d", minutes / 1440),
+    };
+
+    if secs > 0 {
+        format!("in {amount}")
+    } else {
+        format!("{amount} ago")
+    }
+}
+
+/// How mana and times are shown to people, for the `*_with` functions.
+///
+/// The default is what the plain functions use: `M$1234.50`, absolute times in UTC.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    /// Groups thousands, e.g. `Some(',')` for `M$1,234.50`.
+    pub thousands_separator: Option<char>,
+    /// `','` for `M$3,50`.
+    pub decimal_separator: char,
+    /// Absolute times are shown in this offset from UTC.
+    pub timezone: FixedOffset,
+    /// Show times relative to now (`"in 3h"`) instead of as dates.
+    pub relative_times: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            thousands_separator: None,
+            decimal_separator: '.',
+            timezone: FixedOffset::east_opt(0).unwrap(),
+            relative_times: false,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// See [`format_mana`].
+    pub fn mana(&self, amount: f64) -> String {
+        let sign = if amount < 0.0 { "-" } else { "" };
+        let amount = amount.abs();
+
+        let text = if (amount - amount.round()).abs() < 0.005 {
+            amount.round().to_string()
+        } else {
+            format!("{amount:.2}")
+        };
+
+        let (whole, fraction) = match text.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (&*text, None),
+        };
+
+        let mut grouped = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                grouped.extend(self.thousands_separator);
+            }
+            grouped.push(digit);
+        }
+
+        if let Some(fraction) = fraction {
+            grouped.push(self.decimal_separator);
+            grouped.push_str(fraction);
+        }
+
+        format!("{sign}M${grouped}")
+    }
+
+    /// A point in time, e.g. `"2024-05-01 14:00 UTC"`, `"2024-05-01 16:00 +02:00"`,
+    /// or `"in 3h"` with [`relative_times`](Self::relative_times).
+    pub fn time(&self, time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+        if self.relative_times {
+            return format_relative_time(time, now);
+        }
+
+        let local = time.with_timezone(&self.timezone).format("%Y-%m-%d %H:%M");
+
+        if self.timezone.local_minus_utc() == 0 {
+            format!("{local} UTC")
+        } else {
+            format!("{local} {}", self.timezone)
+        }
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_owned();
+    }
+
+    let mut truncated = text
+        .chars()
+        .take(max_chars.saturating_sub(1))
+        .collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// A compact plain-text summary of a market for prompts and chat messages,
+/// at most `max_chars` characters long.
+///
+/// One line each for the question, probability, close time or resolution,
+/// liquidity and the most likely answers. Lines that don't fit are left out,
+/// from the end; the question is shortened if it alone is too long.
+pub fn summarize_market(market: &impl Market, max_chars: usize) -> String {
+    summarize_market_with(market, max_chars, &FormatOptions::default(), Utc::now())
+}
+
+/// [`summarize_market`] with mana and the close time formatted by `options`.
+pub fn summarize_market_with(
+    market: &impl Market,
+    max_chars: usize,
+    options: &FormatOptions,
+    now: DateTime<Utc>,
+) -> String {
+    let data = market.data();
+    let mut lines = vec![];
+
+    if let Some(prob) = market.probability() {
+        lines.push(format!("Probability: {}", format_prob(prob)));
+    }
+
+    match (market.resolution(), market.close_time()) {
+        (Some(resolution), _) if market.is_resolved() => {
+            lines.push(format!("Resolved: {resolution}"));
+        }
+        (_, Some(close_time)) => {
+            let verb = if market.is_active_at(now) {
+                "Closes"
+            } else {
+                "Closed"
+            };
+            let separator = if options.relative_times { "" } else { ":" };
+            lines.push(format!(
+                "{verb}{separator} {}",
+                options.time(close_time, now)
+            ));
+        }
+        _ => {}
+    }
+
+    if let Some(liquidity) = number_f64(&data["totalLiquidity"]) {
+        lines.push(format!("Liquidity: {}", options.mana(liquidity)));
+    }
+
+    let mut summary = truncate(market.question(), max_chars);
+    let fits = |line: &str, summary: &mut String| {
+        let fits = summary.chars().count() + 1 + line.chars().count() <= max_chars;
+        if fits {
+            summary.push('\n');
+            summary.push_str(line);
+        }
+        fits
+    };
+
+    for line in &lines {
+        if !fits(line, &mut summary) {
+            return summary;
+        }
+    }
+
+    let mut answers = market
+        .answers()
+        .into_iter()
+        .filter_map(|a| Some((a.try_text().ok()?.to_owned(), a.probability()?)))
+        .collect::<Vec<_>>();
+    answers.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    // As many answers as fit, noting how many were left out.
+    let mut best = None;
+    for shown in 1..=answers.len() {
+        let mut line = answers[..shown]
+            .iter()
+            .map(|(text, prob)| format!("{text} {}", format_prob(*prob)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if shown < answers.len() {
+            line.push_str(&format!(" (+{} more)", answers.len() - shown));
+        }
+
+        let line = format!("Top answers: {line}");
+        if summary.chars().count() + 1 + line.chars().count() > max_chars {
+            break;
+        }
+        best = Some(line);
+    }
+
+    if let Some(line) = best {
+        fits(&line, &mut summary);
+    }
+
+    summary
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Yes => f.write_str("YES"),
+            Outcome::No => f.write_str("NO"),
+            Outcome::FreeResponse(answer) => f.write_str(answer),
+            Outcome::Numeric(_, value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// `"Will X happen?" 34%`, `"Will X happen?" resolved YES`.
+fn fmt_market(data: &Value, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", data["question"].as_str().unwrap_or("?"))?;
+
+    if data["isResolved"].as_bool().unwrap_or(false) {
+        let resolution = data["resolution"].as_str().unwrap_or("?");
+        return write!(f, " resolved {resolution}");
+    }
+
+    if let Some(prob) = number_f64(&data["probability"]) {
+        write!(f, " {}", format_prob(prob))?;
+    } else if let Some(answers) = data["answers"].as_array() {
+        write!(f, " ({} answers)", answers.len())?;
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for LiteMarket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_market(&self.0, f)
+    }
+}
+
+impl fmt::Display for FullMarket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_market(&self.0, f)
+    }
+}
+
+fn fmt_bet(bet: &Bet, options: &FormatOptions, f: &mut impl fmt::Write) -> fmt::Result {
+    let amount = number_f64(&bet.0["amount"]).unwrap_or(0.0);
+    let outcome = bet.0["outcome"].as_str().unwrap_or("?");
+
+    if amount < 0.0 {
+        write!(f, "sold {} {outcome}", options.mana(-amount))?;
+    } else {
+        write!(f, "{} {outcome}", options.mana(amount))?;
+    }
+
+    if let (Some(before), Some(after)) = (
+        number_f64(&bet.0["probBefore"]),
+        number_f64(&bet.0["probAfter"]),
+    ) {
+        write!(f, " @ {}", format_prob_move(before, after))?;
+    }
+
+    if let Some(limit) = bet.limit_prob() {
+        write!(f, ", limit {}", format_prob(limit))?;
+    }
+
+    Ok(())
+}
+
+/// `M$25 YES @ 34%→41%`, `sold M$10 NO @ 41%→45%`, with `limit 40%` for limit orders.
+impl fmt::Display for Bet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_bet(self, &FormatOptions::default(), f)
+    }
+}
+
+/// `Name (@username)`.
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.0["name"].as_str().unwrap_or("?");
+
+        match self.0["username"].as_str() {
+            Some(username) => write!(f, "{name} (@{username})"),
+            None => f.write_str(name),
+        }
+    }
+}
+
+/// A bet and the market it's on, e.g. `M$25 YES @ 34%→41% on "Will X happen?"`.
+///
+/// Bets on an answer name it: `M$10 YES on "Paris" @ 20%→24% on "Where will...?"`.
+pub fn summarize_bet(bet: &Bet, market: &impl Market) -> String {
+    summarize_bet_with(bet, market, &FormatOptions::default())
+}
+
+/// [`summarize_bet`] with the amount formatted by `options`.
+pub fn summarize_bet_with(bet: &Bet, market: &impl Market, options: &FormatOptions) -> String {
+    let answer = bet.answer_id().map(|answer_id| {
+        market
+            .answers()
+            .into_iter()
+            .find(|a| a.try_id().ok() == Some(answer_id))
+            .and_then(|a| a.try_text().ok().map(str::to_owned))
+            .unwrap_or_else(|| answer_id.to_owned())
+    });
+
+    let mut text = String::new();
+    fmt_bet(bet, options, &mut text).expect("writing to a String");
+    let bet = text;
+    let question = market.data()["question"].as_str().unwrap_or("?");
+
+    match answer {
+        // After the outcome, before the probabilities.
+        Some(answer) => {
+            let (head, tail) = bet.split_once(" @ ").unwrap_or((&bet, ""));
+            let tail = if tail.is_empty() {
+                String::new()
+            } else {
+                format!(" @ {tail}")
+            };
+
+            format!("{head} on {answer:?}{tail} on {question:?}")
+        }
+        None => format!("{bet} on {question:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use crate::fixtures;
+
+    use super::*;
+
+    #[test]
+    fn formats_probabilities() {
+        assert_eq!(format_prob(0.237), "24%");
+        assert_eq!(format_prob(0.0), "0%");
+        assert_eq!(format_prob(0.001), "<1%");
+        assert_eq!(format_prob(0.999), ">99%");
+        assert_eq!(format_prob(1.0), "100%");
+        assert_eq!(format_prob_move(0.34, 0.41), "34%→41%");
+    }
+
+    #[test]
+    fn displays_core_types() {
+        let bet = Bet(json!({
+            "amount": 25,
+            "outcome": "YES",
+            "probBefore": 0.34,
+            "probAfter": 0.41,
+        }));
+        let market = LiteMarket(json!({"question": "Will X happen?", "probability": 0.41}));
+
+        assert_eq!(bet.to_string(), "M$25 YES @ 34%→41%");
+        assert_eq!(
+            summarize_bet(&bet, &market),
+            "M$25 YES @ 34%→41% on \"Will X happen?\""
+        );
+        assert_eq!(market.to_string(), "\"Will X happen?\" 41%");
+
+        let sale = Bet(json!({"amount": -10.5, "outcome": "NO", "limitProb": 0.4}));
+        assert_eq!(sale.to_string(), "sold M$10.50 NO, limit 40%");
+
+        let answer_bet = Bet(json!({
+            "amount": 10,
+            "outcome": "YES",
+            "answerId": "answer1",
+            "probBefore": 0.2,
+            "probAfter": 0.24,
+        }));
+        assert_eq!(
+            summarize_bet(&answer_bet, &fixtures::multi_market()),
+            "M$10 YES on \"Green\" @ 20%→24% on \"Which color will the fixture be?\""
+        );
+        assert_eq!(
+            fixtures::multi_market().to_string(),
+            "\"Which color will the fixture be?\" (3 answers)"
+        );
+
+        let resolved =
+            LiteMarket(json!({"question": "Q?", "isResolved": true, "resolution": "NO"}));
+        assert_eq!(resolved.to_string(), "\"Q?\" resolved NO");
+
+        assert_eq!(
+            User(json!({"name": "Ada", "username": "ada"})).to_string(),
+            "Ada (@ada)"
+        );
+        assert_eq!(Outcome::FreeResponse("a1".to_owned()).to_string(), "a1");
+    }
+
+    #[test]
+    fn parses_probabilities() {
+        assert_eq!(parse_prob("65%").unwrap(), 0.65);
+        assert_eq!(parse_prob(" 65 % ").unwrap(), 0.65);
+        assert_eq!(parse_prob("0.65").unwrap(), 0.65);
+        assert_eq!(parse_prob("65").unwrap(), 0.65);
+        assert_eq!(parse_prob("1").unwrap(), 1.0);
+        assert!(parse_prob("150%").is_err());
+        assert!(parse_prob("-0.1").is_err());
+        assert_eq!(
+            parse_prob("yes").unwrap_err().to_string(),
+            r#"Invalid request: "yes" is not a probability"#
+        );
+    }
+
+    #[test]
+    fn formats_deltas_and_mana() {
+        assert_eq!(delta_bps(0.34, 0.352), 120);
+        assert_eq!(format_delta_bps(0.5, 0.45), "-500bps");
+        assert_eq!(format_mana(25.0), "M$25");
+        assert_eq!(format_mana(-3.5), "-M$3.50");
+        assert_eq!(format_mana(1234567.0), "M$1234567");
+    }
+
+    #[test]
+    fn formats_with_options() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let hours = |h: i64| now + chrono::Duration::hours(h);
+
+        let european = FormatOptions {
+            thousands_separator: Some('.'),
+            decimal_separator: ',',
+            timezone: FixedOffset::east_opt(2 * 3600).unwrap(),
+            relative_times: false,
+        };
+        assert_eq!(european.mana(-1234567.5), "-M$1.234.567,50");
+        assert_eq!(european.mana(999.0), "M$999");
+        assert_eq!(european.time(hours(3), now), "2024-05-01 17:00 +02:00");
+        assert_eq!(
+            FormatOptions::default().time(hours(3), now),
+            "2024-05-01 15:00 UTC"
+        );
+
+        assert_eq!(format_relative_time(hours(3), now), "in 3h");
+        assert_eq!(format_relative_time(hours(-50), now), "2d ago");
+        assert_eq!(
+            format_relative_time(now + chrono::Duration::seconds(90), now),
+            "in 1m"
+        );
+        assert_eq!(format_relative_time(now, now), "now");
+
+        let relative = FormatOptions {
+            thousands_separator: Some(','),
+            relative_times: true,
+            ..Default::default()
+        };
+        let market = LiteMarket(json!({
+            "question": "Q?",
+            "isResolved": false,
+            "closeTime": hours(3).timestamp_millis(),
+            "totalLiquidity": 12500,
+        }));
+        assert_eq!(
+            summarize_market_with(&market, 100, &relative, now),
+            "Q?\nCloses in 3h\nLiquidity: M$12,500"
+        );
+
+        let bet = Bet(json!({"amount": 2500, "outcome": "YES"}));
+        assert_eq!(
+            summarize_bet_with(&bet, &market, &relative),
+            "M$2,500 YES on \"Q?\""
+        );
+    }
+
+    #[test]
+    fn summarizes_markets() {
+        let market =
+            fixtures::multi_market_with(&[("Alice", 0.5), ("Bob", 0.3), ("Carol", 0.2)], true);
+
+        let summary = market.summarize(1000);
+        assert!(summary.starts_with(market.question()));
+        assert!(summary.ends_with("Top answers: Alice 50%, Bob 30%, Carol 20%"));
+
+        let short = market.summarize(summary.chars().count() - 10);
+        assert!(short.ends_with("Top answers: Alice 50% (+2 more)"));
+
+        assert_eq!(market.summarize(10).chars().count(), 10);
+    }
+}
@@ -0,0 +1,184 @@
+//! Refusing to trade on stale data
+//!
+//! A bot that pauses (a slow strategy, a laptop waking up, a long rate limit
+//! wait) can come back to a market it fetched minutes ago and bet on a
+//! probability that's long gone. [`Fetched`] keeps the time a value was
+//! fetched next to it, and [`Fetched::check_fresh`] refuses values older
+//! than a `max_data_age` with [`ManifoldError::StaleData`].
+//!
+//! [`ManifoldClient::fetch`] stamps any response with the client's clock as
+//! of when the request went out, so a slow response counts as older, not
+//! newer. [`ManifoldClient::get_market_fetched`] and
+//! [`ManifoldClient::watch_markets`] stamp markets that way, and snapshots
+//! recorded by [`SnapshotStore::watch`](crate::snapshots::SnapshotStore::watch)
+//! carry the same time. [`ManifoldClient::post_bet_if_fresh`] and
+//! [`SafetyRails::max_data_age`](crate::bot::SafetyRails::max_data_age) use
+//! the checks before betting.
+
+use std::future::Future;
+use std::ops::Deref;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ManifoldError, Result};
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// A value and when it was fetched.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Fetched<T> {
+    pub value: T,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl<T> Fetched<T> {
+    pub fn new(value: T, fetched_at: DateTime<Utc>) -> Fetched<T> {
+        Fetched { value, fetched_at }
+    }
+
+    /// How long ago the value was fetched; zero if `now` is before that.
+    pub fn age(&self, now: DateTime<Utc>) -> Duration {
+        (now - self.fetched_at).to_std().unwrap_or_default()
+    }
+
+    /// The value, unless it was fetched more than `max_age` before `now`.
+    pub fn check_fresh(&self, max_age: Duration, now: DateTime<Utc>) -> Result<&T> {
+        let age = self.age(now);
+
+        if age > max_age {
+            return Err(ManifoldError::StaleData(format!(
+                "data fetched at {} is {age:?} old, more than the allowed {max_age:?}",
+                self.fetched_at
+            )));
+        }
+
+        Ok(&self.value)
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Fetched<U> {
+        Fetched {
+            value: f(self.value),
+            fetched_at: self.fetched_at,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for Fetched<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl ManifoldClient {
+    /// `value`, stamped with the current time of the client's clock.
+    pub fn fetched<T>(&self, value: T) -> Fetched<T> {
+        Fetched::new(value, self.now())
+    }
+
+    /// Awaits `request`, stamping its result with the time it was sent.
+    ///
+    /// E.g. `client.fetch(client.get_bets(..))`. The time is taken before the
+    /// request, so network and rate limit delays add to the age.
+    pub async fn fetch<T>(&self, request: impl Future<Output = Result<T>>) -> Result<Fetched<T>> {
+        let fetched_at = self.now();
+        let value = request.await?;
+
+        Ok(Fetched::new(value, fetched_at))
+    }
+
+    /// [`get_market`](Self::get_market), with the time it was fetched.
+    ///
+    /// A market from the [read cache](crate::cache) keeps the time it was
+    /// first fetched.
+    pub async fn get_market_fetched(&self, market_id: &str) -> Result<Fetched<FullMarket>> {
+        self.execute_shared_fetched(&format!("/market/{market_id}"))
+            .await
+    }
+
+    /// [`Fetched::check_fresh`] by the client's clock.
+    pub fn check_fresh<'a, T>(&self, data: &'a Fetched<T>, max_age: Duration) -> Result<&'a T> {
+        data.check_fresh(max_age, self.now())
+    }
+
+    /// Places a bet decided on `market`, unless `market` is older than `max_data_age`.
+    ///
+    /// Also fails if the bet isn't on `market`, since then its age says nothing.
+    pub async fn post_bet_if_fresh<M: Market>(
+        &self,
+        args: &PlaceBetArgs,
+        market: &Fetched<M>,
+        max_data_age: Duration,
+    ) -> Result<Bet> {
+        let market = self.check_fresh(market, max_data_age)?;
+
+        if market.id() != args.contract_id {
+            return Err(ManifoldError::Validation(format!(
+                "the bet is on {}, but the data is of market {}",
+                args.contract_id,
+                market.id()
+            )));
+        }
+
+        self.place_bet(args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::{fixtures, ManifoldAuthorization};
+
+    #[test]
+    fn refuses_stale_data() {
+        let fetched_at = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let market = Fetched::new(fixtures::binary_market(), fetched_at);
+        let max_age = Duration::from_secs(60);
+
+        let later = |secs: i64| fetched_at + chrono::Duration::seconds(secs);
+
+        assert_eq!(market.age(later(30)), Duration::from_secs(30));
+        assert_eq!(market.age(later(-30)), Duration::ZERO);
+        assert_eq!(
+            market.check_fresh(max_age, later(60)).unwrap().id(),
+            market.id()
+        );
+        assert!(matches!(
+            market.check_fresh(max_age, later(61)),
+            Err(ManifoldError::StaleData(_))
+        ));
+
+        let probability = market.map(|m| m.probability());
+        assert_eq!(probability.fetched_at, fetched_at);
+    }
+
+    #[tokio::test]
+    async fn stamps_responses_with_the_time_they_were_requested() -> Result<()> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let clock = MockClock::new(start);
+        let client = ManifoldClient::builder(ManifoldAuthorization::NoAuthorization)
+            .clock(clock.clone())
+            .build()?;
+
+        let slow_response = async {
+            clock.advance(Duration::from_secs(5));
+            Ok(fixtures::binary_market())
+        };
+        let market = client.fetch(slow_response).await?;
+
+        assert_eq!(market.fetched_at, start);
+        assert_eq!(market.age(client.now()), Duration::from_secs(5));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! Activity in a group, emulated by polling
+//!
+//! [`ManifoldClient::stream_group_activity`] merges what happens in a group
+//! (topic) into one stream, e.g. for a community's Discord relay:
+//!
+//! - markets added to the group,
+//! - bets on the group's markets, picked out of the latest bets site-wide,
+//! - comments on the group's open markets.
+//!
+//! Everything that already happened when the stream starts is skipped, as
+//! are the comments already on a market when it joins the group.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::Stream;
+
+use crate::error::Result;
+use crate::polling::{poll_stream, Seen};
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// How many of the latest bets site-wide are checked for bets in the group each round.
+///
+/// Bets beyond this between two rounds are missed, so busy times call for shorter intervals.
+pub const GROUP_ACTIVITY_BETS: u64 = 1000;
+
+/// How many of the group's open markets are checked for comments each round.
+pub const GROUP_ACTIVITY_MARKETS: usize = 50;
+
+#[derive(Debug, Clone)]
+pub enum GroupActivity {
+    NewMarket { market: LiteMarket },
+    NewBet { bet: Bet },
+    NewComment { market_id: String, comment: Comment },
+}
+
+#[derive(Debug, Default)]
+struct GroupState {
+    /// The group's markets, its bets and the comments on each open market.
+    seen: Seen,
+    /// The group's markets, and the ones of those that aren't resolved yet.
+    markets: HashSet<String>,
+    open_markets: Vec<String>,
+    /// Creation time of the newest bet seen.
+    bets_since: Option<DateTime<Utc>>,
+}
+
+impl GroupState {
+    fn add_markets(&mut self, markets: Vec<LiteMarket>) -> Vec<GroupActivity> {
+        self.markets = markets.iter().map(|m| m.id().to_owned()).collect();
+        self.open_markets = markets
+            .iter()
+            .filter(|m| !m.is_resolved())
+            .take(GROUP_ACTIVITY_MARKETS)
+            .map(|m| m.id().to_owned())
+            .collect();
+
+        let sources = self
+            .open_markets
+            .iter()
+            .map(|id| format!("comments/{id}"))
+            .collect::<HashSet<_>>();
+        self.seen
+            .retain(|source| !source.starts_with("comments/") || sources.contains(source));
+
+        let markets = markets.into_iter().rev().collect();
+
+        self.seen
+            .diff("markets", markets, |market: &LiteMarket| market.id())
+            .new_items()
+            .into_iter()
+            .map(|market| GroupActivity::NewMarket { market })
+            .collect()
+    }
+
+    /// `bets` are newest first, like the API returns them.
+    fn add_bets(&mut self, bets: Vec<Bet>) -> Vec<GroupActivity> {
+        let bets: Vec<Bet> = bets.into_iter().rev().collect();
+
+        for bet in &bets {
+            if let Some(time) = DateTime::from_timestamp_millis(bet.created_time()) {
+                self.bets_since = self.bets_since.max(Some(time));
+            }
+        }
+
+        self.seen
+            .diff("bets", bets, |bet: &Bet| bet.id())
+            .new_items()
+            .into_iter()
+            .filter(|bet| self.markets.contains(bet.contract_id()))
+            .map(|bet| GroupActivity::NewBet { bet })
+            .collect()
+    }
+
+    /// `comments` are newest first, like the API returns them.
+    fn add_comments(&mut self, market_id: &str, comments: Vec<Comment>) -> Vec<GroupActivity> {
+        let comments = comments.into_iter().rev().collect();
+
+        self.seen
+            .diff(
+                &format!("comments/{market_id}"),
+                comments,
+                |comment: &Comment| comment.id(),
+            )
+            .new_items()
+            .into_iter()
+            .map(|comment| GroupActivity::NewComment {
+                market_id: market_id.to_owned(),
+                comment,
+            })
+            .collect()
+    }
+}
+
+impl ManifoldClient {
+    /// Polls a group's activity every `interval`, see the [module documentation](crate::groups).
+    ///
+    /// Each round makes a request per open market of the group (up to
+    /// [`GROUP_ACTIVITY_MARKETS`]). Never ends; a failed request yields an
+    /// error and polling continues.
+    pub fn stream_group_activity<'a>(
+        &'a self,
+        group_id: &'a str,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<GroupActivity>> + 'a {
+        poll_stream(
+            self,
+            interval,
+            GroupState::default(),
+            move |mut state| async move {
+                let mut items = vec![];
+
+                if let Err(e) = self.poll_group(group_id, &mut state, &mut items).await {
+                    items.push(Err(e));
+                }
+
+                Some((state, items))
+            },
+        )
+    }
+
+    async fn poll_group(
+        &self,
+        group_id: &str,
+        state: &mut GroupState,
+        items: &mut Vec<Result<GroupActivity>>,
+    ) -> Result<()> {
+        let markets = self.get_group_markets(group_id).await?;
+        items.extend(state.add_markets(markets).into_iter().map(Ok));
+
+        let bets = self
+            .get_bets_with(&BetsQuery {
+                after_time: state.bets_since,
+                ..BetsQuery::new().limit(GROUP_ACTIVITY_BETS)
+            })
+            .await?;
+        items.extend(state.add_bets(bets).into_iter().map(Ok));
+
+        for market_id in state.open_markets.clone() {
+            let comments = self.get_comments(Some(&market_id), None).await?;
+            items.extend(state.add_comments(&market_id, comments).into_iter().map(Ok));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reports_only_new_activity_in_the_group() {
+        let market = |id: &str| LiteMarket(json!({"id": id, "isResolved": false}));
+        let bet = |id: &str, market_id: &str, time: i64| {
+            Bet(json!({"id": id, "contractId": market_id, "createdTime": time}))
+        };
+
+        let comment = |id: &str| Comment(json!({"id": id}));
+
+        let mut state = GroupState::default();
+        assert!(state.add_markets(vec![market("a")]).is_empty());
+        assert!(state.add_bets(vec![bet("b1", "a", 10)]).is_empty());
+        assert!(state.add_comments("a", vec![comment("c1")]).is_empty());
+
+        let mut events = state.add_markets(vec![market("b"), market("a")]);
+        events.extend(state.add_bets(vec![
+            bet("b4", "b", 30),
+            bet("b3", "elsewhere", 20),
+            bet("b2", "a", 20),
+            bet("b1", "a", 10),
+        ]));
+        events.extend(state.add_comments("a", vec![comment("c2"), comment("c1")]));
+        // The comments already on a market new to the group are its baseline.
+        events.extend(state.add_comments("b", vec![comment("c0")]));
+
+        let events = events
+            .iter()
+            .map(|event| match event {
+                GroupActivity::NewMarket { market } => market.id().to_owned(),
+                GroupActivity::NewBet { bet } => bet.id().to_owned(),
+                GroupActivity::NewComment { comment, .. } => comment.id().to_owned(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(events, ["b", "b2", "b4", "c2"]);
+        assert_eq!(state.bets_since.unwrap().timestamp_millis(), 30);
+        assert_eq!(state.open_markets, ["b", "a"]);
+
+        // Markets leaving the group take their comments' baseline with them.
+        state.add_markets(vec![market("b")]);
+        assert!(state.add_comments("a", vec![comment("c3")]).is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+//! Locking in profit between mirrored binary markets
+//!
+//! When two markets ask the same question – possibly framed the other way
+//! around, like "Will X win?" and "Will X lose?" – their probabilities should
+//! agree. When they don't, buying the cheap side of one and the cheap side of
+//! the other with equal shares pays the same whatever the outcome, for less
+//! than that payout. [`plan_hedge`] finds how much to buy; the legs are
+//! placed together with [`ManifoldClient::execute_hedge`].
+//!
+//! Uses [`CpmmPool`] math, which ignores fees; check that
+//! [`HedgePlan::profit`] comfortably exceeds them.
+
+use crate::error::{ManifoldError, Result};
+use crate::math::CpmmPool;
+use crate::trading::BasketReport;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// How the second market's question relates to the first's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// YES on both markets means the same thing.
+    Same,
+    /// YES on one market means NO on the other.
+    Opposite,
+}
+
+/// Two bets that together pay at least `payout`, whatever happens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HedgePlan {
+    pub legs: [PlaceBetArgs; 2],
+    /// Shares of the smaller leg, paid out in mana either way.
+    pub payout: f64,
+    pub cost: u64,
+    /// `payout - cost`, before fees.
+    pub profit: f64,
+}
+
+/// Finds the hedge between two mirrored markets with the most profit, spending at most `max_cost`.
+///
+/// `None` if the probabilities are too close for a profit.
+pub fn plan_hedge(
+    a: &impl Market,
+    b: &impl Market,
+    framing: Framing,
+    max_cost: u64,
+) -> Result<Option<HedgePlan>> {
+    fn pool(market: &impl Market) -> Result<CpmmPool> {
+        CpmmPool::from_market(market)
+            .ok_or_else(|| ManifoldError::Other(format!("market {} has no CPMM pool", market.id())))
+    }
+
+    plan_hedge_pools((a.id(), &pool(a)?), (b.id(), &pool(b)?), framing, max_cost)
+}
+
+/// [`plan_hedge`] with the markets' pools given directly.
+pub fn plan_hedge_pools(
+    (a_id, a): (&str, &CpmmPool),
+    (b_id, b): (&str, &CpmmPool),
+    framing: Framing,
+    max_cost: u64,
+) -> Result<Option<HedgePlan>> {
+    // The probability of `a`'s event according to `b`.
+    let b_prob = match framing {
+        Framing::Same => b.probability(),
+        Framing::Opposite => 1.0 - b.probability(),
+    };
+
+    // Buy the event where it's cheap and its negation where that's cheap.
+    let a_outcome = if a.probability() < b_prob {
+        Outcome::Yes
+    } else {
+        Outcome::No
+    };
+    let b_outcome = match (framing, &a_outcome) {
+        (Framing::Same, Outcome::Yes) | (Framing::Opposite, Outcome::No) => Outcome::No,
+        _ => Outcome::Yes,
+    };
+
+    let cost = |shares: f64| -> Result<f64> {
+        Ok(a.cost_of_shares(&a_outcome, shares)? + b.cost_of_shares(&b_outcome, shares)?)
+    };
+
+    // The most shares `max_cost` buys on both sides.
+    let max_cost = max_cost as f64;
+    let mut high = max_cost.max(1.0);
+    while cost(high)? < max_cost {
+        high *= 2.0;
+    }
+    let mut low = 0.0;
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if cost(mid)? < max_cost {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    // Profit is concave in the number of shares, so ternary search for its maximum.
+    let (mut low, mut high) = (0.0, low);
+    for _ in 0..100 {
+        let m1 = low + (high - low) / 3.0;
+        let m2 = high - (high - low) / 3.0;
+
+        if m1 - cost(m1)? < m2 - cost(m2)? {
+            low = m1;
+        } else {
+            high = m2;
+        }
+    }
+    let shares = (low + high) / 2.0;
+
+    // Bets are in whole mana.
+    let a_amount = a.cost_of_shares(&a_outcome, shares)?.floor() as u64;
+    let b_amount = b.cost_of_shares(&b_outcome, shares)?.floor() as u64;
+
+    if a_amount == 0 || b_amount == 0 {
+        return Ok(None);
+    }
+
+    let payout = a
+        .shares_for(&a_outcome, a_amount as f64)?
+        .min(b.shares_for(&b_outcome, b_amount as f64)?);
+    let cost = a_amount + b_amount;
+    let profit = payout - cost as f64;
+
+    if profit <= 0.0 {
+        return Ok(None);
+    }
+
+    Ok(Some(HedgePlan {
+        legs: [
+            PlaceBetArgs::new(a_amount, a_id, a_outcome),
+            PlaceBetArgs::new(b_amount, b_id, b_outcome),
+        ],
+        payout,
+        cost,
+        profit,
+    }))
+}
+
+impl ManifoldClient {
+    /// Places both legs of a hedge at once, see [`post_basket`](Self::post_basket).
+    ///
+    /// Check [`BasketReport::is_complete`]: a single filled leg is an unhedged position.
+    pub async fn execute_hedge(&self, plan: &HedgePlan) -> BasketReport {
+        self.post_basket(plan.legs.to_vec()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hedges_diverging_markets() -> Result<()> {
+        let cheap = CpmmPool::new(150.0, 50.0, 0.5); // 25%
+        let dear = CpmmPool::new(50.0, 150.0, 0.5); // 75%
+
+        let plan = plan_hedge_pools(("a", &cheap), ("b", &dear), Framing::Same, 1000)?.unwrap();
+        assert_eq!(plan.legs[0].outcome, Outcome::Yes);
+        assert_eq!(plan.legs[1].outcome, Outcome::No);
+        assert!(plan.profit > 0.0 && plan.cost <= 1000);
+        assert!(plan.payout >= plan.cost as f64);
+
+        // "Will it not happen?" at 25% is the same as "will it happen?" at 75%.
+        let plan =
+            plan_hedge_pools(("a", &cheap), ("b", &cheap), Framing::Opposite, 1000)?.unwrap();
+        assert_eq!(plan.legs[0].outcome, Outcome::Yes);
+        assert_eq!(plan.legs[1].outcome, Outcome::Yes);
+
+        assert_eq!(
+            plan_hedge_pools(("a", &cheap), ("b", &cheap), Framing::Same, 1000)?,
+            None
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+//! Loading Manifold's public data dumps
+//!
+//! Manifold periodically publishes exports of all public markets, bets and
+//! comments. Depending on the dump they're one big JSON array or one object
+//! per line; [`read_dump`] reads either, a record at a time, so dumps don't
+//! have to fit in memory. Markets go through [`migrate_market`], since the
+//! dumps go back to the earliest markets.
+//!
+//! The records can then be loaded into a [`Storage`]: [`import_markets`]
+//! keeps each market, [`import_comments`] each market's comments, and
+//! [`import_history`] turns the bets into probability snapshots in a
+//! [`SnapshotStore`], so charts and analyses have years of history to start
+//! from instead of only what [`SnapshotStore::watch`] has seen.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::marker::PhantomData;
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::migrate::migrate_market;
+use crate::snapshots::{Snapshot, SnapshotStore};
+use crate::storage::Storage;
+use crate::types::*;
+
+/// Comments [`import_comments`] holds before writing them to storage.
+pub const IMPORT_BATCH: usize = 10_000;
+
+/// The records of a dump, read one at a time; see [`read_dump`].
+///
+/// Stops at the first error, since the rest of the dump can't be found after it.
+#[derive(Debug)]
+pub struct DumpRecords<R, T> {
+    reader: BufReader<R>,
+    /// Whether the dump is a JSON array, once the first byte is read.
+    array: Option<bool>,
+    records: usize,
+    done: bool,
+    record: PhantomData<fn() -> T>,
+}
+
+/// Reads a dump that's either a JSON array or newline-delimited JSON objects.
+pub fn read_dump<T: DeserializeOwned, R: Read>(reader: R) -> DumpRecords<R, T> {
+    DumpRecords {
+        reader: BufReader::new(reader),
+        array: None,
+        records: 0,
+        done: false,
+        record: PhantomData,
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> DumpRecords<R, T> {
+    /// The next byte that isn't whitespace, without consuming it.
+    fn peek(&mut self) -> Result<Option<u8>> {
+        loop {
+            let Some(&byte) = self.reader.fill_buf()?.first() else {
+                return Ok(None);
+            };
+
+            if !byte.is_ascii_whitespace() {
+                return Ok(Some(byte));
+            }
+
+            self.reader.consume(1);
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Option<T>> {
+        let mut byte = self.peek()?;
+
+        let array = *self.array.get_or_insert(byte == Some(b'['));
+        if array && self.records == 0 && byte == Some(b'[') {
+            self.reader.consume(1);
+            byte = self.peek()?;
+        }
+
+        let invalid = |kind, message: &str| std::io::Error::new(kind, message.to_owned());
+
+        match byte {
+            None if array => {
+                return Err(
+                    invalid(ErrorKind::UnexpectedEof, "the dump's array isn't closed").into(),
+                )
+            }
+            None => return Ok(None),
+            Some(b']') if array => {
+                self.reader.consume(1);
+
+                if self.peek()?.is_some() {
+                    return Err(
+                        invalid(ErrorKind::InvalidData, "data after the dump's array").into(),
+                    );
+                }
+                return Ok(None);
+            }
+            Some(b',') if array && self.records > 0 => self.reader.consume(1),
+            Some(_) if array && self.records > 0 => {
+                return Err(
+                    invalid(ErrorKind::InvalidData, "expected , or ] between records").into(),
+                )
+            }
+            Some(_) => {}
+        }
+
+        // Records are objects, so parsing one doesn't read past its end.
+        let record = T::deserialize(&mut serde_json::Deserializer::from_reader(&mut self.reader))?;
+        self.records += 1;
+
+        Ok(Some(record))
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for DumpRecords<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.done {
+            return None;
+        }
+
+        let record = self.next_record().transpose();
+        self.done = !matches!(record, Some(Ok(_)));
+
+        record
+    }
+}
+
+/// Reads a markets dump, migrating old markets to the current fields.
+pub fn read_markets(reader: impl Read) -> impl Iterator<Item = Result<FullMarket>> {
+    read_dump::<Value, _>(reader).map(|market| migrate_market(market?))
+}
+
+pub fn read_bets<R: Read>(reader: R) -> DumpRecords<R, Bet> {
+    read_dump(reader)
+}
+
+/// Comments are plain JSON, like those of [`ManifoldClient::get_comments`](crate::ManifoldClient::get_comments).
+pub fn read_comments<R: Read>(reader: R) -> DumpRecords<R, Value> {
+    read_dump(reader)
+}
+
+fn market_key(market_id: &str) -> String {
+    format!("market-{market_id}")
+}
+
+fn comments_key(market_id: &str) -> String {
+    format!("comments-{market_id}")
+}
+
+/// Stores each market of a dump in `storage`, replacing earlier imports of it.
+///
+/// Returns how many markets were stored.
+pub fn import_markets<S: Storage>(
+    storage: &S,
+    markets: impl IntoIterator<Item = Result<FullMarket>>,
+) -> Result<usize> {
+    let mut count = 0;
+
+    for market in markets {
+        let market = market?;
+        storage.save(&market_key(market.try_id()?), &market.0)?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// A market stored by [`import_markets`].
+pub fn load_market<S: Storage>(storage: &S, market_id: &str) -> Result<Option<FullMarket>> {
+    Ok(storage.load(&market_key(market_id))?.map(FullMarket))
+}
+
+/// Adds the comments of a dump to their market's comments in `storage`.
+///
+/// Comments are written [`IMPORT_BATCH`] at a time; ones already stored
+/// (by id) are replaced, so importing a dump twice changes nothing. Returns
+/// how many comments were read per market.
+pub fn import_comments<S: Storage>(
+    storage: &S,
+    comments: impl IntoIterator<Item = Result<Value>>,
+) -> Result<HashMap<String, usize>> {
+    let mut counts = HashMap::new();
+    let mut batch = HashMap::<String, Vec<Value>>::new();
+    let mut batched = 0;
+
+    for comment in comments {
+        let comment = Comment(comment?);
+        comment.try_id()?;
+        let market_id = comment.try_contract_id()?.to_owned();
+
+        *counts.entry(market_id.clone()).or_default() += 1;
+        batch.entry(market_id).or_default().push(comment.0);
+        batched += 1;
+
+        if batched == IMPORT_BATCH {
+            store_comments(storage, std::mem::take(&mut batch))?;
+            batched = 0;
+        }
+    }
+
+    store_comments(storage, batch)?;
+
+    Ok(counts)
+}
+
+fn store_comments<S: Storage>(storage: &S, batch: HashMap<String, Vec<Value>>) -> Result<()> {
+    for (market_id, comments) in batch {
+        let stored: BTreeMap<String, Value> = load_comments(storage, &market_id)?
+            .into_iter()
+            .chain(comments)
+            .filter_map(|comment| Some((comment["id"].as_str()?.to_owned(), comment)))
+            .collect();
+
+        let mut comments = stored.into_values().collect::<Vec<_>>();
+        comments.sort_by_key(|comment| comment["createdTime"].as_i64());
+
+        storage.save(&comments_key(&market_id), &Value::Array(comments))?;
+    }
+
+    Ok(())
+}
+
+/// The comments on a market stored by [`import_comments`], oldest first.
+pub fn load_comments<S: Storage>(storage: &S, market_id: &str) -> Result<Vec<Value>> {
+    Ok(storage
+        .load_as(&comments_key(market_id))?
+        .unwrap_or_default())
+}
+
+/// What a bet says about its market's probability.
+#[derive(Debug)]
+struct BetPoint {
+    time: DateTime<Utc>,
+    answer_id: Option<String>,
+    prob: f64,
+}
+
+impl BetPoint {
+    fn of(bet: &Bet) -> Option<BetPoint> {
+        Some(BetPoint {
+            time: DateTime::from_timestamp_millis(bet.0["createdTime"].as_i64()?)?,
+            answer_id: bet.answer_id().map(str::to_owned),
+            prob: number_f64(&bet.0["probAfter"])?,
+        })
+    }
+}
+
+/// The probability after each bet on a market, oldest first.
+///
+/// Bets on answers update that answer's probability; snapshots of multiple
+/// choice markets carry every answer seen so far.
+pub fn snapshots_from_bets<'a>(bets: impl IntoIterator<Item = &'a Bet>) -> Vec<Snapshot> {
+    snapshots_from_points(bets.into_iter().filter_map(BetPoint::of).collect())
+}
+
+fn snapshots_from_points(mut points: Vec<BetPoint>) -> Vec<Snapshot> {
+    points.sort_by_key(|point| point.time);
+
+    let mut answers = BTreeMap::new();
+
+    points
+        .into_iter()
+        .map(|point| match point.answer_id {
+            Some(answer_id) => {
+                answers.insert(answer_id, point.prob);
+
+                Snapshot {
+                    time: point.time,
+                    probability: None,
+                    answers: answers.clone(),
+                }
+            }
+            None => Snapshot {
+                time: point.time,
+                probability: Some(point.prob),
+                answers: BTreeMap::new(),
+            },
+        })
+        .collect()
+}
+
+/// Records the history of each market in `bets` into `store`.
+///
+/// Only the time, answer and probability of each bet are kept while the
+/// bets are read. Returns how many snapshots were recorded per market. Bets
+/// older than the store's [retention](crate::snapshots::Retention) are
+/// rolled up or dropped as with any other snapshot.
+pub fn import_history<S: Storage>(
+    store: &SnapshotStore<S>,
+    bets: impl IntoIterator<Item = Result<Bet>>,
+) -> Result<HashMap<String, usize>> {
+    let mut by_market = HashMap::<String, Vec<BetPoint>>::new();
+
+    for bet in bets {
+        let bet = bet?;
+
+        if let Some(point) = BetPoint::of(&bet) {
+            by_market
+                .entry(bet.contract_id().to_owned())
+                .or_default()
+                .push(point);
+        }
+    }
+
+    let mut counts = HashMap::new();
+
+    for (market_id, points) in by_market {
+        let snapshots = snapshots_from_points(points);
+        counts.insert(market_id.clone(), snapshots.len());
+
+        store.record_all(&market_id, snapshots)?;
+    }
+
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::snapshots::Retention;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn reads_both_dump_formats() -> Result<()> {
+        let array = br#"
+            [{"id": "old", "question": "Will it?", "mechanism": "dpm-2", "pool": {"YES": 1, "NO": 1}}]
+        "#;
+        let markets = read_markets(&array[..]).collect::<Result<Vec<_>>>()?;
+        assert_eq!(markets.len(), 1);
+        assert_eq!(markets[0].outcome_type(), OutcomeType::Binary);
+
+        let lines = b"{\"id\": \"a\"}\n{\"id\": \"b\"}\n";
+        let comments = read_comments(&lines[..]).collect::<Result<Vec<_>>>()?;
+        assert_eq!(comments, [json!({"id": "a"}), json!({"id": "b"})]);
+
+        let array = br#" [ {"id": "a"} , {"id": "b"} ] "#;
+        let records = read_comments(&array[..]).collect::<Result<Vec<_>>>()?;
+        assert_eq!(records, comments);
+
+        assert_eq!(read_bets(&b"  \n"[..]).count(), 0);
+        assert_eq!(read_bets(&b"[]"[..]).count(), 0);
+
+        for malformed in [
+            &b"[{]"[..],
+            b"[{\"id\": \"a\"}",
+            b"[{} {}]",
+            b"[{},]",
+            b"[{}] {}",
+        ] {
+            let results = read_bets(malformed).collect::<Vec<_>>();
+            assert!(results.last().unwrap().is_err(), "{malformed:?}");
+        }
+
+        // Records are read as they're needed; the error comes after the first one.
+        let mut bets = read_bets(&b"{\"id\": \"a\"}\n{\"id\""[..]);
+        assert_eq!(bets.next().unwrap()?.id(), "a");
+        assert!(bets.next().unwrap().is_err());
+        assert!(bets.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn imports_history_from_bets() -> Result<()> {
+        let start = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let at = |minutes: i64| (start + chrono::Duration::minutes(minutes)).timestamp_millis();
+
+        let dump = [
+            json!({"contractId": "b", "createdTime": at(10), "probAfter": 0.6}),
+            json!({"contractId": "b", "createdTime": at(0), "probAfter": 0.55}),
+            json!({"contractId": "m", "createdTime": at(0), "answerId": "x", "probAfter": 0.3}),
+            json!({"contractId": "m", "createdTime": at(5), "answerId": "y", "probAfter": 0.5}),
+            json!({"contractId": "m", "createdTime": at(6)}),
+        ]
+        .map(|bet| bet.to_string())
+        .join("\n");
+
+        let store = SnapshotStore::new(MemoryStorage::new(), Retention::default());
+
+        let counts = import_history(&store, read_bets(dump.as_bytes()))?;
+        assert_eq!(
+            counts,
+            HashMap::from([("b".to_owned(), 2), ("m".to_owned(), 2)])
+        );
+
+        let binary = store.history("b", start, start + chrono::Duration::hours(1))?;
+        assert_eq!(
+            binary.iter().map(|s| s.probability).collect::<Vec<_>>(),
+            [Some(0.55), Some(0.6)]
+        );
+
+        let multi = store.history("m", start, start + chrono::Duration::hours(1))?;
+        assert_eq!(
+            multi[1].answers,
+            BTreeMap::from([("x".to_owned(), 0.3), ("y".to_owned(), 0.5)])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn imports_markets_and_comments() -> Result<()> {
+        let storage = MemoryStorage::new();
+
+        let markets = br#"[{"id": "m", "question": "Will it?", "outcomeType": "BINARY"}]"#;
+        assert_eq!(import_markets(&storage, read_markets(&markets[..]))?, 1);
+        assert_eq!(load_market(&storage, "m")?.unwrap().question(), "Will it?");
+
+        let comment =
+            |id: &str, time: i64| json!({"id": id, "contractId": "m", "createdTime": time});
+        let dump = [comment("c2", 20), comment("c1", 10)]
+            .map(|comment| comment.to_string())
+            .join("\n");
+
+        let counts = import_comments(&storage, read_comments(dump.as_bytes()))?;
+        assert_eq!(counts, HashMap::from([("m".to_owned(), 2)]));
+
+        // A second import of an overlapping dump doesn't duplicate anything.
+        let more = [comment("c3", 30), comment("c1", 10)].map(Ok);
+        import_comments(&storage, more)?;
+        assert_eq!(
+            load_comments(&storage, "m")?
+                .iter()
+                .map(|comment| comment["id"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            ["c1", "c2", "c3"]
+        );
+
+        Ok(())
+    }
+}
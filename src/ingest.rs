@@ -0,0 +1,354 @@
+//! Creating and resolving markets from external event feeds
+//!
+//! An [`EventSource`] lists events (sports fixtures, releases, elections)
+//! with a stable ID, a title and a start time. [`IngestEngine::sync`] creates
+//! a binary market for every event it hasn't seen yet, closing it before the
+//! event starts, and resolves the linked market once the source reports a result.
+//!
+//! The fixture → market links live in [`IngestState`], which is serializable,
+//! so a job can persist it between runs.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::error::{ManifoldError, Result};
+use crate::types::Market;
+use crate::ManifoldClient;
+
+/// The final result of an external event.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum EventResult {
+    #[serde(rename = "YES")]
+    Yes,
+    #[serde(rename = "NO")]
+    No,
+    #[serde(rename = "CANCEL")]
+    Cancel,
+}
+
+/// An event from an external source that should have a market.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalEvent {
+    /// Stable ID of the event in the source, e.g. a fixture ID.
+    pub fixture_id: String,
+    /// Becomes the market's question.
+    pub title: String,
+    pub start_time: DateTime<Utc>,
+    /// How the market will be resolved; becomes the market's description.
+    pub resolution_rule: String,
+    /// Set by the source once the event is over.
+    #[serde(default)]
+    pub result: Option<EventResult>,
+}
+
+/// Something that can list external events.
+pub trait EventSource {
+    fn fetch_events(&self) -> impl Future<Output = Result<Vec<ExternalEvent>>> + Send;
+}
+
+/// Reference [`EventSource`]: a URL returning a JSON array of [`ExternalEvent`]s.
+#[derive(Debug, Clone)]
+pub struct JsonHttpSource {
+    pub url: String,
+    pub http: reqwest::Client,
+}
+
+impl JsonHttpSource {
+    pub fn new(url: &str) -> JsonHttpSource {
+        JsonHttpSource {
+            url: url.to_owned(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+impl EventSource for JsonHttpSource {
+    async fn fetch_events(&self) -> Result<Vec<ExternalEvent>> {
+        Ok(self
+            .http
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+/// Persistent state of an [`IngestEngine`].
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct IngestState {
+    /// Fixture ID → market ID.
+    pub links: HashMap<String, String>,
+    /// Fixture IDs whose markets were already resolved.
+    pub resolved: HashSet<String>,
+}
+
+/// What one [`IngestEngine::sync`] run did.
+#[derive(Debug, Default)]
+pub struct IngestReport {
+    /// Fixture ID and the ID of the newly created market.
+    pub created: Vec<(String, String)>,
+    /// Fixture ID and the result the market was resolved to.
+    pub resolved: Vec<(String, EventResult)>,
+    pub failed: Vec<(String, ManifoldError)>,
+}
+
+pub struct IngestEngine<S> {
+    pub source: S,
+    pub state: IngestState,
+    /// How long before the event starts its market closes.
+    pub close_before_start: Duration,
+}
+
+impl<S: EventSource> IngestEngine<S> {
+    pub fn new(source: S) -> IngestEngine<S> {
+        Self::with_state(source, IngestState::default())
+    }
+
+    pub fn with_state(source: S, state: IngestState) -> IngestEngine<S> {
+        IngestEngine {
+            source,
+            state,
+            close_before_start: Duration::minutes(5),
+        }
+    }
+
+    /// Creates markets for new events that haven't started yet, and resolves
+    /// linked markets of events that have a result.
+    pub async fn sync(&mut self, client: &ManifoldClient) -> Result<IngestReport> {
+        let events = self.source.fetch_events().await?;
+        let mut report = IngestReport::default();
+
+        for event in events {
+            match self.state.links.get(&event.fixture_id) {
+                None => {
+                    let close_time = event.start_time - self.close_before_start;
+
+                    if event.result.is_some() || close_time <= client.now() {
+                        continue;
+                    }
+
+                    let created = client
+                        .post_market(&json!({
+                            "outcomeType": "BINARY",
+                            "question": event.title,
+                            "description": event.resolution_rule,
+                            "closeTime": close_time.timestamp_millis(),
+                            "initialProb": 50,
+                        }))
+                        .await;
+
+                    match created {
+                        Ok(market) => {
+                            let id = market.id().to_owned();
+
+                            self.state
+                                .links
+                                .insert(event.fixture_id.clone(), id.clone());
+                            report.created.push((event.fixture_id, id));
+                        }
+                        Err(e) => report.failed.push((event.fixture_id, e)),
+                    }
+                }
+                Some(market_id) => {
+                    let Some(result) = event.result else {
+                        continue;
+                    };
+
+                    if self.state.resolved.contains(&event.fixture_id) {
+                        continue;
+                    }
+
+                    let resolution = client
+                        .post_market_resolve(market_id, &json!({ "outcome": result }))
+                        .await;
+
+                    match resolution {
+                        Ok(_) => {
+                            self.state.resolved.insert(event.fixture_id.clone());
+                            report.resolved.push((event.fixture_id, result));
+                        }
+                        Err(e) => report.failed.push((event.fixture_id, e)),
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::fixtures::{self, MockRequest, MockResponse, MockServer};
+    use crate::storage::{MemoryStorage, Storage};
+
+    /// Events the test can change between syncs.
+    #[derive(Debug, Clone, Default)]
+    struct StubSource(Arc<Mutex<Vec<ExternalEvent>>>);
+
+    impl EventSource for StubSource {
+        async fn fetch_events(&self) -> Result<Vec<ExternalEvent>> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    fn event(id: &str, start_time: DateTime<Utc>, result: Option<EventResult>) -> ExternalEvent {
+        ExternalEvent {
+            fixture_id: id.to_owned(),
+            title: format!("Will {id} happen?"),
+            start_time,
+            resolution_rule: "Resolves by the official result.".to_owned(),
+            result,
+        }
+    }
+
+    #[tokio::test]
+    async fn links_resolves_and_resumes_from_saved_state() -> Result<()> {
+        let now = Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap();
+        let tomorrow = now + Duration::days(1);
+
+        let server = MockServer::start(|request: &MockRequest| {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("POST", "/market") => {
+                    // "Will final happen?" gets the market "final-market".
+                    let question = request.body["question"].as_str()?;
+                    let mut market = fixtures::binary_market().0;
+                    market["id"] = json!(format!("{}-market", question.split(' ').nth(1)?));
+
+                    Some(MockResponse::json(market))
+                }
+                ("POST", path) if path.contains("flaky") => {
+                    Some(MockResponse::status(500, json!({"message": "try again"})))
+                }
+                ("POST", path) if path.ends_with("/resolve") => {
+                    Some(MockResponse::json(json!({"success": true})))
+                }
+                _ => None,
+            }
+        })
+        .await?;
+        let client = server.client().clock(MockClock::new(now)).build()?;
+
+        let source = StubSource::default();
+        *source.0.lock().unwrap() = vec![
+            event("final", tomorrow, None),
+            // Its market would have to close before now.
+            event("soon", now + Duration::minutes(2), None),
+            // Over before it was ever seen.
+            event("over", tomorrow, Some(EventResult::Yes)),
+            event("flaky", tomorrow, None),
+        ];
+
+        let mut engine = IngestEngine::new(source.clone());
+        let report = engine.sync(&client).await?;
+
+        assert_eq!(
+            report.created,
+            [
+                ("final".to_owned(), "final-market".to_owned()),
+                ("flaky".to_owned(), "flaky-market".to_owned()),
+            ]
+        );
+        assert!(report.resolved.is_empty() && report.failed.is_empty());
+
+        let created = server.requests();
+        assert_eq!(created.len(), 2);
+        assert_eq!(
+            created[0].body["closeTime"],
+            json!((tomorrow - Duration::minutes(5)).timestamp_millis())
+        );
+
+        // A new run picks up the links from where the last one saved them.
+        let storage = MemoryStorage::new();
+        storage.save_as("ingest", &engine.state)?;
+
+        for event in source.0.lock().unwrap().iter_mut() {
+            event.result = Some(EventResult::No);
+        }
+
+        let mut engine = IngestEngine::with_state(source, storage.load_as("ingest")?.unwrap());
+        let report = engine.sync(&client).await?;
+
+        assert_eq!(report.created, []);
+        assert_eq!(report.resolved, [("final".to_owned(), EventResult::No)]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "flaky");
+        assert_eq!(report.failed[0].1.status(), Some(500));
+
+        let resolution = &server.requests()[2];
+        assert_eq!(resolution.path, "/market/final-market/resolve");
+        assert_eq!(resolution.body, json!({"outcome": "NO"}));
+
+        // Resolved markets aren't resolved again, failed ones are retried.
+        let report = engine.sync(&client).await?;
+
+        assert!(report.resolved.is_empty());
+        assert_eq!(report.failed.len(), 1);
+
+        let paths = server
+            .requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths.iter().filter(|p| p.contains("final")).count(), 1);
+        assert_eq!(paths.iter().filter(|p| p.contains("flaky")).count(), 2);
+        assert!(!paths[2..].contains(&"/market".to_owned()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reads_events_from_json() -> Result<()> {
+        let server = MockServer::start(|request: &MockRequest| {
+            (request.path == "/events").then(|| {
+                MockResponse::json(json!([
+                    {
+                        "fixtureId": "f1",
+                        "title": "Will the home team win?",
+                        "startTime": "2030-01-02T12:00:00Z",
+                        "resolutionRule": "Resolves by the final score.",
+                    },
+                    {
+                        "fixtureId": "f2",
+                        "title": "Will it go to overtime?",
+                        "startTime": "2030-01-02T12:00:00Z",
+                        "resolutionRule": "Resolves by the final score.",
+                        "result": "CANCEL",
+                    },
+                ]))
+            })
+        })
+        .await?;
+
+        let events = JsonHttpSource::new(&format!("{}/events", server.base()))
+            .fetch_events()
+            .await?;
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].fixture_id, "f1");
+        assert_eq!(
+            events[0].start_time,
+            Utc.with_ymd_and_hms(2030, 1, 2, 12, 0, 0).unwrap()
+        );
+        assert_eq!(events[0].result, None);
+        assert_eq!(events[1].result, Some(EventResult::Cancel));
+
+        let missing = JsonHttpSource::new(&format!("{}/nothing", server.base()));
+        assert!(missing.fetch_events().await.is_err());
+
+        Ok(())
+    }
+}
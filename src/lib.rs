@@ -5,14 +5,83 @@
 //! [docs]: https://docs.manifold.markets/api
 //!
 //! See [`ManifoldClient`](ManifoldClient) for usage.
+//!
+//! # Features
+//!
+//! - `arbitrary-precision`: enables `serde_json`'s `arbitrary_precision`, so
+//!   mana and share amounts can be read back exactly (e.g. for accounting exports)
+//!   with accessors like [`Bet::amount_exact`](types::Bet::amount_exact).
+//! - `csv`: reading and writing [`Ledger`](export::Ledger)s as CSV.
+//! - `sqlite`: SQLite-backed caches, like `embeddings::SqliteEmbeddingCache`.
+//! - `config`: reading the deployment configuration, [`config::Config`], from
+//!   TOML files; `yaml` additionally reads it from YAML.
+//! - `keyring`: reading API keys from the OS keyring, see [`credentials`].
+//! - `realtime`: live bets, comments and market updates over Manifold's
+//!   websocket API, `realtime`.
+//! - `test-util`: realistic sample data and a mock API server for tests, `fixtures`.
+//! - `tools`: JSON-schema tool definitions for LLM agents and a dispatcher
+//!   executing their calls, `tools`.
 
 #![feature(iterator_try_collect)]
 
+pub mod analysis;
+pub mod answers;
+pub mod anomaly;
+pub mod bot;
+pub mod bulk;
+pub mod cache;
+pub mod catalog;
+pub mod classify;
 mod client;
+pub mod clock;
+pub mod comments;
+pub mod config;
+pub mod context;
+pub mod create;
+pub mod credentials;
+pub mod embeddings;
 pub mod error;
+pub mod execution;
+pub mod export;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fixtures;
+pub mod format;
+pub mod freshness;
+pub mod groups;
+pub mod hedge;
+pub mod import;
+pub mod ingest;
+pub mod math;
+pub mod migrate;
+pub mod monitor;
+pub mod notifications;
+pub mod orders;
+mod polling;
+pub mod ratelimit;
+#[cfg(feature = "realtime")]
+pub mod realtime;
+pub mod reports;
+pub mod resolution;
+pub mod retry;
+pub mod schema;
+pub mod scoreboard;
+pub mod secret;
+pub mod snapshots;
+pub mod storage;
 pub mod streams;
+pub mod tags;
+pub mod text;
+pub mod timing;
+#[cfg(feature = "tools")]
+pub mod tools;
+pub mod trading;
+pub mod triggers;
 pub mod types;
-pub use client::{ManifoldAuthorization, ManifoldClient};
+pub mod watchlist;
+pub use client::{
+    ApiVersion, GroupRef, ManifoldAuthorization, ManifoldClient, ManifoldClientBuilder,
+    MAX_COVER_IMAGE_BYTES,
+};
 
 #[cfg(test)]
 mod tests {
@@ -23,13 +92,171 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn urls_compose_base_and_version() -> anyhow::Result<()> {
+        let auth = ManifoldAuthorization::NoAuthorization;
+
+        let client = ManifoldClient::new(auth.clone())?;
+        assert_eq!(
+            client.url("/markets"),
+            "https://manifold.markets/api/v0/markets"
+        );
+
+        let client = ManifoldClient::new_custom_base(auth.clone(), "http://localhost:3000/v0/")?;
+        assert_eq!(client.base, "http://localhost:3000/v0");
+        assert_eq!(client.url("/me"), "http://localhost:3000/v0/me");
+
+        // Explicit bases are used as they are.
+        let client = ManifoldClient::new_custom_base(auth.clone(), "http://localhost:3000")?;
+        assert_eq!(client.url("/me"), "http://localhost:3000/me");
+        let client = ManifoldClient::new_custom_base(auth.clone(), "http://localhost:3000/v1")?;
+        assert_eq!(client.url("/me"), "http://localhost:3000/v1/me");
+
+        let client = ManifoldClient::builder(auth.clone())
+            .version(ApiVersion::Custom("v1".to_owned()))
+            .build()?;
+        assert_eq!(client.url("/me"), "https://manifold.markets/api/v1/me");
+
+        let client = ManifoldClient::builder(auth)
+            .base("http://localhost:3000/api")
+            .version(ApiVersion::V0)
+            .build()?;
+        assert_eq!(client.url("/me"), "http://localhost:3000/api/v0/me");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn production_guard() -> anyhow::Result<()> {
+        let auth = ManifoldAuthorization::ApiKey("not-a-key".to_owned());
+
+        let guarded = ManifoldClient::new(auth.clone())?;
+        let result = guarded
+            .post_bet(trading::BetRequest::new(1, "market-id", types::Outcome::Yes))
+            .await;
+        assert!(matches!(
+            result,
+            Err(error::ManifoldError::ProductionGuard(_))
+        ));
+
+        // Mutating requests to other instances are not guarded, so this fails at connecting instead.
+        let local = ManifoldClient::new_custom_base(auth, "http://127.0.0.1:9/api")?;
+        let result = local
+            .post_bet(trading::BetRequest::new(1, "market-id", types::Outcome::Yes))
+            .await;
+        assert!(matches!(result, Err(error::ManifoldError::HttpError(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn builds_from_env() -> anyhow::Result<()> {
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let client = ManifoldClientBuilder::from_vars(vars(&[
+            ("MANIFOLD_API_KEY", "key"),
+            ("MANIFOLD_BASE_URL", "https://dev.manifold.markets/api/v0"),
+            ("MANIFOLD_TIMEOUT_SECS", "2.5"),
+            ("MANIFOLD_REQUESTS_PER_MINUTE", "100"),
+        ]))?
+        .build()?;
+        assert!(client.is_authenticated());
+        assert_eq!(client.base, "https://dev.manifold.markets/api/v0");
+
+        for bad in [
+            vars(&[]),
+            vars(&[("MANIFOLD_API_KEY", " ")]),
+            vars(&[
+                ("MANIFOLD_API_KEY", "key"),
+                ("MANIFOLD_TIMEOUT_SECS", "soon"),
+            ]),
+            vars(&[
+                ("MANIFOLD_API_KEY", "key"),
+                ("MANIFOLD_REQUESTS_PER_MINUTE", "0"),
+            ]),
+        ] {
+            assert!(matches!(
+                ManifoldClientBuilder::from_vars(bad),
+                Err(error::ManifoldError::ConfigError(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn debug_output_hides_the_key() -> anyhow::Result<()> {
+        let auth = ManifoldAuthorization::ApiKey("very-secret-key".to_owned());
+        let builder = ManifoldClient::builder(auth.clone());
+        let client = builder.clone().build()?;
+        let request = client.http_get("/me").build()?;
+
+        for output in [
+            format!("{auth:?}"),
+            format!("{builder:?}"),
+            format!("{client:?}"),
+            format!("{:?}", client.http),
+            format!("{request:?}"),
+        ] {
+            assert!(!output.contains("very-secret-key"), "{output}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keeps_created_markets_when_adding_default_groups_fails() -> anyhow::Result<()> {
+        use fixtures::{MockRequest, MockResponse, MockServer};
+
+        let server = MockServer::start(|request: &MockRequest| {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/group/by-id/fixtureGroupId") => {
+                    Some(MockResponse::json(fixtures::group().0))
+                }
+                ("POST", "/market") => {
+                    let mut market = fixtures::binary_market().0;
+                    market["groupSlugs"] = serde_json::json!([]);
+                    Some(MockResponse::json(market))
+                }
+                ("POST", path) if path.ends_with("/group") => Some(MockResponse::status(
+                    403,
+                    serde_json::json!({"message": "You can't add markets to this group"}),
+                )),
+                _ => None,
+            }
+        })
+        .await?;
+        let client = server
+            .client()
+            .default_group_ids(&["fixtureGroupId"])
+            .build()?;
+
+        let market = client
+            .post_market(&serde_json::json!({"outcomeType": "BINARY", "question": "?"}))
+            .await?;
+
+        assert_eq!(market.id(), fixtures::binary_market().id());
+        assert!(server.requests().iter().any(|r| r.path.ends_with("/group")));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn it_works() -> anyhow::Result<()> {
         dotenv::dotenv().ok();
 
-        let manifold = ManifoldClient::from_api_key(&std::env::var("MANIFOLD_API_KEY").expect(
-            "The test requires a MANIFOLD_API_KEY environment variable (you can use .env)",
-        ))?;
+        let manifold = ManifoldClient::builder(ManifoldAuthorization::ApiKey(
+            std::env::var("MANIFOLD_API_KEY").expect(
+                "The test requires a MANIFOLD_API_KEY environment variable (you can use .env)",
+            ),
+        ))
+        .allow_real_money()
+        .build()?;
 
         let r = manifold
             .stream_markets()
@@ -61,7 +288,7 @@ mod tests {
             println!("will bet on {winning:#?}");
 
             let bet = manifold
-                .post_bet(1, yes_no.id(), winning.0.clone(), None)
+                .post_bet(trading::BetRequest::new(1, yes_no.id(), winning.0.clone()))
                 .await?;
 
             println!("bet: {bet:#?}");
@@ -84,7 +311,7 @@ mod tests {
             println!("will bet on {winning:#?}");
 
             let bet = manifold
-                .post_bet(1, free_response.id(), winning.0.clone(), None)
+                .post_bet(trading::BetRequest::new(1, free_response.id(), winning.0.clone()))
                 .await?;
 
             println!("bet: {bet:#?}");
@@ -107,7 +334,7 @@ mod tests {
             println!("will bet on {winning:#?}");
 
             let bet = manifold
-                .post_bet(1, multiple_choice.id(), winning.0.clone(), None)
+                .post_bet(trading::BetRequest::new(1, multiple_choice.id(), winning.0.clone()))
                 .await?;
 
             println!("bet: {bet:#?}");
@@ -130,7 +357,7 @@ mod tests {
             println!("will bet on {winning:#?}");
 
             let bet = manifold
-                .post_bet(1, pseudo_numeric.id(), winning.0.clone(), None)
+                .post_bet(trading::BetRequest::new(1, pseudo_numeric.id(), winning.0.clone()))
                 .await?;
 
             println!("bet: {bet:#?}");
@@ -0,0 +1,429 @@
+//! Manifold's constant-product market maker (CPMM), for planning trades locally
+//!
+//! Binary and pseudo-numeric markets price YES and NO with a weighted constant
+//! product: `YES^p * NO^(1-p)` stays constant, and the probability is
+//! `p * NO / (p * NO + (1 - p) * YES)`. Buying YES for `M` adds `M` to both pools
+//! and takes out the YES shares that keep the product constant.
+//!
+//! Every trade keeps [`CpmmPool::invariant`] constant; the property tests
+//! below check that round trips never create mana and that probabilities
+//! stay strictly between 0 and 1.
+//!
+//! These functions ignore fees and limit orders resting in the market, so
+//! they slightly overestimate what a bet gets.
+//!
+//! Mana locked in a position can't be used elsewhere until the market
+//! resolves; [`annualized_return`] and [`capital_efficiency`] put positions
+//! with different horizons on equal footing.
+
+use chrono::{DateTime, Utc};
+
+use crate::error::{ManifoldError, Result};
+use crate::types::*;
+
+const SECONDS_PER_YEAR: f64 = 365.25 * 24.0 * 60.0 * 60.0;
+
+/// Positions resolving sooner than this are annualized as if they took this long,
+/// so that a market resolving in a minute doesn't show an astronomical rate.
+const MIN_HORIZON_YEARS: f64 = 1.0 / 365.25;
+
+/// The liquidity pool of a CPMM market.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CpmmPool {
+    pub yes: f64,
+    pub no: f64,
+    /// The weight of the YES pool, `0.5` for most markets.
+    pub p: f64,
+}
+
+fn binary_outcome(outcome: &Outcome) -> Result<bool> {
+    match outcome {
+        Outcome::Yes => Ok(true),
+        Outcome::No => Ok(false),
+        _ => Err(ManifoldError::Other(format!(
+            "CPMM math only supports YES and NO, not {outcome:?}"
+        ))),
+    }
+}
+
+/// Finds `x` in `[low, high]` with `f(x) = target`, for an increasing `f`.
+fn bisect(mut low: f64, mut high: f64, target: f64, f: impl Fn(f64) -> f64) -> f64 {
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+
+        if f(mid) < target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    (low + high) / 2.0
+}
+
+impl CpmmPool {
+    pub fn new(yes: f64, no: f64, p: f64) -> CpmmPool {
+        CpmmPool { yes, no, p }
+    }
+
+    /// The pool of a binary or pseudo-numeric market, if it has one.
+    pub fn from_market(market: &impl Market) -> Option<CpmmPool> {
+        let data = market.data();
+
+        Some(CpmmPool {
+            yes: number_f64(&data["pool"]["YES"])?,
+            no: number_f64(&data["pool"]["NO"])?,
+            p: number_f64(&data["p"]).unwrap_or(0.5),
+        })
+    }
+
+    /// `YES^p * NO^(1-p)`, unchanged by buying and selling.
+    pub fn invariant(&self) -> f64 {
+        self.yes.powf(self.p) * self.no.powf(1.0 - self.p)
+    }
+
+    /// Strictly between 0 and 1, even far beyond the pool's depth where the
+    /// exact value is closer to 0 or 1 than an f64 can tell apart.
+    pub fn probability(&self) -> f64 {
+        let prob = self.p * self.no / (self.p * self.no + (1.0 - self.p) * self.yes);
+
+        // The smallest positive and the largest below 1.
+        prob.clamp(f64::MIN_POSITIVE, 1.0 - f64::EPSILON / 2.0)
+    }
+
+    /// The pool after buying `outcome` for `amount`, and the shares bought.
+    pub fn buy(&self, outcome: &Outcome, amount: f64) -> Result<(CpmmPool, f64)> {
+        let k = self.invariant();
+        let (yes, no) = (self.yes + amount, self.no + amount);
+
+        Ok(if binary_outcome(outcome)? {
+            let new_yes = (k / no.powf(1.0 - self.p)).powf(1.0 / self.p);
+            (CpmmPool::new(new_yes, no, self.p), yes - new_yes)
+        } else {
+            let new_no = (k / yes.powf(self.p)).powf(1.0 / (1.0 - self.p));
+            (CpmmPool::new(yes, new_no, self.p), no - new_no)
+        })
+    }
+
+    pub fn shares_for(&self, outcome: &Outcome, amount: f64) -> Result<f64> {
+        Ok(self.buy(outcome, amount)?.1)
+    }
+
+    pub fn probability_after(&self, outcome: &Outcome, amount: f64) -> Result<f64> {
+        Ok(self.buy(outcome, amount)?.0.probability())
+    }
+
+    /// How much mana buys `shares` of `outcome`.
+    pub fn cost_of_shares(&self, outcome: &Outcome, shares: f64) -> Result<f64> {
+        binary_outcome(outcome)?;
+
+        // Every share costs less than M$1, so the cost is below `shares`.
+        Ok(bisect(0.0, shares.max(0.0), shares, |amount| {
+            self.shares_for(outcome, amount).unwrap_or(0.0)
+        }))
+    }
+
+    /// The pool after selling `shares` of `outcome`, and the mana received.
+    ///
+    /// Selling puts the shares back into the pool and takes out as many
+    /// YES/NO pairs as keeps the product constant; each pair is worth M$1.
+    pub fn sell(&self, outcome: &Outcome, shares: f64) -> Result<(CpmmPool, f64)> {
+        let k = self.invariant();
+        let is_yes = binary_outcome(outcome)?;
+
+        let (yes, no) = if is_yes {
+            (self.yes + shares, self.no)
+        } else {
+            (self.yes, self.no + shares)
+        };
+
+        // Taking out more pairs lowers the product, so solve on its negation.
+        let amount = bisect(0.0, yes.min(no), -k, |a| {
+            -((yes - a).powf(self.p) * (no - a).powf(1.0 - self.p))
+        });
+
+        Ok((CpmmPool::new(yes - amount, no - amount, self.p), amount))
+    }
+
+    /// How much to spend on `outcome` to move the probability to `target`.
+    ///
+    /// Zero if the probability is already there or beyond.
+    pub fn amount_to_probability(&self, outcome: &Outcome, target: f64) -> Result<f64> {
+        let is_yes = binary_outcome(outcome)?;
+        let current = self.probability();
+
+        if target <= 0.0 || target >= 1.0 {
+            return Err(ManifoldError::Other(format!(
+                "target probability {target} is not in (0, 1)"
+            )));
+        }
+
+        if (is_yes && target <= current) || (!is_yes && target >= current) {
+            return Ok(0.0);
+        }
+
+        // Grow the bracket until it contains the target.
+        let moved = |amount: f64| {
+            let prob = self.probability_after(outcome, amount).unwrap_or(current);
+            if is_yes {
+                prob
+            } else {
+                -prob
+            }
+        };
+        let target = if is_yes { target } else { -target };
+
+        let mut high = self.yes.max(self.no).max(1.0);
+        while moved(high) < target {
+            high *= 2.0;
+        }
+
+        Ok(bisect(0.0, high, target, moved))
+    }
+}
+
+/// Compounds a `total_return` earned over `years` into a yearly rate.
+pub fn annualize(total_return: f64, years: f64) -> f64 {
+    (1.0 + total_return).powf(1.0 / years.max(MIN_HORIZON_YEARS)) - 1.0
+}
+
+fn years_until(time: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+    (time - now).num_seconds() as f64 / SECONDS_PER_YEAR
+}
+
+/// Expected yearly return of buying at `entry_prob` if the true probability is `belief_prob`.
+///
+/// Takes the side the belief favors: YES if `belief_prob > entry_prob`, otherwise NO,
+/// so this is zero only without an edge. Ignores price impact; see [`CpmmPool`] for that.
+pub fn annualized_return(
+    entry_prob: f64,
+    belief_prob: f64,
+    expected_resolution: DateTime<Utc>,
+) -> f64 {
+    annualized_return_at(entry_prob, belief_prob, expected_resolution, Utc::now())
+}
+
+fn annualized_return_at(
+    entry_prob: f64,
+    belief_prob: f64,
+    expected_resolution: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> f64 {
+    let total_return = if belief_prob >= entry_prob {
+        belief_prob / entry_prob - 1.0
+    } else {
+        (1.0 - belief_prob) / (1.0 - entry_prob) - 1.0
+    };
+
+    annualize(total_return, years_until(expected_resolution, now))
+}
+
+/// A position for [`capital_efficiency`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holding {
+    /// Mana currently in the position.
+    pub invested: f64,
+    /// Expected payout at resolution, by your own probabilities.
+    pub expected_value: f64,
+    pub expected_resolution: DateTime<Utc>,
+}
+
+/// How well a portfolio uses its mana, see [`capital_efficiency`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CapitalEfficiency {
+    pub invested: f64,
+    pub expected_profit: f64,
+    /// Time until resolution, averaged weighing each holding by the mana in it.
+    pub average_years: f64,
+    /// The expected return, annualized over `average_years`.
+    pub annualized_return: f64,
+    /// The holding with the lowest annualized return, by index: the first to sell to free up mana.
+    pub least_efficient: Option<usize>,
+}
+
+pub fn capital_efficiency(holdings: &[Holding]) -> CapitalEfficiency {
+    capital_efficiency_at(holdings, Utc::now())
+}
+
+fn capital_efficiency_at(holdings: &[Holding], now: DateTime<Utc>) -> CapitalEfficiency {
+    let invested = holdings.iter().map(|h| h.invested).sum::<f64>();
+
+    if invested <= 0.0 {
+        return CapitalEfficiency::default();
+    }
+
+    let expected_profit = holdings
+        .iter()
+        .map(|h| h.expected_value - h.invested)
+        .sum::<f64>();
+    let average_years = holdings
+        .iter()
+        .map(|h| h.invested * years_until(h.expected_resolution, now).max(0.0))
+        .sum::<f64>()
+        / invested;
+
+    let least_efficient = holdings
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| h.invested > 0.0)
+        .map(|(i, h)| {
+            let rate = annualize(
+                h.expected_value / h.invested - 1.0,
+                years_until(h.expected_resolution, now),
+            );
+            (i, rate)
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i);
+
+    CapitalEfficiency {
+        invested,
+        expected_profit,
+        average_years,
+        annualized_return: annualize(expected_profit / invested, average_years),
+        least_efficient,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn buys_and_sells() -> Result<()> {
+        let pool = CpmmPool::new(100.0, 100.0, 0.5);
+        assert!(close(pool.probability(), 0.5));
+
+        let (after, shares) = pool.buy(&Outcome::Yes, 10.0)?;
+        assert!(after.probability() > 0.5);
+        assert!(close(after.invariant(), pool.invariant()));
+        // 10 into each pool, and shares taken out of YES: (110 - s) * 110 = 100 * 100
+        assert!(close(shares, 110.0 - 10_000.0 / 110.0));
+
+        assert!(close(pool.cost_of_shares(&Outcome::Yes, shares)?, 10.0));
+
+        let (back, amount) = after.sell(&Outcome::Yes, shares)?;
+        assert!(close(amount, 10.0));
+        assert!(close(back.probability(), 0.5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn probabilities_stay_in_range_far_beyond_the_pool() -> Result<()> {
+        // The exact probabilities are about 1 - 1e-76 and 1e-76.
+        let yes = CpmmPool::new(1.0, 1.0, 0.05).probability_after(&Outcome::Yes, 10_000.0)?;
+        assert!(yes > 0.0 && yes < 1.0);
+
+        let no = CpmmPool::new(1.0, 1.0, 0.95).probability_after(&Outcome::No, 10_000.0)?;
+        assert!(no > 0.0 && no < 1e-70);
+
+        Ok(())
+    }
+
+    #[test]
+    fn finds_amount_to_probability() -> Result<()> {
+        let pool = CpmmPool::new(200.0, 50.0, 0.6);
+
+        for (outcome, target) in [(Outcome::Yes, 0.5), (Outcome::No, 0.1)] {
+            let amount = pool.amount_to_probability(&outcome, target)?;
+
+            assert!(close(pool.probability_after(&outcome, amount)?, target));
+        }
+
+        assert_eq!(pool.amount_to_probability(&Outcome::Yes, 0.1)?, 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn annualizes_returns() {
+        let now = Utc::now();
+        let in_a_year = now + chrono::Duration::seconds(SECONDS_PER_YEAR as i64);
+        let in_a_quarter = now + chrono::Duration::seconds((SECONDS_PER_YEAR / 4.0) as i64);
+
+        assert!(close(annualized_return_at(0.5, 0.6, in_a_year, now), 0.2));
+        assert!(close(annualized_return_at(0.5, 0.4, in_a_year, now), 0.2));
+        assert!(close(
+            annualized_return_at(0.5, 0.6, in_a_quarter, now),
+            1.2f64.powi(4) - 1.0
+        ));
+        assert_eq!(annualized_return_at(0.5, 0.5, in_a_year, now), 0.0);
+
+        let efficiency = capital_efficiency_at(
+            &[
+                Holding {
+                    invested: 100.0,
+                    expected_value: 120.0,
+                    expected_resolution: in_a_year,
+                },
+                Holding {
+                    invested: 100.0,
+                    expected_value: 105.0,
+                    expected_resolution: in_a_quarter,
+                },
+            ],
+            now,
+        );
+        assert_eq!(efficiency.invested, 200.0);
+        assert!(close(efficiency.expected_profit, 25.0));
+        assert!(close(efficiency.average_years, 0.625));
+        // 20% a year vs 5% a quarter (~21.6% a year).
+        assert_eq!(efficiency.least_efficient, Some(0));
+    }
+
+    fn pools() -> impl Strategy<Value = CpmmPool> {
+        (1.0..100_000.0, 1.0..100_000.0, 0.05..0.95)
+            .prop_map(|(yes, no, p)| CpmmPool::new(yes, no, p))
+    }
+
+    fn outcomes() -> impl Strategy<Value = Outcome> {
+        prop_oneof![Just(Outcome::Yes), Just(Outcome::No)]
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_never_creates_mana(
+            pool in pools(),
+            outcome in outcomes(),
+            amount in 0.01..10_000.0,
+        ) {
+            let (after, shares) = pool.buy(&outcome, amount)?;
+            let (_, returned) = after.sell(&outcome, shares)?;
+
+            prop_assert!(returned <= amount * (1.0 + 1e-9));
+        }
+
+        #[test]
+        fn probabilities_stay_in_range(
+            pool in pools(),
+            outcome in outcomes(),
+            amount in 0.0..10_000.0,
+        ) {
+            let prob = pool.probability_after(&outcome, amount)?;
+
+            prop_assert!(pool.probability() > 0.0 && pool.probability() < 1.0);
+            prop_assert!(prob > 0.0 && prob < 1.0);
+        }
+
+        #[test]
+        fn shares_grow_with_amount(
+            pool in pools(),
+            outcome in outcomes(),
+            amount in 0.0..10_000.0,
+            extra in 0.01..1_000.0,
+        ) {
+            let shares = pool.shares_for(&outcome, amount)?;
+            let more = pool.shares_for(&outcome, amount + extra)?;
+
+            prop_assert!(shares >= 0.0);
+            prop_assert!(more > shares);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Loading market JSON saved by older versions of Manifold
+//!
+//! Markets archived before multiple choice markets moved to `cpmm-multi-1`
+//! look different: free response and multiple choice markets used the
+//! `dpm-2` mechanism without per-answer probabilities, binary DPM markets
+//! had no `probability`, the earliest markets had no `outcomeType`, and
+//! topics were `tags` rather than `groupSlugs`.
+//!
+//! [`migrate_market`] fills in the current fields from the old ones where it
+//! can, so archives load with the same [`Market`] accessors as fresh data.
+//! Fields that are already present are never overwritten.
+
+use serde_json::{json, Map, Value};
+
+use crate::error::{ManifoldError, Result};
+use crate::types::*;
+
+/// Mechanisms that predate the current market model.
+pub const LEGACY_MECHANISMS: &[&str] = &["dpm-2", "dpm-1"];
+
+/// Whether a market was saved in the old format and needs [`migrate_market`].
+pub fn is_legacy(market: &Value) -> bool {
+    let mechanism = market["mechanism"].as_str();
+
+    market["outcomeType"].is_null()
+        || mechanism.is_some_and(|m| LEGACY_MECHANISMS.contains(&m))
+        || (market["groupSlugs"].is_null() && market["tags"].is_array())
+}
+
+/// DPM probabilities are proportional to the square of each outcome's shares.
+fn dpm_probabilities<'a>(
+    shares: impl Iterator<Item = (&'a String, &'a Value)>,
+) -> Vec<(String, f64)> {
+    let squares = shares
+        .filter_map(|(outcome, shares)| Some((outcome.clone(), number_f64(shares)?.powi(2))))
+        .collect::<Vec<_>>();
+    let total = squares.iter().map(|(_, s)| s).sum::<f64>();
+
+    if total <= 0.0 {
+        return vec![];
+    }
+
+    squares.into_iter().map(|(o, s)| (o, s / total)).collect()
+}
+
+fn set_missing(market: &mut Map<String, Value>, key: &str, value: Value) {
+    if market.get(key).is_none_or(Value::is_null) {
+        market.insert(key.to_owned(), value);
+    }
+}
+
+/// Converts a market from an old export to the current format, best-effort.
+///
+/// Markets already in the current format are returned unchanged. Fails only
+/// if the JSON isn't an object with an `id` and a `question`.
+pub fn migrate_market(market: Value) -> Result<FullMarket> {
+    let Value::Object(mut data) = market else {
+        return Err(ManifoldError::SchemaError(
+            "Archived market is not an object".to_owned(),
+            Some(market),
+        ));
+    };
+
+    if !data.get("id").is_some_and(Value::is_string)
+        || !data.get("question").is_some_and(Value::is_string)
+    {
+        return Err(ManifoldError::SchemaError(
+            "Archived market has no id or question".to_owned(),
+            Some(Value::Object(data)),
+        ));
+    }
+
+    // The first markets were all binary.
+    set_missing(&mut data, "outcomeType", json!("BINARY"));
+
+    if let Some(tags) = data.get("tags").and_then(Value::as_array) {
+        let slugs = tags
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|tag| json!(tag.to_lowercase()))
+            .collect();
+        set_missing(&mut data, "groupSlugs", Value::Array(slugs));
+    }
+
+    let resolved = data.get("resolution").is_some_and(Value::is_string);
+    set_missing(&mut data, "isResolved", json!(resolved));
+
+    let is_dpm = data
+        .get("mechanism")
+        .and_then(Value::as_str)
+        .is_some_and(|m| LEGACY_MECHANISMS.contains(&m));
+
+    if is_dpm {
+        let probabilities = data
+            .get("totalShares")
+            .and_then(Value::as_object)
+            .map(|shares| dpm_probabilities(shares.iter()))
+            .unwrap_or_default();
+
+        match data["outcomeType"].as_str() {
+            Some("BINARY") => {
+                if let Some((_, prob)) = probabilities.iter().find(|(o, _)| o == "YES") {
+                    set_missing(&mut data, "probability", json!(prob));
+                }
+            }
+            Some("FREE_RESPONSE" | "MULTIPLE_CHOICE") => {
+                set_missing(&mut data, "shouldAnswersSumToOne", json!(true));
+
+                if let Some(Value::Array(answers)) = data.get_mut("answers") {
+                    // Answer "0" was a hidden placeholder for "none of the above".
+                    answers.retain(|a| a["id"] != "0");
+
+                    for answer in answers.iter_mut().filter_map(Value::as_object_mut) {
+                        let id = answer.get("id").and_then(Value::as_str).unwrap_or_default();
+
+                        if let Some((_, prob)) = probabilities.iter().find(|(o, _)| o == id) {
+                            let prob = *prob;
+                            set_missing(answer, "probability", json!(prob));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(FullMarket(Value::Object(data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_dpm_markets() -> Result<()> {
+        let binary = json!({
+            "id": "old", "question": "Will it?", "mechanism": "dpm-2",
+            "totalShares": {"YES": 30.0, "NO": 10.0}, "tags": ["Science"],
+        });
+        assert!(is_legacy(&binary));
+
+        let market = migrate_market(binary)?;
+        assert_eq!(market.outcome_type(), OutcomeType::Binary);
+        assert_eq!(market.probability(), Some(0.9));
+        assert_eq!(market.group_slugs(), vec!["science"]);
+        assert!(!market.is_resolved());
+
+        let free_response = json!({
+            "id": "fr", "question": "Which?", "mechanism": "dpm-2",
+            "outcomeType": "FREE_RESPONSE", "resolution": "1",
+            "totalShares": {"0": 0.0, "1": 20.0, "2": 10.0},
+            "answers": [
+                {"id": "0", "text": ""},
+                {"id": "1", "text": "One"},
+                {"id": "2", "text": "Two"},
+            ],
+        });
+
+        let market = migrate_market(free_response)?;
+        let answers = market.answers();
+        assert!(market.is_resolved());
+        assert!(market.should_answers_sum_to_one());
+        assert_eq!(answers.len(), 2);
+        assert_eq!(answers[0].probability(), Some(0.8));
+
+        let current = json!({"id": "new", "question": "?", "outcomeType": "BINARY",
+            "mechanism": "cpmm-1", "probability": 0.3, "groupSlugs": [], "isResolved": false});
+        assert!(!is_legacy(&current));
+        assert_eq!(migrate_market(current.clone())?.0, current);
+
+        assert!(migrate_market(json!({"question": "no id"})).is_err());
+
+        Ok(())
+    }
+}
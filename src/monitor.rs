@@ -0,0 +1,357 @@
+//! Long-running watchers over markets
+//!
+//! These are streams that wake up on their own (timers, polling) and
+//! yield events, so bots don't each have to write their own timer code.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::{stream, Stream, StreamExt};
+
+use crate::analysis::{CommentAnalyzer, CommentFlag};
+use crate::error::Result;
+use crate::freshness::Fetched;
+use crate::polling::{poll_stream, Seen};
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// 24 hours, 1 hour and 5 minutes before close.
+pub const DEFAULT_COUNTDOWN_OFFSETS: [Duration; 3] = [
+    Duration::from_secs(24 * 60 * 60),
+    Duration::from_secs(60 * 60),
+    Duration::from_secs(5 * 60),
+];
+
+/// A market is about to close, see [`ManifoldClient::watch_close_countdown`].
+#[derive(Debug, Clone)]
+pub struct CountdownEvent {
+    pub market_id: String,
+    pub question: String,
+    pub close_time: DateTime<Utc>,
+    /// Which of the requested offsets this event is for.
+    pub offset: Duration,
+}
+
+/// How long [`ManifoldClient::watch_close_countdown`] waits before fetching
+/// a market again after a failed fetch.
+pub const COUNTDOWN_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+fn fire_time(close_time: DateTime<Utc>, offset: Duration) -> DateTime<Utc> {
+    close_time - chrono::Duration::from_std(offset).unwrap_or_default()
+}
+
+/// Takes the offsets in `pending` that are due by `now`, earliest first.
+///
+/// `pending` holds the offsets that haven't fired yet, `None` before the
+/// market was first fetched; offsets already passed by then are skipped.
+fn take_due(
+    pending: &mut Option<Vec<Duration>>,
+    offsets: &[Duration],
+    close_time: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<Duration> {
+    let pending = pending.get_or_insert_with(|| {
+        offsets
+            .iter()
+            .filter(|offset| fire_time(close_time, **offset) > now)
+            .copied()
+            .collect()
+    });
+
+    // A close time that moved earlier can make several offsets overdue at once.
+    let (mut due, rest): (Vec<_>, _) = pending
+        .iter()
+        .partition(|offset| fire_time(close_time, **offset) <= now);
+    *pending = rest;
+
+    due.sort_by(|a, b| b.cmp(a));
+    due
+}
+
+impl ManifoldClient {
+    /// Yields an event `offset` before each market closes, for each of the `offsets`.
+    ///
+    /// Offsets that have already passed when the stream starts are skipped.
+    /// Each market is fetched again right before its event fires, so a changed
+    /// close time reschedules its remaining events instead of firing early,
+    /// and the ones a close time moved earlier has made overdue fire right away.
+    ///
+    /// A failed fetch yields an error and the market is fetched again after
+    /// [`COUNTDOWN_RETRY_DELAY`]. The stream ends after the last event.
+    pub fn watch_close_countdown<'a>(
+        &'a self,
+        market_ids: &'a [&'a str],
+        offsets: &'a [Duration],
+    ) -> impl Stream<Item = Result<CountdownEvent>> + 'a {
+        let now = self.now();
+        let wakeups = (0..market_ids.len()).map(|index| Reverse((now, index)));
+        let state = (
+            BinaryHeap::from_iter(wakeups),
+            vec![None; market_ids.len()],
+            VecDeque::new(),
+        );
+
+        stream::unfold(
+            state,
+            move |(mut wakeups, mut pending, mut ready)| async move {
+                while ready.is_empty() {
+                    let Reverse((wake_at, index)) = wakeups.pop()?;
+
+                    if wake_at > self.now() {
+                        self.clock().sleep_until(wake_at).await;
+                    }
+
+                    let market = match self.get_market(market_ids[index]).await {
+                        Ok(market) => market,
+                        Err(e) => {
+                            let retry_at = self.now()
+                                + chrono::Duration::from_std(COUNTDOWN_RETRY_DELAY)
+                                    .unwrap_or_default();
+                            wakeups.push(Reverse((retry_at, index)));
+                            ready.push_back(Err(e));
+                            break;
+                        }
+                    };
+
+                    let now = self.now();
+                    let pending = &mut pending[index];
+
+                    let Some(close_time) = market.close_time().filter(|close| *close > now) else {
+                        // Nothing to count down to once the close time is gone or has passed.
+                        *pending = Some(vec![]);
+                        continue;
+                    };
+
+                    ready.extend(take_due(pending, offsets, close_time, now).into_iter().map(
+                        |offset| {
+                            Ok(CountdownEvent {
+                                market_id: market.id().to_owned(),
+                                question: market.question().to_owned(),
+                                close_time,
+                                offset,
+                            })
+                        },
+                    ));
+
+                    let next = pending
+                        .iter()
+                        .flatten()
+                        .map(|o| fire_time(close_time, *o))
+                        .min();
+                    wakeups.extend(next.map(|next| Reverse((next, index))));
+                }
+
+                let event = ready.pop_front()?;
+                Some((event, (wakeups, pending, ready)))
+            },
+        )
+    }
+
+    /// Polls `market_ids` every `interval`, yielding each market as it's fetched,
+    /// with the time it was fetched.
+    ///
+    /// Never ends; a failed fetch yields an error and polling continues.
+    pub fn watch_markets<'a>(
+        &'a self,
+        market_ids: &'a [String],
+        interval: Duration,
+    ) -> impl Stream<Item = Result<Fetched<FullMarket>>> + 'a {
+        let rounds = stream::unfold(true, move |first| async move {
+            if !first {
+                self.clock().sleep(interval).await;
+            }
+
+            Some((stream::iter(market_ids), false))
+        });
+
+        rounds.flatten().then(move |id| self.get_market_fetched(id))
+    }
+
+    /// Polls the comments on `market_ids` every `interval`, yielding the new
+    /// ones `analyzer` flags.
+    ///
+    /// Comments already there when a market is first fetched aren't analyzed,
+    /// even if that's a few rounds in because the first fetches failed.
+    /// Never ends; a failed fetch or analysis yields an error and polling continues.
+    pub fn watch_comments<'a, A: CommentAnalyzer>(
+        &'a self,
+        market_ids: &'a [String],
+        interval: Duration,
+        analyzer: &'a A,
+    ) -> impl Stream<Item = Result<CommentFlag>> + 'a {
+        poll_stream(
+            self,
+            interval,
+            Seen::default(),
+            move |mut seen| async move {
+                let mut items = vec![];
+
+                for market_id in market_ids {
+                    let comments = match self.get_comments(Some(market_id), None).await {
+                        Ok(comments) => comments,
+                        Err(e) => {
+                            items.push(Err(e));
+                            continue;
+                        }
+                    };
+
+                    // Oldest first, so flags come out in the order they were posted.
+                    let comments = comments.into_iter().rev().collect();
+                    let new = seen.diff(market_id, comments, |comment: &Comment| {
+                        comment.0["id"].as_str().unwrap_or_default()
+                    });
+
+                    for Comment(comment) in new.new_items() {
+                        let flag = analyzer.analyze(&comment).await.map(|note| {
+                            note.map(|note| CommentFlag {
+                                market_id: market_id.clone(),
+                                comment,
+                                note,
+                            })
+                        });
+
+                        items.extend(flag.transpose());
+                    }
+                }
+
+                Some((seen, items))
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::TimeZone;
+    use futures_util::TryStreamExt;
+    use serde_json::json;
+
+    use super::*;
+    use crate::analysis::KeywordAnalyzer;
+    use crate::clock::MockClock;
+    use crate::fixtures::{MockRequest, MockResponse, MockServer};
+
+    #[tokio::test]
+    async fn counts_down_by_the_clients_clock() -> Result<()> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let close = start + chrono::Duration::hours(2);
+        let server = MockServer::start(move |req: &MockRequest| {
+            (req.path == "/market/a").then(|| {
+                MockResponse::json(json!({
+                    "id": "a",
+                    "question": "Will it close?",
+                    "closeTime": close.timestamp_millis(),
+                }))
+            })
+        })
+        .await?;
+        let clock = MockClock::new(start);
+        let client = server.client().clock(clock.clone()).build()?;
+
+        let offsets = [
+            Duration::from_secs(3 * 60 * 60),
+            Duration::from_secs(60 * 60),
+            Duration::from_secs(5 * 60),
+        ];
+        let events: Vec<_> = client
+            .watch_close_countdown(&["a"], &offsets)
+            .try_collect()
+            .await?;
+
+        // The 3 hour offset had already passed.
+        let fired: Vec<_> = events.iter().map(|e| (e.offset, e.close_time)).collect();
+        assert_eq!(fired, [(offsets[1], close), (offsets[2], close)]);
+        assert_eq!(
+            clock.sleeps(),
+            [Duration::from_secs(60 * 60), Duration::from_secs(55 * 60)]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn counts_down_through_errors_and_earlier_closes() -> Result<()> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let minutes = move |m: i64| start + chrono::Duration::minutes(m);
+        let fetches = AtomicUsize::new(0);
+        let server = MockServer::start(move |req: &MockRequest| {
+            if req.path != "/market/a" {
+                return None;
+            }
+
+            let close = match fetches.fetch_add(1, Ordering::SeqCst) {
+                0 => minutes(120),
+                1 => return Some(MockResponse::status(503, json!({"message": "Unavailable"}))),
+                _ => minutes(80),
+            };
+            Some(MockResponse::json(
+                json!({"id": "a", "question": "Q?", "closeTime": close.timestamp_millis()}),
+            ))
+        })
+        .await?;
+        let clock = MockClock::new(start);
+        let client = server.client().clock(clock.clone()).build()?;
+
+        let offsets = [60, 30, 5].map(|m| Duration::from_secs(m * 60));
+        let events: Vec<_> = client
+            .watch_close_countdown(&["a"], &offsets)
+            .map(|event| event.map(|e| (e.offset, e.close_time)))
+            .collect()
+            .await;
+
+        assert!(events[0].is_err());
+        // Moving the close time 40 minutes earlier made two offsets overdue.
+        let fired: Vec<_> = events[1..].iter().map(|e| *e.as_ref().unwrap()).collect();
+        assert_eq!(
+            fired,
+            [
+                (offsets[0], minutes(80)),
+                (offsets[1], minutes(80)),
+                (offsets[2], minutes(80)),
+            ]
+        );
+        assert_eq!(client.now(), minutes(75));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn takes_the_baseline_from_the_first_successful_poll() -> Result<()> {
+        let comment = |id: &str, text: &str| json!({"id": id, "text": text});
+        let polls = AtomicUsize::new(0);
+        let server = MockServer::start(move |req: &MockRequest| {
+            if req.path != "/comments" {
+                return None;
+            }
+
+            Some(match polls.fetch_add(1, Ordering::SeqCst) {
+                0 => MockResponse::status(503, json!({"message": "Unavailable"})),
+                1 => MockResponse::json(json!([comment("c1", "how will this resolve")])),
+                _ => MockResponse::json(json!([
+                    comment("c2", "this should resolve yes"),
+                    comment("c1", "how will this resolve"),
+                ])),
+            })
+        })
+        .await?;
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        let client = server.client().clock(clock).build()?;
+
+        let market_ids = ["a".to_owned()];
+        let analyzer = KeywordAnalyzer::default();
+        let mut flags =
+            Box::pin(client.watch_comments(&market_ids, Duration::from_secs(60), &analyzer));
+
+        assert!(flags.try_next().await.is_err());
+
+        let flag = flags.try_next().await?.unwrap();
+        assert_eq!(flag.comment["id"], "c2");
+        assert_eq!(flag.market_id, "a");
+
+        Ok(())
+    }
+}
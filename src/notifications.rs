@@ -0,0 +1,253 @@
+//! The authorized user's notifications, emulated by polling
+//!
+//! The API doesn't expose the notification inbox to API keys, so
+//! [`ManifoldClient::stream_notifications`] rebuilds the notifications bots
+//! care about most from public endpoints:
+//!
+//! - comments on the user's open markets,
+//! - resolutions of markets the user recently bet on,
+//! - fills of the user's limit orders,
+//! - managrams (`MANA_PAYMENT` transactions) to the user.
+//!
+//! Everything that already happened when the stream starts, or when a
+//! source is first fetched successfully, is skipped.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use futures_util::{Stream, StreamExt, TryStreamExt};
+
+use crate::error::Result;
+use crate::polling::{poll_stream, Seen};
+use crate::streams::Paginator;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// How many of the user's latest bets are checked for fills and bet-on markets.
+pub const NOTIFICATION_BETS: u64 = 1000;
+
+/// How many of the markets the user recently bet on are watched for resolution.
+pub const NOTIFICATION_MARKETS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub enum Notification {
+    /// Someone else commented on one of the user's markets.
+    NewComment {
+        market_id: String,
+        comment: Comment,
+    },
+    /// A market the user bet on resolved.
+    MarketResolved {
+        market: FullMarket,
+    },
+    /// More of one of the user's limit orders was filled.
+    LimitOrderFilled {
+        bet: Bet,
+        /// Mana filled since the last notification for this order.
+        filled_amount: f64,
+    },
+    ManagramReceived {
+        txn: Txn,
+    },
+}
+
+#[derive(Debug, Default)]
+struct InboxState {
+    user_id: Option<String>,
+    /// Comments per market, the user's bets and managrams.
+    seen: Seen,
+    /// Amount filled so far of each open limit order.
+    orders: HashMap<String, f64>,
+    /// Markets the user bet on, and the ones of those that aren't resolved yet.
+    bet_markets: HashSet<String>,
+    open_markets: HashSet<String>,
+}
+
+impl ManifoldClient {
+    /// Polls for the authorized user's notifications every `interval`, see the
+    /// [module documentation](crate::notifications).
+    ///
+    /// Each round makes a request per open market of the user and per watched
+    /// market, so keep the interval in minutes. Never ends; a failed request
+    /// yields an error and polling continues.
+    pub fn stream_notifications(
+        &self,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<Notification>> + '_ {
+        poll_stream(
+            self,
+            interval,
+            InboxState::default(),
+            move |mut state| async move {
+                let mut items = vec![];
+
+                let me = match &state.user_id {
+                    Some(me) => me.clone(),
+                    None => match self.get_me().await {
+                        Ok(me) => me.id().to_owned(),
+                        Err(e) => return Some((state, vec![Err(e)])),
+                    },
+                };
+                state.user_id = Some(me.clone());
+
+                for result in [
+                    self.poll_comments(&me, &mut state, &mut items).await,
+                    self.poll_bets(&me, &mut state, &mut items).await,
+                    self.poll_managrams(&me, &mut state, &mut items).await,
+                ] {
+                    if let Err(e) = result {
+                        items.push(Err(e));
+                    }
+                }
+
+                Some((state, items))
+            },
+        )
+    }
+
+    async fn poll_comments(
+        &self,
+        me: &str,
+        state: &mut InboxState,
+        items: &mut Vec<Result<Notification>>,
+    ) -> Result<()> {
+        let markets: Vec<_> = Paginator::cursor("/markets")
+            .param("userId", me)
+            .stream(self)
+            .try_filter(|m: &LiteMarket| std::future::ready(!m.is_resolved()))
+            .take(NOTIFICATION_MARKETS)
+            .try_collect()
+            .await?;
+
+        let sources = markets
+            .iter()
+            .map(|market| format!("comments/{}", market.id()))
+            .collect::<HashSet<_>>();
+        state
+            .seen
+            .retain(|source| !source.starts_with("comments/") || sources.contains(source));
+
+        for market in markets {
+            let comments = self.get_comments(Some(market.id()), None).await?;
+            let comments = comments.into_iter().rev().collect();
+            let source = format!("comments/{}", market.id());
+
+            for comment in state
+                .seen
+                .diff(&source, comments, |comment: &Comment| {
+                    comment.0["id"].as_str().unwrap_or_default()
+                })
+                .new_items()
+            {
+                if comment.0["userId"] != me {
+                    items.push(Ok(Notification::NewComment {
+                        market_id: market.id().to_owned(),
+                        comment,
+                    }));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn poll_bets(
+        &self,
+        me: &str,
+        state: &mut InboxState,
+        items: &mut Vec<Result<Notification>>,
+    ) -> Result<()> {
+        let bets = self
+            .get_bets_with(&BetsQuery::new().user_id(me).limit(NOTIFICATION_BETS))
+            .await?;
+
+        // Markets bet on before the first successful fetch resolved before it, too.
+        let baseline = state
+            .seen
+            .diff("bets", bets.clone(), |bet: &Bet| bet.id())
+            .is_baseline();
+
+        for bet in bets.iter().rev() {
+            if bet.limit_prob().is_none() {
+                continue;
+            }
+
+            let filled = bet.amount();
+
+            match state.orders.get(bet.id()) {
+                Some(&before) if filled > before => {
+                    items.push(Ok(Notification::LimitOrderFilled {
+                        bet: bet.clone(),
+                        filled_amount: filled - before,
+                    }));
+                }
+                _ => {}
+            }
+
+            if bet.is_filled() || bet.is_cancelled() {
+                state.orders.remove(bet.id());
+            } else {
+                state.orders.insert(bet.id().to_owned(), filled);
+            }
+        }
+
+        // Bets are newest first, so these are the most recently bet on markets.
+        let mut recent_markets = vec![];
+        for bet in &bets {
+            if !recent_markets.contains(&bet.contract_id()) {
+                recent_markets.push(bet.contract_id());
+            }
+        }
+
+        for market_id in recent_markets.into_iter().take(NOTIFICATION_MARKETS) {
+            if state.bet_markets.insert(market_id.to_owned()) {
+                state.open_markets.insert(market_id.to_owned());
+            }
+        }
+
+        let mut resolved = vec![];
+
+        for market_id in &state.open_markets {
+            let market = self.get_market(market_id).await?;
+
+            if market.is_resolved() {
+                resolved.push(market_id.clone());
+
+                if !baseline {
+                    items.push(Ok(Notification::MarketResolved { market }));
+                }
+            }
+        }
+
+        for market_id in resolved {
+            state.open_markets.remove(&market_id);
+        }
+
+        Ok(())
+    }
+
+    async fn poll_managrams(
+        &self,
+        me: &str,
+        state: &mut InboxState,
+        items: &mut Vec<Result<Notification>>,
+    ) -> Result<()> {
+        let txns = self
+            .stream_txns(None, Some(me), Some("MANA_PAYMENT"))
+            .take(100)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let txns = txns.into_iter().rev().collect();
+
+        for txn in state
+            .seen
+            .diff("managrams", txns, |txn: &Txn| txn.id())
+            .new_items()
+        {
+            items.push(Ok(Notification::ManagramReceived { txn }));
+        }
+
+        Ok(())
+    }
+}
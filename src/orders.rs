@@ -0,0 +1,446 @@
+//! Tracking the authorized user's limit orders
+//!
+//! A limit order is a [`Bet`] with a `limitProb`; every time it's matched, a
+//! fill is appended to its `fills`. [`ManifoldClient::watch_order_fills`]
+//! polls the orders and yields an [`OrderFill`] for each new one.
+//!
+//! The open orders of everyone on a market make up its [`OrderBook`]
+//! ([`ManifoldClient::get_order_book`]). [`OrderBook::simulate`] estimates
+//! whom a bet would be matched against, e.g. to avoid trading with yourself.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use futures_util::Stream;
+
+use crate::error::Result;
+use crate::math::CpmmPool;
+use crate::polling::{poll_stream, Seen};
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// An open limit order in an [`OrderBook`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitOrder {
+    pub bet_id: String,
+    pub user_id: String,
+    /// `true` for YES orders.
+    pub yes: bool,
+    pub limit_prob: f64,
+    /// Mana of the order not filled yet.
+    pub remaining: f64,
+}
+
+/// The open limit orders on a binary market.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrderBook {
+    /// Best (highest) price first.
+    pub yes: Vec<LimitOrder>,
+    /// Best (lowest) probability first.
+    pub no: Vec<LimitOrder>,
+}
+
+/// How a bet would be filled, see [`OrderBook::simulate`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FillEstimate {
+    /// Mana filled by the pool.
+    pub pool: f64,
+    /// Mana matched against other users' orders.
+    pub others: f64,
+    /// Mana matched against the given user's own orders, by bet id.
+    pub own: Vec<(String, f64)>,
+}
+
+impl FillEstimate {
+    pub fn own_amount(&self) -> f64 {
+        self.own.iter().map(|(_, amount)| amount).sum()
+    }
+
+    /// Mana filled in all.
+    pub fn total(&self) -> f64 {
+        self.pool + self.others + self.own_amount()
+    }
+
+    /// The part of the bet that would be matched against the user's own orders.
+    pub fn own_fraction(&self) -> f64 {
+        let total = self.total();
+
+        if total > 0.0 {
+            self.own_amount() / total
+        } else {
+            0.0
+        }
+    }
+}
+
+impl OrderBook {
+    /// Builds the book from bets, keeping the open limit orders on YES and NO.
+    pub fn from_bets(bets: &[Bet]) -> OrderBook {
+        let mut book = OrderBook::default();
+
+        for bet in bets {
+            let (Some(limit_prob), Some(order_amount)) =
+                (bet.limit_prob(), number_f64(&bet.0["orderAmount"]))
+            else {
+                continue;
+            };
+
+            if bet.is_filled() || bet.is_cancelled() {
+                continue;
+            }
+
+            let order = LimitOrder {
+                bet_id: bet.id().to_owned(),
+                user_id: bet.user_id().to_owned(),
+                yes: bet.outcome() == "YES",
+                limit_prob,
+                remaining: (order_amount - bet.amount()).max(0.0),
+            };
+
+            match bet.outcome() {
+                "YES" => book.yes.push(order),
+                "NO" => book.no.push(order),
+                _ => {}
+            }
+        }
+
+        book.yes
+            .sort_by(|a, b| b.limit_prob.total_cmp(&a.limit_prob));
+        book.no
+            .sort_by(|a, b| a.limit_prob.total_cmp(&b.limit_prob));
+
+        book
+    }
+
+    /// Estimates how `order` would be filled by `pool` and the orders in the
+    /// book, telling `user_id`'s own orders apart.
+    ///
+    /// Walks the opposite orders from the best price, buying from the pool
+    /// until its probability reaches each order's limit. Ignores fees.
+    pub fn simulate(
+        &self,
+        pool: &CpmmPool,
+        order: &PlaceBetArgs,
+        user_id: &str,
+    ) -> Result<FillEstimate> {
+        let yes = match order.outcome {
+            Outcome::Yes => true,
+            Outcome::No => false,
+            _ => return Ok(FillEstimate::default()),
+        };
+
+        let opposite = if yes { &self.no } else { &self.yes };
+        let mut pool = *pool;
+        let mut remaining = order.amount as f64;
+        let mut estimate = FillEstimate::default();
+
+        // Buying YES raises the probability, so it meets NO orders above it.
+        let reachable = |prob: f64| match order.limit_prob {
+            Some(limit) if yes => prob <= limit,
+            Some(limit) => prob >= limit,
+            None => true,
+        };
+
+        for resting in opposite {
+            if remaining <= 0.0 || !reachable(resting.limit_prob) {
+                break;
+            }
+
+            let to_limit = pool.amount_to_probability(&order.outcome, resting.limit_prob)?;
+            if to_limit >= remaining {
+                break;
+            }
+
+            pool = pool.buy(&order.outcome, to_limit)?.0;
+            estimate.pool += to_limit;
+            remaining -= to_limit;
+
+            // The resting order buys the other side of the same shares.
+            let (ours, theirs) = if yes {
+                (resting.limit_prob, 1.0 - resting.limit_prob)
+            } else {
+                (1.0 - resting.limit_prob, resting.limit_prob)
+            };
+            let matched = remaining.min(resting.remaining / theirs * ours);
+            remaining -= matched;
+
+            if resting.user_id == user_id {
+                estimate.own.push((resting.bet_id.clone(), matched));
+            } else {
+                estimate.others += matched;
+            }
+        }
+
+        if remaining > 0.0 {
+            let to_limit = match order.limit_prob {
+                Some(limit) => pool.amount_to_probability(&order.outcome, limit)?,
+                None => remaining,
+            };
+            estimate.pool += remaining.min(to_limit);
+        }
+
+        Ok(estimate)
+    }
+}
+
+/// Which limit orders [`ManifoldClient::watch_order_fills`] watches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderSelector {
+    /// These orders, by bet id.
+    Bets(Vec<String>),
+    /// All of the user's open orders on a market, including ones placed later.
+    Market(String),
+}
+
+/// Part of a limit order getting matched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderFill {
+    pub bet_id: String,
+    pub market_id: String,
+    pub outcome: String,
+    /// Mana of the order spent in this fill.
+    pub amount: f64,
+    pub shares: f64,
+    /// `amount / shares`: the probability the shares were bought at.
+    pub price: f64,
+    /// The bet this fill was matched against, `None` for fills from the pool.
+    pub matched_bet_id: Option<String>,
+    /// Mana the other side put in, `shares - amount`; `None` for fills from the pool.
+    pub counterparty_amount: Option<f64>,
+    /// Milliseconds since the epoch.
+    pub timestamp: i64,
+    /// Whether the order is now completely filled.
+    pub order_filled: bool,
+}
+
+/// The fills of `order` after the first `seen` ones.
+pub fn new_fills(order: &Bet, seen: usize) -> Vec<OrderFill> {
+    let Some(fills) = order.0["fills"].as_array() else {
+        return vec![];
+    };
+
+    let count = fills.len();
+
+    fills
+        .iter()
+        .enumerate()
+        .skip(seen)
+        .filter_map(|(i, fill)| {
+            let amount = number_f64(&fill["amount"])?;
+            let shares = number_f64(&fill["shares"])?;
+            let matched_bet_id = fill["matchedBetId"].as_str().map(str::to_owned);
+
+            Some(OrderFill {
+                bet_id: order.id().to_owned(),
+                market_id: order.contract_id().to_owned(),
+                outcome: order.outcome().to_owned(),
+                amount,
+                shares,
+                price: if shares == 0.0 { 0.0 } else { amount / shares },
+                counterparty_amount: matched_bet_id.as_ref().map(|_| shares - amount),
+                matched_bet_id,
+                timestamp: fill["timestamp"]
+                    .as_i64()
+                    .unwrap_or_else(|| order.created_time()),
+                order_filled: order.is_filled() && i + 1 == count,
+            })
+        })
+        .collect()
+}
+
+impl ManifoldClient {
+    /// `GET /v0/bets?kinds=open-limit`
+    ///
+    /// The open limit orders on a binary market.
+    pub async fn get_order_book(&self, market_id: &str) -> Result<OrderBook> {
+        let bets: Vec<Bet> = self
+            .execute(self.http_get("/bets").query(&[
+                ("contractId", market_id),
+                ("kinds", "open-limit"),
+                ("limit", "1000"),
+            ]))
+            .await?;
+
+        Ok(OrderBook::from_bets(&bets))
+    }
+
+    /// Polls the authorized user's limit orders every `interval`, yielding each new fill.
+    ///
+    /// Fills from before the first successful poll aren't reported. Orders stop
+    /// being watched once they're filled or cancelled; the stream ends when
+    /// no [`OrderSelector::Bets`] are left, and never for
+    /// [`OrderSelector::Market`]. A failed request yields an error and polling continues.
+    pub fn watch_order_fills(
+        &self,
+        orders: OrderSelector,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<OrderFill>> + '_ {
+        struct State {
+            orders: OrderSelector,
+            user_id: Option<String>,
+            /// Fills of the watched orders, by `{bet id}/{index}`.
+            seen: Seen,
+            /// Filled or cancelled orders.
+            done: HashSet<String>,
+        }
+
+        let state = State {
+            orders,
+            user_id: None,
+            seen: Seen::default(),
+            done: HashSet::new(),
+        };
+
+        poll_stream(self, interval, state, move |mut state| async move {
+            if let OrderSelector::Bets(ids) = &state.orders {
+                if ids.is_empty() {
+                    return None;
+                }
+            }
+
+            if state.user_id.is_none() {
+                match self.get_me().await {
+                    Ok(me) => state.user_id = Some(me.id().to_owned()),
+                    Err(e) => return Some((state, vec![Err(e)])),
+                }
+            }
+
+            let market_id = match &state.orders {
+                OrderSelector::Market(market_id) => Some(market_id.as_str()),
+                OrderSelector::Bets(_) => None,
+            };
+
+            let bets = match self
+                .get_bets(
+                    state.user_id.as_deref(),
+                    None,
+                    market_id,
+                    None,
+                    Some(1000),
+                    None,
+                )
+                .await
+            {
+                Ok(bets) => bets,
+                Err(e) => return Some((state, vec![Err(e)])),
+            };
+
+            let mut fills = vec![];
+
+            for bet in bets.iter().rev() {
+                let watched = match &state.orders {
+                    OrderSelector::Bets(ids) => ids.iter().any(|id| id == bet.id()),
+                    OrderSelector::Market(_) => bet.limit_prob().is_some(),
+                };
+                if !watched || state.done.contains(bet.id()) {
+                    continue;
+                }
+
+                fills.extend(
+                    new_fills(bet, 0)
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, fill)| (format!("{}/{i}", bet.id()), fill)),
+                );
+
+                if bet.is_filled() || bet.is_cancelled() {
+                    state.done.insert(bet.id().to_owned());
+                    if let OrderSelector::Bets(ids) = &mut state.orders {
+                        ids.retain(|id| id != bet.id());
+                    }
+                }
+            }
+
+            // Orders placed later are new to the source, so all their fills are reported.
+            let fills = state
+                .seen
+                .diff("fills", fills, |(key, _)| key)
+                .new_items()
+                .into_iter()
+                .map(|(_, fill)| Ok(fill))
+                .collect();
+
+            Some((state, fills))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reads_new_fills() {
+        let order = Bet(json!({
+            "id": "order",
+            "contractId": "market",
+            "outcome": "YES",
+            "createdTime": 1,
+            "limitProb": 0.4,
+            "isFilled": true,
+            "fills": [
+                {"amount": 4.0, "shares": 10.0, "matchedBetId": null, "timestamp": 1},
+                {"amount": 6.0, "shares": 15.0, "matchedBetId": "theirs", "timestamp": 2},
+            ],
+        }));
+
+        assert_eq!(new_fills(&order, 0).len(), 2);
+        assert_eq!(
+            new_fills(&order, 1),
+            [OrderFill {
+                bet_id: "order".to_owned(),
+                market_id: "market".to_owned(),
+                outcome: "YES".to_owned(),
+                amount: 6.0,
+                shares: 15.0,
+                price: 0.4,
+                matched_bet_id: Some("theirs".to_owned()),
+                counterparty_amount: Some(9.0),
+                timestamp: 2,
+                order_filled: true,
+            }]
+        );
+        assert!(new_fills(&order, 2).is_empty());
+    }
+
+    #[test]
+    fn simulates_fills() -> Result<()> {
+        let order = |id: &str, user: &str, outcome: &str, limit_prob: f64, amount: f64| {
+            Bet(json!({
+                "id": id,
+                "userId": user,
+                "outcome": outcome,
+                "limitProb": limit_prob,
+                "orderAmount": amount,
+                "amount": 0.0,
+                "isFilled": false,
+            }))
+        };
+
+        let book = OrderBook::from_bets(&[
+            order("mine", "me", "NO", 0.55, 45.0),
+            order("theirs", "them", "NO", 0.6, 40.0),
+            order("yes", "them", "YES", 0.4, 10.0),
+        ]);
+        assert_eq!(book.no[0].bet_id, "mine");
+        assert_eq!(book.yes.len(), 1);
+
+        let pool = CpmmPool::new(100.0, 100.0, 0.5);
+
+        // M$45 of NO at 55% is matched by M$55 of YES.
+        let estimate = book.simulate(&pool, &PlaceBetArgs::new(100, "m", Outcome::Yes), "me")?;
+        let to_55 = pool.amount_to_probability(&Outcome::Yes, 0.55)?;
+        assert!((estimate.own_amount() - 55.0).abs() < 1e-9);
+        assert!(estimate.own_fraction() > 0.5);
+        assert!(estimate.others > 0.0);
+        assert!(estimate.pool > to_55);
+
+        // Too small to reach any order.
+        let small = book.simulate(&pool, &PlaceBetArgs::new(1, "m", Outcome::Yes), "me")?;
+        assert_eq!(small.own_fraction(), 0.0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+//! Polling sources and reporting only what's new
+//!
+//! The watchers in [`monitor`](crate::monitor), [`anomaly`](crate::anomaly),
+//! [`notifications`](crate::notifications), [`orders`](crate::orders) and
+//! [`groups`](crate::groups) all fetch some lists every interval and yield
+//! what wasn't there before. [`poll_stream`] runs the rounds, and [`Seen`]
+//! tells new items from old ones, per source (a market's comments, the
+//! user's bets, ...).
+//!
+//! A source's first successful fetch is its baseline: everything in it is
+//! old. A source whose first fetch failed, or that only turns up later (a
+//! market added to a group), gets its baseline then, so its history is
+//! never reported as new.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::time::Duration;
+
+use futures_util::{stream, Stream};
+
+use crate::error::Result;
+use crate::ManifoldClient;
+
+/// Items of one fetch of a source, see [`Seen::diff`].
+#[derive(Debug)]
+pub(crate) enum Polled<T> {
+    /// The source's first successful fetch; all of it is old.
+    Baseline(Vec<T>),
+    /// The items that weren't in the previous fetch, oldest first.
+    New(Vec<T>),
+}
+
+impl<T> Polled<T> {
+    pub(crate) fn is_baseline(&self) -> bool {
+        matches!(self, Polled::Baseline(_))
+    }
+
+    /// The new items; none for a baseline.
+    pub(crate) fn new_items(self) -> Vec<T> {
+        match self {
+            Polled::Baseline(_) => vec![],
+            Polled::New(items) => items,
+        }
+    }
+}
+
+/// The ids in the last successful fetch of each source.
+///
+/// Only the last fetch is kept, which is enough for lists that only grow
+/// at the front (newest first, like the API returns them) and keeps the
+/// memory bounded by the size of a fetch.
+#[derive(Debug, Default)]
+pub(crate) struct Seen {
+    sources: HashMap<String, HashSet<String>>,
+}
+
+impl Seen {
+    /// Records a successful fetch of `source`, oldest first, and returns what's new in it.
+    pub(crate) fn diff<T>(
+        &mut self,
+        source: &str,
+        items: Vec<T>,
+        id: impl Fn(&T) -> &str,
+    ) -> Polled<T> {
+        let ids = items.iter().map(|item| id(item).to_owned()).collect();
+
+        let Some(previous) = self.sources.insert(source.to_owned(), ids) else {
+            return Polled::Baseline(items);
+        };
+
+        Polled::New(
+            items
+                .into_iter()
+                .filter(|item| !previous.contains(id(item)))
+                .collect(),
+        )
+    }
+
+    /// Forgets the sources `keep` returns false for, e.g. markets no longer watched.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.sources.retain(|source, _| keep(source));
+    }
+}
+
+/// Calls `poll` right away and then every `interval` by the client's clock,
+/// yielding the results it returns; ends when it returns `None`.
+///
+/// `poll` gets the state and hands it back with the round's items, errors
+/// included, so one failed request doesn't end the stream.
+pub(crate) fn poll_stream<'a, S, T, F, Fut>(
+    client: &'a ManifoldClient,
+    interval: Duration,
+    state: S,
+    poll: F,
+) -> impl Stream<Item = Result<T>> + 'a
+where
+    S: 'a,
+    T: 'a,
+    F: FnMut(S) -> Fut + 'a,
+    Fut: Future<Output = Option<(S, Vec<Result<T>>)>> + 'a,
+{
+    let rounds = (Some(state), VecDeque::new(), poll, true);
+
+    stream::unfold(
+        rounds,
+        move |(mut state, mut pending, mut poll, mut first)| async move {
+            while pending.is_empty() {
+                if !first {
+                    client.clock().sleep(interval).await;
+                }
+                first = false;
+
+                let (next, items) = poll(state.take()?).await?;
+                state = Some(next);
+                pending.extend(items);
+            }
+
+            let item = pending.pop_front()?;
+            Some((item, (state, pending, poll, first)))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_against_each_sources_baseline() {
+        let mut seen = Seen::default();
+        fn id<'a>(item: &'a &str) -> &'a str {
+            item
+        }
+
+        assert!(seen.diff("a", vec!["1", "2"], id).is_baseline());
+        assert_eq!(seen.diff("a", vec!["1", "2", "3"], id).new_items(), ["3"]);
+        assert_eq!(seen.diff("a", vec!["3", "4"], id).new_items(), ["4"]);
+
+        // A source showing up later gets its own baseline.
+        assert!(seen.diff("b", vec!["1", "9"], id).is_baseline());
+
+        seen.retain(|source| source != "a");
+        assert!(seen.diff("a", vec!["4", "5"], id).is_baseline());
+    }
+}
@@ -0,0 +1,254 @@
+//! Client-side rate limiting
+//!
+//! Manifold rejects clients that send too many requests with `429 Too Many
+//! Requests`. A [`RateLimiter`] on the client spaces requests out before that
+//! happens. By default it keeps to [`MANIFOLD_REQUESTS_PER_MINUTE`], with
+//! separate budgets for reads and bets so a fast scan of the markets can't
+//! hold up a bot's orders; see [`RateLimitConfig`] and
+//! [`ManifoldClientBuilder::rate_limit`](crate::ManifoldClientBuilder::rate_limit).
+//!
+//! Manifold counts requests per IP address, but every client built gets its
+//! own budgets, each the full default. Several clients in one process (e.g.
+//! for several accounts) should share one with
+//! [`ManifoldClientBuilder::share_reads_with`](crate::ManifoldClientBuilder::share_reads_with),
+//! or split the limit between them, or they can go over it together.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+use crate::error::{ManifoldError, Result};
+
+/// Manifold's published limit, per IP address.
+pub const MANIFOLD_REQUESTS_PER_MINUTE: u32 = 500;
+
+/// Budgets of a [`RateLimiter`].
+///
+/// The default splits [`MANIFOLD_REQUESTS_PER_MINUTE`] into 400 reads and 100 bets.
+/// Both budgets must be at least 1; turn the limit off with
+/// [`without_rate_limit`](crate::ManifoldClientBuilder::without_rate_limit) instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// `GET` requests per minute.
+    pub reads_per_minute: u32,
+    /// Bets, sales, cancellations and every other request that changes data, per minute.
+    pub bets_per_minute: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            reads_per_minute: 400,
+            bets_per_minute: MANIFOLD_REQUESTS_PER_MINUTE - 400,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Fails with [`ManifoldError::ConfigError`] if a budget is 0.
+    pub fn validate(&self) -> Result<()> {
+        for (name, budget) in [
+            ("reads_per_minute", self.reads_per_minute),
+            ("bets_per_minute", self.bets_per_minute),
+        ] {
+            if budget == 0 {
+                return Err(ManifoldError::ConfigError(format!(
+                    "{name} is 0; a rate limit budget must be at least 1"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The client's rate limit: a [`TokenBucket`] for reads, and one for bets
+/// unless both share a budget.
+#[derive(Debug)]
+pub struct RateLimiter {
+    reads: TokenBucket,
+    bets: Option<TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            reads: TokenBucket::per_minute(config.reads_per_minute),
+            bets: Some(TokenBucket::per_minute(config.bets_per_minute)),
+        }
+    }
+
+    /// One budget of `requests` per minute for every request.
+    pub fn shared(requests: u32) -> RateLimiter {
+        RateLimiter {
+            reads: TokenBucket::per_minute(requests),
+            bets: None,
+        }
+    }
+
+    /// The budget requests with `method` count against.
+    pub fn bucket(&self, method: &Method) -> &TokenBucket {
+        match &self.bets {
+            Some(bets) if method != Method::GET => bets,
+            _ => &self.reads,
+        }
+    }
+
+    /// Waits on `clock` until a request with `method` may be sent.
+    pub async fn acquire(&self, method: &Method, clock: &dyn Clock) {
+        self.bucket(method).acquire(clock).await
+    }
+}
+
+/// Allows bursts of up to `capacity` requests, refilling at a constant rate.
+///
+/// A bucket that doesn't refill (`per_second` of 0 or less) never makes
+/// requests wait, so it doesn't limit anything.
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    /// Negative when requests are queued up waiting for tokens.
+    tokens: f64,
+    /// `None` until the first request, with the bucket full.
+    updated: Option<DateTime<Utc>>,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, per_second: f64) -> TokenBucket {
+        TokenBucket {
+            capacity: f64::from(capacity.max(1)),
+            per_second,
+            state: Mutex::new(BucketState {
+                tokens: f64::from(capacity.max(1)),
+                updated: None,
+            }),
+        }
+    }
+
+    /// `requests` per minute, all of which can be used in a burst.
+    pub fn per_minute(requests: u32) -> TokenBucket {
+        TokenBucket::new(requests, f64::from(requests) / 60.0)
+    }
+
+    /// Takes a token at `now`, returning how long to wait before it can be used.
+    pub fn reserve(&self, now: DateTime<Utc>) -> Duration {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(updated) = state.updated {
+            let elapsed = (now - updated).to_std().unwrap_or_default();
+            let refilled = elapsed.as_secs_f64() * self.per_second;
+            state.tokens = (state.tokens + refilled).min(self.capacity);
+        }
+        state.updated = Some(now);
+
+        state.tokens -= 1.0;
+
+        if state.tokens >= 0.0 || self.per_second <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.per_second)
+        }
+    }
+
+    /// Waits on `clock` until a request may be sent.
+    pub async fn acquire(&self, clock: &dyn Clock) {
+        let wait = self.reserve(clock.now());
+
+        if !wait.is_zero() {
+            clock.sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::{ManifoldAuthorization, ManifoldClient};
+
+    #[test]
+    fn spaces_requests_after_a_burst() {
+        let bucket = TokenBucket::per_minute(2);
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::from_secs(30));
+
+        // Queued behind the previous one.
+        assert_eq!(bucket.reserve(now), Duration::from_secs(60));
+
+        // Refilled by then.
+        let later = now + chrono::Duration::minutes(2);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn waits_on_the_clock() {
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        let limiter = RateLimiter::shared(1);
+
+        limiter.acquire(&Method::GET, &clock).await;
+        limiter.acquire(&Method::POST, &clock).await;
+        limiter.acquire(&Method::GET, &clock).await;
+
+        assert_eq!(clock.sleeps(), [Duration::from_secs(60); 2]);
+    }
+
+    #[test]
+    fn bets_have_their_own_budget() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let limiter = RateLimiter::new(RateLimitConfig {
+            reads_per_minute: 1,
+            bets_per_minute: 1,
+        });
+
+        assert_eq!(limiter.bucket(&Method::GET).reserve(now), Duration::ZERO);
+        assert!(limiter.bucket(&Method::GET).reserve(now) > Duration::ZERO);
+        assert_eq!(limiter.bucket(&Method::POST).reserve(now), Duration::ZERO);
+
+        let shared = RateLimiter::shared(1);
+        assert_eq!(shared.bucket(&Method::GET).reserve(now), Duration::ZERO);
+        assert!(shared.bucket(&Method::POST).reserve(now) > Duration::ZERO);
+    }
+
+    #[test]
+    fn rejects_empty_budgets() {
+        let builder = ManifoldClient::builder(ManifoldAuthorization::NoAuthorization);
+
+        assert!(RateLimitConfig::default().validate().is_ok());
+
+        for result in [
+            builder
+                .clone()
+                .rate_limit(RateLimitConfig {
+                    reads_per_minute: 0,
+                    ..Default::default()
+                })
+                .build(),
+            builder
+                .clone()
+                .rate_limit(RateLimitConfig {
+                    bets_per_minute: 0,
+                    ..Default::default()
+                })
+                .build(),
+            builder.requests_per_minute(0).build(),
+        ] {
+            assert!(matches!(result, Err(ManifoldError::ConfigError(_))));
+        }
+    }
+}
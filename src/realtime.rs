@@ -0,0 +1,566 @@
+//! Live updates over Manifold's websocket API
+//!
+//! Polling [`ManifoldClient::stream_bets`] is slow and spends the rate limit
+//! on empty pages. A [`Realtime`] connection instead subscribes to
+//! [`Topic`]s and yields what the server pushes as [`RealtimeEvent`]s:
+//!
+//! ```no_run
+//! # use futures_util::TryStreamExt;
+//! # async fn example(client: manifold_markets::ManifoldClient) -> manifold_markets::error::Result<()> {
+//! let mut bets = Box::pin(client.realtime().bets(Some("marketId")));
+//!
+//! while let Some(bet) = bets.try_next().await? {
+//!     println!("{} bet M${}", bet.user_id(), bet.amount());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The websocket URL follows the client's [base URL](crate::ManifoldClientBuilder::base)
+//! (see [`realtime_url`]), so a client pointed at dev or a local server
+//! listens there too.
+//!
+//! A dropped connection is reopened after
+//! [`reconnect_delay`](Realtime::reconnect_delay) and the topics are
+//! subscribed to again, as is one that doesn't answer a ping within
+//! [`ping_interval`](Realtime::ping_interval). Whatever happened while
+//! disconnected is missed, so bots that can't miss anything should catch
+//! up with the REST endpoints.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use futures_util::{stream, SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::error::{ManifoldError, Result};
+use crate::types::*;
+use crate::ManifoldClient;
+
+pub const REALTIME_URL: &str = "wss://api.manifold.markets/ws";
+
+/// The websocket URL for an API base URL: `ws(s)://` on the same host, at
+/// `/ws`, with Manifold's own hosts moved to their `api.` subdomain.
+///
+/// `https://manifold.markets/api/v0` gives [`REALTIME_URL`].
+pub fn realtime_url(base: &str) -> Result<String> {
+    let mut url = reqwest::Url::parse(base)
+        .map_err(|e| ManifoldError::ConfigError(format!("invalid base URL {base:?}: {e}")))?;
+
+    let scheme = match url.scheme() {
+        "https" => "wss",
+        _ => "ws",
+    };
+    let host = match url.host_str() {
+        Some(host) if host.ends_with("manifold.markets") && !host.starts_with("api.") => {
+            Some(format!("api.{host}"))
+        }
+        _ => None,
+    };
+
+    let invalid =
+        || ManifoldError::ConfigError(format!("can't derive a websocket URL from {base:?}"));
+    url.set_scheme(scheme).map_err(|_| invalid())?;
+    if let Some(host) = host {
+        url.set_host(Some(&host)).map_err(|_| invalid())?;
+    }
+    url.set_path("/ws");
+    url.set_query(None);
+
+    Ok(url.to_string())
+}
+
+type Connection = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// What to subscribe to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Topic {
+    /// Bets on any market.
+    NewBets,
+    /// Comments on any market.
+    NewComments,
+    /// Markets as they're created.
+    NewMarkets,
+    /// Changes to any market.
+    MarketUpdates,
+    /// Bets on a market, by id.
+    MarketBets(String),
+    /// Comments on a market, by id.
+    MarketComments(String),
+    /// Changes to a market (probability, volume, ...), by id.
+    Market(String),
+}
+
+impl Topic {
+    /// The topic's name in the websocket protocol, e.g. `contract/[id]/new-bet`.
+    pub fn name(&self) -> String {
+        match self {
+            Topic::NewBets => "global/new-bet".to_owned(),
+            Topic::NewComments => "global/new-comment".to_owned(),
+            Topic::NewMarkets => "global/new-contract".to_owned(),
+            Topic::MarketUpdates => "global/updated-contract".to_owned(),
+            Topic::MarketBets(id) => format!("contract/{id}/new-bet"),
+            Topic::MarketComments(id) => format!("contract/{id}/new-comment"),
+            Topic::Market(id) => format!("contract/{id}"),
+        }
+    }
+}
+
+/// The changed fields of a market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketUpdate {
+    pub market_id: String,
+    /// Only the fields that changed, in the API's naming.
+    pub changes: Value,
+}
+
+impl MarketUpdate {
+    /// The new probability of a binary market, if it changed.
+    pub fn probability(&self) -> Option<f64> {
+        number_f64(&self.changes["prob"]).or_else(|| number_f64(&self.changes["probability"]))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RealtimeEvent {
+    /// Bets placed together, e.g. a bet and the limit orders it filled.
+    NewBets {
+        bets: Vec<Bet>,
+    },
+    NewComment {
+        comment: Comment,
+    },
+    NewMarket {
+        market: FullMarket,
+    },
+    MarketUpdate(MarketUpdate),
+    /// A topic this module doesn't know the data of.
+    Other {
+        topic: String,
+        data: Value,
+    },
+}
+
+/// The event in a message from the server; `None` for acknowledgements and pongs.
+///
+/// Fails on a rejected subscription or a malformed message.
+pub fn parse_message(text: &str) -> Result<Option<RealtimeEvent>> {
+    let message: Value = serde_json::from_str(text)?;
+    let malformed = |reason: &str| {
+        ManifoldError::SchemaError(format!("realtime message {reason}"), Some(message.clone()))
+    };
+
+    match message["type"].as_str() {
+        Some("ack") if message["success"] == json!(false) => Err(ManifoldError::Other(
+            message["error"]
+                .as_str()
+                .unwrap_or("subscription rejected")
+                .to_owned(),
+        )),
+        Some("broadcast") => {
+            let topic = message["topic"]
+                .as_str()
+                .ok_or_else(|| malformed("has no topic"))?;
+            let data = &message["data"];
+
+            let event = if topic.ends_with("/new-bet") {
+                RealtimeEvent::NewBets {
+                    bets: serde_json::from_value(data["bets"].clone())
+                        .map_err(|_| malformed("has no bets"))?,
+                }
+            } else if topic.ends_with("/new-comment") {
+                RealtimeEvent::NewComment {
+                    comment: Comment(data["comment"].clone()),
+                }
+            } else if topic == "global/new-contract" {
+                RealtimeEvent::NewMarket {
+                    market: FullMarket(data["contract"].clone()),
+                }
+            } else if topic == "global/updated-contract"
+                || topic
+                    .strip_prefix("contract/")
+                    .is_some_and(|rest| !rest.contains('/'))
+            {
+                let market_id = data["contract"]["id"]
+                    .as_str()
+                    .or_else(|| topic.strip_prefix("contract/"))
+                    .ok_or_else(|| malformed("has no market id"))?;
+
+                RealtimeEvent::MarketUpdate(MarketUpdate {
+                    market_id: market_id.to_owned(),
+                    changes: data["contract"].clone(),
+                })
+            } else {
+                RealtimeEvent::Other {
+                    topic: topic.to_owned(),
+                    data: data.clone(),
+                }
+            };
+
+            Ok(Some(event))
+        }
+        Some(_) => Ok(None),
+        None => Err(malformed("has no type")),
+    }
+}
+
+/// A subscription to Manifold's websocket API, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Realtime<'a> {
+    client: &'a ManifoldClient,
+    /// `None` for the one derived from the client's base URL.
+    url: Option<String>,
+    reconnect_delay: Duration,
+    ping_interval: Duration,
+}
+
+impl ManifoldClient {
+    /// Live updates, see [`crate::realtime`].
+    pub fn realtime(&self) -> Realtime<'_> {
+        Realtime {
+            client: self,
+            url: None,
+            reconnect_delay: Duration::from_secs(5),
+            ping_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+struct State {
+    connection: Option<Connection>,
+    connected_before: bool,
+    /// Whether a ping went out and nothing came back since.
+    ping_unanswered: bool,
+    txid: u64,
+    pending: VecDeque<Result<RealtimeEvent>>,
+}
+
+impl<'a> Realtime<'a> {
+    /// The websocket URL, [derived](realtime_url) from the client's base URL by default.
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_owned());
+        self
+    }
+
+    /// How long to wait before reconnecting, 5 seconds by default.
+    pub fn reconnect_delay(mut self, delay: Duration) -> Self {
+        self.reconnect_delay = delay;
+        self
+    }
+
+    /// Ping the server after this long without a message, 30 seconds by
+    /// default, and reconnect if nothing comes back for as long again.
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+
+    /// Everything published on `topics`.
+    ///
+    /// Never ends; connection errors are yielded and followed by a reconnect.
+    pub fn subscribe(self, topics: Vec<Topic>) -> impl Stream<Item = Result<RealtimeEvent>> + 'a {
+        let state = State {
+            connection: None,
+            connected_before: false,
+            ping_unanswered: false,
+            txid: 0,
+            pending: VecDeque::new(),
+        };
+
+        stream::unfold(state, move |mut state| {
+            let this = self.clone();
+            let topics = topics.clone();
+
+            async move {
+                loop {
+                    if let Some(item) = state.pending.pop_front() {
+                        return Some((item, state));
+                    }
+
+                    if let Err(e) = this.poll(&topics, &mut state).await {
+                        state.connection = None;
+                        state.pending.push_back(Err(e));
+                    }
+                }
+            }
+        })
+    }
+
+    /// Reads one message into `state.pending`, (re)connecting first if needed.
+    async fn poll(&self, topics: &[Topic], state: &mut State) -> Result<()> {
+        let Some(connection) = state.connection.as_mut() else {
+            if state.connected_before {
+                self.client.clock().sleep(self.reconnect_delay).await;
+            }
+            state.connected_before = true;
+
+            let url = match &self.url {
+                Some(url) => url.clone(),
+                None => realtime_url(&self.client.url(""))?,
+            };
+            let (mut connection, _) = tokio_tungstenite::connect_async(&url).await?;
+            state.ping_unanswered = false;
+
+            state.txid += 1;
+            let subscribe = json!({
+                "type": "subscribe",
+                "txid": state.txid,
+                "topics": topics.iter().map(Topic::name).collect::<Vec<_>>(),
+            });
+            connection
+                .send(Message::Text(subscribe.to_string()))
+                .await?;
+
+            state.connection = Some(connection);
+            return Ok(());
+        };
+
+        let message = match tokio::time::timeout(self.ping_interval, connection.next()).await {
+            Ok(Some(message)) => message?,
+            Ok(None) => {
+                state.connection = None;
+                return Ok(());
+            }
+            Err(_) if state.ping_unanswered => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("no answer to a ping within {:?}", self.ping_interval),
+                )
+                .into());
+            }
+            Err(_) => {
+                state.ping_unanswered = true;
+                state.txid += 1;
+                let ping = json!({"type": "ping", "txid": state.txid});
+                connection.send(Message::Text(ping.to_string())).await?;
+                return Ok(());
+            }
+        };
+
+        state.ping_unanswered = false;
+
+        match message {
+            Message::Text(text) => {
+                if let Some(event) = parse_message(&text).transpose() {
+                    state.pending.push_back(event);
+                }
+            }
+            Message::Close(_) => state.connection = None,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// New bets, on `market_id` or on any market.
+    pub fn bets(self, market_id: Option<&str>) -> impl Stream<Item = Result<Bet>> + 'a {
+        let topic = match market_id {
+            Some(id) => Topic::MarketBets(id.to_owned()),
+            None => Topic::NewBets,
+        };
+
+        self.subscribe(vec![topic]).flat_map(|event| {
+            let bets = match event {
+                Ok(RealtimeEvent::NewBets { bets }) => bets.into_iter().map(Ok).collect(),
+                Ok(_) => vec![],
+                Err(e) => vec![Err(e)],
+            };
+
+            stream::iter(bets)
+        })
+    }
+
+    /// New comments, on `market_id` or on any market.
+    pub fn comments(self, market_id: Option<&str>) -> impl Stream<Item = Result<Comment>> + 'a {
+        let topic = match market_id {
+            Some(id) => Topic::MarketComments(id.to_owned()),
+            None => Topic::NewComments,
+        };
+
+        self.subscribe(vec![topic]).filter_map(|event| async move {
+            match event {
+                Ok(RealtimeEvent::NewComment { comment }) => Some(Ok(comment)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+    }
+
+    /// Changes to `market_id`, or to any market.
+    pub fn market_updates(
+        self,
+        market_id: Option<&str>,
+    ) -> impl Stream<Item = Result<MarketUpdate>> + 'a {
+        let topic = match market_id {
+            Some(id) => Topic::Market(id.to_owned()),
+            None => Topic::MarketUpdates,
+        };
+
+        self.subscribe(vec![topic]).filter_map(|event| async move {
+            match event {
+                Ok(RealtimeEvent::MarketUpdate(update)) => Some(Ok(update)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+    use super::*;
+    use crate::ManifoldAuthorization;
+
+    fn broadcast(topic: &str, data: Value) -> String {
+        json!({"type": "broadcast", "topic": topic, "data": data}).to_string()
+    }
+
+    #[test]
+    fn parses_broadcasts() -> Result<()> {
+        assert_eq!(
+            Topic::MarketBets("m".to_owned()).name(),
+            "contract/m/new-bet"
+        );
+
+        let bets = parse_message(&broadcast(
+            "contract/m/new-bet",
+            json!({"bets": [{"id": "b1"}, {"id": "b2"}]}),
+        ))?;
+        assert!(matches!(bets, Some(RealtimeEvent::NewBets { bets }) if bets.len() == 2));
+
+        let update = parse_message(&broadcast("contract/m", json!({"contract": {"prob": 0.4}})))?;
+        let Some(RealtimeEvent::MarketUpdate(update)) = update else {
+            panic!("not an update: {update:?}");
+        };
+        assert_eq!(
+            (update.market_id.as_str(), update.probability()),
+            ("m", Some(0.4))
+        );
+
+        let global = parse_message(&broadcast(
+            "global/updated-contract",
+            json!({"contract": {"id": "n", "volume": 10}}),
+        ))?;
+        assert!(matches!(global, Some(RealtimeEvent::MarketUpdate(u)) if u.market_id == "n"));
+
+        let orders = parse_message(&broadcast("contract/m/orders", json!({})))?;
+        assert!(matches!(orders, Some(RealtimeEvent::Other { .. })));
+
+        assert!(parse_message(r#"{"type": "ack", "txid": 1, "success": true}"#)?.is_none());
+        assert!(parse_message(r#"{"type": "ack", "txid": 1, "success": false}"#).is_err());
+        assert_eq!(
+            parse_message(
+                r#"{"type": "ack", "txid": 1, "success": false, "error": "no such topic"}"#
+            )
+            .unwrap_err()
+            .to_string(),
+            "no such topic"
+        );
+        assert!(parse_message(&broadcast("contract/m/new-bet", json!({}))).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn derives_the_url_from_the_base() -> Result<()> {
+        assert_eq!(
+            realtime_url("https://manifold.markets/api/v0")?,
+            REALTIME_URL
+        );
+        assert_eq!(
+            realtime_url("https://dev.manifold.markets/api/v0")?,
+            "wss://api.dev.manifold.markets/ws"
+        );
+        assert_eq!(
+            realtime_url("http://127.0.0.1:8080/api/v0")?,
+            "ws://127.0.0.1:8080/ws"
+        );
+        assert!(realtime_url("not a url").is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resubscribes_after_silent_and_dropped_connections() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let (subscribed, mut subscribes) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            for round in 0.. {
+                let (stream, _) = listener.accept().await.unwrap();
+                let subscribed = subscribed.clone();
+
+                tokio::spawn(async move {
+                    let mut path = String::new();
+                    // The error type is tungstenite's.
+                    #[allow(clippy::result_large_err)]
+                    let record_path = |req: &Request, res: Response| {
+                        path = req.uri().path().to_owned();
+                        Ok(res)
+                    };
+                    let mut ws = tokio_tungstenite::accept_hdr_async(stream, record_path)
+                        .await
+                        .unwrap();
+
+                    let subscribe = ws.next().await.unwrap().unwrap();
+                    subscribed
+                        .send((path, subscribe.into_text().unwrap()))
+                        .unwrap();
+
+                    match round {
+                        // Reads everything, pings included, and never answers.
+                        0 => while let Some(Ok(_)) = ws.next().await {},
+                        1 => drop(ws),
+                        _ => {
+                            let bets = json!({"bets": [{"id": "b1"}]});
+                            ws.send(Message::Text(broadcast("contract/m/new-bet", bets)))
+                                .await
+                                .unwrap();
+                            while let Some(Ok(_)) = ws.next().await {}
+                        }
+                    }
+                });
+            }
+        });
+
+        let client = ManifoldClient::builder(ManifoldAuthorization::NoAuthorization)
+            .base(&format!("http://127.0.0.1:{port}/api/v0"))
+            .build()?;
+        let mut bets = Box::pin(
+            client
+                .realtime()
+                .reconnect_delay(Duration::ZERO)
+                .ping_interval(Duration::from_millis(100))
+                .bets(Some("m")),
+        );
+
+        let bet = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let Ok(bet) = bets.next().await.unwrap() {
+                    return bet;
+                }
+            }
+        })
+        .await
+        .expect("no bet after reconnecting");
+        assert_eq!(bet.id(), "b1");
+
+        for _ in 0..3 {
+            let (path, subscribe) = subscribes.recv().await.unwrap();
+            let subscribe: Value = serde_json::from_str(&subscribe)?;
+
+            assert_eq!(path, "/ws");
+            assert_eq!(subscribe["topics"], json!(["contract/m/new-bet"]));
+        }
+
+        Ok(())
+    }
+}
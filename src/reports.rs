@@ -0,0 +1,375 @@
+//! Reports that aggregate API data the way creators and traders ask for it
+//!
+//! The API only exposes raw records (transactions, bets); these helpers
+//! fetch and sum them up, optionally by [category](crate::classify).
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike};
+use futures_util::TryStreamExt;
+
+use crate::classify::{Classifier, UNCLASSIFIED};
+use crate::error::Result;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// Subsidy added to and withdrawn from markets, in mana.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SubsidySpend {
+    pub added: f64,
+    pub removed: f64,
+}
+
+impl SubsidySpend {
+    /// Mana still spent after withdrawals.
+    pub fn net(&self) -> f64 {
+        self.added - self.removed
+    }
+}
+
+/// Subsidies one user has put into markets, see [`ManifoldClient::subsidy_report`].
+#[derive(Debug, Clone, Default)]
+pub struct SubsidyReport {
+    pub user_id: String,
+    /// Keyed by market id.
+    pub per_market: BTreeMap<String, SubsidySpend>,
+    /// Keyed by `YYYY-MM`, in UTC.
+    pub per_month: BTreeMap<String, SubsidySpend>,
+}
+
+impl SubsidyReport {
+    pub fn new(user_id: &str) -> SubsidyReport {
+        SubsidyReport {
+            user_id: user_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds a transaction to the report.
+    ///
+    /// Counts `ADD_SUBSIDY` from the user to a market and `REMOVE_SUBSIDY` back
+    /// from a market to the user; returns whether `txn` was one of those.
+    pub fn record(&mut self, txn: &Txn) -> bool {
+        let (market_id, added) = match txn.category() {
+            "ADD_SUBSIDY" if txn.from_id() == self.user_id => (txn.to_id(), true),
+            "REMOVE_SUBSIDY" if txn.to_id() == self.user_id => (txn.from_id(), false),
+            _ => return false,
+        };
+
+        for spend in [
+            self.per_market.entry(market_id.to_owned()).or_default(),
+            self.per_month.entry(month_of(txn)).or_default(),
+        ] {
+            if added {
+                spend.added += txn.amount();
+            } else {
+                spend.removed += txn.amount();
+            }
+        }
+
+        true
+    }
+
+    pub fn total(&self) -> SubsidySpend {
+        self.per_market
+            .values()
+            .fold(SubsidySpend::default(), |total, spend| SubsidySpend {
+                added: total.added + spend.added,
+                removed: total.removed + spend.removed,
+            })
+    }
+}
+
+/// Mana a creator earned and spent on markets, see [`CreatorReport`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CreatorIncome {
+    /// Paid to create markets (`CREATE_CONTRACT_ANTE`).
+    pub creation_fees: f64,
+    /// `UNIQUE_BETTOR_BONUS`es for new traders on the creator's markets.
+    pub bettor_bonuses: f64,
+    /// The creator's share of trading fees, see [`CREATOR_FEE_CATEGORIES`].
+    pub creator_fees: f64,
+}
+
+impl CreatorIncome {
+    /// Bonuses and fees earned, minus creation fees.
+    pub fn net(&self) -> f64 {
+        self.bettor_bonuses + self.creator_fees - self.creation_fees
+    }
+}
+
+/// Transaction categories that pay creators their share of trading fees.
+///
+/// Older markets paid them out on resolution, newer ones as trades happen.
+pub const CREATOR_FEE_CATEGORIES: [&str; 2] = ["CONTRACT_RESOLUTION_FEE", "CREATOR_FEE"];
+
+/// A creator's income from their markets, see [`ManifoldClient::creator_report`].
+#[derive(Debug, Clone, Default)]
+pub struct CreatorReport {
+    pub user_id: String,
+    /// Keyed by market id.
+    pub per_market: BTreeMap<String, CreatorIncome>,
+    /// Keyed by `YYYY-MM`, in UTC.
+    pub per_month: BTreeMap<String, CreatorIncome>,
+}
+
+impl CreatorReport {
+    pub fn new(user_id: &str) -> CreatorReport {
+        CreatorReport {
+            user_id: user_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds a transaction to the report.
+    ///
+    /// Counts creation fees paid by the user, and bonuses and creator fees
+    /// paid to them; returns whether `txn` was one of those.
+    pub fn record(&mut self, txn: &Txn) -> bool {
+        let category = txn.category();
+
+        let field: fn(&mut CreatorIncome) -> &mut f64 = match category {
+            "CREATE_CONTRACT_ANTE" if txn.from_id() == self.user_id => |i| &mut i.creation_fees,
+            "UNIQUE_BETTOR_BONUS" if txn.to_id() == self.user_id => |i| &mut i.bettor_bonuses,
+            _ if CREATOR_FEE_CATEGORIES.contains(&category) && txn.to_id() == self.user_id => {
+                |i| &mut i.creator_fees
+            }
+            _ => return false,
+        };
+
+        let Some(market_id) = txn.contract_id() else {
+            return false;
+        };
+
+        for income in [
+            self.per_market.entry(market_id.to_owned()).or_default(),
+            self.per_month.entry(month_of(txn)).or_default(),
+        ] {
+            *field(income) += txn.amount();
+        }
+
+        true
+    }
+
+    pub fn total(&self) -> CreatorIncome {
+        self.per_market
+            .values()
+            .fold(CreatorIncome::default(), |total, income| CreatorIncome {
+                creation_fees: total.creation_fees + income.creation_fees,
+                bettor_bonuses: total.bettor_bonuses + income.bettor_bonuses,
+                creator_fees: total.creator_fees + income.creator_fees,
+            })
+    }
+}
+
+/// `YYYY-MM` of a transaction, in UTC.
+fn month_of(txn: &Txn) -> String {
+    DateTime::from_timestamp_millis(txn.created_time())
+        .map(|t| format!("{:04}-{:02}", t.year(), t.month()))
+        .unwrap_or_default()
+}
+
+/// Activity in one category, see [`activity_by_category`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CategoryActivity {
+    pub markets: usize,
+    pub bets: usize,
+    /// Mana traded, counting sales as positive.
+    pub volume: f64,
+}
+
+/// Breaks `markets` and the `bets` on them down by the category `classifier` assigns.
+///
+/// Unclassified markets are counted under [`UNCLASSIFIED`]; bets on markets
+/// not in `markets` are ignored.
+pub fn activity_by_category<M: Market>(
+    classifier: &impl Classifier,
+    markets: &[M],
+    bets: &[Bet],
+) -> BTreeMap<String, CategoryActivity> {
+    let mut categories = BTreeMap::<String, CategoryActivity>::new();
+    let mut market_categories = std::collections::HashMap::new();
+
+    for market in markets {
+        let category = classifier
+            .classify(market)
+            .unwrap_or_else(|| UNCLASSIFIED.to_owned());
+
+        categories.entry(category.clone()).or_default().markets += 1;
+        market_categories.insert(market.id(), category);
+    }
+
+    for bet in bets {
+        if let Some(category) = market_categories.get(bet.contract_id()) {
+            let activity = categories.get_mut(category).expect("category was inserted");
+
+            activity.bets += 1;
+            activity.volume += bet.amount().abs();
+        }
+    }
+
+    categories
+}
+
+impl ManifoldClient {
+    /// Sums up the liquidity subsidies `user_id` has added to markets, per market and per month.
+    ///
+    /// Goes through the user's whole transaction history (see [`stream_txns`](Self::stream_txns)),
+    /// which may take a while for active creators.
+    pub async fn subsidy_report(&self, user_id: &str) -> Result<SubsidyReport> {
+        let mut report = SubsidyReport::new(user_id);
+
+        let mut added = Box::pin(self.stream_txns(Some(user_id), None, Some("ADD_SUBSIDY")));
+        while let Some(txn) = added.try_next().await? {
+            report.record(&txn);
+        }
+
+        let mut removed = Box::pin(self.stream_txns(None, Some(user_id), Some("REMOVE_SUBSIDY")));
+        while let Some(txn) = removed.try_next().await? {
+            report.record(&txn);
+        }
+
+        Ok(report)
+    }
+
+    /// Sums up what `user_id` earned from their markets, and paid to create
+    /// them, per market and per month; see [`CreatorReport`].
+    ///
+    /// Like [`subsidy_report`](Self::subsidy_report), goes through the user's
+    /// whole history of these transactions.
+    pub async fn creator_report(&self, user_id: &str) -> Result<CreatorReport> {
+        let mut report = CreatorReport::new(user_id);
+
+        let mut antes =
+            Box::pin(self.stream_txns(Some(user_id), None, Some("CREATE_CONTRACT_ANTE")));
+        while let Some(txn) = antes.try_next().await? {
+            report.record(&txn);
+        }
+
+        for category in ["UNIQUE_BETTOR_BONUS"]
+            .into_iter()
+            .chain(CREATOR_FEE_CATEGORIES)
+        {
+            let mut income = Box::pin(self.stream_txns(None, Some(user_id), Some(category)));
+            while let Some(txn) = income.try_next().await? {
+                report.record(&txn);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn txn(category: &str, from: &str, to: &str, amount: f64, created: i64) -> Txn {
+        Txn(json!({
+            "id": "txn",
+            "category": category,
+            "fromId": from,
+            "toId": to,
+            "amount": amount,
+            "createdTime": created,
+        }))
+    }
+
+    #[test]
+    fn breaks_down_by_category() {
+        let markets = [
+            LiteMarket(json!({"id": "m1", "question": "Who wins the election?"})),
+            LiteMarket(json!({"id": "m2", "question": "Will it snow?"})),
+        ];
+        let bets = [
+            Bet(json!({"contractId": "m1", "amount": 10})),
+            Bet(json!({"contractId": "m1", "amount": -4})),
+            Bet(json!({"contractId": "elsewhere", "amount": 1})),
+        ];
+
+        let classifier = crate::classify::RulesClassifier::with_default_rules();
+        let activity = activity_by_category(&classifier, &markets, &bets);
+
+        assert_eq!(
+            activity["politics"],
+            CategoryActivity {
+                markets: 1,
+                bets: 2,
+                volume: 14.0
+            }
+        );
+        assert_eq!(activity[UNCLASSIFIED].markets, 1);
+    }
+
+    #[test]
+    fn aggregates_subsidies() {
+        // 2024-01-15 and 2024-02-15
+        let (jan, feb) = (1_705_276_800_000, 1_707_955_200_000);
+
+        let mut report = SubsidyReport::new("me");
+
+        assert!(report.record(&txn("ADD_SUBSIDY", "me", "m1", 100.0, jan)));
+        assert!(report.record(&txn("ADD_SUBSIDY", "me", "m2", 50.0, feb)));
+        assert!(report.record(&txn("REMOVE_SUBSIDY", "m1", "me", 30.0, feb)));
+        assert!(!report.record(&txn("ADD_SUBSIDY", "someone", "m1", 10.0, feb)));
+        assert!(!report.record(&txn("UNIQUE_BETTOR_BONUS", "bank", "me", 5.0, feb)));
+
+        assert_eq!(report.per_market["m1"].net(), 70.0);
+        assert_eq!(report.per_month["2024-01"].added, 100.0);
+        assert_eq!(
+            report.per_month["2024-02"],
+            SubsidySpend {
+                added: 50.0,
+                removed: 30.0
+            }
+        );
+        assert_eq!(report.total().net(), 120.0);
+    }
+
+    #[test]
+    fn aggregates_creator_income() {
+        let (jan, feb) = (1_705_276_800_000, 1_707_955_200_000);
+
+        let with_market = |mut txn: Txn, market_id: &str| {
+            txn.0["data"] = json!({"contractId": market_id});
+            txn
+        };
+
+        let mut report = CreatorReport::new("me");
+
+        assert!(report.record(&with_market(
+            txn("CREATE_CONTRACT_ANTE", "me", "m1", 50.0, jan),
+            "m1"
+        )));
+        assert!(report.record(&with_market(
+            txn("UNIQUE_BETTOR_BONUS", "bank", "me", 5.0, jan),
+            "m1"
+        )));
+        assert!(report.record(&with_market(
+            txn("UNIQUE_BETTOR_BONUS", "bank", "me", 5.0, feb),
+            "m2"
+        )));
+        assert!(report.record(&with_market(
+            txn("CREATOR_FEE", "m1", "me", 12.5, feb),
+            "m1"
+        )));
+        assert!(!report.record(&with_market(
+            txn("UNIQUE_BETTOR_BONUS", "bank", "other", 5.0, feb),
+            "m1"
+        )));
+        assert!(!report.record(&txn("ADD_SUBSIDY", "me", "m1", 100.0, feb)));
+
+        assert_eq!(
+            report.per_market["m1"],
+            CreatorIncome {
+                creation_fees: 50.0,
+                bettor_bonuses: 5.0,
+                creator_fees: 12.5
+            }
+        );
+        assert_eq!(report.per_month["2024-02"].net(), 17.5);
+        assert_eq!(report.total().net(), -27.5);
+    }
+}
@@ -0,0 +1,283 @@
+//! Estimating when a market will actually resolve
+//!
+//! Mana in a position is locked until the market resolves, so comparing
+//! opportunities needs a resolution date. The close time is a good start,
+//! but questions often name an earlier date ("...before June 1, 2025?"),
+//! and some creators routinely resolve days or weeks after closing.
+//! [`estimate_resolution`] combines the three; the creator's lag comes from
+//! [`ManifoldClient::creator_resolution_lag`].
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures_util::{StreamExt, TryStreamExt};
+
+use crate::error::Result;
+use crate::streams::Paginator;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// How many of a creator's most recent markets [`ManifoldClient::creator_resolution_lag`] looks at.
+pub const LAG_SAMPLE: usize = 200;
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+fn month(token: &str) -> Option<u32> {
+    // Full names and abbreviations like "sep" or "sept".
+    MONTHS
+        .iter()
+        .position(|m| token.len() >= 3 && m.starts_with(token))
+        .map(|i| i as u32 + 1)
+}
+
+fn year(token: &str) -> Option<i32> {
+    token.parse().ok().filter(|y| (2015..=2100).contains(y))
+}
+
+fn day(token: &str) -> Option<u32> {
+    let digits = token.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    digits.parse().ok().filter(|d| (1..=31).contains(d))
+}
+
+fn last_day_of_month(year: i32, month: u32) -> Option<NaiveDate> {
+    let (year, month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+
+    NaiveDate::from_ymd_opt(year, month, 1)?.pred_opt()
+}
+
+/// The latest date a question mentions, as the end of that day (or month, quarter, year) in UTC.
+///
+/// Understands `2025-06-01`, `June 1, 2025`, `1 June 2025`, `June 2025`,
+/// `Q2 2025` and bare years like `by 2025`.
+pub fn extract_date(question: &str) -> Option<DateTime<Utc>> {
+    let mut dates = vec![];
+
+    for word in question.split_whitespace() {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+            dates.push(date);
+        }
+    }
+
+    let tokens = question
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    let token = |i: usize| tokens.get(i).map(String::as_str).unwrap_or_default();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let (date, used) = if let Some(m) = month(token(i)) {
+            match (day(token(i + 1)), year(token(i + 1)), year(token(i + 2))) {
+                // June 1, 2025
+                (Some(d), None, Some(y)) => (NaiveDate::from_ymd_opt(y, m, d), 3),
+                // June 2025
+                (_, Some(y), _) => (last_day_of_month(y, m), 2),
+                _ => (None, 1),
+            }
+        } else if let (Some(d), Some(m), Some(y)) =
+            (day(token(i)), month(token(i + 1)), year(token(i + 2)))
+        {
+            // 1 June 2025
+            (NaiveDate::from_ymd_opt(y, m, d), 3)
+        } else if let (Some(q), Some(y)) = (
+            token(i)
+                .strip_prefix('q')
+                .and_then(|q| q.parse::<u32>().ok())
+                .filter(|q| (1..=4).contains(q)),
+            year(token(i + 1)),
+        ) {
+            (last_day_of_month(y, q * 3), 2)
+        } else if let Some(y) = year(token(i)) {
+            (NaiveDate::from_ymd_opt(y, 12, 31), 1)
+        } else {
+            (None, 1)
+        };
+
+        dates.extend(date);
+        i += used;
+    }
+
+    dates
+        .into_iter()
+        .max()
+        .and_then(|date| date.and_hms_opt(23, 59, 59))
+        .map(|date| date.and_utc())
+}
+
+/// When `market` will probably resolve.
+///
+/// Resolved markets return their resolution time. Otherwise, this is the
+/// earlier of the close time and the date in the question (see
+/// [`extract_date`]), plus the creator's typical `lag` after that, but never
+/// in the past. `None` if the market has neither a close time nor a date.
+pub fn estimate_resolution(market: &impl Market, lag: Option<Duration>) -> Option<DateTime<Utc>> {
+    estimate_resolution_at(market, lag, Utc::now())
+}
+
+/// [`estimate_resolution`] as of `now`, e.g. [`ManifoldClient::now`].
+pub fn estimate_resolution_at(
+    market: &impl Market,
+    lag: Option<Duration>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    if market.is_resolved() {
+        return market.resolution_time();
+    }
+
+    let expected = match (market.close_time(), extract_date(market.question())) {
+        (Some(close), Some(date)) => close.min(date),
+        (close, date) => close.or(date)?,
+    };
+
+    Some((expected + lag.unwrap_or_else(Duration::zero)).max(now))
+}
+
+/// The median time between closing and resolving of `markets`.
+pub fn resolution_lag<M: Market>(markets: &[M]) -> Option<Duration> {
+    let mut lags = markets
+        .iter()
+        .filter(|m| m.is_resolved() && m.resolution() != Some("CANCEL"))
+        .filter_map(|m| Some(m.resolution_time()? - m.close_time()?))
+        // Resolving early just means the answer was known; it doesn't make later markets faster.
+        .map(|lag| lag.max(Duration::zero()))
+        .collect::<Vec<_>>();
+
+    if lags.is_empty() {
+        return None;
+    }
+
+    lags.sort();
+    Some(lags[lags.len() / 2])
+}
+
+impl ManifoldClient {
+    /// How long the creator usually takes to resolve a market after it closes,
+    /// from their [`LAG_SAMPLE`] most recent markets. See [`resolution_lag`].
+    pub async fn creator_resolution_lag(&self, creator_id: &str) -> Result<Option<Duration>> {
+        let markets: Vec<LiteMarket> = Paginator::cursor("/markets")
+            .param("userId", creator_id)
+            .stream(self)
+            .take(LAG_SAMPLE)
+            .try_collect()
+            .await?;
+
+        Ok(resolution_lag(&markets))
+    }
+
+    /// [`estimate_resolution`] using the creator's [lag](Self::creator_resolution_lag)
+    /// and the client's [clock](Self::clock).
+    pub async fn estimate_resolution(&self, market: &impl Market) -> Result<Option<DateTime<Utc>>> {
+        let lag = self.creator_resolution_lag(market.creator_id()).await?;
+
+        Ok(estimate_resolution_at(market, lag, self.now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::fixtures::{MockRequest, MockResponse, MockServer};
+
+    fn end_of(y: i32, m: u32, d: u32) -> Option<DateTime<Utc>> {
+        Some(Utc.with_ymd_and_hms(y, m, d, 23, 59, 59).unwrap())
+    }
+
+    #[test]
+    fn extracts_dates() {
+        assert_eq!(
+            extract_date("Will X happen before June 1, 2031?"),
+            end_of(2031, 6, 1)
+        );
+        assert_eq!(extract_date("By 3rd Sept 2031?"), end_of(2031, 9, 3));
+        assert_eq!(extract_date("GDP in Q1 2031"), end_of(2031, 3, 31));
+        assert_eq!(
+            extract_date("Released in February 2032?"),
+            end_of(2032, 2, 29)
+        );
+        assert_eq!(
+            extract_date("Between 2031-01-05 and end of 2033?"),
+            end_of(2033, 12, 31)
+        );
+        assert_eq!(extract_date("Will it rain tomorrow?"), None);
+        assert_eq!(extract_date("May I ask a question?"), None);
+    }
+
+    #[test]
+    fn estimates_resolution() {
+        let now = Utc.with_ymd_and_hms(2031, 1, 1, 0, 0, 0).unwrap();
+        let close = Utc.with_ymd_and_hms(2031, 12, 31, 0, 0, 0).unwrap();
+        let market = LiteMarket(json!({
+            "question": "Will X happen by March 2031?",
+            "closeTime": close.timestamp_millis(),
+        }));
+
+        assert_eq!(
+            estimate_resolution_at(&market, None, now),
+            end_of(2031, 3, 31)
+        );
+        assert_eq!(
+            estimate_resolution_at(&market, Some(Duration::days(1)), now),
+            end_of(2031, 4, 1)
+        );
+
+        // Never in the past.
+        let later = Utc.with_ymd_and_hms(2031, 6, 1, 0, 0, 0).unwrap();
+        assert_eq!(estimate_resolution_at(&market, None, later), Some(later));
+
+        let resolved = |close: i64, resolution: i64| {
+            LiteMarket(json!({
+                "isResolved": true,
+                "resolution": "YES",
+                "closeTime": close,
+                "resolutionTime": resolution,
+            }))
+        };
+        let day = Duration::days(1).num_milliseconds();
+
+        assert_eq!(
+            resolution_lag(&[resolved(0, 3 * day), resolved(0, day), resolved(5 * day, 0)]),
+            Some(Duration::days(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn estimates_by_the_clients_clock() -> Result<()> {
+        let server = MockServer::start(|req: &MockRequest| {
+            (req.path == "/markets").then(|| MockResponse::json(json!([])))
+        })
+        .await?;
+        let now = Utc.with_ymd_and_hms(2031, 6, 1, 0, 0, 0).unwrap();
+        let client = server.client().clock(MockClock::new(now)).build()?;
+
+        let market = LiteMarket(json!({
+            "question": "Will X happen by March 2031?",
+            "creatorId": "c",
+        }));
+
+        assert_eq!(client.estimate_resolution(&market).await?, Some(now));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+//! Retrying failed reads
+//!
+//! With a [`RetryPolicy`] on the client (see
+//! [`ManifoldClientBuilder::retry_policy`](crate::ManifoldClientBuilder::retry_policy)),
+//! `GET` requests that fail with a rate limit, a server error, a timeout or a
+//! connection failure (see [`ManifoldError::is_retryable`]) are sent again
+//! after an exponentially growing, jittered wait. A `Retry-After` from the
+//! server replaces the computed wait.
+//!
+//! Bets and every other request that changes data are never retried: one
+//! that timed out may still have gone through, and only the caller can tell
+//! whether sending it again is safe.
+
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::error::ManifoldError;
+
+/// When and how often to retry failed reads, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 turns retrying off.
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts. A longer `Retry-After` fails the
+    /// request instead of waiting.
+    pub max_backoff: Duration,
+    /// Each wait varies randomly by up to this fraction, so clients that
+    /// failed together don't all retry at the same moment.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    /// 3 retries, waiting about 0.5, 1 and 2 seconds.
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.25,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// No retries.
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// How long to wait before retry number `retry` (from 0) after `error`,
+    /// or `None` if the request shouldn't be retried.
+    pub fn delay(&self, retry: u32, error: &ManifoldError) -> Option<Duration> {
+        self.delay_with(retry, error, &mut rand::thread_rng())
+    }
+
+    fn delay_with(
+        &self,
+        retry: u32,
+        error: &ManifoldError,
+        rng: &mut impl Rng,
+    ) -> Option<Duration> {
+        if retry >= self.max_retries || !error.is_retryable() {
+            return None;
+        }
+
+        if let ManifoldError::RateLimited(_, Some(retry_after)) = error {
+            return (*retry_after <= self.max_backoff).then_some(*retry_after);
+        }
+
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+
+        if jitter == 0.0 {
+            return Some(backoff);
+        }
+
+        Some(
+            backoff
+                .mul_f64(1.0 + rng.gen_range(-jitter..=jitter))
+                .min(self.max_backoff),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    use chrono::{TimeZone, Utc};
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::error::Result;
+    use crate::fixtures::{self, MockRequest, MockResponse, MockServer};
+
+    #[test]
+    fn backs_off_exponentially() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let policy = RetryPolicy::default();
+        let server_error = ManifoldError::Server(503, "unavailable".to_owned());
+
+        for (retry, expected) in [(0, 500), (1, 1000), (2, 2000)] {
+            let delay = policy.delay_with(retry, &server_error, &mut rng).unwrap();
+            let expected = Duration::from_millis(expected);
+
+            assert!(delay >= expected.mul_f64(0.75) && delay <= expected.mul_f64(1.25));
+        }
+
+        assert_eq!(policy.delay_with(3, &server_error, &mut rng), None);
+
+        let policy = RetryPolicy {
+            max_retries: 20,
+            jitter: 0.0,
+            ..policy
+        };
+        assert_eq!(
+            policy.delay_with(15, &server_error, &mut rng),
+            Some(policy.max_backoff)
+        );
+    }
+
+    #[test]
+    fn honors_retry_after() {
+        let policy = RetryPolicy::default();
+
+        let error =
+            ManifoldError::RateLimited("slow down".to_owned(), Some(Duration::from_secs(7)));
+        assert_eq!(policy.delay(0, &error), Some(Duration::from_secs(7)));
+
+        let error =
+            ManifoldError::RateLimited("slow down".to_owned(), Some(Duration::from_secs(60)));
+        assert_eq!(policy.delay(0, &error), None);
+
+        assert!(policy
+            .delay(0, &ManifoldError::RateLimited("slow down".to_owned(), None))
+            .is_some());
+        assert_eq!(
+            policy.delay(0, &ManifoldError::NotFound("market".to_owned())),
+            None
+        );
+        assert_eq!(
+            RetryPolicy::none().delay(0, &ManifoldError::Server(500, String::new())),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_reads_but_not_writes() -> Result<()> {
+        // Every path fails once with a 503, then answers.
+        let attempts = Mutex::new(HashSet::new());
+        let server = MockServer::start(move |req: &MockRequest| {
+            if attempts.lock().unwrap().insert(req.path.clone()) {
+                return Some(MockResponse::status(503, json!({"message": "Unavailable"})));
+            }
+
+            match req.path.as_str() {
+                "/get-related-markets" => Some(MockResponse::json(json!([]))),
+                "/group/by-id/g/markets" => Some(MockResponse::json(json!([]))),
+                path => fixtures::api_response(&req.method, path).map(MockResponse::json),
+            }
+        })
+        .await?;
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        let client = server
+            .client()
+            .retry_policy(RetryPolicy::default())
+            .clock(clock)
+            .build()?;
+
+        assert!(client.get_group_markets("g").await?.is_empty());
+        assert!(client.get_related_markets("m", 5).await?.is_empty());
+
+        let error = client.close_market("m", None).await.unwrap_err();
+        assert!(matches!(error, ManifoldError::Server(503, _)));
+
+        let paths = server
+            .requests()
+            .into_iter()
+            .map(|req| req.path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "/group/by-id/g/markets",
+                "/group/by-id/g/markets",
+                "/get-related-markets",
+                "/get-related-markets",
+                "/market/m/close",
+            ]
+        );
+
+        Ok(())
+    }
+}
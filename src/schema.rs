@@ -0,0 +1,398 @@
+//! Fully-typed API objects
+//!
+//! The [`types`](crate::types) wrappers keep the API's JSON as is and read
+//! fields on access. The structs here parse it once instead: every field the
+//! API may leave out is an `Option`, so a missing field is a `None` rather
+//! than a panic in some accessor later on, and fields this crate doesn't know
+//! about yet end up in `extra` and are written back out unchanged. So do
+//! values of [`OutcomeType`] and [`Visibility`] it doesn't know, kept as
+//! their `Unknown` variant.
+//!
+//! They're a module of their own, next to rather than instead of the
+//! wrappers, since the client returns wrappers and their accessors are what
+//! most code uses.
+//!
+//! Convert between the two with `TryFrom`/`From`:
+//!
+//! ```
+//! # use manifold_markets::{schema, types};
+//! # let wrapper = types::Group(serde_json::json!({"id": "g", "slug": "s", "name": "G"}));
+//! let group = schema::Group::try_from(&wrapper)?;
+//! assert_eq!(group.slug.as_deref(), Some("s"));
+//!
+//! let wrapper: types::Group = group.into();
+//! # Ok::<(), manifold_markets::error::ManifoldError>(())
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{ManifoldError, Result};
+use crate::types::{self, OutcomeType, ProfitCached, Visibility};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_deposits: Option<f64>,
+    /// Milliseconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profit_cached: Option<ProfitCached>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Group {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_members: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A market as listed by `GET /v0/markets`, without answers and description.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiteMarket {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub question: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creator_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creator_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creator_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome_type: Option<OutcomeType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mechanism: Option<String>,
+    /// Binary markets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
+    /// Shares in the pool, by outcome.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<BTreeMap<String, f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_liquidity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f64>,
+    #[serde(rename = "volume24Hours")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume_24_hours: Option<f64>,
+    /// Milliseconds since the epoch, like the other times.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_time: Option<i64>,
+    #[serde(default)]
+    pub is_resolved: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+    #[serde(default)]
+    pub group_slugs: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An answer of a free response or multiple choice market.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Answer {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A market as returned by `GET /v0/market/[marketId]`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "LiteMarket", into = "LiteMarket")]
+pub struct FullMarket {
+    pub market: LiteMarket,
+    pub answers: Vec<Answer>,
+    /// TipTap JSON.
+    pub description: Option<Value>,
+    pub text_description: Option<String>,
+}
+
+// Parsed through `LiteMarket` rather than flattening it, which
+// `arbitrary-precision` numbers don't survive.
+impl TryFrom<LiteMarket> for FullMarket {
+    type Error = serde_json::Error;
+
+    fn try_from(mut market: LiteMarket) -> std::result::Result<Self, Self::Error> {
+        let mut take = |field: &str| market.extra.remove(field).unwrap_or(Value::Null);
+
+        let answers = take("answers");
+        let description = take("description");
+        let text_description = take("textDescription");
+
+        Ok(FullMarket {
+            answers: Option::<Vec<Answer>>::deserialize(answers)?.unwrap_or_default(),
+            description: Option::deserialize(description)?,
+            text_description: Option::deserialize(text_description)?,
+            market,
+        })
+    }
+}
+
+impl From<FullMarket> for LiteMarket {
+    fn from(full: FullMarket) -> LiteMarket {
+        let mut market = full.market;
+        let mut put = |field: &str, value: Value| {
+            if !value.is_null() {
+                market.extra.insert(field.to_owned(), value);
+            }
+        };
+
+        put(
+            "answers",
+            serde_json::to_value(full.answers).unwrap_or_default(),
+        );
+        put("description", full.description.unwrap_or_default());
+        put("textDescription", full.text_description.into());
+
+        market
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bet {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// `YES`, `NO`, or an answer id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub answer_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shares: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prob_before: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prob_after: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_prob: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_filled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_cancelled: Option<bool>,
+    /// Milliseconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_time: Option<i64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn parse<T: DeserializeOwned>(value: &Value, name: &str) -> Result<T> {
+    serde_json::from_value(value.clone()).map_err(|e| {
+        ManifoldError::SchemaError(format!("not a valid {name}: {e}"), Some(value.clone()))
+    })
+}
+
+fn unparse(typed: impl Serialize) -> Value {
+    // Maps with string keys and plain values always serialize.
+    serde_json::to_value(typed).unwrap_or_default()
+}
+
+impl TryFrom<&types::User> for User {
+    type Error = ManifoldError;
+
+    fn try_from(user: &types::User) -> Result<User> {
+        parse(&user.0, "User")
+    }
+}
+
+impl From<User> for types::User {
+    fn from(user: User) -> types::User {
+        types::User(unparse(user))
+    }
+}
+
+impl TryFrom<&types::Group> for Group {
+    type Error = ManifoldError;
+
+    fn try_from(group: &types::Group) -> Result<Group> {
+        parse(&group.0, "Group")
+    }
+}
+
+impl From<Group> for types::Group {
+    fn from(group: Group) -> types::Group {
+        types::Group(unparse(group))
+    }
+}
+
+impl TryFrom<&types::LiteMarket> for LiteMarket {
+    type Error = ManifoldError;
+
+    fn try_from(market: &types::LiteMarket) -> Result<LiteMarket> {
+        parse(&market.0, "LiteMarket")
+    }
+}
+
+impl From<LiteMarket> for types::LiteMarket {
+    fn from(market: LiteMarket) -> types::LiteMarket {
+        types::LiteMarket(unparse(market))
+    }
+}
+
+impl TryFrom<&types::FullMarket> for FullMarket {
+    type Error = ManifoldError;
+
+    fn try_from(market: &types::FullMarket) -> Result<FullMarket> {
+        parse(&market.0, "FullMarket")
+    }
+}
+
+impl From<FullMarket> for types::FullMarket {
+    fn from(market: FullMarket) -> types::FullMarket {
+        types::FullMarket(unparse(market))
+    }
+}
+
+impl TryFrom<&types::Bet> for Bet {
+    type Error = ManifoldError;
+
+    fn try_from(bet: &types::Bet) -> Result<Bet> {
+        parse(&bet.0, "Bet")
+    }
+}
+
+impl From<Bet> for types::Bet {
+    fn from(bet: Bet) -> types::Bet {
+        types::Bet(unparse(bet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn parses_and_round_trips_fixtures() -> Result<()> {
+        let market = fixtures::multi_market();
+        let typed = FullMarket::try_from(&market)?;
+
+        assert_eq!(
+            typed.market.question.as_deref(),
+            Some("Which color will the fixture be?")
+        );
+        assert_eq!(typed.market.mechanism.as_deref(), Some("cpmm-multi-1"));
+        assert_eq!(typed.answers.len(), 3);
+        assert_eq!(typed.answers[0].text.as_deref(), Some("Red"));
+        assert_eq!(
+            FullMarket::try_from(&types::FullMarket::from(typed.clone()))?,
+            typed
+        );
+
+        let binary = fixtures::binary_market();
+        let typed = LiteMarket::try_from(&types::LiteMarket(binary.0.clone()))?;
+        assert_eq!(typed.pool.as_ref().map(|pool| pool["YES"]), Some(120.0));
+        assert_eq!(typed.extra["coverImageUrl"], binary.0["coverImageUrl"]);
+        assert_eq!(
+            LiteMarket::try_from(&types::LiteMarket::from(typed.clone()))?,
+            typed
+        );
+
+        let user = User::try_from(&fixtures::user())?;
+        assert_eq!(User::try_from(&types::User::from(user.clone()))?, user);
+
+        let group = Group::try_from(&fixtures::group())?;
+        assert_eq!(Group::try_from(&types::Group::from(group.clone()))?, group);
+
+        let bet = fixtures::bet_with_fills();
+        let typed = Bet::try_from(&bet)?;
+        assert_eq!(typed.shares, Some(bet.shares()));
+        assert_eq!(Bet::try_from(&types::Bet::from(typed.clone()))?, typed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_fields_are_none() -> Result<()> {
+        let bet = Bet::try_from(&types::Bet(json!({"id": "b", "futureField": [1]})))?;
+
+        assert_eq!(bet.amount, None);
+        assert_eq!(bet.extra["futureField"], json!([1]));
+
+        assert!(matches!(
+            Bet::try_from(&types::Bet(json!({"amount": 10}))),
+            Err(ManifoldError::SchemaError(_, Some(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_unknown_types_and_visibilities() -> Result<()> {
+        let market = types::LiteMarket(json!({
+            "id": "m",
+            "outcomeType": "QUADRATIC_FUNDING",
+            "visibility": "hidden",
+        }));
+        let typed = LiteMarket::try_from(&market)?;
+
+        assert_eq!(
+            typed.outcome_type,
+            Some(OutcomeType::Unknown("QUADRATIC_FUNDING".to_owned()))
+        );
+        assert_eq!(
+            typed.visibility,
+            Some(Visibility::Unknown("hidden".to_owned()))
+        );
+        assert!(types::Market::try_outcome_type(&market).is_err());
+
+        // Written back out as the API sent them.
+        let written = types::LiteMarket::from(typed);
+        assert_eq!(written.0["outcomeType"], "QUADRATIC_FUNDING");
+        assert_eq!(written.0["visibility"], "hidden");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+//! Trading competition scoreboards
+//!
+//! Tournaments on Manifold usually run on a group's markets for a fixed
+//! period. [`build_scoreboard`] ranks traders by the profit of the bets they
+//! placed in that period, valued at resolution (or the current probability
+//! for open markets), breaking ties with [`TieBreak`] rules;
+//! [`ManifoldClient::group_scoreboard`] fetches everything it needs.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+
+use crate::error::Result;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// How to order traders with the same profit, applied in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+    /// More mana traded ranks first.
+    HigherVolume,
+    /// Fewer bets ranks first: the same profit with less trading.
+    FewerBets,
+    /// The earliest first bet ranks first.
+    EarlierStart,
+}
+
+/// One trader's line on a scoreboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    /// 1-based; traders tied on profit and every tie-break share a rank.
+    pub rank: usize,
+    pub user_id: String,
+    /// Realized and unrealized profit.
+    pub profit: f64,
+    /// Profit on markets that have resolved.
+    pub realized: f64,
+    /// Mana traded, counting sales as positive.
+    pub volume: f64,
+    pub bets: usize,
+    pub markets: usize,
+    pub first_bet_time: i64,
+}
+
+/// What one YES share of a bet's market (or answer) is worth, `None` if cancelled or unknown.
+fn yes_value(market: &impl Market, answer_id: Option<&str>) -> Option<f64> {
+    let (data, probability) = match answer_id {
+        Some(answer_id) => {
+            let answer = market.answers().into_iter().find(|a| a.id() == answer_id)?;
+            let probability = answer.probability();
+            (answer.0, probability)
+        }
+        None => (market.data().clone(), market.probability()),
+    };
+
+    match data["resolution"].as_str() {
+        Some("YES") => Some(1.0),
+        Some("NO") => Some(0.0),
+        Some("MKT") => number_f64(&data["resolutionProbability"]),
+        Some(_) => None,
+        None if market.is_resolved() && answer_id.is_none() => None,
+        None => probability,
+    }
+}
+
+fn compare(a: &Standing, b: &Standing, tie_breaks: &[TieBreak]) -> Ordering {
+    let mut ordering = b.profit.total_cmp(&a.profit);
+
+    for tie_break in tie_breaks {
+        ordering = ordering.then_with(|| match tie_break {
+            TieBreak::HigherVolume => b.volume.total_cmp(&a.volume),
+            TieBreak::FewerBets => a.bets.cmp(&b.bets),
+            TieBreak::EarlierStart => a.first_bet_time.cmp(&b.first_bet_time),
+        });
+    }
+
+    ordering
+}
+
+/// Ranks the traders of the `bets` placed between `start` and `end` on `markets`.
+///
+/// Only YES/NO shares are valued (including answers of multiple choice
+/// markets); bets on cancelled markets and on markets missing from `markets`
+/// are left out.
+pub fn build_scoreboard<M: Market>(
+    markets: &[M],
+    bets: &[Bet],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    tie_breaks: &[TieBreak],
+) -> Vec<Standing> {
+    let markets: HashMap<&str, &M> = markets.iter().map(|m| (m.id(), m)).collect();
+    let window = start.timestamp_millis()..end.timestamp_millis();
+
+    // (user, market, answer) -> (cost, YES shares, NO shares)
+    let mut positions = BTreeMap::<(&str, &str, Option<&str>), (f64, f64, f64)>::new();
+    let mut standings = BTreeMap::<&str, Standing>::new();
+
+    for bet in bets {
+        if !window.contains(&bet.created_time()) || !markets.contains_key(bet.contract_id()) {
+            continue;
+        }
+
+        let standing = standings.entry(bet.user_id()).or_insert_with(|| Standing {
+            rank: 0,
+            user_id: bet.user_id().to_owned(),
+            profit: 0.0,
+            realized: 0.0,
+            volume: 0.0,
+            bets: 0,
+            markets: 0,
+            first_bet_time: bet.created_time(),
+        });
+        standing.bets += 1;
+        standing.volume += bet.amount().abs();
+        standing.first_bet_time = standing.first_bet_time.min(bet.created_time());
+
+        let position = positions
+            .entry((bet.user_id(), bet.contract_id(), bet.answer_id()))
+            .or_default();
+        position.0 += bet.amount();
+
+        match bet.outcome() {
+            "YES" => position.1 += bet.shares(),
+            "NO" => position.2 += bet.shares(),
+            _ => {}
+        }
+    }
+
+    let mut traded = HashSet::new();
+
+    for ((user_id, market_id, answer_id), (cost, yes, no)) in positions {
+        let market = markets[market_id];
+        let Some(yes_value) = yes_value(market, answer_id) else {
+            continue;
+        };
+
+        let standing = standings.get_mut(user_id).expect("user has bets");
+        let profit = yes * yes_value + no * (1.0 - yes_value) - cost;
+
+        standing.profit += profit;
+        if market.is_resolved() {
+            standing.realized += profit;
+        }
+
+        if traded.insert((user_id, market_id)) {
+            standing.markets += 1;
+        }
+    }
+
+    let mut standings = standings.into_values().collect::<Vec<_>>();
+    standings.sort_by(|a, b| compare(a, b, tie_breaks));
+
+    for i in 0..standings.len() {
+        standings[i].rank =
+            if i > 0 && compare(&standings[i - 1], &standings[i], tie_breaks).is_eq() {
+                standings[i - 1].rank
+            } else {
+                i + 1
+            };
+    }
+
+    standings
+}
+
+impl ManifoldClient {
+    /// [`build_scoreboard`] for the markets of the group `group_id`.
+    ///
+    /// Fetches every market of the group and its bets back to `start`.
+    pub async fn group_scoreboard(
+        &self,
+        group_id: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        tie_breaks: &[TieBreak],
+    ) -> Result<Vec<Standing>> {
+        let mut markets = vec![];
+        let mut bets = vec![];
+
+        for market in self.get_group_markets(group_id).await? {
+            let mut market_bets: Vec<Bet> = self
+                .stream_bets_with(
+                    &BetsQuery::new()
+                        .contract_id(market.id())
+                        .after_time(start)
+                        .before_time(end),
+                )
+                .try_collect()
+                .await?;
+
+            bets.append(&mut market_bets);
+            markets.push(self.get_market(market.id()).await?);
+        }
+
+        Ok(build_scoreboard(&markets, &bets, start, end, tie_breaks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+
+    fn bet(
+        user_id: &str,
+        market_id: &str,
+        outcome: &str,
+        amount: f64,
+        shares: f64,
+        time: i64,
+    ) -> Bet {
+        Bet(json!({
+            "contractId": market_id,
+            "userId": user_id,
+            "outcome": outcome,
+            "amount": amount,
+            "shares": shares,
+            "createdTime": time,
+        }))
+    }
+
+    #[test]
+    fn ranks_traders() {
+        let markets = [
+            LiteMarket(json!({"id": "yes", "isResolved": true, "resolution": "YES"})),
+            LiteMarket(json!({"id": "open", "probability": 0.5})),
+            LiteMarket(json!({"id": "cancelled", "isResolved": true, "resolution": "CANCEL"})),
+        ];
+        let bets = [
+            bet("alice", "yes", "YES", 10.0, 20.0, 1),
+            bet("bob", "open", "YES", 10.0, 30.0, 2),
+            bet("bob", "cancelled", "NO", 100.0, 200.0, 2),
+            bet("carol", "yes", "NO", 10.0, 20.0, 3),
+            bet("dave", "yes", "YES", 5.0, 15.0, 4),
+            // Outside the window.
+            bet("carol", "yes", "YES", 100.0, 200.0, 100),
+        ];
+
+        let (start, end) = (
+            Utc.timestamp_millis_opt(0).unwrap(),
+            Utc.timestamp_millis_opt(100).unwrap(),
+        );
+        let standings = build_scoreboard(&markets, &bets, start, end, &[TieBreak::FewerBets]);
+
+        let ranks = standings
+            .iter()
+            .map(|s| (s.user_id.as_str(), s.rank, s.profit))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranks,
+            [
+                ("alice", 1, 10.0),
+                ("dave", 1, 10.0),
+                ("bob", 3, 5.0),
+                ("carol", 4, -10.0)
+            ]
+        );
+        assert_eq!(standings[2].realized, 0.0);
+        assert_eq!(standings[2].markets, 1);
+
+        let standings = build_scoreboard(&markets, &bets, start, end, &[TieBreak::EarlierStart]);
+        assert_eq!(standings[1].user_id, "dave");
+        assert_eq!(standings[1].rank, 2);
+    }
+}
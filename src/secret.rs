@@ -0,0 +1,115 @@
+//! Keeping API keys out of logs
+//!
+//! [`SecretString`] holds a key without ever printing it, and [`redact`]
+//! scrubs authorization headers from text that's about to be logged, like
+//! error messages recorded in the [`AuditLog`](crate::bot::AuditLog).
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub const REDACTED: &str = "[redacted]";
+
+/// A string that shows up as `[redacted]` in `Debug`, `Display` and serialized output.
+///
+/// Deserializes from a plain string, so it can be used in config files.
+/// Serialized output reads back as `[redacted]`, not the secret, so mark
+/// fields of structs that are written out and read back again with
+/// `#[serde(skip_serializing)]`, like `config::Config::api_key`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> SecretString {
+        SecretString(secret.into())
+    }
+
+    /// The actual secret, for the one place that needs it.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        SecretString(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        SecretString(secret.to_owned())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString({REDACTED})")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(SecretString)
+    }
+}
+
+/// Replaces the credentials of `Key ...` and `Bearer ...` authorization values in `text`.
+pub fn redact(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some((start, scheme)) = ["Key ", "Bearer "]
+        .iter()
+        .filter_map(|scheme| Some((rest.find(scheme)?, *scheme)))
+        .min()
+    {
+        let token_start = start + scheme.len();
+        let token_len = rest[token_start..]
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | ',' | ')' | '}'))
+            .unwrap_or(rest.len() - token_start);
+
+        out.push_str(&rest[..token_start]);
+        out.push_str(if token_len > 0 { REDACTED } else { "" });
+        rest = &rest[token_start + token_len..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hides_secrets() {
+        let key = SecretString::new("abc-123");
+
+        assert_eq!(format!("{key:?}"), "SecretString([redacted])");
+        assert_eq!(key.to_string(), REDACTED);
+        assert_eq!(serde_json::to_string(&key).unwrap(), "\"[redacted]\"");
+        assert_eq!(
+            serde_json::from_str::<SecretString>("\"abc-123\"").unwrap(),
+            key
+        );
+
+        assert_eq!(
+            redact(r#"headers: {"authorization": "Key abc-123", "x": "Bearer eyJ.x.y"}"#),
+            r#"headers: {"authorization": "Key [redacted]", "x": "Bearer [redacted]"}"#
+        );
+        assert_eq!(redact("no secrets here"), "no secrets here");
+    }
+}
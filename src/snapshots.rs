@@ -0,0 +1,235 @@
+//! Probability history of markets, recorded by polling
+//!
+//! Charting a binary market's history can be done from its bets, but that's
+//! hard for multiple choice markets. A [`SnapshotStore`] instead records the
+//! probabilities of a set of markets at intervals (see
+//! [`SnapshotStore::watch`]) into a [`Storage`], one document per market.
+//!
+//! Snapshots are kept as they are for [`Retention::raw`], then rolled up to
+//! the last one of each hour, which are kept for [`Retention::hourly`].
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, DurationRound, Utc};
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::storage::Storage;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// How long snapshots are kept, see the [module documentation](crate::snapshots).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    pub raw: Duration,
+    pub hourly: Duration,
+}
+
+impl Default for Retention {
+    /// Raw snapshots for a week, hourly ones for a year.
+    fn default() -> Self {
+        Retention {
+            raw: Duration::from_secs(7 * 24 * 60 * 60),
+            hourly: Duration::from_secs(365 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub time: DateTime<Utc>,
+    /// Binary markets.
+    pub probability: Option<f64>,
+    /// Multiple choice markets, by answer id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub answers: BTreeMap<String, f64>,
+}
+
+impl Snapshot {
+    /// The market's probabilities, as of now.
+    pub fn of(market: &impl Market) -> Snapshot {
+        Snapshot {
+            time: Utc::now(),
+            probability: market.probability(),
+            answers: market
+                .answers()
+                .iter()
+                .filter_map(|a| Some((a.try_id().ok()?.to_owned(), a.probability()?)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct History {
+    /// Oldest first, like `raw`.
+    hourly: Vec<Snapshot>,
+    raw: Vec<Snapshot>,
+}
+
+/// Snapshots of market probabilities in a [`Storage`].
+#[derive(Debug)]
+pub struct SnapshotStore<S: Storage> {
+    storage: S,
+    pub retention: Retention,
+}
+
+fn key(market_id: &str) -> String {
+    format!("snapshots-{market_id}")
+}
+
+fn hour(time: DateTime<Utc>) -> DateTime<Utc> {
+    time.duration_trunc(chrono::Duration::hours(1))
+        .unwrap_or(time)
+}
+
+impl<S: Storage> SnapshotStore<S> {
+    pub fn new(storage: S, retention: Retention) -> SnapshotStore<S> {
+        SnapshotStore { storage, retention }
+    }
+
+    /// Adds a snapshot of a market, rolling up and dropping the ones that
+    /// are too old as of `snapshot.time`.
+    pub fn record(&self, market_id: &str, snapshot: Snapshot) -> Result<()> {
+        self.record_all(market_id, vec![snapshot])
+    }
+
+    /// Adds many snapshots of a market at once, e.g. history reconstructed from
+    /// [bets](crate::import::snapshots_from_bets), rolling up as of the newest one.
+    pub fn record_all(&self, market_id: &str, snapshots: Vec<Snapshot>) -> Result<()> {
+        let Some(now) = snapshots.iter().map(|s| s.time).max() else {
+            return Ok(());
+        };
+
+        let mut history: History = self.storage.load_as(&key(market_id))?.unwrap_or_default();
+
+        let raw_since = now - self.retention.raw;
+        let hourly_since = now - self.retention.hourly;
+
+        history.raw.extend(snapshots);
+        history.raw.sort_by_key(|s| s.time);
+
+        let expired = history.raw.partition_point(|s| s.time < raw_since);
+
+        history.hourly.extend(history.raw.drain(..expired));
+        history.hourly.sort_by_key(|s| s.time);
+
+        // The last snapshot of each hour stands for it.
+        history.hourly.reverse();
+        history.hourly.dedup_by_key(|s| hour(s.time));
+        history.hourly.reverse();
+
+        history.hourly.retain(|s| s.time >= hourly_since);
+
+        self.storage.save_as(&key(market_id), &history)
+    }
+
+    /// The snapshots of a market taken between `from` and `to`, oldest first;
+    /// hourly ones where the raw ones were rolled up.
+    pub fn history(
+        &self,
+        market_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Snapshot>> {
+        let history: History = self.storage.load_as(&key(market_id))?.unwrap_or_default();
+
+        Ok(history
+            .hourly
+            .into_iter()
+            .chain(history.raw)
+            .filter(|s| from <= s.time && s.time <= to)
+            .collect())
+    }
+
+    /// Forgets all snapshots of a market.
+    pub fn clear(&self, market_id: &str) -> Result<()> {
+        self.storage.remove(&key(market_id))
+    }
+
+    /// Polls `market_ids` every `interval` (see [`ManifoldClient::watch_markets`]),
+    /// recording a snapshot of each and yielding it.
+    ///
+    /// Snapshots are timed by when the market was fetched, not when it arrived.
+    ///
+    /// Never ends; a failed fetch or save yields an error and polling continues.
+    pub fn watch<'a>(
+        &'a self,
+        client: &'a ManifoldClient,
+        market_ids: &'a [String],
+        interval: Duration,
+    ) -> impl Stream<Item = Result<(String, Snapshot)>> + 'a {
+        client
+            .watch_markets(market_ids, interval)
+            .map(move |market| {
+                let market = market?;
+                let snapshot = Snapshot {
+                    time: market.fetched_at,
+                    ..Snapshot::of(&*market)
+                };
+
+                self.record(market.id(), snapshot.clone())?;
+
+                Ok((market.id().to_owned(), snapshot))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::storage::MemoryStorage;
+
+    use super::*;
+
+    #[test]
+    fn rolls_up_old_snapshots() -> Result<()> {
+        let store = SnapshotStore::new(
+            MemoryStorage::new(),
+            Retention {
+                raw: Duration::from_secs(2 * 60 * 60),
+                hourly: Duration::from_secs(24 * 60 * 60),
+            },
+        );
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let at = |minutes: i64| start + chrono::Duration::minutes(minutes);
+        let snapshot = |minutes: i64| Snapshot {
+            time: at(minutes),
+            probability: Some(minutes as f64 / 10_000.0),
+            answers: BTreeMap::new(),
+        };
+
+        // Every 15 minutes for 4 hours.
+        for minutes in (0..=240).step_by(15) {
+            store.record("m", snapshot(minutes))?;
+        }
+
+        let times = |history: Vec<Snapshot>| {
+            history
+                .iter()
+                .map(|s| (s.time - start).num_minutes())
+                .collect::<Vec<_>>()
+        };
+
+        // The first two hours rolled up, at minutes 45 and 105.
+        assert_eq!(
+            times(store.history("m", at(0), at(240))?),
+            [45, 105, 120, 135, 150, 165, 180, 195, 210, 225, 240]
+        );
+        assert_eq!(times(store.history("m", at(100), at(130))?), [105, 120]);
+
+        // A day later, only the newest snapshots are left.
+        store.record("m", snapshot(24 * 60 + 200))?;
+        assert_eq!(
+            times(store.history("m", at(0), at(10_000))?).first(),
+            Some(&225)
+        );
+
+        Ok(())
+    }
+}
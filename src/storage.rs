@@ -0,0 +1,190 @@
+//! Small key-value persistence for long-running tools
+//!
+//! Things like [trigger sets](crate::triggers::TriggerSet) need to survive
+//! restarts. They store JSON documents through the [`Storage`] trait, so
+//! applications can choose where they go: [`FileStorage`] for a directory
+//! of JSON files, [`MemoryStorage`] for tests, or their own backend.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::Result;
+
+/// A key-value store of JSON documents.
+pub trait Storage {
+    fn load(&self, key: &str) -> Result<Option<Value>>;
+
+    /// Replaces the document at `key`. Implementations should make this atomic.
+    fn save(&self, key: &str, value: &Value) -> Result<()>;
+
+    fn remove(&self, key: &str) -> Result<()>;
+
+    /// [`load`](Self::load) and deserialize.
+    fn load_as<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>>
+    where
+        Self: Sized,
+    {
+        Ok(self.load(key)?.map(serde_json::from_value).transpose()?)
+    }
+
+    /// Serialize and [`save`](Self::save).
+    fn save_as<T: Serialize>(&self, key: &str, value: &T) -> Result<()>
+    where
+        Self: Sized,
+    {
+        self.save(key, &serde_json::to_value(value)?)
+    }
+}
+
+impl<S: Storage + ?Sized> Storage for &S {
+    fn load(&self, key: &str) -> Result<Option<Value>> {
+        (**self).load(key)
+    }
+
+    fn save(&self, key: &str, value: &Value) -> Result<()> {
+        (**self).save(key, value)
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        (**self).remove(key)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    documents: Mutex<HashMap<String, Value>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        Default::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self, key: &str) -> Result<Option<Value>> {
+        Ok(self.documents.lock().unwrap().get(key).cloned())
+    }
+
+    fn save(&self, key: &str, value: &Value) -> Result<()> {
+        self.documents
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), value.clone());
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.documents.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// One `<key>.json` file per document in a directory.
+///
+/// Writes go to a temporary file that is then renamed over the old one,
+/// so a crash never leaves a half-written document.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    /// Uses `dir`, creating it if needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<FileStorage> {
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(FileStorage {
+            dir: dir.as_ref().to_owned(),
+        })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let name = key
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        self.dir.join(format!("{name}.json"))
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self, key: &str) -> Result<Option<Value>> {
+        match std::fs::read(self.path(key)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, key: &str, value: &Value) -> Result<()> {
+        let path = self.path(key);
+        let tmp = path.with_extension("json.tmp");
+
+        std::fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+        std::fs::rename(&tmp, &path)?;
+
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        match std::fs::remove_file(self.path(key)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn round_trip(storage: &impl Storage) -> Result<()> {
+        assert_eq!(storage.load("bot/state")?, None);
+
+        storage.save_as("bot/state", &vec![1, 2, 3])?;
+        assert_eq!(
+            storage.load_as::<Vec<i32>>("bot/state")?,
+            Some(vec![1, 2, 3])
+        );
+
+        storage.save("bot/state", &json!({"replaced": true}))?;
+        assert_eq!(storage.load("bot/state")?, Some(json!({"replaced": true})));
+
+        storage.remove("bot/state")?;
+        storage.remove("bot/state")?;
+        assert_eq!(storage.load("bot/state")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_storage() -> Result<()> {
+        round_trip(&MemoryStorage::new())
+    }
+
+    #[test]
+    fn file_storage() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("storage-{}", std::process::id()));
+
+        round_trip(&FileStorage::open(&dir)?)?;
+
+        std::fs::remove_dir_all(dir)?;
+
+        Ok(())
+    }
+}
@@ -1,5 +1,8 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
 
-
+use chrono::{DateTime, Utc};
 use futures_util::{stream, Stream, StreamExt, TryStreamExt};
 
 use crate::error::{ManifoldError, Result};
@@ -9,72 +12,395 @@ use serde_json::Value;
 
 use crate::{types::*, ManifoldClient};
 
+/// How an endpoint pages through its results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pagination {
+    /// `before` = the id of the last item of the previous page, until a page is empty.
+    ///
+    /// Used by `/markets`, `/users`, `/bets`.
+    Cursor,
+    /// `offset` = the number of items so far and `limit` = `page_size`, until a page is short.
+    ///
+    /// Used by `/txns` and `/search-markets`.
+    Offset { page_size: usize },
+    /// `param` = the `createdTime` of the last item of the previous page and
+    /// `limit` = `page_size`, until a page is short.
+    ///
+    /// Used by `/comments` (as `beforeTime`) and `/managrams` (as `before`).
+    /// Items created in the same millisecond as the last one of a page may be skipped.
+    BeforeTime {
+        param: &'static str,
+        page_size: usize,
+    },
+}
+
+/// Where the next page of a [`Paginator`] starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageStart {
+    Before(Option<String>),
+    Offset(usize),
+    BeforeTime(Option<i64>),
+}
+
+/// A list endpoint and how it pages, for [`ManifoldClient::stream_endpoint`].
+///
+/// [`Paginator`] covers the endpoints Manifold has; implement this for ones it
+/// doesn't (custom deployments, beta routes) to stream them the same way,
+/// with the client's rate limiting and error handling.
+///
+/// ```
+/// # use manifold_markets::error::Result;
+/// # use manifold_markets::streams::PaginatedEndpoint;
+/// # use serde_json::Value;
+/// /// `GET /v0/beta/things?page=N`, answering `{"things": [...], "more": bool}`.
+/// struct Things;
+///
+/// impl PaginatedEndpoint for Things {
+///     type Cursor = u32;
+///
+///     fn path(&self) -> &str {
+///         "/beta/things"
+///     }
+///
+///     fn first(&self) -> u32 {
+///         0
+///     }
+///
+///     fn params(&self, page: &u32) -> Result<Vec<(String, String)>> {
+///         Ok(vec![("page".to_owned(), page.to_string())])
+///     }
+///
+///     fn page(&self, page: &u32, mut response: Value) -> Result<(Vec<Value>, Option<u32>)> {
+///         let more = response["more"].as_bool() == Some(true);
+///         let things = serde_json::from_value(response["things"].take())?;
+///
+///         Ok((things, more.then_some(page + 1)))
+///     }
+/// }
+/// ```
+pub trait PaginatedEndpoint {
+    /// Where a page starts: an id, an offset, a timestamp...
+    type Cursor: Clone;
+
+    /// Relative to the API version, like the client's own paths.
+    fn path(&self) -> &str;
+
+    fn first(&self) -> Self::Cursor;
+
+    /// Query parameters of the page starting at `cursor`.
+    ///
+    /// Fails if `cursor` can't start a page of this endpoint.
+    fn params(&self, cursor: &Self::Cursor) -> Result<Vec<(String, String)>>;
+
+    /// Splits the response of the page at `cursor` into its items and where
+    /// the next page starts, `None` after the last one.
+    ///
+    /// The stream also ends at the first empty page.
+    fn page(
+        &self,
+        cursor: &Self::Cursor,
+        response: Value,
+    ) -> Result<(Vec<Value>, Option<Self::Cursor>)>;
+}
+
+/// Streams all results of a list endpoint, page by page, see [`Pagination`].
+///
+/// Each page is requested with the same parameters plus the page position,
+/// so a failed page can simply be requested again.
+#[derive(Debug, Clone)]
+pub struct Paginator {
+    pub path: String,
+    pub params: Vec<(String, String)>,
+    pub pagination: Pagination,
+}
+
+impl Paginator {
+    pub fn cursor(path: &str) -> Paginator {
+        Paginator {
+            path: path.to_owned(),
+            params: vec![],
+            pagination: Pagination::Cursor,
+        }
+    }
+
+    pub fn offset(path: &str, page_size: usize) -> Paginator {
+        Paginator {
+            path: path.to_owned(),
+            params: vec![],
+            pagination: Pagination::Offset { page_size },
+        }
+    }
+
+    pub fn before_time(path: &str, param: &'static str, page_size: usize) -> Paginator {
+        Paginator {
+            path: path.to_owned(),
+            params: vec![],
+            pagination: Pagination::BeforeTime { param, page_size },
+        }
+    }
+
+    pub fn param(mut self, name: &str, value: impl ToString) -> Self {
+        self.params.push((name.to_owned(), value.to_string()));
+        self
+    }
+
+    /// [`param`](Self::param), if there is a value.
+    pub fn param_opt(self, name: &str, value: Option<impl ToString>) -> Self {
+        match value {
+            Some(value) => self.param(name, value),
+            None => self,
+        }
+    }
+
+    fn page_params(&self, start: &PageStart) -> Result<Vec<(String, String)>> {
+        let mut params = self.params.clone();
+
+        match (start, self.pagination) {
+            (PageStart::Before(Some(before)), _) => {
+                params.push(("before".to_owned(), before.clone()))
+            }
+            (PageStart::Before(None), _) => {}
+            (PageStart::Offset(offset), Pagination::Offset { page_size }) => {
+                params.push(("limit".to_owned(), page_size.to_string()));
+                params.push(("offset".to_owned(), offset.to_string()));
+            }
+            (PageStart::BeforeTime(before), Pagination::BeforeTime { param, page_size }) => {
+                params.push(("limit".to_owned(), page_size.to_string()));
+
+                // Replaces the time the first page started at, if any.
+                if let Some(before) = before {
+                    params.retain(|(name, _)| name != param);
+                    params.push((param.to_owned(), before.to_string()));
+                }
+            }
+            (PageStart::Offset(_) | PageStart::BeforeTime(_), pagination) => {
+                return Err(ManifoldError::Validation(format!(
+                    "{start:?} can't start a page of {pagination:?} pagination"
+                )))
+            }
+        }
+
+        Ok(params)
+    }
+
+    /// Where the page after `page` starts, if there is one.
+    fn next_start(&self, start: &PageStart, page: &[Value]) -> Result<Option<PageStart>> {
+        let Some(last) = page.last() else {
+            return Ok(None);
+        };
+
+        Ok(match (start, self.pagination) {
+            (PageStart::Offset(offset), Pagination::Offset { page_size }) => {
+                (page.len() >= page_size).then_some(PageStart::Offset(offset + page.len()))
+            }
+            (PageStart::BeforeTime(_), Pagination::BeforeTime { page_size, .. }) => {
+                let last_time = last["createdTime"].as_i64().ok_or_else(|| {
+                    ManifoldError::SchemaError(
+                        "Not a number createdTime?".to_owned(),
+                        Some(last["createdTime"].clone()),
+                    )
+                })?;
+
+                (page.len() >= page_size).then_some(PageStart::BeforeTime(Some(last_time)))
+            }
+            _ => {
+                let last_id = last["id"].as_str().ok_or_else(|| {
+                    ManifoldError::SchemaError(
+                        "Not a string id?".to_owned(),
+                        Some(last["id"].clone()),
+                    )
+                })?;
+
+                Some(PageStart::Before(Some(last_id.to_owned())))
+            }
+        })
+    }
+
+    pub fn stream<'a, T: DeserializeOwned>(
+        self,
+        client: &'a ManifoldClient,
+    ) -> impl Stream<Item = Result<T>> + 'a {
+        client.stream_endpoint(self)
+    }
+}
+
+impl PaginatedEndpoint for Paginator {
+    type Cursor = PageStart;
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn first(&self) -> PageStart {
+        match self.pagination {
+            Pagination::Cursor => PageStart::Before(None),
+            Pagination::Offset { .. } => PageStart::Offset(0),
+            Pagination::BeforeTime { .. } => PageStart::BeforeTime(None),
+        }
+    }
+
+    fn params(&self, start: &PageStart) -> Result<Vec<(String, String)>> {
+        self.page_params(start)
+    }
+
+    fn page(&self, start: &PageStart, response: Value) -> Result<(Vec<Value>, Option<PageStart>)> {
+        let Value::Array(page) = response else {
+            return Err(ManifoldError::SchemaError(
+                "Streaming response returned not an array?".to_owned(),
+                Some(response),
+            ));
+        };
+
+        let next = self.next_start(start, &page)?;
+
+        Ok((page, next))
+    }
+}
+
 impl ManifoldClient {
+    /// Streams all items of a paginated endpoint, requesting each page once the
+    /// previous one is consumed.
+    pub fn stream_endpoint<'a, E, T>(&'a self, endpoint: E) -> impl Stream<Item = Result<T>> + 'a
+    where
+        E: PaginatedEndpoint + 'a,
+        T: DeserializeOwned,
+    {
+        let endpoint = Arc::new(endpoint);
+
+        stream::try_unfold(Some(endpoint.first()), move |cursor| {
+            let endpoint = endpoint.clone();
+
+            async move {
+                let Some(cursor) = cursor else {
+                    return Result::Ok(None);
+                };
+
+                let response = self
+                    .execute::<Value>(
+                        self.http_get(endpoint.path())
+                            .query(&endpoint.params(&cursor)?),
+                    )
+                    .await?;
+
+                let (page, next) = endpoint.page(&cursor, response)?;
+
+                if page.is_empty() {
+                    return Ok(None);
+                }
+
+                let page = page
+                    .into_iter()
+                    .map(serde_json::from_value)
+                    .try_collect::<Vec<T>>()?;
+
+                Ok(Some((page, next)))
+            }
+        })
+        .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+        .try_flatten()
+    }
+}
+
+impl ManifoldClient {
+    /// Streams a list endpoint that pages with `before`, see [`Paginator`].
     pub fn stream_paginated<T: DeserializeOwned>(
         &self,
         path: String,
         params: Vec<(String, String)>,
     ) -> impl Stream<Item = Result<T>> + '_ {
-        stream::try_unfold(None, move |before| {
+        Paginator {
+            path,
+            params,
+            pagination: Pagination::Cursor,
+        }
+        .stream(self)
+    }
+
+    /// Like [`stream_paginated`](Self::stream_paginated), but never holds a whole page in memory.
+    ///
+    /// Each page's body is parsed element by element as it arrives (see [`json_array_stream`]),
+    /// which keeps peak memory low during full-catalog scans of pages with very large markets.
+    pub fn stream_paginated_chunked<T: DeserializeOwned>(
+        &self,
+        path: String,
+        params: Vec<(String, String)>,
+    ) -> impl Stream<Item = Result<T>> + '_ {
+        enum PageState {
+            Next(Option<String>),
+            InPage(
+                Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+                Option<String>,
+            ),
+            Done,
+        }
+
+        stream::try_unfold(PageState::Next(None), move |state| {
             let params = params.clone();
             let path = path.clone();
 
             async move {
-                let params = match before {
-                    Some(before) => {
-                        let mut params = params.clone();
-                        params.push(("before".to_owned(), before));
-                        params
-                    }
-                    None => params.clone(),
-                };
+                let mut state = state;
 
-                let result = self
-                    .http_get(&path)
-                    .query(&params)
-                    .send()
-                    .await?
-                    .error_for_status()?
-                    .json::<Value>()
-                    .await?;
+                loop {
+                    state = match state {
+                        PageState::Done => return Ok(None),
+                        PageState::Next(before) => {
+                            let mut params = params.clone();
 
-                let result = result.as_array().ok_or_else(|| {
-                    ManifoldError::SchemaError(
-                        "Streaming response returned not an array?".to_owned(),
-                        Some(result.clone()),
-                    )
-                })?;
+                            if let Some(before) = before {
+                                params.push(("before".to_owned(), before));
+                            }
 
-                let Some(last) = result.last() else {
-                return Result::Ok(None)
-            };
-
-                let last_id = last["id"]
-                    .as_str()
-                    .ok_or_else(|| {
-                        ManifoldError::SchemaError(
-                            "Not a string id?".to_owned(),
-                            Some(last["id"].clone()),
-                        )
-                    })?
-                    .to_owned();
-
-                let result = result
-                    .iter()
-                    .map(|v| serde_json::from_value(v.clone()))
-                    .try_collect::<Vec<T>>()?;
+                            let response = self
+                                .execute_response(self.http_get(&path).query(&params))
+                                .await?;
+
+                            PageState::InPage(Box::pin(json_array_stream(response)), None)
+                        }
+                        PageState::InPage(mut items, last_id) => match items.next().await {
+                            Some(value) => {
+                                let value = value?;
+
+                                let id = value["id"]
+                                    .as_str()
+                                    .ok_or_else(|| {
+                                        ManifoldError::SchemaError(
+                                            "Not a string id?".to_owned(),
+                                            Some(value["id"].clone()),
+                                        )
+                                    })?
+                                    .to_owned();
+
+                                let item = serde_json::from_value(value)?;
 
-                Ok(Some((result, Some(last_id))))
+                                return Ok(Some((item, PageState::InPage(items, Some(id)))));
+                            }
+                            None => match last_id {
+                                Some(id) => PageState::Next(Some(id)),
+                                None => PageState::Done,
+                            },
+                        },
+                    }
+                }
             }
         })
-        .map_ok(|v| stream::iter(v.into_iter().map(Ok)))
-        .try_flatten()
     }
 
     pub fn stream_markets(&self) -> impl Stream<Item = Result<LiteMarket>> + '_ {
         self.stream_paginated("/markets".to_owned(), vec![])
     }
 
+    /// Same as [`stream_markets`](Self::stream_markets), using [`stream_paginated_chunked`](Self::stream_paginated_chunked).
+    pub fn stream_markets_chunked(&self) -> impl Stream<Item = Result<LiteMarket>> + '_ {
+        self.stream_paginated_chunked("/markets".to_owned(), vec![])
+    }
+
+    /// Same as [`stream_markets`](Self::stream_markets), skipping unlisted and private markets.
+    pub fn stream_public_markets(&self) -> impl Stream<Item = Result<LiteMarket>> + '_ {
+        self.stream_markets()
+            .try_filter(|m| futures_util::future::ready(m.is_public()))
+    }
+
     pub fn stream_users(&self) -> impl Stream<Item = Result<User>> + '_ {
         self.stream_paginated("/users".to_owned(), vec![])
     }
@@ -86,24 +412,401 @@ impl ManifoldClient {
         contract_id: Option<&str>,
         contract_slug: Option<&str>,
     ) -> impl Stream<Item = Result<Bet>> + '_ {
-        let mut params = vec![];
+        self.stream_bets_with(&BetsQuery {
+            user_id: user_id.map(str::to_owned),
+            username: username.map(str::to_owned),
+            contract_id: contract_id.map(str::to_owned),
+            contract_slug: contract_slug.map(str::to_owned),
+            ..BetsQuery::default()
+        })
+    }
 
-        if let Some(user_id) = user_id {
-            params.push(("userId".to_owned(), user_id.to_owned()));
+    /// Streams bets, newest first, filtered by `query` but for its `limit` and `before`.
+    ///
+    /// With [`after_time`](BetsQuery::after_time), the stream ends at the
+    /// first older bet instead of paging back through the whole history.
+    pub fn stream_bets_with(&self, query: &BetsQuery) -> impl Stream<Item = Result<Bet>> + '_ {
+        let params = BetsQuery {
+            limit: None,
+            before: None,
+            ..query.clone()
         }
+        .params();
+        let after = query.after_time.map_or(i64::MIN, |t| t.timestamp_millis());
+
+        self.stream_paginated("/bets".to_owned(), params)
+            .try_take_while(move |bet: &Bet| {
+                std::future::ready(bet.try_created_time().map(|t| t >= after))
+            })
+    }
+
+    /// `GET /v0/txns`
+    ///
+    /// Streams transactions, newest first, optionally filtered by sender,
+    /// receiver and category (e.g. `ADD_SUBSIDY`).
+    ///
+    /// Unlike the other lists, this endpoint pages by offset.
+    pub fn stream_txns(
+        &self,
+        from_id: Option<&str>,
+        to_id: Option<&str>,
+        category: Option<&str>,
+    ) -> impl Stream<Item = Result<Txn>> + '_ {
+        Paginator::offset("/txns", 100)
+            .param_opt("fromId", from_id)
+            .param_opt("toId", to_id)
+            .param_opt("category", category)
+            .stream(self)
+    }
+
+    /// `GET /v0/managrams`, paginated
+    ///
+    /// Streams managrams, newest first, optionally only those sent to or by a user.
+    /// Managrams sent in the same millisecond as the last one of a page may be skipped.
+    pub fn stream_managrams(
+        &self,
+        to_id: Option<&str>,
+        from_id: Option<&str>,
+    ) -> impl Stream<Item = Result<Txn>> + '_ {
+        Paginator::before_time("/managrams", "before", 100)
+            .param_opt("toId", to_id)
+            .param_opt("fromId", from_id)
+            .stream(self)
+    }
+
+    /// `GET /v0/comments`, paginated
+    ///
+    /// Streams the comments on a market (by ID or slug), newest first,
+    /// optionally only those created in `after_time..before_time`.
+    /// Pages by `beforeTime`, so busy markets don't need one giant request.
+    pub fn stream_comments(
+        &self,
+        contract_id: Option<&str>,
+        contract_slug: Option<&str>,
+        after_time: Option<DateTime<Utc>>,
+        before_time: Option<DateTime<Utc>>,
+    ) -> impl Stream<Item = Result<Comment>> + '_ {
+        Paginator::before_time("/comments", "beforeTime", 100)
+            .param_opt("contractId", contract_id)
+            .param_opt("contractSlug", contract_slug)
+            .param_opt("afterTime", after_time.map(|t| t.timestamp_millis()))
+            .param_opt("beforeTime", before_time.map(|t| t.timestamp_millis()))
+            .stream(self)
+            // In case the server ignores `afterTime`.
+            .try_take_while(move |comment: &Comment| {
+                let after = after_time.map_or(i64::MIN, |t| t.timestamp_millis());
+
+                std::future::ready(Ok(
+                    comment.0["createdTime"].as_i64().unwrap_or(i64::MAX) >= after
+                ))
+            })
+    }
+
+    /// `GET /v0/search-markets`
+    ///
+    /// Streams all markets matching `term`, in the order of `sort`
+    /// (`score`, `newest`, `liquidity`, ...; relevance by default).
+    /// `filter` and `contract_type` narrow the results down as in
+    /// [`search_markets`](Self::search_markets).
+    pub fn stream_search_markets(
+        &self,
+        term: &str,
+        filter: Option<&str>,
+        sort: Option<&str>,
+        contract_type: Option<&str>,
+    ) -> impl Stream<Item = Result<LiteMarket>> + '_ {
+        Paginator::offset("/search-markets", 100)
+            .param("term", term)
+            .param_opt("filter", filter)
+            .param_opt("sort", sort)
+            .param_opt("contractType", contract_type)
+            .stream(self)
+    }
+}
+
+/// Parses a response whose body is a JSON array, yielding the elements as they arrive.
+///
+/// Only the element currently being received is buffered, instead of the whole body.
+pub fn json_array_stream(response: reqwest::Response) -> impl Stream<Item = Result<Value>> {
+    let state = (
+        response.bytes_stream(),
+        ArraySplitter::default(),
+        VecDeque::<Vec<u8>>::new(),
+    );
+
+    stream::try_unfold(state, |(mut bytes, mut splitter, mut ready)| async move {
+        loop {
+            if let Some(raw) = ready.pop_front() {
+                let value = serde_json::from_slice::<Value>(&raw)?;
+
+                return Ok(Some((value, (bytes, splitter, ready))));
+            }
+
+            match bytes.next().await {
+                Some(chunk) => splitter.push(&chunk?, &mut ready)?,
+                None => {
+                    splitter.finish()?;
 
-        if let Some(username) = username {
-            params.push(("username".to_owned(), username.to_owned()));
+                    return Ok(None);
+                }
+            }
         }
+    })
+}
+
+/// Splits a top-level JSON array, fed in arbitrary chunks, into the raw bytes of its elements.
+#[derive(Debug, Default)]
+struct ArraySplitter {
+    opened: bool,
+    closed: bool,
+    current: Vec<u8>,
+    nesting: usize,
+    in_string: bool,
+    escaped: bool,
+}
 
-        if let Some(contract_id) = contract_id {
-            params.push(("contractId".to_owned(), contract_id.to_owned()));
+impl ArraySplitter {
+    fn push(&mut self, chunk: &[u8], out: &mut VecDeque<Vec<u8>>) -> Result<()> {
+        for &b in chunk {
+            if self.closed {
+                if !b.is_ascii_whitespace() {
+                    return Err(ManifoldError::SchemaError(
+                        "Trailing data after the array".to_owned(),
+                        None,
+                    ));
+                }
+                continue;
+            }
+
+            if !self.opened {
+                match b {
+                    b'[' => self.opened = true,
+                    _ if b.is_ascii_whitespace() => {}
+                    _ => {
+                        return Err(ManifoldError::SchemaError(
+                            "Streaming response returned not an array?".to_owned(),
+                            None,
+                        ))
+                    }
+                }
+                continue;
+            }
+
+            if self.in_string {
+                self.current.push(b);
+
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            match b {
+                b'"' => {
+                    self.in_string = true;
+                    self.current.push(b);
+                }
+                b'[' | b'{' => {
+                    self.nesting += 1;
+                    self.current.push(b);
+                }
+                b']' | b'}' if self.nesting > 0 => {
+                    self.nesting -= 1;
+                    self.current.push(b);
+                }
+                b',' | b']' if self.nesting == 0 => {
+                    if !self.current.is_empty() {
+                        out.push_back(std::mem::take(&mut self.current));
+                    }
+                    self.closed = b == b']';
+                }
+                _ if self.nesting == 0 && b.is_ascii_whitespace() => {}
+                _ => self.current.push(b),
+            }
         }
 
-        if let Some(contract_slug) = contract_slug {
-            params.push(("contractSlug".to_owned(), contract_slug.to_owned()));
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.closed {
+            Ok(())
+        } else {
+            Err(ManifoldError::SchemaError(
+                "Response body ended in the middle of an array".to_owned(),
+                None,
+            ))
         }
+    }
+}
 
-        self.stream_paginated("/bets".to_owned(), params)
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn paginator_finds_next_pages() -> Result<()> {
+        let page = [json!({"id": "a"}), json!({"id": "b"})];
+
+        let cursor = Paginator::cursor("/bets").param("userId", "u");
+        let first = PageStart::Before(None);
+        let next = cursor.next_start(&first, &page)?;
+        assert_eq!(next, Some(PageStart::Before(Some("b".to_owned()))));
+        assert_eq!(
+            cursor.page_params(&next.unwrap())?,
+            vec![
+                ("userId".to_owned(), "u".to_owned()),
+                ("before".to_owned(), "b".to_owned())
+            ]
+        );
+        assert_eq!(cursor.next_start(&first, &[])?, None);
+
+        let offset = Paginator::offset("/txns", 2);
+        let next = offset.next_start(&PageStart::Offset(4), &page)?;
+        assert_eq!(next, Some(PageStart::Offset(6)));
+        assert_eq!(offset.next_start(&PageStart::Offset(6), &page[..1])?, None);
+        assert_eq!(
+            offset.page_params(&PageStart::Offset(6))?,
+            vec![
+                ("limit".to_owned(), "2".to_owned()),
+                ("offset".to_owned(), "6".to_owned())
+            ]
+        );
+
+        let by_time = Paginator::before_time("/comments", "beforeTime", 2).param("beforeTime", 100);
+        let page = [json!({"createdTime": 90}), json!({"createdTime": 80})];
+        let next = by_time.next_start(&PageStart::BeforeTime(None), &page)?;
+        assert_eq!(next, Some(PageStart::BeforeTime(Some(80))));
+        assert_eq!(
+            by_time.page_params(&next.unwrap())?,
+            vec![
+                ("limit".to_owned(), "2".to_owned()),
+                ("beforeTime".to_owned(), "80".to_owned())
+            ]
+        );
+        assert_eq!(
+            by_time.next_start(&PageStart::BeforeTime(Some(80)), &page[..1])?,
+            None
+        );
+
+        // The same, through the trait.
+        let (items, next) = by_time.page(&by_time.first(), json!(page))?;
+        assert_eq!(items, page);
+        assert_eq!(next, Some(PageStart::BeforeTime(Some(80))));
+        assert!(by_time
+            .page(&by_time.first(), json!({"error": "?"}))
+            .is_err());
+
+        // Managrams page by time too, as `before`.
+        let managrams = Paginator::before_time("/managrams", "before", 2).param("toId", "u");
+        let (_, next) = managrams.page(&managrams.first(), json!(page))?;
+        assert_eq!(
+            managrams.params(&next.unwrap())?,
+            vec![
+                ("toId".to_owned(), "u".to_owned()),
+                ("limit".to_owned(), "2".to_owned()),
+                ("before".to_owned(), "80".to_owned())
+            ]
+        );
+        assert_eq!(
+            managrams.page(&managrams.first(), json!(page[..1]))?.1,
+            None
+        );
+
+        // A start of another kind of pagination is refused, not sent.
+        assert!(matches!(
+            managrams.params(&PageStart::Offset(2)),
+            Err(ManifoldError::Validation(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn filters_bets_by_query() -> Result<()> {
+        use chrono::TimeZone;
+
+        use crate::fixtures::{MockRequest, MockResponse, MockServer};
+
+        let server = MockServer::start(|request: &MockRequest| {
+            (request.path == "/bets").then(|| {
+                MockResponse::json(json!([
+                    {"id": "new", "createdTime": 3000},
+                    {"id": "old", "createdTime": 1000},
+                ]))
+            })
+        })
+        .await?;
+        let client = server.client().build()?;
+
+        let query = BetsQuery::new()
+            .contract_id("m")
+            .limit(10)
+            .before("b")
+            .after_time(Utc.timestamp_millis_opt(2000).unwrap());
+
+        client.get_bets_with(&query).await?;
+        assert_eq!(
+            server.requests()[0].query,
+            "contractId=m&limit=10&before=b&afterTime=2000"
+        );
+
+        // The stream pages on its own, and stops at the first older bet.
+        let bets: Vec<Bet> = client.stream_bets_with(&query).try_collect().await?;
+        assert_eq!(bets.len(), 1);
+        assert_eq!(bets[0].id(), "new");
+        assert_eq!(server.requests()[1].query, "contractId=m&afterTime=2000");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fails_on_bets_without_a_time() -> Result<()> {
+        use crate::fixtures::{MockRequest, MockResponse, MockServer};
+
+        let server = MockServer::start(|request: &MockRequest| {
+            (request.path == "/bets").then(|| MockResponse::json(json!([{"id": "bad"}])))
+        })
+        .await?;
+        let client = server.client().build()?;
+
+        let result: Result<Vec<Bet>> = client
+            .stream_bets_with(&BetsQuery::new())
+            .try_collect()
+            .await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn array_splitter_handles_any_chunking() {
+        let body = br#" [ {"id": "a", "q": "Will [x], {y} happen?\\\" ]"}, {"id":"b","pool":{"YES":1.5}} ,3 ] "#;
+
+        for chunk_size in 1..body.len() {
+            let mut splitter = ArraySplitter::default();
+            let mut out = VecDeque::new();
+
+            for chunk in body.chunks(chunk_size) {
+                splitter.push(chunk, &mut out).unwrap();
+            }
+            splitter.finish().unwrap();
+
+            let values = out
+                .iter()
+                .map(|raw| serde_json::from_slice::<Value>(raw).unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(values.len(), 3);
+            assert_eq!(values[0]["q"], "Will [x], {y} happen?\\\" ]");
+            assert_eq!(values[1]["pool"]["YES"], 1.5);
+            assert_eq!(values[2], 3);
+        }
     }
 }
@@ -0,0 +1,191 @@
+//! Local tags on markets
+//!
+//! Manifold's groups (topics) are shared and moderated. [`MarketTags`] lets a
+//! bot or an analysis label markets however it likes ("my-election-book",
+//! "needs-review"), persisted through a [`Storage`] and never sent to Manifold.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::error::Result;
+use crate::storage::Storage;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// Where [`MarketTags`] are saved unless given another key.
+pub const TAGS_KEY: &str = "tags";
+
+/// Tags of markets, by market id.
+#[derive(Debug)]
+pub struct MarketTags<S: Storage> {
+    storage: S,
+    key: String,
+    tags: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl<S: Storage> MarketTags<S> {
+    /// Loads the tags saved under `key`, if any.
+    pub fn load(storage: S, key: &str) -> Result<MarketTags<S>> {
+        let tags = storage.load_as(key)?.unwrap_or_default();
+
+        Ok(MarketTags {
+            storage,
+            key: key.to_owned(),
+            tags,
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        self.storage.save_as(&self.key, &self.tags)
+    }
+
+    /// Tags a market. Returns whether it didn't have the tag yet.
+    pub fn tag(&mut self, market_id: &str, tag: &str) -> Result<bool> {
+        let added = self
+            .tags
+            .entry(market_id.to_owned())
+            .or_default()
+            .insert(tag.to_owned());
+
+        if added {
+            self.save()?;
+        }
+
+        Ok(added)
+    }
+
+    /// Removes a tag from a market. Returns whether it had it.
+    pub fn untag(&mut self, market_id: &str, tag: &str) -> Result<bool> {
+        let Some(tags) = self.tags.get_mut(market_id) else {
+            return Ok(false);
+        };
+
+        if !tags.remove(tag) {
+            return Ok(false);
+        }
+
+        if tags.is_empty() {
+            self.tags.remove(market_id);
+        }
+        self.save()?;
+
+        Ok(true)
+    }
+
+    /// Removes all tags of a market, e.g. once it resolved.
+    pub fn forget(&mut self, market_id: &str) -> Result<Option<BTreeSet<String>>> {
+        let tags = self.tags.remove(market_id);
+
+        if tags.is_some() {
+            self.save()?;
+        }
+
+        Ok(tags)
+    }
+
+    /// Renames a tag on every market. Returns how many markets had it.
+    pub fn rename(&mut self, tag: &str, new: &str) -> Result<usize> {
+        let mut renamed = 0;
+
+        for tags in self.tags.values_mut() {
+            if tags.remove(tag) {
+                tags.insert(new.to_owned());
+                renamed += 1;
+            }
+        }
+
+        if renamed > 0 {
+            self.save()?;
+        }
+
+        Ok(renamed)
+    }
+
+    pub fn tags_of(&self, market_id: &str) -> BTreeSet<&str> {
+        self.tags
+            .get(market_id)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Markets with the tag, by id.
+    pub fn tagged(&self, tag: &str) -> Vec<&str> {
+        self.tags
+            .iter()
+            .filter(|(_, tags)| tags.contains(tag))
+            .map(|(market_id, _)| market_id.as_str())
+            .collect()
+    }
+
+    /// Markets with every one of the tags, by id.
+    pub fn tagged_all(&self, tags: &[&str]) -> Vec<&str> {
+        self.tags
+            .iter()
+            .filter(|(_, market_tags)| tags.iter().all(|t| market_tags.contains(*t)))
+            .map(|(market_id, _)| market_id.as_str())
+            .collect()
+    }
+
+    /// Every tag in use, with how many markets have it.
+    pub fn counts(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+
+        for tag in self.tags.values().flatten() {
+            *counts.entry(tag.as_str()).or_default() += 1;
+        }
+
+        counts
+    }
+
+    /// Fetches the markets with the tag, one after the other.
+    pub async fn fetch_tagged(
+        &self,
+        client: &ManifoldClient,
+        tag: &str,
+    ) -> Result<Vec<FullMarket>> {
+        let mut markets = vec![];
+
+        for id in self.tagged(tag) {
+            markets.push(client.get_market(id).await?);
+        }
+
+        Ok(markets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::MemoryStorage;
+
+    use super::*;
+
+    #[test]
+    fn tags_markets() -> Result<()> {
+        let storage = MemoryStorage::default();
+
+        let mut tags = MarketTags::load(&storage, TAGS_KEY)?;
+        assert!(tags.tag("a", "election")?);
+        assert!(tags.tag("a", "review")?);
+        assert!(tags.tag("b", "election")?);
+        assert!(!tags.tag("b", "election")?);
+
+        let mut reloaded = MarketTags::load(&storage, TAGS_KEY)?;
+        assert_eq!(reloaded.tagged("election"), ["a", "b"]);
+        assert_eq!(reloaded.tagged_all(&["election", "review"]), ["a"]);
+        assert_eq!(
+            reloaded.tags_of("a"),
+            BTreeSet::from(["election", "review"])
+        );
+
+        assert_eq!(reloaded.rename("election", "politics")?, 2);
+        assert!(reloaded.untag("a", "review")?);
+        assert!(!reloaded.untag("c", "review")?);
+        assert!(reloaded.forget("b")?.is_some());
+
+        let reloaded = MarketTags::load(&storage, TAGS_KEY)?;
+        assert_eq!(reloaded.counts(), BTreeMap::from([("politics", 1)]));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Text normalization for comparing user-provided strings
+//! (questions, answers) with what's on Manifold.
+
+/// Lowercases, trims and collapses all whitespace runs into single spaces.
+pub fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|w| w.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// [`normalize`], also ignoring punctuation at the ends, so that
+/// `"Will it rain tomorrow?"` and `"will it rain tomorrow"` are the same question.
+pub fn normalize_question(question: &str) -> String {
+    normalize(question)
+        .trim_matches(|c: char| c.is_ascii_punctuation() || c.is_whitespace())
+        .to_owned()
+}
+
+/// [`normalize`], with punctuation replaced by spaces: `"St. Louis-based"` → `"st louis based"`.
+pub fn fold(text: &str) -> String {
+    normalize(
+        &text
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+            .collect::<String>(),
+    )
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (above + 1)
+                .min(row[j] + 1)
+                .min(diagonal + usize::from(ca != cb));
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Edit-distance similarity in `[0, 1]`.
+fn char_similarity(a: &str, b: &str) -> f64 {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let longest = a.len().max(b.len());
+
+    if longest == 0 {
+        return 1.0;
+    }
+
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+fn token_similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        1.0
+    } else if a.len().min(b.len()) >= 3 && (a.starts_with(b) || b.starts_with(a)) {
+        // Abbreviations: "man" / "manchester", "univ" / "university"
+        0.8
+    } else {
+        char_similarity(a, b)
+    }
+}
+
+/// How well each token of `from` is matched by some token of `to`, on average.
+fn tokens_covered(from: &[&str], to: &[&str]) -> f64 {
+    if from.is_empty() {
+        return 0.0;
+    }
+
+    from.iter()
+        .map(|f| {
+            to.iter()
+                .map(|t| token_similarity(f, t))
+                .fold(0.0, f64::max)
+        })
+        .sum::<f64>()
+        / from.len() as f64
+}
+
+/// Fuzzy similarity of two short texts in `[0, 1]`, ignoring case and punctuation.
+///
+/// Tolerates typos, abbreviated words (`"Man United"` ~ `"Manchester United"`)
+/// and different word order.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (fold(a), fold(b));
+
+    if a == b {
+        return 1.0;
+    }
+
+    let a_tokens = a.split(' ').collect::<Vec<_>>();
+    let b_tokens = b.split(' ').collect::<Vec<_>>();
+
+    let tokens =
+        (tokens_covered(&a_tokens, &b_tokens) + tokens_covered(&b_tokens, &a_tokens)) / 2.0;
+
+    tokens.max(char_similarity(&a, &b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_case_and_whitespace() {
+        assert_eq!(normalize("  Man   United\n"), "man united");
+    }
+
+    #[test]
+    fn fuzzy_similarity() {
+        assert_eq!(similarity("Man. United", "man united"), 1.0);
+        assert!(similarity("Man United", "Manchester United") > 0.85);
+        assert!(similarity("Man United", "Manchester City") < 0.6);
+        assert!(similarity("Manchestr Untied", "Manchester United") > 0.8);
+        assert!(similarity("United Manchester", "Manchester United") > 0.95);
+        assert!(similarity("Arsenal", "Chelsea") < 0.3);
+    }
+
+    #[test]
+    fn normalizes_questions() {
+        assert_eq!(
+            normalize_question("Will it  rain tomorrow? "),
+            normalize_question("\"will it rain Tomorrow\"")
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Request latency and server clock skew tracking
+//!
+//! Every response passes through [`LatencyTracker::record`], which keeps
+//! latency statistics and estimates how far the local clock is from the
+//! server's using the `Date` header. Market-making near close times needs
+//! to know both; see [`ManifoldClient::latency_stats`](crate::ManifoldClient::latency_stats).
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderValue;
+
+/// How much a new skew sample moves the estimate.
+const SKEW_SMOOTHING: f64 = 0.2;
+
+/// Snapshot of the client's request timings.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    /// Number of completed requests.
+    pub requests: u64,
+    pub last: Option<Duration>,
+    pub mean: Option<Duration>,
+    pub max: Option<Duration>,
+    /// Estimated `server time - local time`.
+    ///
+    /// The `Date` header only has a one second resolution, so this is
+    /// a smoothed estimate that's accurate to a few hundred milliseconds at best.
+    pub clock_skew: Option<chrono::Duration>,
+
+    total: Duration,
+}
+
+impl LatencyStats {
+    /// Current time on the server, according to the skew estimate.
+    pub fn server_now(&self) -> DateTime<Utc> {
+        self.server_time(Utc::now())
+    }
+
+    /// Time on the server when the local clock shows `local`.
+    pub fn server_time(&self, local: DateTime<Utc>) -> DateTime<Utc> {
+        local + self.clock_skew.unwrap_or_else(chrono::Duration::zero)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct LatencyTracker {
+    stats: Mutex<LatencyStats>,
+}
+
+impl LatencyTracker {
+    pub(crate) fn stats(&self) -> LatencyStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Records a request sent at `sent_at` that took `latency` to get a response
+    /// with the given `Date` header.
+    pub(crate) fn record(
+        &self,
+        sent_at: DateTime<Utc>,
+        latency: Duration,
+        date: Option<&HeaderValue>,
+    ) {
+        let mut stats = self.stats.lock().unwrap();
+
+        stats.requests += 1;
+        stats.total += latency;
+        stats.last = Some(latency);
+        stats.mean = Some(stats.total / stats.requests as u32);
+        stats.max = Some(stats.max.map_or(latency, |max| max.max(latency)));
+
+        let server_time = date
+            .and_then(|d| d.to_str().ok())
+            .and_then(|d| DateTime::parse_from_rfc2822(d).ok());
+
+        if let Some(server_time) = server_time {
+            // The server stamped the response somewhere in the middle of the request,
+            // and truncated the time to the second, so on average it's half a second late.
+            let local_midpoint =
+                sent_at + chrono::Duration::from_std(latency / 2).unwrap_or_default();
+            let sample = server_time.with_timezone(&Utc) + chrono::Duration::milliseconds(500)
+                - local_midpoint;
+
+            stats.clock_skew = Some(match stats.clock_skew {
+                None => sample,
+                Some(skew) => {
+                    let ms = skew.num_milliseconds() as f64 * (1.0 - SKEW_SMOOTHING)
+                        + sample.num_milliseconds() as f64 * SKEW_SMOOTHING;
+
+                    chrono::Duration::milliseconds(ms.round() as i64)
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_skew_from_date_header() {
+        let tracker = LatencyTracker::default();
+        let sent_at = DateTime::parse_from_rfc3339("2024-01-01T12:00:00.000Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        // The server is 10 seconds ahead.
+        let date = HeaderValue::from_static("Mon, 01 Jan 2024 12:00:10 GMT");
+
+        tracker.record(sent_at, Duration::from_millis(200), Some(&date));
+        tracker.record(sent_at, Duration::from_millis(400), None);
+
+        let stats = tracker.stats();
+
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.mean, Some(Duration::from_millis(300)));
+        assert_eq!(stats.max, Some(Duration::from_millis(400)));
+        assert_eq!(
+            stats.clock_skew,
+            Some(chrono::Duration::milliseconds(10_400))
+        );
+    }
+}
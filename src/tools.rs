@@ -0,0 +1,449 @@
+//! Tool definitions for LLM agents
+//!
+//! [`definitions`] describes a few client operations as JSON-schema tools,
+//! in the OpenAI function-calling format (most other APIs accept the same
+//! schemas). When the model calls one, [`ToolCall::parse`] validates the
+//! arguments and a [`ToolDispatcher`] executes it, enforcing its own limits
+//! on top of whatever the model was told: a maximum per bet and, with
+//! [`ToolDispatcher::max_spend`], a budget for the whole session.
+//!
+//! Requires the `tools` feature.
+
+use std::sync::{Arc, Mutex};
+
+use futures_util::{StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::comments::CommentContent;
+use crate::error::{ManifoldError, Result};
+use crate::trading::BetRequest;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// How many results `search_markets` returns at most.
+pub const MAX_SEARCH_RESULTS: usize = 20;
+
+/// The tools, as a JSON array for the `tools` field of a chat completion request.
+pub fn definitions() -> Vec<Value> {
+    let tool = |name: &str, description: &str, parameters: Value| {
+        json!({
+            "type": "function",
+            "function": {
+                "name": name,
+                "description": description,
+                "parameters": parameters,
+            },
+        })
+    };
+
+    vec![
+        tool(
+            "search_markets",
+            "Search Manifold markets by text. Returns their ids, questions and probabilities.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "term": {"type": "string", "description": "Words to search for."},
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": MAX_SEARCH_RESULTS,
+                        "description": "How many markets to return, 10 by default.",
+                    },
+                },
+                "required": ["term"],
+                "additionalProperties": false,
+            }),
+        ),
+        tool(
+            "get_market",
+            "Get a Manifold market with its description and current probability.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "market_id": {"type": "string"},
+                },
+                "required": ["market_id"],
+                "additionalProperties": false,
+            }),
+        ),
+        tool(
+            "place_bet",
+            "Bet mana on YES or NO in a binary market. With limit_prob, the bet only fills up to that probability and the rest stays as a limit order.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "market_id": {"type": "string"},
+                    "outcome": {"type": "string", "enum": ["YES", "NO"]},
+                    "amount": {"type": "integer", "minimum": 1, "description": "Mana to spend."},
+                    "limit_prob": {"type": "number", "minimum": 0.001, "maximum": 0.999},
+                },
+                "required": ["market_id", "outcome", "amount"],
+                "additionalProperties": false,
+            }),
+        ),
+        tool(
+            "comment",
+            "Post a comment on a Manifold market.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "market_id": {"type": "string"},
+                    "markdown": {"type": "string", "description": "The comment, as markdown."},
+                },
+                "required": ["market_id", "markdown"],
+                "additionalProperties": false,
+            }),
+        ),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchMarkets {
+    pub term: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GetMarket {
+    pub market_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaceBet {
+    pub market_id: String,
+    pub outcome: Outcome,
+    pub amount: u64,
+    #[serde(default)]
+    pub limit_prob: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Comment {
+    pub market_id: String,
+    pub markdown: String,
+}
+
+/// A validated call to one of the [`definitions`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolCall {
+    SearchMarkets(SearchMarkets),
+    GetMarket(GetMarket),
+    PlaceBet(PlaceBet),
+    Comment(Comment),
+}
+
+fn invalid(message: impl Into<String>) -> ManifoldError {
+    ManifoldError::InvalidToolCall(message.into())
+}
+
+fn parse_arguments<T: DeserializeOwned>(name: &str, arguments: &str) -> Result<T> {
+    serde_json::from_str(arguments).map_err(|e| invalid(format!("{name}: {e}")))
+}
+
+impl ToolCall {
+    /// Parses a call from the tool `name` and its `arguments`, a JSON object
+    /// as a string (the way models return them).
+    ///
+    /// Rejects unknown tools, unknown or missing arguments and values outside
+    /// the ranges in the schemas.
+    pub fn parse(name: &str, arguments: &str) -> Result<ToolCall> {
+        let call = match name {
+            "search_markets" => ToolCall::SearchMarkets(parse_arguments(name, arguments)?),
+            "get_market" => ToolCall::GetMarket(parse_arguments(name, arguments)?),
+            "place_bet" => ToolCall::PlaceBet(parse_arguments(name, arguments)?),
+            "comment" => ToolCall::Comment(parse_arguments(name, arguments)?),
+            _ => return Err(invalid(format!("unknown tool {name:?}"))),
+        };
+
+        call.validate()?;
+
+        Ok(call)
+    }
+
+    fn validate(&self) -> Result<()> {
+        match self {
+            ToolCall::SearchMarkets(args) => {
+                if args.term.trim().is_empty() {
+                    return Err(invalid("search_markets: term is empty"));
+                }
+                if args
+                    .limit
+                    .is_some_and(|l| !(1..=MAX_SEARCH_RESULTS).contains(&l))
+                {
+                    return Err(invalid(format!(
+                        "search_markets: limit must be between 1 and {MAX_SEARCH_RESULTS}"
+                    )));
+                }
+            }
+            ToolCall::GetMarket(_) => {}
+            ToolCall::PlaceBet(args) => {
+                if !matches!(args.outcome, Outcome::Yes | Outcome::No) {
+                    return Err(invalid("place_bet: outcome must be YES or NO"));
+                }
+                if args.amount == 0 {
+                    return Err(invalid("place_bet: amount must be positive"));
+                }
+                if args
+                    .limit_prob
+                    .is_some_and(|p| !(0.001..=0.999).contains(&p))
+                {
+                    return Err(invalid(
+                        "place_bet: limit_prob must be between 0.001 and 0.999",
+                    ));
+                }
+            }
+            ToolCall::Comment(args) => {
+                if args.markdown.trim().is_empty() {
+                    return Err(invalid("comment: markdown is empty"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Executes [`ToolCall`]s with a client.
+///
+/// Clones share the mana spent so far, so they count against the same
+/// [`max_spend`](Self::max_spend).
+#[derive(Debug, Clone)]
+pub struct ToolDispatcher<'a> {
+    client: &'a ManifoldClient,
+    max_bet: u64,
+    max_spend: Option<u64>,
+    spent: Arc<Mutex<u64>>,
+    allow_comments: bool,
+}
+
+impl<'a> ToolDispatcher<'a> {
+    /// A dispatcher that refuses bets over `max_bet` mana; 0 disables betting.
+    pub fn new(client: &'a ManifoldClient, max_bet: u64) -> ToolDispatcher<'a> {
+        ToolDispatcher {
+            client,
+            max_bet,
+            max_spend: None,
+            spent: Default::default(),
+            allow_comments: true,
+        }
+    }
+
+    /// Refuse bets once they'd take the mana bet through this dispatcher
+    /// over `total`. Unlimited by default.
+    ///
+    /// Limit orders count with their full amount, filled or not.
+    pub fn max_spend(mut self, total: u64) -> Self {
+        self.max_spend = Some(total);
+        self
+    }
+
+    pub fn allow_comments(mut self, allow: bool) -> Self {
+        self.allow_comments = allow;
+        self
+    }
+
+    /// Mana bet through this dispatcher and its clones so far.
+    pub fn spent(&self) -> u64 {
+        *self.spent.lock().unwrap()
+    }
+
+    /// Counts `amount` against [`max_spend`](Self::max_spend), failing if it doesn't fit.
+    fn reserve(&self, amount: u64) -> Result<()> {
+        let mut spent = self.spent.lock().unwrap();
+
+        if let Some(max_spend) = self.max_spend {
+            if *spent + amount > max_spend {
+                return Err(invalid(format!(
+                    "place_bet: amount {amount} is over the {} left of the session's {max_spend}",
+                    max_spend.saturating_sub(*spent)
+                )));
+            }
+        }
+
+        *spent += amount;
+
+        Ok(())
+    }
+
+    /// [`ToolCall::parse`]s and [`dispatch`](Self::dispatch)es a call.
+    pub async fn call(&self, name: &str, arguments: &str) -> Result<Value> {
+        self.dispatch(&ToolCall::parse(name, arguments)?).await
+    }
+
+    /// Executes `call`, returning the result to pass back to the model.
+    pub async fn dispatch(&self, call: &ToolCall) -> Result<Value> {
+        match call {
+            ToolCall::SearchMarkets(args) => {
+                let markets: Vec<LiteMarket> = self
+                    .client
+                    .stream_search_markets(&args.term, None, None, None)
+                    .take(args.limit.unwrap_or(10))
+                    .try_collect()
+                    .await?;
+
+                Ok(markets
+                    .iter()
+                    .map(|m| {
+                        json!({
+                            "id": m.id(),
+                            "question": m.question(),
+                            "outcome_type": m.data()["outcomeType"],
+                            "probability": m.probability(),
+                            "is_resolved": m.is_resolved(),
+                        })
+                    })
+                    .collect())
+            }
+            ToolCall::GetMarket(args) => Ok(self.client.get_market(&args.market_id).await?.0),
+            ToolCall::PlaceBet(args) => {
+                if args.amount > self.max_bet {
+                    return Err(invalid(format!(
+                        "place_bet: amount {} is over the limit of {}",
+                        args.amount, self.max_bet
+                    )));
+                }
+
+                let mut request =
+                    BetRequest::new(args.amount, &args.market_id, args.outcome.clone());
+                if let Some(prob) = args.limit_prob {
+                    request = request.limit_prob(prob);
+                }
+
+                self.reserve(args.amount)?;
+
+                match self.client.post_bet(request).await {
+                    Ok(bet) => Ok(bet.0),
+                    Err(e) => {
+                        // After a failed connection or a server error the bet
+                        // may have gone through, so it stays counted.
+                        let maybe_placed = match &e {
+                            ManifoldError::HttpError(e) => e.status().is_none(),
+                            ManifoldError::Server(..) => true,
+                            _ => false,
+                        };
+
+                        if !maybe_placed {
+                            *self.spent.lock().unwrap() -= args.amount;
+                        }
+
+                        Err(e)
+                    }
+                }
+            }
+            ToolCall::Comment(args) => {
+                if !self.allow_comments {
+                    return Err(invalid("comment: commenting is disabled"));
+                }
+
+                let comment = self
+                    .client
+                    .post_comment(
+                        &args.market_id,
+                        CommentContent::Markdown(args.markdown.clone()),
+                    )
+                    .await?;
+
+                Ok(comment.0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, MockRequest, MockResponse, MockServer};
+
+    #[test]
+    fn parses_and_validates_calls() -> Result<()> {
+        let names = definitions()
+            .iter()
+            .map(|t| t["function"]["name"].as_str().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["search_markets", "get_market", "place_bet", "comment"]
+        );
+
+        assert_eq!(
+            ToolCall::parse(
+                "place_bet",
+                r#"{"market_id": "m", "outcome": "NO", "amount": 10, "limit_prob": 0.4}"#
+            )?,
+            ToolCall::PlaceBet(PlaceBet {
+                market_id: "m".to_owned(),
+                outcome: Outcome::No,
+                amount: 10,
+                limit_prob: Some(0.4),
+            })
+        );
+
+        for (name, arguments) in [
+            ("sell_everything", "{}"),
+            ("get_market", r#"{"market_id": "m", "extra": 1}"#),
+            ("place_bet", r#"{"market_id": "m", "outcome": "YES"}"#),
+            (
+                "place_bet",
+                r#"{"market_id": "m", "outcome": "YES", "amount": 0}"#,
+            ),
+            (
+                "place_bet",
+                r#"{"market_id": "m", "outcome": "YES", "amount": 5, "limit_prob": 1.5}"#,
+            ),
+            ("search_markets", r#"{"term": "ai", "limit": 500}"#),
+            ("comment", r#"{"market_id": "m", "markdown": " "}"#),
+        ] {
+            assert!(
+                matches!(
+                    ToolCall::parse(name, arguments),
+                    Err(ManifoldError::InvalidToolCall(_))
+                ),
+                "{name} {arguments}"
+            );
+        }
+
+        Ok(())
+    }
+    #[tokio::test]
+    async fn caps_the_sessions_spending() -> Result<()> {
+        let server = MockServer::start(|req: &MockRequest| match req.body["amount"].as_u64() {
+            Some(30) => Some(MockResponse::status(
+                400,
+                json!({"message": "Insufficient balance"}),
+            )),
+            Some(_) => Some(MockResponse::json(fixtures::bet_with_fills().0)),
+            None => None,
+        })
+        .await?;
+        let client = server.client().build()?;
+
+        let dispatcher = ToolDispatcher::new(&client, 50).max_spend(100);
+        let bet =
+            |amount: u64| format!(r#"{{"market_id": "m", "outcome": "YES", "amount": {amount}}}"#);
+
+        dispatcher.call("place_bet", &bet(50)).await?;
+
+        // Rejected by the server, so it doesn't count.
+        assert!(dispatcher.call("place_bet", &bet(30)).await.is_err());
+
+        dispatcher.clone().call("place_bet", &bet(40)).await?;
+        assert_eq!(dispatcher.spent(), 90);
+
+        assert!(matches!(
+            dispatcher.call("place_bet", &bet(20)).await,
+            Err(ManifoldError::InvalidToolCall(_))
+        ));
+        dispatcher.call("place_bet", &bet(10)).await?;
+        assert_eq!(server.requests().len(), 4);
+
+        Ok(())
+    }
+}
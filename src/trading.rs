@@ -0,0 +1,586 @@
+//! Trading helpers on top of the bet endpoints
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+
+use crate::error::{ManifoldError, Result};
+use crate::math::CpmmPool;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// A bet for [`ManifoldClient::post_bet`], with every parameter `POST /v0/bet` takes.
+///
+/// ```
+/// # use chrono::{Duration, Utc};
+/// # use manifold_markets::trading::BetRequest;
+/// # use manifold_markets::types::Outcome;
+/// let order = BetRequest::new(25, "market-id", Outcome::Yes)
+///     .limit_prob(0.4)
+///     .expires_at(Utc::now() + Duration::hours(1))
+///     .build()?;
+/// # Ok::<(), manifold_markets::error::ManifoldError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct BetRequest {
+    args: PlaceBetArgs,
+}
+
+impl BetRequest {
+    pub fn new(amount: u64, contract_id: &str, outcome: Outcome) -> BetRequest {
+        BetRequest {
+            args: PlaceBetArgs::new(amount, contract_id, outcome),
+        }
+    }
+
+    /// Makes it a limit order at `prob`, 0.001 to 0.999.
+    pub fn limit_prob(mut self, prob: f64) -> Self {
+        self.args.limit_prob = Some(prob);
+        self
+    }
+
+    /// Limit orders: cancel whatever isn't filled by `time`.
+    pub fn expires_at(mut self, time: DateTime<Utc>) -> Self {
+        self.args.expires_at = Some(time.timestamp_millis());
+        self
+    }
+
+    /// `cpmm-multi-1` markets: the answer to bet `YES` or `NO` on.
+    pub fn answer_id(mut self, answer_id: &str) -> Self {
+        self.args.answer_id = Some(answer_id.to_owned());
+        self
+    }
+
+    /// Only simulate the bet, e.g. to see the fill and fees before committing.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.args.dry_run = dry_run;
+        self
+    }
+
+    /// The parameters, if they make sense together.
+    ///
+    /// Fails with [`ManifoldError::Validation`] on a zero amount, a limit
+    /// outside of 0.001..=0.999, an expiration without a limit, or an answer
+    /// with an outcome other than `YES` or `NO`.
+    pub fn build(self) -> Result<PlaceBetArgs> {
+        let args = self.args;
+        let invalid = |message: String| Err(ManifoldError::Validation(message));
+
+        if args.amount == 0 {
+            return invalid("a bet needs an amount".to_owned());
+        }
+
+        if let Some(prob) = args.limit_prob.filter(|p| !(0.001..=0.999).contains(p)) {
+            return invalid(format!(
+                "limit probability {prob} is not between 0.001 and 0.999"
+            ));
+        }
+
+        if args.expires_at.is_some() && args.limit_prob.is_none() {
+            return invalid("only limit orders can expire".to_owned());
+        }
+
+        if args.answer_id.is_some() && !matches!(args.outcome, Outcome::Yes | Outcome::No) {
+            return invalid(format!(
+                "bets on an answer are YES or NO, not {}",
+                args.outcome
+            ));
+        }
+
+        Ok(args)
+    }
+}
+
+impl From<PlaceBetArgs> for BetRequest {
+    fn from(args: PlaceBetArgs) -> Self {
+        BetRequest { args }
+    }
+}
+
+/// Outcome of each leg of [`ManifoldClient::post_basket`], in the order they were given.
+#[derive(Debug)]
+pub struct BasketReport {
+    pub legs: Vec<(PlaceBetArgs, Result<Bet>)>,
+}
+
+impl BasketReport {
+    pub fn succeeded(&self) -> impl Iterator<Item = (&PlaceBetArgs, &Bet)> {
+        self.legs
+            .iter()
+            .filter_map(|(args, result)| Some((args, result.as_ref().ok()?)))
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = (&PlaceBetArgs, &ManifoldError)> {
+        self.legs
+            .iter()
+            .filter_map(|(args, result)| Some((args, result.as_ref().err()?)))
+    }
+
+    /// Whether every leg was placed.
+    pub fn is_complete(&self) -> bool {
+        self.legs.iter().all(|(_, result)| result.is_ok())
+    }
+}
+
+/// What [`ManifoldClient::buy_shares`] did.
+#[derive(Debug)]
+pub struct SharesBought {
+    /// Shares asked for.
+    pub requested: f64,
+    /// Shares the pool promised for the bet's amount, before fees.
+    pub expected: f64,
+    pub bet: Bet,
+}
+
+impl SharesBought {
+    /// Shares the bet actually got.
+    pub fn achieved(&self) -> f64 {
+        self.bet.shares()
+    }
+
+    /// Requested minus achieved shares; positive if fees or a moved pool cost some.
+    pub fn shortfall(&self) -> f64 {
+        self.requested - self.achieved()
+    }
+}
+
+/// The bet that buys about `shares` of `outcome` from the market's pool, and the shares it
+/// should get.
+///
+/// The amount is rounded up to whole mana, so ignoring fees the bet gets at least `shares`.
+pub fn share_buy(
+    market: &impl Market,
+    outcome: Outcome,
+    shares: f64,
+) -> Result<(PlaceBetArgs, f64)> {
+    // Checks that the market is binary and the outcome YES or NO.
+    let args = BetEncoding::of(market)?.binary_bet(market.id(), outcome, 0, None)?;
+
+    let pool = CpmmPool::from_market(market).ok_or_else(|| {
+        ManifoldError::Validation(format!("market {} has no CPMM pool", market.id()))
+    })?;
+
+    let amount = pool.cost_of_shares(&args.outcome, shares)?.ceil().max(1.0);
+    let expected = pool.shares_for(&args.outcome, amount)?;
+
+    Ok((
+        PlaceBetArgs {
+            amount: amount as u64,
+            ..args
+        },
+        expected,
+    ))
+}
+
+/// The fewest shares [`ManifoldClient::sell_fraction`] sells, so a small
+/// fraction of a small position isn't sent as a sale of dust.
+pub const MIN_SELL_SHARES: f64 = 0.01;
+
+/// How many of `held` shares selling `fraction` of them means, or `None` for all of them.
+///
+/// Fails if `fraction` isn't in `(0, 1]` or the result is below [`MIN_SELL_SHARES`].
+pub fn shares_to_sell(held: f64, fraction: f64) -> Result<Option<f64>> {
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(ManifoldError::Validation(format!(
+            "can't sell a fraction of {fraction} of a position"
+        )));
+    }
+
+    if fraction == 1.0 && held > 0.0 {
+        // Selling everything leaves no dust behind.
+        return Ok(None);
+    }
+
+    let shares = held * fraction;
+
+    if shares.is_nan() || shares < MIN_SELL_SHARES {
+        return Err(ManifoldError::Validation(format!(
+            "selling {fraction} of {held} shares is below the minimum of {MIN_SELL_SHARES}"
+        )));
+    }
+
+    Ok(Some(shares))
+}
+
+/// How a bet on a market picks its outcome, which depends on the market's mechanism.
+///
+/// [`post_bet`](ManifoldClient::post_bet) takes the outcome as is, and a `YES`
+/// sent to a multiple choice market without an answer fails. [`ManifoldClient::bet_yes`],
+/// [`bet_no`](ManifoldClient::bet_no) and [`bet_answer`](ManifoldClient::bet_answer)
+/// encode it for the market instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BetEncoding {
+    /// Binary and pseudo-numeric markets: `YES` or `NO`.
+    Binary,
+    /// `cpmm-multi-1` markets: `YES` or `NO` on an answer, given as `answerId`.
+    MultiAnswer,
+    /// `dpm-2` free response markets: the answer's id as the outcome. Answers can only be bought.
+    DpmAnswer,
+}
+
+impl BetEncoding {
+    pub fn of(market: &impl Market) -> Result<BetEncoding> {
+        if market.mechanism() == Some("cpmm-multi-1") {
+            return Ok(BetEncoding::MultiAnswer);
+        }
+
+        match (market.mechanism(), market.try_outcome_type()?) {
+            (_, OutcomeType::Binary | OutcomeType::PseudoNumeric | OutcomeType::Stonk) => {
+                Ok(BetEncoding::Binary)
+            }
+            (Some("dpm-2"), _) => Ok(BetEncoding::DpmAnswer),
+            (mechanism, outcome_type) => Err(ManifoldError::Validation(format!(
+                "don't know how to bet on market {} ({outcome_type:?}, mechanism {mechanism:?})",
+                market.id()
+            ))),
+        }
+    }
+
+    /// A bet of `side` (`YES` or `NO`) on a binary market.
+    pub fn binary_bet(
+        self,
+        market_id: &str,
+        side: Outcome,
+        amount: u64,
+        limit_prob: Option<f64>,
+    ) -> Result<PlaceBetArgs> {
+        check_side(&side)?;
+
+        if self != BetEncoding::Binary {
+            return Err(ManifoldError::Validation(format!(
+                "market {market_id} has answers; bet on one of them with bet_answer"
+            )));
+        }
+
+        Ok(PlaceBetArgs {
+            limit_prob,
+            ..PlaceBetArgs::new(amount, market_id, side)
+        })
+    }
+
+    /// A bet of `side` (`YES` or `NO`) on an answer of a multiple choice or free response market.
+    pub fn answer_bet(
+        self,
+        market_id: &str,
+        answer_id: &str,
+        side: Outcome,
+        amount: u64,
+    ) -> Result<PlaceBetArgs> {
+        check_side(&side)?;
+
+        match self {
+            BetEncoding::Binary => Err(ManifoldError::Validation(format!(
+                "market {market_id} has no answers; use bet_yes or bet_no"
+            ))),
+            BetEncoding::MultiAnswer => Ok(PlaceBetArgs {
+                answer_id: Some(answer_id.to_owned()),
+                ..PlaceBetArgs::new(amount, market_id, side)
+            }),
+            BetEncoding::DpmAnswer if side == Outcome::Yes => Ok(PlaceBetArgs::new(
+                amount,
+                market_id,
+                Outcome::FreeResponse(answer_id.to_owned()),
+            )),
+            BetEncoding::DpmAnswer => Err(ManifoldError::Validation(format!(
+                "answers of market {market_id} can only be bought, not bet NO on"
+            ))),
+        }
+    }
+}
+
+fn check_side(side: &Outcome) -> Result<()> {
+    match side {
+        Outcome::Yes | Outcome::No => Ok(()),
+        other => Err(ManifoldError::Validation(format!(
+            "a bet's side is YES or NO, not {other:?}"
+        ))),
+    }
+}
+
+impl ManifoldClient {
+    /// How bets on a market are encoded, fetching the market the first time.
+    ///
+    /// A market's mechanism never changes, so this is cached for the client's lifetime.
+    pub async fn bet_encoding(&self, market_id: &str) -> Result<BetEncoding> {
+        if let Some(encoding) = self.bet_encodings.lock().unwrap().get(market_id) {
+            return Ok(*encoding);
+        }
+
+        let encoding = BetEncoding::of(&self.get_market(market_id).await?)?;
+
+        self.bet_encodings
+            .lock()
+            .unwrap()
+            .insert(market_id.to_owned(), encoding);
+
+        Ok(encoding)
+    }
+
+    /// Bets YES on a binary or pseudo-numeric market, optionally as a limit order.
+    pub async fn bet_yes(&self, market_id: &str, amount: u64, limit: Option<f64>) -> Result<Bet> {
+        let args = self.bet_encoding(market_id).await?.binary_bet(
+            market_id,
+            Outcome::Yes,
+            amount,
+            limit,
+        )?;
+
+        self.place_bet(&args).await
+    }
+
+    /// Bets NO on a binary or pseudo-numeric market, optionally as a limit order.
+    pub async fn bet_no(&self, market_id: &str, amount: u64, limit: Option<f64>) -> Result<Bet> {
+        let args = self.bet_encoding(market_id).await?.binary_bet(
+            market_id,
+            Outcome::No,
+            amount,
+            limit,
+        )?;
+
+        self.place_bet(&args).await
+    }
+
+    /// Bets `side` (`YES` or `NO`) on an answer, however the market's mechanism wants it.
+    pub async fn bet_answer(
+        &self,
+        market_id: &str,
+        answer_id: &str,
+        side: Outcome,
+        amount: u64,
+    ) -> Result<Bet> {
+        let args = self
+            .bet_encoding(market_id)
+            .await?
+            .answer_bet(market_id, answer_id, side, amount)?;
+
+        self.place_bet(&args).await
+    }
+
+    /// Bets on a binary market for about `shares` of `outcome`, priced at the current pool
+    /// (see [`share_buy`]).
+    ///
+    /// Fees and limit orders in the market make the achieved share count differ slightly.
+    pub async fn buy_shares(
+        &self,
+        market_id: &str,
+        outcome: Outcome,
+        shares: f64,
+    ) -> Result<SharesBought> {
+        let market = self.get_market(market_id).await?;
+        let (args, expected) = share_buy(&market, outcome, shares)?;
+
+        Ok(SharesBought {
+            requested: shares,
+            expected,
+            bet: self.place_bet(&args).await?,
+        })
+    }
+
+    /// Sells `fraction` (0 to 1) of the authorized user's `outcome` shares in a binary market.
+    ///
+    /// Looks the position up first, so callers don't have to compute share counts;
+    /// see [`shares_to_sell`] for the validation.
+    pub async fn sell_fraction(
+        &self,
+        market_id: &str,
+        outcome: Outcome,
+        fraction: f64,
+    ) -> Result<Bet> {
+        check_side(&outcome)?;
+
+        let me = self.get_me().await?;
+        let held: f64 = self
+            .get_market_positions(market_id, None, None, None, Some(me.id()))
+            .await?
+            .iter()
+            .filter(|position| position.answer_id().is_none())
+            .map(|position| position.shares(&outcome.to_string()))
+            .sum();
+
+        let shares = shares_to_sell(held, fraction)?;
+
+        self.sell_shares(market_id, Some(outcome), shares, None)
+            .await
+    }
+
+    /// How much time is left to place a bet on `market`, keeping a `safety_margin`.
+    ///
+    /// Uses the server's clock (the client's [`clock`](Self::clock), corrected as in
+    /// [`latency_stats`](Self::latency_stats)) and
+    /// accounts for the mean request latency. Fails with [`ManifoldError::MarketClosing`]
+    /// if a bet sent now can't plausibly land before the market closes.
+    ///
+    /// Markets without a close time always have time left.
+    pub fn time_to_bet(
+        &self,
+        market: &impl Market,
+        safety_margin: Duration,
+    ) -> Result<Option<Duration>> {
+        let Some(close_time) = market.close_time() else {
+            return Ok(None);
+        };
+
+        let stats = self.latency_stats();
+        let needed = safety_margin + stats.mean.unwrap_or_default();
+        let left = (close_time - stats.server_time(self.now()))
+            .to_std()
+            .unwrap_or_default();
+
+        if left <= needed {
+            return Err(ManifoldError::MarketClosing(format!(
+                "market {} closes at {close_time}, {left:?} from now; a bet needs at least {needed:?}",
+                market.id()
+            )));
+        }
+
+        Ok(Some(left - needed))
+    }
+
+    /// Places a bet, unless the market closes within `safety_margin` (see [`time_to_bet`](Self::time_to_bet)).
+    ///
+    /// Saves a request that would fail anyway, with a clearer error than the server's.
+    pub async fn post_bet_before_close(
+        &self,
+        args: &PlaceBetArgs,
+        safety_margin: Duration,
+    ) -> Result<Bet> {
+        let market = self.get_market(&args.contract_id).await?;
+
+        self.time_to_bet(&market, safety_margin)?;
+
+        self.place_bet(args).await
+    }
+
+    /// Places bets on several markets at once, e.g. to trade correlated markets together.
+    ///
+    /// All legs are sent concurrently. A failed leg doesn't stop or undo the
+    /// others; the report says which ones went through, so the caller can
+    /// retry or unwind.
+    pub async fn post_basket(&self, legs: Vec<PlaceBetArgs>) -> BasketReport {
+        let results = join_all(legs.iter().map(|args| self.place_bet(args))).await;
+
+        BasketReport {
+            legs: legs.into_iter().zip(results).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn checks_bet_requests() -> Result<()> {
+        let expiry = chrono::TimeZone::with_ymd_and_hms(&Utc, 2030, 1, 1, 0, 0, 0).unwrap();
+
+        let order = BetRequest::new(25, "m", Outcome::No)
+            .limit_prob(0.4)
+            .expires_at(expiry)
+            .answer_id("a")
+            .dry_run(true)
+            .build()?;
+        assert_eq!(
+            serde_json::to_value(&order)?,
+            serde_json::json!({
+                "amount": 25,
+                "contractId": "m",
+                "outcome": "NO",
+                "limitProb": 0.4,
+                "answerId": "a",
+                "expiresAt": expiry.timestamp_millis(),
+                "dryRun": true,
+            })
+        );
+
+        let invalid = [
+            BetRequest::new(0, "m", Outcome::Yes),
+            BetRequest::new(10, "m", Outcome::Yes).limit_prob(1.0),
+            BetRequest::new(10, "m", Outcome::Yes).expires_at(expiry),
+            BetRequest::new(10, "m", Outcome::FreeResponse("a".to_owned())).answer_id("a"),
+        ];
+
+        for request in invalid {
+            assert!(
+                matches!(request.clone().build(), Err(ManifoldError::Validation(_))),
+                "{request:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encodes_bets_per_mechanism() -> Result<()> {
+        let binary = BetEncoding::of(&fixtures::binary_market())?;
+        let multi = BetEncoding::of(&fixtures::multi_market())?;
+        let dpm = BetEncoding::of(&fixtures::dpm_market())?;
+
+        assert_eq!(
+            (binary, multi, dpm),
+            (
+                BetEncoding::Binary,
+                BetEncoding::MultiAnswer,
+                BetEncoding::DpmAnswer
+            )
+        );
+
+        let yes = binary.binary_bet("m", Outcome::Yes, 10, Some(0.4))?;
+        assert_eq!((yes.outcome, yes.limit_prob), (Outcome::Yes, Some(0.4)));
+
+        let no = multi.answer_bet("m", "answer1", Outcome::No, 10)?;
+        assert_eq!(
+            (no.outcome, no.answer_id.as_deref()),
+            (Outcome::No, Some("answer1"))
+        );
+
+        let buy = dpm.answer_bet("m", "1", Outcome::Yes, 10)?;
+        assert_eq!(
+            (buy.outcome, buy.answer_id),
+            (Outcome::FreeResponse("1".to_owned()), None)
+        );
+
+        assert!(multi.binary_bet("m", Outcome::Yes, 10, None).is_err());
+        assert!(binary.answer_bet("m", "a", Outcome::Yes, 10).is_err());
+        assert!(dpm.answer_bet("m", "1", Outcome::No, 10).is_err());
+        assert!(binary
+            .binary_bet("m", Outcome::FreeResponse("a".to_owned()), 10, None)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn prices_share_buys_from_the_pool() -> Result<()> {
+        let market = fixtures::binary_market();
+        let (args, expected) = share_buy(&market, Outcome::Yes, 50.0)?;
+
+        assert_eq!(args.outcome, Outcome::Yes);
+        assert!(expected >= 50.0);
+
+        // One mana less wouldn't have been enough.
+        let pool = CpmmPool::from_market(&market).unwrap();
+        assert!(pool.shares_for(&Outcome::Yes, args.amount as f64 - 1.0)? < 50.0);
+
+        assert!(share_buy(&fixtures::multi_market(), Outcome::Yes, 50.0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn validates_sold_fractions() -> Result<()> {
+        assert_eq!(shares_to_sell(100.0, 0.25)?, Some(25.0));
+        assert_eq!(shares_to_sell(10.9, 0.5)?, Some(5.45));
+        assert_eq!(shares_to_sell(0.4, 1.0)?, None);
+        assert_eq!(shares_to_sell(1.5, 0.5)?, Some(0.75));
+
+        assert!(shares_to_sell(0.015, 0.5).is_err());
+        assert!(shares_to_sell(0.0, 1.0).is_err());
+        assert!(shares_to_sell(100.0, 0.0).is_err());
+        assert!(shares_to_sell(100.0, 1.5).is_err());
+        assert!(shares_to_sell(100.0, f64::NAN).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,650 @@
+//! Client-side conditional orders
+//!
+//! Manifold has no conditional orders, so a [`TriggerSet`] keeps them locally:
+//! "if market X goes above 60%, buy NO on Y for M$50", "if X resolves YES,
+//! sell everything on Z". Triggers are persisted through a [`Storage`], checked
+//! against fresh market data (see [`ManifoldClient::watch_markets`]), and fire once.
+//!
+//! Bets go through [`ManifoldClient::post_bet_before_close`], so a trigger that
+//! fires right before a market closes fails cleanly instead of racing the close.
+//!
+//! Manifold has no limit sells either. [`TriggerSet::add_limit_sell`] emulates
+//! one with a trigger that sells once the probability gets past the limit, and
+//! [`TriggerSet::limit_sells`] lists them as [`LimitSell`]s, mirroring the
+//! [`LimitOrder`](crate::orders::LimitOrder)s of the buy side.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::{ManifoldError, Result};
+use crate::storage::Storage;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// When a [`Trigger`] fires.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Condition {
+    #[serde(rename_all = "camelCase")]
+    ProbAbove { market_id: String, prob: f64 },
+    #[serde(rename_all = "camelCase")]
+    ProbBelow { market_id: String, prob: f64 },
+    /// The market resolved to `resolution` (`YES`, `NO`, `MKT`, `CANCEL` or an answer id).
+    #[serde(rename_all = "camelCase")]
+    ResolvedAs {
+        market_id: String,
+        resolution: String,
+    },
+}
+
+impl Condition {
+    /// The market this condition looks at.
+    pub fn market_id(&self) -> &str {
+        match self {
+            Condition::ProbAbove { market_id, .. }
+            | Condition::ProbBelow { market_id, .. }
+            | Condition::ResolvedAs { market_id, .. } => market_id,
+        }
+    }
+
+    /// Whether `market` satisfies this condition; always false for other markets.
+    pub fn is_met(&self, market: &impl Market) -> bool {
+        if market.id() != self.market_id() {
+            return false;
+        }
+
+        match self {
+            Condition::ProbAbove { prob, .. } => {
+                !market.is_resolved() && market.probability().is_some_and(|p| p > *prob)
+            }
+            Condition::ProbBelow { prob, .. } => {
+                !market.is_resolved() && market.probability().is_some_and(|p| p < *prob)
+            }
+            Condition::ResolvedAs { resolution, .. } => {
+                market.is_resolved() && market.resolution() == Some(resolution)
+            }
+        }
+    }
+}
+
+/// What a [`Trigger`] does when it fires.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Action {
+    Bet(PlaceBetArgs),
+    /// Sells all shares in the market, or only those of `outcome`.
+    #[serde(rename_all = "camelCase")]
+    SellAll {
+        market_id: String,
+        outcome: Option<Outcome>,
+    },
+    /// Sells `shares` of `outcome` in the market, or all of them.
+    #[serde(rename_all = "camelCase")]
+    Sell {
+        market_id: String,
+        outcome: Outcome,
+        shares: Option<f64>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trigger {
+    pub id: String,
+    pub condition: Condition,
+    pub action: Action,
+    pub created_time: i64,
+}
+
+impl Trigger {
+    pub fn new(condition: Condition, action: Action) -> Trigger {
+        Trigger::new_at(condition, action, Utc::now())
+    }
+
+    /// A trigger created at `now`, e.g. [`ManifoldClient::now`].
+    pub fn new_at(condition: Condition, action: Action, now: DateTime<Utc>) -> Trigger {
+        // Triggers created at the same time, which a mock clock makes likely, still get their own ids.
+        static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+        let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+
+        Trigger {
+            id: format!(
+                "{:x}-{sequence:x}",
+                now.timestamp_nanos_opt().unwrap_or_default()
+            ),
+            condition,
+            action,
+            created_time: now.timestamp_millis(),
+        }
+    }
+}
+
+/// A limit sell emulated by a trigger, see [`TriggerSet::add_limit_sell`].
+///
+/// Like a [`LimitOrder`](crate::orders::LimitOrder), `limit_prob` is a
+/// probability for both sides: YES shares are sold once the probability
+/// goes above it, NO shares once it goes below.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitSell {
+    pub trigger_id: String,
+    pub market_id: String,
+    /// `true` to sell YES shares.
+    pub yes: bool,
+    pub limit_prob: f64,
+    /// Shares to sell; all of the side's if `None`.
+    pub shares: Option<f64>,
+}
+
+impl LimitSell {
+    /// The limit sell `trigger` emulates, if it's one.
+    pub fn from_trigger(trigger: &Trigger) -> Option<LimitSell> {
+        let Action::Sell {
+            market_id,
+            outcome,
+            shares,
+        } = &trigger.action
+        else {
+            return None;
+        };
+
+        let (yes, limit_prob) = match (&trigger.condition, outcome) {
+            (Condition::ProbAbove { prob, .. }, Outcome::Yes) => (true, *prob),
+            (Condition::ProbBelow { prob, .. }, Outcome::No) => (false, *prob),
+            _ => return None,
+        };
+
+        (trigger.condition.market_id() == market_id).then(|| LimitSell {
+            trigger_id: trigger.id.clone(),
+            market_id: market_id.clone(),
+            yes,
+            limit_prob,
+            shares: *shares,
+        })
+    }
+}
+
+/// A trigger that fired, with the result of its action.
+#[derive(Debug)]
+pub struct FiredTrigger {
+    pub trigger: Trigger,
+    pub result: Result<Value>,
+    /// Whether the action failed in a way that shows it didn't go through,
+    /// so the trigger was put back to fire again; see [`TriggerSet::on_market`].
+    pub rearmed: bool,
+}
+
+/// Whether a failed action surely didn't go through and may simply be tried
+/// again: rate limits and failed connections. After a timeout or a server
+/// error the trade may have been made, like for [`retry`](crate::retry).
+fn can_rearm(error: &ManifoldError) -> bool {
+    match error {
+        ManifoldError::RateLimited(..) => true,
+        ManifoldError::HttpError(e) => e.is_connect(),
+        _ => false,
+    }
+}
+
+/// A persisted set of triggers, see the [module docs](self).
+#[derive(Debug)]
+pub struct TriggerSet<S: Storage> {
+    storage: S,
+    key: String,
+    triggers: Vec<Trigger>,
+    clock: Arc<dyn Clock>,
+    /// Passed to [`ManifoldClient::post_bet_before_close`]; 5 seconds by default.
+    pub safety_margin: Duration,
+}
+
+impl<S: Storage> TriggerSet<S> {
+    /// Loads the triggers saved under `key`, if any.
+    pub fn load(storage: S, key: &str) -> Result<TriggerSet<S>> {
+        let triggers = storage.load_as(key)?.unwrap_or_default();
+
+        Ok(TriggerSet {
+            storage,
+            key: key.to_owned(),
+            triggers,
+            clock: Arc::new(SystemClock),
+            safety_margin: Duration::from_secs(5),
+        })
+    }
+
+    /// Where [`add_limit_sell`](Self::add_limit_sell) gets the creation time
+    /// from, the system clock by default; usually the client's, see [`crate::clock`].
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    fn save(&self) -> Result<()> {
+        self.storage.save_as(&self.key, &self.triggers)
+    }
+
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    pub fn add(&mut self, trigger: Trigger) -> Result<()> {
+        self.triggers.push(trigger);
+        self.save()
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<Option<Trigger>> {
+        let Some(index) = self.triggers.iter().position(|t| t.id == id) else {
+            return Ok(None);
+        };
+
+        let trigger = self.triggers.remove(index);
+        self.save()?;
+
+        Ok(Some(trigger))
+    }
+
+    /// Adds a trigger selling `shares` (or all) of the YES or NO shares held
+    /// in `market_id` once its probability gets past `limit_prob`.
+    ///
+    /// Cancel it by removing its trigger. Fails with
+    /// [`ManifoldError::Validation`] on a limit outside of 0.001..=0.999 or
+    /// shares that aren't a positive number.
+    pub fn add_limit_sell(
+        &mut self,
+        market_id: &str,
+        yes: bool,
+        limit_prob: f64,
+        shares: Option<f64>,
+    ) -> Result<LimitSell> {
+        if !(0.001..=0.999).contains(&limit_prob) {
+            return Err(ManifoldError::Validation(format!(
+                "limit probability {limit_prob} is not between 0.001 and 0.999"
+            )));
+        }
+
+        if shares.is_some_and(|shares| !(shares.is_finite() && shares > 0.0)) {
+            return Err(ManifoldError::Validation(format!(
+                "a limit sell needs a positive number of shares, not {shares:?}"
+            )));
+        }
+
+        let market_id = market_id.to_owned();
+        let (condition, outcome) = if yes {
+            let condition = Condition::ProbAbove {
+                market_id: market_id.clone(),
+                prob: limit_prob,
+            };
+            (condition, Outcome::Yes)
+        } else {
+            let condition = Condition::ProbBelow {
+                market_id: market_id.clone(),
+                prob: limit_prob,
+            };
+            (condition, Outcome::No)
+        };
+
+        let trigger = Trigger::new_at(
+            condition,
+            Action::Sell {
+                market_id,
+                outcome,
+                shares,
+            },
+            self.clock.now(),
+        );
+        let sell = LimitSell::from_trigger(&trigger).expect("built as a limit sell");
+
+        self.add(trigger)?;
+
+        Ok(sell)
+    }
+
+    /// The triggers that are limit sells.
+    pub fn limit_sells(&self) -> Vec<LimitSell> {
+        self.triggers
+            .iter()
+            .filter_map(LimitSell::from_trigger)
+            .collect()
+    }
+
+    /// Markets the triggers' conditions look at.
+    pub fn market_ids(&self) -> Vec<String> {
+        self.triggers
+            .iter()
+            .map(|t| t.condition.market_id().to_owned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Triggers whose conditions `market` satisfies.
+    pub fn due(&self, market: &impl Market) -> Vec<&Trigger> {
+        self.triggers
+            .iter()
+            .filter(|t| t.condition.is_met(market))
+            .collect()
+    }
+
+    /// Fires the triggers that are due given a fresh `market`.
+    ///
+    /// Fired triggers are removed and saved *before* their actions run, so a
+    /// crash can lose an action but never repeat it. Triggers whose action
+    /// failed with a rate limit or a failed connection are put back and saved
+    /// again, to fire on the next fresh market; after other failures,
+    /// including server errors, the action may have gone through.
+    pub async fn on_market(
+        &mut self,
+        client: &ManifoldClient,
+        market: &impl Market,
+    ) -> Result<Vec<FiredTrigger>> {
+        let (due, rest) = std::mem::take(&mut self.triggers)
+            .into_iter()
+            .partition::<Vec<_>, _>(|t| t.condition.is_met(market));
+
+        self.triggers = rest;
+
+        if due.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.save()?;
+
+        let mut fired = vec![];
+
+        for trigger in due {
+            let result = match &trigger.action {
+                Action::Bet(args) => client
+                    .post_bet_before_close(args, self.safety_margin)
+                    .await
+                    .map(|bet| bet.0),
+                Action::SellAll { market_id, outcome } => client
+                    .sell_shares(market_id, outcome.clone(), None, None)
+                    .await
+                    .map(|sale| sale.0),
+                Action::Sell {
+                    market_id,
+                    outcome,
+                    shares,
+                } => client
+                    .sell_shares(market_id, Some(outcome.clone()), *shares, None)
+                    .await
+                    .map(|sale| sale.0),
+            };
+
+            let rearmed = result.as_ref().is_err_and(can_rearm);
+            if rearmed {
+                self.triggers.push(trigger.clone());
+            }
+
+            fired.push(FiredTrigger {
+                trigger,
+                result,
+                rearmed,
+            });
+        }
+
+        if fired.iter().any(|fired| fired.rearmed) {
+            self.save()?;
+        }
+
+        Ok(fired)
+    }
+
+    /// Polls the triggers' markets every `interval` and fires triggers until none are left.
+    ///
+    /// Retryable errors fetching markets (see [`ManifoldError::is_retryable`])
+    /// are logged and polling goes on; other ones end the run. Errors of
+    /// actions are passed to `on_fire` with the trigger.
+    pub async fn run(
+        &mut self,
+        client: &ManifoldClient,
+        interval: Duration,
+        mut on_fire: impl FnMut(&FiredTrigger),
+    ) -> Result<()> {
+        let market_ids = self.market_ids();
+        let mut markets = Box::pin(client.watch_markets(&market_ids, interval));
+
+        while !self.triggers.is_empty() {
+            let market = match markets.next().await {
+                Some(Ok(market)) => market,
+                Some(Err(e)) if e.is_retryable() => {
+                    log::warn!("couldn't fetch a market for triggers, retrying: {e}");
+                    continue;
+                }
+                Some(Err(e)) => return Err(e),
+                None => break,
+            };
+
+            for fired in self.on_market(client, &*market).await? {
+                on_fire(&fired);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use std::sync::atomic::AtomicUsize;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::fixtures::{self, MockRequest, MockResponse, MockServer};
+    use crate::storage::MemoryStorage;
+
+    fn market(id: &str, prob: f64, resolution: Option<&str>) -> FullMarket {
+        FullMarket(json!({
+            "id": id,
+            "probability": prob,
+            "isResolved": resolution.is_some(),
+            "resolution": resolution,
+        }))
+    }
+
+    #[test]
+    fn conditions() {
+        let above = Condition::ProbAbove {
+            market_id: "x".to_owned(),
+            prob: 0.6,
+        };
+        let resolved = Condition::ResolvedAs {
+            market_id: "x".to_owned(),
+            resolution: "YES".to_owned(),
+        };
+
+        assert!(above.is_met(&market("x", 0.65, None)));
+        assert!(!above.is_met(&market("x", 0.55, None)));
+        assert!(!above.is_met(&market("y", 0.65, None)));
+        assert!(!above.is_met(&market("x", 1.0, Some("YES"))));
+
+        assert!(resolved.is_met(&market("x", 1.0, Some("YES"))));
+        assert!(!resolved.is_met(&market("x", 0.0, Some("NO"))));
+    }
+
+    #[test]
+    fn triggers_persist() -> Result<()> {
+        let storage = MemoryStorage::new();
+
+        let mut set = TriggerSet::load(&storage, "triggers")?;
+        set.add(Trigger::new(
+            Condition::ProbAbove {
+                market_id: "x".to_owned(),
+                prob: 0.6,
+            },
+            Action::Bet(PlaceBetArgs::new(50, "y", Outcome::No)),
+        ))?;
+        set.add(Trigger::new(
+            Condition::ResolvedAs {
+                market_id: "x".to_owned(),
+                resolution: "YES".to_owned(),
+            },
+            Action::SellAll {
+                market_id: "z".to_owned(),
+                outcome: None,
+            },
+        ))?;
+
+        let reloaded = TriggerSet::load(&storage, "triggers")?;
+
+        assert_eq!(reloaded.triggers(), set.triggers());
+        assert_eq!(reloaded.market_ids(), vec!["x".to_owned()]);
+        assert_eq!(reloaded.due(&market("x", 0.7, None)).len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn limit_sells() -> Result<()> {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let mut set =
+            TriggerSet::load(MemoryStorage::new(), "triggers")?.clock(MockClock::new(now));
+
+        let yes = set.add_limit_sell("x", true, 0.8, None)?;
+        let no = set.add_limit_sell("x", false, 0.3, Some(20.5))?;
+        set.add(Trigger::new(
+            Condition::ProbAbove {
+                market_id: "x".to_owned(),
+                prob: 0.9,
+            },
+            Action::SellAll {
+                market_id: "x".to_owned(),
+                outcome: None,
+            },
+        ))?;
+
+        assert_eq!(set.limit_sells(), [yes.clone(), no.clone()]);
+        assert_eq!((no.yes, no.limit_prob, no.shares), (false, 0.3, Some(20.5)));
+
+        let due_ids = |prob: f64| {
+            set.due(&market("x", prob, None))
+                .iter()
+                .map(|t| t.id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(due_ids(0.85), [yes.trigger_id.as_str()]);
+        assert_eq!(due_ids(0.25), [no.trigger_id.as_str()]);
+        assert!(due_ids(0.5).is_empty());
+
+        // Created at the same time by the clock, but with their own ids.
+        assert_ne!(yes.trigger_id, no.trigger_id);
+        assert!(set
+            .triggers()
+            .iter()
+            .take(2)
+            .all(|t| t.created_time == now.timestamp_millis()));
+        assert!(set.add_limit_sell("x", true, 1.0, None).is_err());
+        assert!(set.add_limit_sell("x", true, 0.5, Some(0.0)).is_err());
+        assert!(set.add_limit_sell("x", true, 0.5, Some(f64::NAN)).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn limit_sells_sell_fractional_shares_and_rearm() -> Result<()> {
+        let sells = Arc::new(AtomicUsize::new(0));
+
+        let server = MockServer::start({
+            let sells = sells.clone();
+
+            move |request: &MockRequest| match (request.method.as_str(), request.path.as_str()) {
+                ("POST", "/market/x/sell") if sells.fetch_add(1, Ordering::SeqCst) == 0 => {
+                    Some(MockResponse::status(429, json!({"message": "slow down"})))
+                }
+                ("POST", "/market/x/sell") => {
+                    Some(MockResponse::json(fixtures::bet_with_fills().0))
+                }
+                _ => None,
+            }
+        })
+        .await?;
+        let client = server.client().build()?;
+
+        let mut set = TriggerSet::load(MemoryStorage::new(), "triggers")?;
+        set.add_limit_sell("x", true, 0.8, Some(12.5))?;
+
+        let fired = set.on_market(&client, &market("x", 0.85, None)).await?;
+        assert!(fired[0].rearmed && fired[0].result.is_err());
+        assert_eq!(set.limit_sells().len(), 1);
+
+        let fired = set.on_market(&client, &market("x", 0.85, None)).await?;
+        assert!(!fired[0].rearmed && fired[0].result.is_ok());
+        assert!(set.limit_sells().is_empty());
+
+        let sell = server.requests().pop().unwrap();
+        assert_eq!(sell.body, json!({"outcome": "YES", "shares": 12.5}));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rearms_only_when_the_action_surely_failed() -> Result<()> {
+        let market_fetches = Arc::new(AtomicUsize::new(0));
+        let bets = Arc::new(AtomicUsize::new(0));
+
+        let server = MockServer::start({
+            let market_fetches = market_fetches.clone();
+            let bets = bets.clone();
+
+            move |request: &MockRequest| match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/market/x") if market_fetches.fetch_add(1, Ordering::SeqCst) == 0 => {
+                    Some(MockResponse::status(503, json!({"message": "unavailable"})))
+                }
+                ("GET", "/market/x") => {
+                    let mut market = fixtures::binary_market().0;
+                    market["id"] = json!("x");
+                    market["probability"] = json!(0.7);
+                    Some(MockResponse::json(market))
+                }
+                ("POST", "/bet") => match bets.fetch_add(1, Ordering::SeqCst) {
+                    0 => Some(MockResponse::status(429, json!({"message": "slow down"}))),
+                    // The bet may have been placed anyway.
+                    1 => Some(MockResponse::status(502, json!({"message": "bad gateway"}))),
+                    _ => Some(MockResponse::json(fixtures::bet_with_fills().0)),
+                },
+                _ => None,
+            }
+        })
+        .await?;
+        let client = server
+            .client()
+            .clock(MockClock::new(
+                Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap(),
+            ))
+            .build()?;
+
+        let storage = MemoryStorage::new();
+        let mut set = TriggerSet::load(&storage, "triggers")?;
+        set.add(Trigger::new_at(
+            Condition::ProbAbove {
+                market_id: "x".to_owned(),
+                prob: 0.6,
+            },
+            Action::Bet(PlaceBetArgs::new(50, "x", Outcome::Yes)),
+            client.now(),
+        ))?;
+
+        let mut fired = vec![];
+        set.run(&client, Duration::from_secs(10), |f| {
+            fired.push((f.result.is_ok(), f.rearmed))
+        })
+        .await?;
+
+        assert_eq!(fired, [(false, true), (false, false)]);
+        assert_eq!(bets.load(Ordering::SeqCst), 2);
+        assert!(TriggerSet::load(&storage, "triggers")?
+            .triggers()
+            .is_empty());
+
+        Ok(())
+    }
+}
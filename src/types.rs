@@ -11,12 +11,157 @@
 //! from the API. This might be a questionable decision, feel free
 //! to open an issue if you think it should be changed.
 //! (When we stop using `serde_json::Value` we won't need those at all)
+//!
+//! For data that doesn't come straight from the API – archives, or
+//! user-generated content passed around by bots – the `try_*` variants
+//! return a [`SchemaError`](ManifoldError::SchemaError) instead of panicking.
+//! Long-running processes that can't switch to them yet can make the
+//! `expect`-based accessors log a warning and return a default value instead:
+//! for the whole process with [`set_lenient_accessors`], or around some code
+//! with [`with_lenient_accessors`] or
+//! [`ManifoldClient::with_accessors`](crate::ManifoldClient::with_accessors).
+//! [`Market::outcome_type`] has no safe default and always panics on an
+//! unknown type.
+//!
+//! The [`schema`](crate::schema) module has fully-typed versions of the
+//! main objects, for code that would rather parse everything up front.
+//!
+//! Mana and share amounts are read through [`number_f64`], which also
+//! works when the `arbitrary-precision` feature is enabled. The `*_exact`
+//! accessors return the number exactly as the API sent it.
 
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
+
+use crate::error::{ManifoldError, Result};
+
+static LENIENT_ACCESSORS: AtomicBool = AtomicBool::new(false);
+static LENIENT_FALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static SCOPED_LENIENCY: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// Makes accessors like [`User::balance`] log a warning and return a
+/// default (`0.0`, `""`, ...) instead of panicking on a missing or malformed field.
+///
+/// Applies to the whole process; see [`with_lenient_accessors`] to only
+/// change some code. Defaults can be silently wrong, so prefer the `try_*`
+/// accessors where possible.
+pub fn set_lenient_accessors(lenient: bool) {
+    LENIENT_ACCESSORS.store(lenient, Ordering::Relaxed);
+}
+
+/// Runs `f` with accessors lenient or not, whatever [`set_lenient_accessors`] says.
+///
+/// Only applies on the current thread, so keep `.await`s out of `f`.
+pub fn with_lenient_accessors<R>(lenient: bool, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<bool>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SCOPED_LENIENCY.with(|scoped| scoped.set(self.0));
+        }
+    }
+
+    let _restore = Restore(SCOPED_LENIENCY.with(|scoped| scoped.replace(Some(lenient))));
+
+    f()
+}
+
+/// Whether accessors are lenient here, see [`with_lenient_accessors`].
+pub fn lenient_accessors() -> bool {
+    SCOPED_LENIENCY
+        .with(Cell::get)
+        .unwrap_or_else(|| LENIENT_ACCESSORS.load(Ordering::Relaxed))
+}
+
+/// How many times an accessor returned a default in lenient mode, for monitoring.
+pub fn lenient_fallbacks() -> u64 {
+    LENIENT_FALLBACKS.load(Ordering::Relaxed)
+}
+
+/// `expect`, unless [lenient](set_lenient_accessors).
+trait OrLenient<T> {
+    fn or_lenient(self, message: &str) -> T;
+}
+
+#[track_caller]
+fn lenient_default<T: Default>(message: &str) -> T {
+    if !lenient_accessors() {
+        panic!("{message}");
+    }
+
+    LENIENT_FALLBACKS.fetch_add(1, Ordering::Relaxed);
+    log::warn!("{message}, using the default");
+
+    T::default()
+}
+
+impl<T: Default> OrLenient<T> for Option<T> {
+    #[track_caller]
+    fn or_lenient(self, message: &str) -> T {
+        match self {
+            Some(value) => value,
+            None => lenient_default(message),
+        }
+    }
+}
+
+impl<T: Default, E: std::fmt::Debug> OrLenient<T> for std::result::Result<T, E> {
+    #[track_caller]
+    fn or_lenient(self, message: &str) -> T {
+        match self {
+            Ok(value) => value,
+            Err(e) if lenient_accessors() => lenient_default(&format!("{message}: {e:?}")),
+            Err(e) => panic!("{message}: {e:?}"),
+        }
+    }
+}
+
+fn schema_error(value: &Value, message: String) -> ManifoldError {
+    ManifoldError::SchemaError(message, Some(value.clone()))
+}
+
+fn try_str<'a>(value: &'a Value, field: &str, name: &str) -> Result<&'a str> {
+    value[field]
+        .as_str()
+        .ok_or_else(|| schema_error(&value[field], format!("{name}.{field} is not a string")))
+}
+
+fn try_f64(value: &Value, field: &str, name: &str) -> Result<f64> {
+    number_f64(&value[field])
+        .ok_or_else(|| schema_error(&value[field], format!("{name}.{field} is not a number")))
+}
+
+fn try_i64(value: &Value, field: &str, name: &str) -> Result<i64> {
+    value[field]
+        .as_i64()
+        .ok_or_else(|| schema_error(&value[field], format!("{name}.{field} is not a number")))
+}
+
+/// Reads a JSON number as `f64`, even if it doesn't fit one exactly.
+///
+/// With `arbitrary-precision`, numbers outside of `f64`'s range are
+/// still parsed (lossily) instead of being treated as missing.
+pub fn number_f64(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_number()?.to_string().parse().ok())
+}
+
+/// The textual representation of a JSON number.
+///
+/// With the `arbitrary-precision` feature this is exactly what the API sent,
+/// otherwise it's the shortest representation of the parsed `f64`.
+pub fn number_text(value: &Value) -> Option<String> {
+    value.as_number().map(|n| n.to_string())
+}
 
 /// For binary markets, this is YES or NO. For free response markets, this is the ID of the free response answer. For numeric markets, this is a string representing the target bucket, and an additional value parameter is required which is a number representing the target value. (Bet on numeric markets at your own peril.)
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -38,42 +183,62 @@ impl User {
         self.0["avatarUrl"].as_str()
     }
     pub fn balance(&self) -> f64 {
-        self.0["balance"]
-            .as_f64()
-            .expect("User.balance is not a number")
+        number_f64(&self.0["balance"]).or_lenient("User.balance is not a number")
+    }
+    pub fn balance_exact(&self) -> String {
+        number_text(&self.0["balance"]).or_lenient("User.balance is not a number")
     }
     pub fn created_time(&self) -> i64 {
         self.0["createdTime"]
             .as_i64()
-            .expect("User.createdTime is not a number")
+            .or_lenient("User.createdTime is not a number")
     }
     pub fn id(&self) -> &str {
-        self.0["id"].as_str().expect("User.id is not a string")
+        self.0["id"].as_str().or_lenient("User.id is not a string")
     }
     pub fn name(&self) -> &str {
-        self.0["name"].as_str().expect("User.name is not a string")
+        self.0["name"]
+            .as_str()
+            .or_lenient("User.name is not a string")
     }
     pub fn total_deposits(&self) -> i64 {
         self.0["totalDeposits"]
             .as_i64()
-            .expect("User.totalDeposits is not a number")
+            .or_lenient("User.totalDeposits is not a number")
     }
     pub fn url(&self) -> &str {
-        self.0["url"].as_str().expect("User.url is not a string")
+        self.0["url"]
+            .as_str()
+            .or_lenient("User.url is not a string")
     }
     pub fn username(&self) -> &str {
         self.0["username"]
             .as_str()
-            .expect("User.username is not a string")
+            .or_lenient("User.username is not a string")
     }
     pub fn profit_cached(&self) -> ProfitCached {
         serde_json::from_value(self.0["profitCached"].clone())
-            .expect("User.profitCached is not a ProfitCached")
+            .or_lenient("User.profitCached is not a ProfitCached")
+    }
+    pub fn try_balance(&self) -> Result<f64> {
+        try_f64(&self.0, "balance", "User")
+    }
+    pub fn try_created_time(&self) -> Result<i64> {
+        try_i64(&self.0, "createdTime", "User")
+    }
+    pub fn try_id(&self) -> Result<&str> {
+        try_str(&self.0, "id", "User")
+    }
+    pub fn try_name(&self) -> Result<&str> {
+        try_str(&self.0, "name", "User")
+    }
+    pub fn try_username(&self) -> Result<&str> {
+        try_str(&self.0, "username", "User")
     }
 }
 
 /// Struct from the User API
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct ProfitCached {
     #[serde(rename = "allTime")]
     all_time: f64,
@@ -86,7 +251,66 @@ pub struct ProfitCached {
 #[serde(transparent)]
 pub struct Group(pub Value);
 
-/// One of BINARY, FREE_RESPONSE, MULTIPLE_CHOICE, or PSEUDO_NUMERIC.
+impl Group {
+    pub fn id(&self) -> &str {
+        self.0["id"].as_str().or_lenient("Group.id is not a string")
+    }
+    pub fn slug(&self) -> &str {
+        self.0["slug"]
+            .as_str()
+            .or_lenient("Group.slug is not a string")
+    }
+    pub fn name(&self) -> &str {
+        self.0["name"]
+            .as_str()
+            .or_lenient("Group.name is not a string")
+    }
+    pub fn try_id(&self) -> Result<&str> {
+        try_str(&self.0, "id", "Group")
+    }
+    pub fn try_slug(&self) -> Result<&str> {
+        try_str(&self.0, "slug", "Group")
+    }
+    pub fn try_name(&self) -> Result<&str> {
+        try_str(&self.0, "name", "Group")
+    }
+}
+
+/// A member's role in a group, ordered from least to most privileged.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupRole {
+    Member,
+    Moderator,
+    Admin,
+}
+
+impl GroupRole {
+    /// Moderators and admins can moderate content in the group.
+    pub fn can_moderate(&self) -> bool {
+        *self >= GroupRole::Moderator
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct GroupMember(pub Value);
+
+impl GroupMember {
+    pub fn user_id(&self) -> &str {
+        self.0["userId"]
+            .as_str()
+            .or_else(|| self.0["member_id"].as_str())
+            .or_lenient("GroupMember.userId is not a string")
+    }
+    /// Members without an explicit role are plain members.
+    pub fn role(&self) -> GroupRole {
+        serde_json::from_value(self.0["role"].clone()).unwrap_or(GroupRole::Member)
+    }
+}
+
+/// One of BINARY, FREE_RESPONSE, MULTIPLE_CHOICE, PSEUDO_NUMERIC, POLL,
+/// BOUNTIED_QUESTION, NUMBER, or STONK.
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub enum OutcomeType {
     #[serde(rename = "BINARY")]
@@ -97,6 +321,63 @@ pub enum OutcomeType {
     MultipleChoice,
     #[serde(rename = "PSEUDO_NUMERIC")]
     PseudoNumeric,
+    /// Votes on options, without trading; the options are in `options`.
+    #[serde(rename = "POLL")]
+    Poll,
+    /// A question with a bounty for the best answers (comments), without trading.
+    #[serde(rename = "BOUNTIED_QUESTION")]
+    BountiedQuestion,
+    /// A number, traded as a `cpmm-multi-1` market with an answer per range.
+    #[serde(rename = "NUMBER")]
+    Number,
+    /// A `cpmm-1` market that never resolves, traded like a binary one.
+    #[serde(rename = "STONK")]
+    Stonk,
+    /// A type this crate doesn't know yet, as the API sent it, so that
+    /// [`schema`](crate::schema) structs still parse and are written back out
+    /// unchanged. The [`Market`] accessors treat it as an error.
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+/// Who can add answers to a multiple choice market.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AddAnswersMode {
+    Disabled,
+    OnlyCreator,
+    Anyone,
+}
+
+/// Most answers a multiple choice market can have.
+pub const MAX_ANSWERS: usize = 100;
+
+/// Longest answer text Manifold accepts, in characters.
+pub const MAX_ANSWER_LENGTH: usize = 240;
+
+/// Mana it costs to create a market, which becomes its liquidity.
+///
+/// Manifold's prices when this was written; they change now and then.
+pub const MARKET_CREATION_COST: u64 = 100;
+
+/// Mana each answer of a multiple choice market adds to its creation cost.
+pub const ANSWER_CREATION_COST: u64 = 10;
+
+/// Who can see a market.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Shown on the homepage and in search results.
+    Public,
+    /// Accessible by link, but not listed.
+    Unlisted,
+    /// Only visible to members of a private group.
+    Private,
+    /// A visibility this crate doesn't know yet, as the API sent it, so that
+    /// [`schema`](crate::schema) structs still parse and are written back out
+    /// unchanged. [`Market::visibility`] takes it as [`Unlisted`](Visibility::Unlisted).
+    #[serde(untagged)]
+    Unknown(String),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -113,70 +394,268 @@ pub trait Market {
     fn id(&self) -> &str {
         self.data()["id"]
             .as_str()
-            .expect("Market.id is not a string")
+            .or_lenient("Market.id is not a string")
     }
 
+    fn question(&self) -> &str {
+        self.data()["question"]
+            .as_str()
+            .or_lenient("Market.question is not a string")
+    }
+
+    fn creator_id(&self) -> &str {
+        self.data()["creatorId"]
+            .as_str()
+            .or_lenient("Market.creatorId is not a string")
+    }
+
+    /// How the market trades: `cpmm-1`, `cpmm-multi-1`, or `dpm-2` for old markets.
+    fn mechanism(&self) -> Option<&str> {
+        self.data()["mechanism"].as_str()
+    }
+
+    /// Panics on an unknown type, even with [lenient](set_lenient_accessors)
+    /// accessors: any default could be bet on the wrong way.
     fn outcome_type(&self) -> OutcomeType {
-        serde_json::from_value(self.data()["outcomeType"].clone())
-            .expect("Market.outcomeType is not an OutcomeType")
+        self.try_outcome_type().unwrap_or_else(|e| panic!("{e}"))
     }
 
+    /// Empty for polls and bountied questions, which have no pool.
     fn pool(&self) -> Vec<(Outcome, f64)> {
-        let val: HashMap<String, f64> = serde_json::from_value(self.data()["pool"].clone())
-            .expect("Market.pool is not a HashMap<String, f64>");
+        let outcome_type = self.outcome_type();
+        let val = || -> HashMap<String, f64> {
+            serde_json::from_value(self.data()["pool"].clone())
+                .or_lenient("Market.pool is not a HashMap<String, f64>")
+        };
 
-        match self.outcome_type() {
-            OutcomeType::Binary => val
+        match outcome_type {
+            OutcomeType::Binary | OutcomeType::PseudoNumeric | OutcomeType::Stonk => val()
                 .into_iter()
                 .map(|(k, v)| {
                     let outcome = match k.as_str() {
                         "YES" => Outcome::Yes,
                         "NO" => Outcome::No,
-                        _ => panic!("OutcomeType::Binary has an invalid outcome"),
+                        _ => panic!("OutcomeType::{outcome_type:?} has an invalid outcome"),
                     };
                     (outcome, v)
                 })
                 .collect(),
-            OutcomeType::FreeResponse => val
-                .into_iter()
-                .map(|(k, v)| (Outcome::FreeResponse(k), v))
-                .collect(),
-            OutcomeType::MultipleChoice => val
+            OutcomeType::FreeResponse | OutcomeType::MultipleChoice | OutcomeType::Number => val()
                 .into_iter()
                 .map(|(k, v)| (Outcome::FreeResponse(k), v))
                 .collect(),
-            OutcomeType::PseudoNumeric => val
-                .into_iter()
-                .map(|(k, v)| {
-                    let outcome = match k.as_str() {
-                        "YES" => Outcome::Yes,
-                        "NO" => Outcome::No,
-                        _ => panic!("OutcomeType::PseudoNumeric has an invalid outcome"),
-                    };
-                    (outcome, v)
-                })
-                .collect(),
+            OutcomeType::Poll | OutcomeType::BountiedQuestion | OutcomeType::Unknown(_) => vec![],
+        }
+    }
+
+    /// Markets without an explicit visibility are public. Visibilities this
+    /// crate doesn't know are taken as [`Unlisted`](Visibility::Unlisted), so
+    /// such markets aren't treated as public.
+    fn visibility(&self) -> Visibility {
+        match &self.data()["visibility"] {
+            Value::Null => Visibility::Public,
+            visibility => match serde_json::from_value(visibility.clone()) {
+                Ok(Visibility::Unknown(_)) | Err(_) => Visibility::Unlisted,
+                Ok(visibility) => visibility,
+            },
         }
     }
 
+    fn is_public(&self) -> bool {
+        self.visibility() == Visibility::Public
+    }
+
+    /// Slugs of the groups (topics) the market is in.
+    fn group_slugs(&self) -> Vec<&str> {
+        self.data()["groupSlugs"]
+            .as_array()
+            .map(|slugs| slugs.iter().filter_map(|s| s.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Answers of free response and multiple choice markets.
+    ///
+    /// Only [`FullMarket`] includes answers; this is empty for other markets.
+    fn answers(&self) -> Vec<Answer> {
+        self.data()["answers"]
+            .as_array()
+            .map(|answers| answers.iter().cloned().map(Answer).collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether the answers of a multi market are mutually exclusive, with probabilities summing to one.
+    ///
+    /// Older free response and multiple choice markets don't have the field; their answers always sum to one.
+    fn should_answers_sum_to_one(&self) -> bool {
+        self.data()["shouldAnswersSumToOne"]
+            .as_bool()
+            .unwrap_or(true)
+    }
+
+    /// Who can add answers; free response markets without the field are open to anyone.
+    fn add_answers_mode(&self) -> AddAnswersMode {
+        serde_json::from_value(self.data()["addAnswersMode"].clone()).unwrap_or(
+            match self.try_outcome_type() {
+                Ok(OutcomeType::FreeResponse) => AddAnswersMode::Anyone,
+                _ => AddAnswersMode::Disabled,
+            },
+        )
+    }
+
+    /// The image shown on the market's card, if it has one.
+    fn cover_image_url(&self) -> Option<&str> {
+        self.data()["coverImageUrl"].as_str()
+    }
+
     fn close_time(&self) -> Option<DateTime<Utc>> {
-        let ts_ms: Option<i64> =
-            serde_json::from_value(self.data()["closeTime"].clone()).expect("Invalid closeTime");
+        let ts_ms: Option<i64> = serde_json::from_value(self.data()["closeTime"].clone())
+            .or_lenient("Invalid closeTime");
 
         let ts_ms = ts_ms?;
 
-        Some(DateTime::from_utc(
-            chrono::NaiveDateTime::from_timestamp_opt(
-                ts_ms / 1000,
-                (ts_ms % 1000) as u32 * 1_000_000,
-            )
-            .expect("Market.closeTime is not a valid timestamp"),
-            chrono::Utc,
-        ))
+        Some(
+            DateTime::from_timestamp_millis(ts_ms)
+                .or_lenient("Market.closeTime is not a valid timestamp"),
+        )
     }
 
     fn is_active(&self) -> bool {
-        self.close_time().map(|t| t > Utc::now()).unwrap_or(true)
+        self.is_active_at(Utc::now())
+    }
+
+    /// Whether the market is still open at `now`, see [`ManifoldClient::now`](crate::ManifoldClient::now).
+    fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.close_time().map(|t| t > now).unwrap_or(true)
+    }
+
+    /// Current probability of binary markets.
+    fn probability(&self) -> Option<f64> {
+        number_f64(&self.data()["probability"])
+    }
+
+    /// `YES`, `NO`, `MKT`, `CANCEL`, or an answer id, once resolved.
+    fn resolution(&self) -> Option<&str> {
+        self.data()["resolution"].as_str()
+    }
+
+    fn is_resolved(&self) -> bool {
+        self.data()["isResolved"].as_bool().unwrap_or(false)
+    }
+
+    fn resolution_time(&self) -> Option<DateTime<Utc>> {
+        self.data()["resolutionTime"]
+            .as_i64()
+            .and_then(DateTime::from_timestamp_millis)
+    }
+
+    /// When the market will probably resolve, without the creator's
+    /// usual lag; see [`estimate_resolution`](crate::resolution::estimate_resolution).
+    fn estimated_resolution(&self) -> Option<DateTime<Utc>>
+    where
+        Self: Sized,
+    {
+        crate::resolution::estimate_resolution(self, None)
+    }
+
+    /// A plain-text summary of at most `max_chars` characters, see
+    /// [`summarize_market`](crate::format::summarize_market).
+    fn summarize(&self, max_chars: usize) -> String
+    where
+        Self: Sized,
+    {
+        crate::format::summarize_market(self, max_chars)
+    }
+
+    fn try_id(&self) -> Result<&str> {
+        try_str(self.data(), "id", "Market")
+    }
+
+    fn try_question(&self) -> Result<&str> {
+        try_str(self.data(), "question", "Market")
+    }
+
+    fn try_creator_id(&self) -> Result<&str> {
+        try_str(self.data(), "creatorId", "Market")
+    }
+
+    fn try_outcome_type(&self) -> Result<OutcomeType> {
+        match serde_json::from_value(self.data()["outcomeType"].clone()) {
+            Ok(OutcomeType::Unknown(_)) | Err(_) => Err(schema_error(
+                &self.data()["outcomeType"],
+                "Market.outcomeType is not an OutcomeType".to_owned(),
+            )),
+            Ok(outcome_type) => Ok(outcome_type),
+        }
+    }
+
+    /// Like [`pool`](Self::pool), but also fails on outcomes that don't match the market type.
+    fn try_pool(&self) -> Result<Vec<(Outcome, f64)>> {
+        let binary = match self.try_outcome_type()? {
+            OutcomeType::Binary | OutcomeType::PseudoNumeric | OutcomeType::Stonk => true,
+            OutcomeType::FreeResponse | OutcomeType::MultipleChoice | OutcomeType::Number => false,
+            OutcomeType::Poll | OutcomeType::BountiedQuestion | OutcomeType::Unknown(_) => {
+                return Ok(vec![])
+            }
+        };
+
+        let data = &self.data()["pool"];
+        let val: HashMap<String, f64> = serde_json::from_value(data.clone()).map_err(|_| {
+            schema_error(data, "Market.pool is not a HashMap<String, f64>".to_owned())
+        })?;
+
+        val.into_iter()
+            .map(|(k, v)| match (binary, k.as_str()) {
+                (true, "YES") => Ok((Outcome::Yes, v)),
+                (true, "NO") => Ok((Outcome::No, v)),
+                (true, _) => Err(schema_error(
+                    data,
+                    format!("Market.pool has an invalid outcome {k}"),
+                )),
+                (false, _) => Ok((Outcome::FreeResponse(k), v)),
+            })
+            .collect()
+    }
+
+    fn try_close_time(&self) -> Result<Option<DateTime<Utc>>> {
+        let data = &self.data()["closeTime"];
+
+        if data.is_null() {
+            return Ok(None);
+        }
+
+        data.as_i64()
+            .and_then(DateTime::from_timestamp_millis)
+            .map(Some)
+            .ok_or_else(|| {
+                schema_error(data, "Market.closeTime is not a valid timestamp".to_owned())
+            })
+    }
+}
+
+/// An answer of a free response or multiple choice market.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Answer(pub Value);
+
+impl Answer {
+    pub fn id(&self) -> &str {
+        self.0["id"]
+            .as_str()
+            .or_lenient("Answer.id is not a string")
+    }
+    pub fn text(&self) -> &str {
+        self.0["text"]
+            .as_str()
+            .or_lenient("Answer.text is not a string")
+    }
+    pub fn probability(&self) -> Option<f64> {
+        number_f64(&self.0["probability"])
+    }
+    pub fn try_id(&self) -> Result<&str> {
+        try_str(&self.0, "id", "Answer")
+    }
+    pub fn try_text(&self) -> Result<&str> {
+        try_str(&self.0, "text", "Answer")
     }
 }
 
@@ -192,6 +671,853 @@ impl Market for LiteMarket {
     }
 }
 
+/// Parameters of `POST /v0/bet`; see [`ManifoldClient::place_bet`](crate::ManifoldClient::place_bet)
+/// and [`BetRequest`](crate::trading::BetRequest), which checks them.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceBetArgs {
+    pub amount: u64,
+    pub contract_id: String,
+    pub outcome: Outcome,
+    pub limit_prob: Option<f64>,
+    /// The answer of a `cpmm-multi-1` market to bet `YES` or `NO` on.
+    ///
+    /// [`ManifoldClient::bet_answer`](crate::ManifoldClient::bet_answer) sets it as needed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer_id: Option<String>,
+    /// Limit orders: when what's left unfilled is cancelled, in milliseconds since the epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    /// Only simulate the bet; the returned bet is what would have been placed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+impl PlaceBetArgs {
+    pub fn new(amount: u64, contract_id: &str, outcome: Outcome) -> PlaceBetArgs {
+        PlaceBetArgs {
+            amount,
+            contract_id: contract_id.to_owned(),
+            outcome,
+            limit_prob: None,
+            answer_id: None,
+            expires_at: None,
+            dry_run: false,
+        }
+    }
+}
+
+/// Filters of `GET /v0/bets`, for [`ManifoldClient::get_bets_with`](crate::ManifoldClient::get_bets_with)
+/// and [`stream_bets_with`](crate::ManifoldClient::stream_bets_with).
+///
+/// ```
+/// # use chrono::{Duration, Utc};
+/// # use manifold_markets::types::BetsQuery;
+/// let recent = BetsQuery::new()
+///     .contract_id("market-id")
+///     .after_time(Utc::now() - Duration::days(1))
+///     .limit(100);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BetsQuery {
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub contract_id: Option<String>,
+    pub contract_slug: Option<String>,
+    /// At most this many bets, up to 1000 (the default). Streams ignore it.
+    pub limit: Option<u64>,
+    /// Only bets older than the bet with this id. Streams ignore it and page on their own.
+    pub before: Option<String>,
+    pub after_time: Option<DateTime<Utc>>,
+    pub before_time: Option<DateTime<Utc>>,
+}
+
+impl BetsQuery {
+    pub fn new() -> BetsQuery {
+        BetsQuery::default()
+    }
+
+    /// Only bets by this user.
+    pub fn user_id(mut self, user_id: &str) -> Self {
+        self.user_id = Some(user_id.to_owned());
+        self
+    }
+
+    /// Only bets by the user with this username.
+    pub fn username(mut self, username: &str) -> Self {
+        self.username = Some(username.to_owned());
+        self
+    }
+
+    /// Only bets on this market.
+    pub fn contract_id(mut self, contract_id: &str) -> Self {
+        self.contract_id = Some(contract_id.to_owned());
+        self
+    }
+
+    /// Only bets on the market with this slug.
+    pub fn contract_slug(mut self, contract_slug: &str) -> Self {
+        self.contract_slug = Some(contract_slug.to_owned());
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn before(mut self, bet_id: &str) -> Self {
+        self.before = Some(bet_id.to_owned());
+        self
+    }
+
+    /// Only bets created after `time`.
+    pub fn after_time(mut self, time: DateTime<Utc>) -> Self {
+        self.after_time = Some(time);
+        self
+    }
+
+    /// Only bets created before `time`.
+    pub fn before_time(mut self, time: DateTime<Utc>) -> Self {
+        self.before_time = Some(time);
+        self
+    }
+
+    /// The query string parameters.
+    pub fn params(&self) -> Vec<(String, String)> {
+        let millis = |time: &DateTime<Utc>| time.timestamp_millis().to_string();
+
+        [
+            ("userId", self.user_id.clone()),
+            ("username", self.username.clone()),
+            ("contractId", self.contract_id.clone()),
+            ("contractSlug", self.contract_slug.clone()),
+            ("limit", self.limit.map(|limit| limit.to_string())),
+            ("before", self.before.clone()),
+            ("afterTime", self.after_time.as_ref().map(millis)),
+            ("beforeTime", self.before_time.as_ref().map(millis)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_owned(), value?)))
+        .collect()
+    }
+}
+
+/// How to resolve a single answer of a market whose answers don't sum to one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnswerResolution {
+    Yes,
+    No,
+    Cancel,
+    /// Resolve to a probability, given in percent.
+    Mkt(u8),
+}
+
+impl AnswerResolution {
+    /// The body of `POST /v0/market/[marketId]/resolve` resolving `answer_id` this way.
+    ///
+    /// Fails with [`ManifoldError::Validation`] for probabilities over 100, like [`Resolution::body`].
+    pub fn body(&self, answer_id: &str) -> Result<Value> {
+        let (outcome, probability_int) = match self {
+            AnswerResolution::Yes => ("YES", None),
+            AnswerResolution::No => ("NO", None),
+            AnswerResolution::Cancel => ("CANCEL", None),
+            AnswerResolution::Mkt(percent) if *percent > 100 => {
+                return Err(ManifoldError::Validation(format!(
+                    "probability {percent}% is over 100"
+                )));
+            }
+            AnswerResolution::Mkt(percent) => ("MKT", Some(percent)),
+        };
+
+        let mut body = json!({"answerId": answer_id, "outcome": outcome});
+
+        if let Some(percent) = probability_int {
+            body["probabilityInt"] = json!(percent);
+        }
+
+        Ok(body)
+    }
+}
+
+/// The range of a pseudo-numeric market, and how its probability maps onto it.
+///
+/// Linear scales map probability `p` to `min + p * (max - min)`; log scales to
+/// `min - 1 + (max - min + 1)^p`, so a log scale market starting at 0 is at 0 too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericScale {
+    pub min: f64,
+    pub max: f64,
+    pub log_scale: bool,
+}
+
+impl NumericScale {
+    pub fn of(market: &impl Market) -> Result<NumericScale> {
+        let data = market.data();
+        let (Some(min), Some(max)) = (number_f64(&data["min"]), number_f64(&data["max"])) else {
+            return Err(schema_error(
+                data,
+                "Numeric market has no min and max".to_owned(),
+            ));
+        };
+
+        Ok(NumericScale {
+            min,
+            max,
+            log_scale: data["isLogScale"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Where `value` lies in the range, 0 to 1.
+    pub fn probability(&self, value: f64) -> Result<f64> {
+        let NumericScale { min, max, .. } = *self;
+
+        if !(min..=max).contains(&value) {
+            return Err(ManifoldError::Validation(format!(
+                "value {value} is not between {min} and {max}"
+            )));
+        }
+
+        Ok(if self.log_scale {
+            (value - min + 1.0).log10() / (max - min + 1.0).log10()
+        } else {
+            (value - min) / (max - min)
+        })
+    }
+
+    /// The value at `probability`, 0 to 1, of the range.
+    pub fn value(&self, probability: f64) -> Result<f64> {
+        let NumericScale { min, max, .. } = *self;
+
+        if !(0.0..=1.0).contains(&probability) {
+            return Err(ManifoldError::Validation(format!(
+                "probability {probability} is not between 0 and 1"
+            )));
+        }
+
+        Ok(if self.log_scale {
+            min - 1.0 + (max - min + 1.0).powf(probability)
+        } else {
+            min + probability * (max - min)
+        })
+    }
+}
+
+/// How to resolve a market; see [`ManifoldClient::resolve_market`](crate::ManifoldClient::resolve_market).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// Binary markets.
+    Yes,
+    No,
+    /// Binary markets: resolve to a probability, given in percent.
+    Mkt(u8),
+    /// Any market.
+    Cancel,
+    /// Free response and multiple choice markets: the answer with this index.
+    Answer(u64),
+    /// Free response and multiple choice markets: several answers, as
+    /// `(answer index, percent)` weights that sum to 100.
+    Weighted(Vec<(u64, u8)>),
+    /// Numeric markets: the value, and where it lies between min and max
+    /// in percent; see [`Resolution::numeric`].
+    Numeric {
+        value: f64,
+        probability_int: f64,
+    },
+}
+
+impl Resolution {
+    /// `MKT` at a fractional `probability`, 0 to 1, rounded to a whole percent.
+    pub fn mkt(probability: f64) -> Result<Resolution> {
+        if !(0.0..=1.0).contains(&probability) {
+            return Err(ManifoldError::Validation(format!(
+                "probability {probability} is not between 0 and 1"
+            )));
+        }
+
+        Ok(Resolution::Mkt((probability * 100.0).round() as u8))
+    }
+
+    /// Resolves a numeric `market` to `value`, placing it between the market's min and max.
+    pub fn numeric(market: &impl Market, value: f64) -> Result<Resolution> {
+        let probability = NumericScale::of(market)?.probability(value)?;
+
+        Ok(Resolution::Numeric {
+            value,
+            probability_int: probability * 100.0,
+        })
+    }
+
+    /// Resolves a numeric `market` to the value at `probability`, 0 to 1, of its range.
+    pub fn numeric_at(market: &impl Market, probability: f64) -> Result<Resolution> {
+        let value = NumericScale::of(market)?.value(probability)?;
+
+        Ok(Resolution::Numeric {
+            value,
+            probability_int: probability * 100.0,
+        })
+    }
+
+    /// The request body of `POST /v0/market/[marketId]/resolve`.
+    ///
+    /// Fails with [`ManifoldError::Validation`] for probabilities over 100 and
+    /// weights that don't sum to 100.
+    pub fn body(&self) -> Result<Value> {
+        let invalid = |message: String| Err(ManifoldError::Validation(message));
+
+        Ok(match self {
+            Resolution::Yes => json!({"outcome": "YES"}),
+            Resolution::No => json!({"outcome": "NO"}),
+            Resolution::Cancel => json!({"outcome": "CANCEL"}),
+            Resolution::Mkt(percent) if *percent > 100 => {
+                return invalid(format!("probability {percent}% is over 100"));
+            }
+            Resolution::Mkt(percent) => json!({"outcome": "MKT", "probabilityInt": percent}),
+            Resolution::Answer(index) => json!({"outcome": index}),
+            Resolution::Weighted(weights) => {
+                let total: u32 = weights.iter().map(|(_, pct)| u32::from(*pct)).sum();
+
+                if total != 100 {
+                    return invalid(format!("answer weights sum to {total}, not 100"));
+                }
+
+                json!({
+                    "outcome": "MKT",
+                    "resolutions": weights
+                        .iter()
+                        .map(|(answer, pct)| json!({"answer": answer, "pct": pct}))
+                        .collect::<Vec<_>>(),
+                })
+            }
+            Resolution::Numeric {
+                value,
+                probability_int,
+            } => {
+                if !(0.0..=100.0).contains(probability_int) {
+                    return invalid(format!(
+                        "probability {probability_int}% is not between 0 and 100"
+                    ));
+                }
+
+                json!({"outcome": "MKT", "value": value, "probabilityInt": probability_int})
+            }
+        })
+    }
+}
+
+/// Parameters of `POST /v0/market`; see [`ManifoldClient::post_market`](crate::ManifoldClient::post_market).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMarketArgs {
+    pub outcome_type: OutcomeType,
+    pub question: String,
+    /// A plain string or TipTap JSON.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_markdown: Option<String>,
+    /// Milliseconds since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub group_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cover_image_url: Option<String>,
+
+    /// Binary markets: between 1 and 99.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_prob: Option<u8>,
+
+    /// Numeric markets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_log_scale: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_value: Option<f64>,
+
+    /// Multiple choice markets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub answers: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub add_answers_mode: Option<AddAnswersMode>,
+}
+
+impl CreateMarketArgs {
+    pub fn new(outcome_type: OutcomeType, question: &str) -> CreateMarketArgs {
+        CreateMarketArgs {
+            outcome_type,
+            question: question.to_owned(),
+            description: None,
+            description_markdown: None,
+            close_time: None,
+            visibility: None,
+            group_ids: vec![],
+            cover_image_url: None,
+            initial_prob: None,
+            min: None,
+            max: None,
+            is_log_scale: None,
+            initial_value: None,
+            answers: None,
+            add_answers_mode: None,
+        }
+    }
+
+    /// What creating the market costs, see [`MARKET_CREATION_COST`] and [`ANSWER_CREATION_COST`].
+    pub fn creation_cost(&self) -> u64 {
+        let answers = match self.outcome_type {
+            OutcomeType::MultipleChoice => self.answers.as_ref().map_or(0, Vec::len),
+            _ => 0,
+        };
+
+        MARKET_CREATION_COST + ANSWER_CREATION_COST * answers as u64
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(transparent)]
 pub struct Bet(pub Value);
+
+impl Bet {
+    pub fn id(&self) -> &str {
+        self.0["id"].as_str().or_lenient("Bet.id is not a string")
+    }
+    pub fn contract_id(&self) -> &str {
+        self.0["contractId"]
+            .as_str()
+            .or_lenient("Bet.contractId is not a string")
+    }
+    pub fn user_id(&self) -> &str {
+        self.0["userId"]
+            .as_str()
+            .or_lenient("Bet.userId is not a string")
+    }
+    /// `YES`/`NO`, or an answer ID for older free response markets.
+    pub fn outcome(&self) -> &str {
+        self.0["outcome"]
+            .as_str()
+            .or_lenient("Bet.outcome is not a string")
+    }
+    pub fn answer_id(&self) -> Option<&str> {
+        self.0["answerId"].as_str()
+    }
+    pub fn created_time(&self) -> i64 {
+        self.0["createdTime"]
+            .as_i64()
+            .or_lenient("Bet.createdTime is not a number")
+    }
+    /// Mana spent, negative for sales.
+    pub fn amount(&self) -> f64 {
+        number_f64(&self.0["amount"]).or_lenient("Bet.amount is not a number")
+    }
+    pub fn amount_exact(&self) -> String {
+        number_text(&self.0["amount"]).or_lenient("Bet.amount is not a number")
+    }
+    pub fn shares(&self) -> f64 {
+        number_f64(&self.0["shares"]).or_lenient("Bet.shares is not a number")
+    }
+    pub fn shares_exact(&self) -> String {
+        number_text(&self.0["shares"]).or_lenient("Bet.shares is not a number")
+    }
+    pub fn prob_before(&self) -> f64 {
+        number_f64(&self.0["probBefore"]).or_lenient("Bet.probBefore is not a number")
+    }
+    pub fn prob_after(&self) -> f64 {
+        number_f64(&self.0["probAfter"]).or_lenient("Bet.probAfter is not a number")
+    }
+    /// Only set for limit orders.
+    pub fn limit_prob(&self) -> Option<f64> {
+        number_f64(&self.0["limitProb"])
+    }
+    pub fn is_filled(&self) -> bool {
+        self.0["isFilled"].as_bool().unwrap_or(true)
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0["isCancelled"].as_bool().unwrap_or(false)
+    }
+    /// Creator, platform and liquidity fees paid, in total.
+    pub fn fees(&self) -> f64 {
+        self.0["fees"]
+            .as_object()
+            .map(|fees| fees.values().filter_map(number_f64).sum())
+            .unwrap_or(0.0)
+    }
+    pub fn try_id(&self) -> Result<&str> {
+        try_str(&self.0, "id", "Bet")
+    }
+    pub fn try_contract_id(&self) -> Result<&str> {
+        try_str(&self.0, "contractId", "Bet")
+    }
+    pub fn try_user_id(&self) -> Result<&str> {
+        try_str(&self.0, "userId", "Bet")
+    }
+    pub fn try_outcome(&self) -> Result<&str> {
+        try_str(&self.0, "outcome", "Bet")
+    }
+    pub fn try_created_time(&self) -> Result<i64> {
+        try_i64(&self.0, "createdTime", "Bet")
+    }
+    pub fn try_amount(&self) -> Result<f64> {
+        try_f64(&self.0, "amount", "Bet")
+    }
+    pub fn try_shares(&self) -> Result<f64> {
+        try_f64(&self.0, "shares", "Bet")
+    }
+    pub fn try_prob_before(&self) -> Result<f64> {
+        try_f64(&self.0, "probBefore", "Bet")
+    }
+    pub fn try_prob_after(&self) -> Result<f64> {
+        try_f64(&self.0, "probAfter", "Bet")
+    }
+}
+
+/// A user's position in a market (a "contract metric").
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Position(pub Value);
+
+/// What the API calls a [`Position`].
+pub type ContractMetric = Position;
+
+impl Position {
+    pub fn contract_id(&self) -> &str {
+        self.0["contractId"]
+            .as_str()
+            .or_lenient("Position.contractId is not a string")
+    }
+    pub fn user_id(&self) -> &str {
+        self.0["userId"]
+            .as_str()
+            .or_lenient("Position.userId is not a string")
+    }
+    /// The answer of a multiple choice market the position is in, if any.
+    pub fn answer_id(&self) -> Option<&str> {
+        self.0["answerId"].as_str()
+    }
+    pub fn has_shares(&self) -> bool {
+        self.0["hasShares"].as_bool().unwrap_or(false)
+    }
+    /// Shares held of each outcome, keyed by `YES`/`NO` or answer id.
+    pub fn total_shares(&self) -> HashMap<String, f64> {
+        self.0["totalShares"]
+            .as_object()
+            .map(|shares| {
+                shares
+                    .iter()
+                    .filter_map(|(outcome, n)| Some((outcome.clone(), number_f64(n)?)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    /// Shares held of `outcome` (`YES`/`NO` or an answer id), zero if none.
+    pub fn shares(&self, outcome: &str) -> f64 {
+        number_f64(&self.0["totalShares"][outcome]).unwrap_or(0.0)
+    }
+    /// Mana currently invested in the position.
+    pub fn invested(&self) -> f64 {
+        number_f64(&self.0["invested"]).unwrap_or(0.0)
+    }
+    pub fn profit(&self) -> f64 {
+        number_f64(&self.0["profit"]).unwrap_or(0.0)
+    }
+    /// What the position is worth at the current probability.
+    pub fn payout(&self) -> f64 {
+        number_f64(&self.0["payout"]).unwrap_or(0.0)
+    }
+
+    pub fn try_contract_id(&self) -> Result<&str> {
+        try_str(&self.0, "contractId", "Position")
+    }
+    pub fn try_user_id(&self) -> Result<&str> {
+        try_str(&self.0, "userId", "Position")
+    }
+    pub fn try_invested(&self) -> Result<f64> {
+        try_f64(&self.0, "invested", "Position")
+    }
+    pub fn try_profit(&self) -> Result<f64> {
+        try_f64(&self.0, "profit", "Position")
+    }
+}
+
+/// Smallest amount Manifold lets a managram send to each recipient.
+pub const MIN_MANAGRAM: u64 = 10;
+
+/// A mana transaction: bonuses, payouts, subsidies, tips, etc.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Txn(pub Value);
+
+impl Txn {
+    pub fn id(&self) -> &str {
+        self.0["id"].as_str().or_lenient("Txn.id is not a string")
+    }
+    pub fn created_time(&self) -> i64 {
+        self.0["createdTime"]
+            .as_i64()
+            .or_lenient("Txn.createdTime is not a number")
+    }
+    pub fn from_id(&self) -> &str {
+        self.0["fromId"]
+            .as_str()
+            .or_lenient("Txn.fromId is not a string")
+    }
+    /// `USER`, `CONTRACT`, `BANK`, ...
+    pub fn from_type(&self) -> &str {
+        self.0["fromType"]
+            .as_str()
+            .or_lenient("Txn.fromType is not a string")
+    }
+    pub fn to_id(&self) -> &str {
+        self.0["toId"]
+            .as_str()
+            .or_lenient("Txn.toId is not a string")
+    }
+    pub fn to_type(&self) -> &str {
+        self.0["toType"]
+            .as_str()
+            .or_lenient("Txn.toType is not a string")
+    }
+    pub fn amount(&self) -> f64 {
+        number_f64(&self.0["amount"]).or_lenient("Txn.amount is not a number")
+    }
+    pub fn amount_exact(&self) -> String {
+        number_text(&self.0["amount"]).or_lenient("Txn.amount is not a number")
+    }
+    /// `M$` for mana; other tokens exist for some transaction kinds.
+    pub fn token(&self) -> &str {
+        self.0["token"].as_str().unwrap_or("M$")
+    }
+    /// `ADD_SUBSIDY`, `CONTRACT_RESOLUTION_PAYOUT`, `UNIQUE_BETTOR_BONUS`, ...
+    pub fn category(&self) -> &str {
+        self.0["category"]
+            .as_str()
+            .or_lenient("Txn.category is not a string")
+    }
+    /// The market a transaction is about, for bonuses, fees and payouts.
+    ///
+    /// From `data.contractId`, or the sender or receiver if that's a market.
+    pub fn contract_id(&self) -> Option<&str> {
+        if let Some(id) = self.0["data"]["contractId"].as_str() {
+            return Some(id);
+        }
+
+        if self.0["fromType"] == "CONTRACT" {
+            self.0["fromId"].as_str()
+        } else if self.0["toType"] == "CONTRACT" {
+            self.0["toId"].as_str()
+        } else {
+            None
+        }
+    }
+    pub fn try_id(&self) -> Result<&str> {
+        try_str(&self.0, "id", "Txn")
+    }
+    pub fn try_created_time(&self) -> Result<i64> {
+        try_i64(&self.0, "createdTime", "Txn")
+    }
+    pub fn try_from_id(&self) -> Result<&str> {
+        try_str(&self.0, "fromId", "Txn")
+    }
+    pub fn try_to_id(&self) -> Result<&str> {
+        try_str(&self.0, "toId", "Txn")
+    }
+    pub fn try_amount(&self) -> Result<f64> {
+        try_f64(&self.0, "amount", "Txn")
+    }
+    pub fn try_category(&self) -> Result<&str> {
+        try_str(&self.0, "category", "Txn")
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Comment(pub Value);
+
+impl Comment {
+    pub fn id(&self) -> &str {
+        self.0["id"]
+            .as_str()
+            .or_lenient("Comment.id is not a string")
+    }
+    pub fn contract_id(&self) -> &str {
+        self.0["contractId"]
+            .as_str()
+            .or_lenient("Comment.contractId is not a string")
+    }
+    pub fn user_id(&self) -> &str {
+        self.0["userId"]
+            .as_str()
+            .or_lenient("Comment.userId is not a string")
+    }
+    pub fn user_username(&self) -> &str {
+        self.0["userUsername"]
+            .as_str()
+            .or_lenient("Comment.userUsername is not a string")
+    }
+    pub fn created_time(&self) -> i64 {
+        self.0["createdTime"]
+            .as_i64()
+            .or_lenient("Comment.createdTime is not a number")
+    }
+    /// The comment as TipTap JSON; `Null` for legacy comments, which only have `text`.
+    pub fn content(&self) -> &Value {
+        &self.0["content"]
+    }
+    /// The plain text of the comment, see [`comment_text`](crate::analysis::comment_text).
+    pub fn text(&self) -> String {
+        crate::analysis::comment_text(&self.0)
+    }
+    /// The comment this one replies to.
+    pub fn reply_to_comment_id(&self) -> Option<&str> {
+        self.0["replyToCommentId"].as_str()
+    }
+    pub fn try_id(&self) -> Result<&str> {
+        try_str(&self.0, "id", "Comment")
+    }
+    pub fn try_contract_id(&self) -> Result<&str> {
+        try_str(&self.0, "contractId", "Comment")
+    }
+    pub fn try_user_id(&self) -> Result<&str> {
+        try_str(&self.0, "userId", "Comment")
+    }
+    pub fn try_created_time(&self) -> Result<i64> {
+        try_i64(&self.0, "createdTime", "Comment")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trading::BetEncoding;
+
+    #[test]
+    fn try_accessors_never_panic() {
+        let garbage = [
+            json!(null),
+            json!("market"),
+            json!([1, 2]),
+            json!({"id": 1, "question": null, "outcomeType": "BINARY", "pool": {"MAYBE": 1}}),
+            json!({"closeTime": 1e300, "amount": "10", "createdTime": -1.5}),
+        ];
+
+        for value in garbage {
+            let market = FullMarket(value.clone());
+            assert!(market.try_id().is_err());
+            assert!(market.try_question().is_err());
+            assert!(market.try_pool().is_err());
+            let _ = market.try_close_time();
+            let _ = market.try_outcome_type();
+
+            let bet = Bet(value.clone());
+            assert!(bet.try_amount().is_err());
+            assert!(bet.try_created_time().is_err());
+
+            assert!(User(value.clone()).try_balance().is_err());
+            assert!(Answer(value).try_text().is_err());
+        }
+
+        let market = FullMarket(json!({"id": "m", "question": "?", "outcomeType": "BINARY",
+            "pool": {"YES": 1.0, "NO": 2.0}, "closeTime": 1700000000000i64}));
+        assert_eq!(market.try_id().unwrap(), "m");
+        assert_eq!(market.try_pool().unwrap().len(), 2);
+        assert!(market.try_close_time().unwrap().is_some());
+    }
+
+    #[test]
+    fn lenient_accessors_return_defaults() {
+        let user = User(json!({"id": "u", "balance": "lots"}));
+
+        with_lenient_accessors(true, || {
+            let before = lenient_fallbacks();
+            assert_eq!(user.balance(), 0.0);
+            assert_eq!(user.name(), "");
+            assert_eq!(user.id(), "u");
+            assert_eq!(lenient_fallbacks() - before, 2);
+        });
+
+        assert!(std::panic::catch_unwind(|| user.balance()).is_err());
+
+        let unknown = FullMarket(json!({"id": "m", "outcomeType": "SOMETHING_NEW"}));
+        assert!(with_lenient_accessors(true, || {
+            std::panic::catch_unwind(|| unknown.outcome_type()).is_err()
+        }));
+        assert!(BetEncoding::of(&unknown).is_err());
+    }
+
+    #[test]
+    fn unknown_visibilities_are_not_public() {
+        let market = |visibility: Value| FullMarket(json!({"id": "m", "visibility": visibility}));
+
+        assert_eq!(market(Value::Null).visibility(), Visibility::Public);
+        assert_eq!(market(json!("private")).visibility(), Visibility::Private);
+        assert_eq!(market(json!("hidden")).visibility(), Visibility::Unlisted);
+        assert!(!market(json!(7)).is_public());
+    }
+
+    #[test]
+    fn resolution_bodies() -> Result<()> {
+        assert_eq!(
+            Resolution::Mkt(70).body()?,
+            json!({"outcome": "MKT", "probabilityInt": 70})
+        );
+        assert_eq!(Resolution::Answer(2).body()?, json!({"outcome": 2}));
+        assert_eq!(
+            Resolution::Weighted(vec![(0, 60), (1, 40)]).body()?,
+            json!({"outcome": "MKT", "resolutions": [{"answer": 0, "pct": 60}, {"answer": 1, "pct": 40}]})
+        );
+
+        assert!(Resolution::Mkt(101).body().is_err());
+
+        assert_eq!(
+            AnswerResolution::Mkt(70).body("a")?,
+            json!({"answerId": "a", "outcome": "MKT", "probabilityInt": 70})
+        );
+        assert_eq!(
+            AnswerResolution::Cancel.body("a")?,
+            json!({"answerId": "a", "outcome": "CANCEL"})
+        );
+        assert!(matches!(
+            AnswerResolution::Mkt(101).body("a"),
+            Err(ManifoldError::Validation(_))
+        ));
+        assert!(Resolution::Weighted(vec![(0, 60), (1, 30)]).body().is_err());
+        assert!(Resolution::Weighted(vec![]).body().is_err());
+
+        let numeric = FullMarket(json!({"min": 0, "max": 200, "isLogScale": false}));
+        assert_eq!(
+            Resolution::numeric(&numeric, 50.0)?.body()?,
+            json!({"outcome": "MKT", "value": 50.0, "probabilityInt": 25.0})
+        );
+        assert!(Resolution::numeric(&numeric, 250.0).is_err());
+
+        let log = FullMarket(json!({"min": 0, "max": 999, "isLogScale": true}));
+        let Resolution::Numeric {
+            probability_int, ..
+        } = Resolution::numeric(&log, 9.0)?
+        else {
+            unreachable!()
+        };
+        assert!((probability_int - 100.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(Resolution::mkt(0.634)?, Resolution::Mkt(63));
+        assert!(Resolution::mkt(63.0).is_err());
+
+        let scale = NumericScale::of(&log)?;
+        for value in [0.0, 9.0, 99.0, 500.0, 999.0] {
+            let probability = scale.probability(value)?;
+            assert!((scale.value(probability)? - value).abs() < 1e-9);
+        }
+        assert!(scale.value(1.5).is_err());
+        assert_eq!(
+            Resolution::numeric_at(&numeric, 0.25)?,
+            Resolution::numeric(&numeric, 50.0)?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+//! A local list of watched markets
+//!
+//! The API doesn't expose the markets a user follows, so a [`Watchlist`]
+//! keeps its own list, persisted through a [`Storage`]. It can be polled
+//! with [`Watchlist::watch`], which goes through
+//! [`ManifoldClient::watch_markets`].
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::freshness::Fetched;
+use crate::storage::Storage;
+use crate::types::*;
+use crate::ManifoldClient;
+
+/// Where [`Watchlist`]s are saved unless given another key.
+pub const WATCHLIST_KEY: &str = "watchlist";
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedMarket {
+    pub market_id: String,
+    pub added_time: DateTime<Utc>,
+    pub note: Option<String>,
+}
+
+/// Markets being watched, in the order they were added.
+#[derive(Debug)]
+pub struct Watchlist<S: Storage> {
+    storage: S,
+    key: String,
+    markets: Vec<WatchedMarket>,
+    /// `markets`' ids, for [`ManifoldClient::watch_markets`].
+    ids: Vec<String>,
+}
+
+impl<S: Storage> Watchlist<S> {
+    /// Loads the watchlist saved under `key`, if any.
+    pub fn load(storage: S, key: &str) -> Result<Watchlist<S>> {
+        let markets: Vec<WatchedMarket> = storage.load_as(key)?.unwrap_or_default();
+        let ids = markets.iter().map(|m| m.market_id.clone()).collect();
+
+        Ok(Watchlist {
+            storage,
+            key: key.to_owned(),
+            markets,
+            ids,
+        })
+    }
+
+    fn save(&mut self) -> Result<()> {
+        self.ids = self.markets.iter().map(|m| m.market_id.clone()).collect();
+        self.storage.save_as(&self.key, &self.markets)
+    }
+
+    pub fn list(&self) -> &[WatchedMarket] {
+        &self.markets
+    }
+
+    pub fn market_ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    pub fn contains(&self, market_id: &str) -> bool {
+        self.ids.iter().any(|id| id == market_id)
+    }
+
+    /// Adds a market, unless it's already watched. Returns whether it was added.
+    pub fn add(&mut self, market_id: &str, note: Option<&str>) -> Result<bool> {
+        if self.contains(market_id) {
+            return Ok(false);
+        }
+
+        self.markets.push(WatchedMarket {
+            market_id: market_id.to_owned(),
+            added_time: Utc::now(),
+            note: note.map(str::to_owned),
+        });
+        self.save()?;
+
+        Ok(true)
+    }
+
+    pub fn remove(&mut self, market_id: &str) -> Result<Option<WatchedMarket>> {
+        let Some(index) = self.markets.iter().position(|m| m.market_id == market_id) else {
+            return Ok(None);
+        };
+
+        let market = self.markets.remove(index);
+        self.save()?;
+
+        Ok(Some(market))
+    }
+
+    /// Fetches all watched markets, one after the other.
+    pub async fn fetch(&self, client: &ManifoldClient) -> Result<Vec<FullMarket>> {
+        let mut markets = vec![];
+
+        for id in &self.ids {
+            markets.push(client.get_market(id).await?);
+        }
+
+        Ok(markets)
+    }
+
+    /// Polls the watched markets, see [`ManifoldClient::watch_markets`].
+    ///
+    /// Uses the list as it is when called; changes show up on the next call.
+    pub fn watch<'a>(
+        &'a self,
+        client: &'a ManifoldClient,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<Fetched<FullMarket>>> + 'a {
+        client.watch_markets(&self.ids, interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::MemoryStorage;
+
+    use super::*;
+
+    #[test]
+    fn adds_and_removes_markets() -> Result<()> {
+        let storage = MemoryStorage::default();
+
+        let mut watchlist = Watchlist::load(&storage, WATCHLIST_KEY)?;
+        assert!(watchlist.add("a", Some("election"))?);
+        assert!(watchlist.add("b", None)?);
+        assert!(!watchlist.add("a", None)?);
+
+        let reloaded = Watchlist::load(&storage, WATCHLIST_KEY)?;
+        assert_eq!(reloaded.market_ids(), ["a", "b"]);
+        assert_eq!(reloaded.list()[0].note.as_deref(), Some("election"));
+
+        assert!(watchlist.remove("a")?.is_some());
+        assert!(watchlist.remove("a")?.is_none());
+        assert_eq!(
+            Watchlist::load(&storage, WATCHLIST_KEY)?.market_ids(),
+            ["b"]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+//! Replays the API payloads in `tests/corpus` through the typed accessors
+//! and the [`schema`] structs, offline.
+//!
+//! Each file holds a JSON array of one kind of object, named after it:
+//! `markets.json`, `bets.json`, `users.json`, `groups.json`,
+//! `comments.json` or `txns.json`. New files and new payloads are picked up
+//! as they're added; markets go through [`migrate_market`] first, so
+//! archived `dpm-2` markets belong in the corpus too.
+//!
+//! Every check runs on every payload, and the test fails with the full list
+//! of what didn't conform rather than at the first failure.
+
+use std::fmt::Display;
+use std::path::Path;
+
+use manifold_markets::answers::AnswerProbabilities;
+use manifold_markets::migrate::migrate_market;
+use manifold_markets::schema;
+use manifold_markets::types::*;
+use serde_json::Value;
+
+/// Where a payload came from, for failure messages.
+struct Payload<'a> {
+    file: &'a str,
+    index: usize,
+    data: &'a Value,
+}
+
+impl Payload<'_> {
+    fn check<T, E: Display>(
+        &self,
+        failures: &mut Vec<String>,
+        name: &str,
+        result: std::result::Result<T, E>,
+    ) -> Option<T> {
+        result
+            .map_err(|e| {
+                failures.push(format!(
+                    "{}[{}] ({}): {name}: {e}",
+                    self.file,
+                    self.index,
+                    self.data["id"].as_str().unwrap_or("no id"),
+                ))
+            })
+            .ok()
+    }
+}
+
+/// Parses into `S` and back, and fails unless nothing was lost on the way.
+fn round_trip<W, S>(wrapper: &W) -> Result<(), String>
+where
+    S: for<'a> TryFrom<&'a W, Error = manifold_markets::error::ManifoldError>
+        + Into<W>
+        + PartialEq
+        + Clone
+        + std::fmt::Debug,
+{
+    let typed = S::try_from(wrapper).map_err(|e| e.to_string())?;
+    let again = S::try_from(&typed.clone().into()).map_err(|e| e.to_string())?;
+
+    if typed == again {
+        Ok(())
+    } else {
+        Err(format!("{typed:?} came back as {again:?}"))
+    }
+}
+
+fn check_market(payload: &Payload, failures: &mut Vec<String>) {
+    let Some(market) = payload.check(
+        failures,
+        "migrate_market",
+        migrate_market(payload.data.clone()),
+    ) else {
+        return;
+    };
+
+    payload.check(failures, "try_id", market.try_id());
+    payload.check(failures, "try_question", market.try_question());
+    let outcome_type = payload.check(failures, "try_outcome_type", market.try_outcome_type());
+
+    match outcome_type {
+        Some(OutcomeType::Binary | OutcomeType::PseudoNumeric | OutcomeType::Stonk) => {
+            payload.check(
+                failures,
+                "probability",
+                market.probability().ok_or("missing"),
+            );
+            payload.check(failures, "try_pool", market.try_pool());
+        }
+        Some(OutcomeType::FreeResponse | OutcomeType::MultipleChoice | OutcomeType::Number) => {
+            let answers = market.answers();
+            if answers.is_empty() {
+                payload.check(failures, "answers", Err::<(), _>("no answers"));
+            }
+
+            for answer in &answers {
+                payload.check(failures, "Answer.try_id", answer.try_id());
+                payload.check(failures, "Answer.try_text", answer.try_text());
+            }
+
+            let probabilities = AnswerProbabilities::from_market(&market);
+            for answer in &answers {
+                let prob = answer
+                    .try_id()
+                    .ok()
+                    .and_then(|id| probabilities.get(id))
+                    .filter(|prob| (0.0..=1.0).contains(prob));
+                payload.check(failures, "AnswerProbabilities", prob.ok_or("missing"));
+            }
+        }
+        Some(OutcomeType::Poll) => {
+            if market.data()["options"]
+                .as_array()
+                .is_none_or(Vec::is_empty)
+            {
+                payload.check(failures, "options", Err::<(), _>("no options"));
+            }
+            payload.check(failures, "try_pool", market.try_pool());
+        }
+        Some(OutcomeType::BountiedQuestion) => {
+            payload.check(failures, "try_pool", market.try_pool());
+        }
+        Some(OutcomeType::Unknown(_)) | None => {}
+    }
+
+    payload.check(
+        failures,
+        "schema::FullMarket",
+        round_trip::<_, schema::FullMarket>(&market),
+    );
+}
+
+fn check_bet(payload: &Payload, failures: &mut Vec<String>) {
+    let bet = Bet(payload.data.clone());
+
+    payload.check(failures, "try_id", bet.try_id());
+    payload.check(failures, "try_contract_id", bet.try_contract_id());
+    payload.check(failures, "try_user_id", bet.try_user_id());
+    payload.check(failures, "try_outcome", bet.try_outcome());
+    payload.check(failures, "try_created_time", bet.try_created_time());
+    payload.check(failures, "try_amount", bet.try_amount());
+    payload.check(failures, "try_shares", bet.try_shares());
+    payload.check(failures, "schema::Bet", round_trip::<_, schema::Bet>(&bet));
+}
+
+fn check_user(payload: &Payload, failures: &mut Vec<String>) {
+    let user = User(payload.data.clone());
+
+    payload.check(failures, "try_id", user.try_id());
+    payload.check(failures, "try_name", user.try_name());
+    payload.check(failures, "try_username", user.try_username());
+    payload.check(failures, "try_created_time", user.try_created_time());
+    payload.check(failures, "try_balance", user.try_balance());
+    payload.check(
+        failures,
+        "schema::User",
+        round_trip::<_, schema::User>(&user),
+    );
+}
+
+fn check_group(payload: &Payload, failures: &mut Vec<String>) {
+    let group = Group(payload.data.clone());
+
+    payload.check(failures, "try_id", group.try_id());
+    payload.check(failures, "try_slug", group.try_slug());
+    payload.check(failures, "try_name", group.try_name());
+    payload.check(
+        failures,
+        "schema::Group",
+        round_trip::<_, schema::Group>(&group),
+    );
+}
+
+fn check_comment(payload: &Payload, failures: &mut Vec<String>) {
+    let comment = Comment(payload.data.clone());
+
+    payload.check(failures, "try_id", comment.try_id());
+    payload.check(failures, "try_contract_id", comment.try_contract_id());
+    payload.check(failures, "try_user_id", comment.try_user_id());
+    payload.check(failures, "try_created_time", comment.try_created_time());
+
+    if comment.text().trim().is_empty() {
+        payload.check(failures, "text", Err::<(), _>("empty"));
+    }
+}
+
+fn check_txn(payload: &Payload, failures: &mut Vec<String>) {
+    let txn = Txn(payload.data.clone());
+
+    payload.check(failures, "try_id", txn.try_id());
+    payload.check(failures, "try_created_time", txn.try_created_time());
+    payload.check(failures, "try_from_id", txn.try_from_id());
+    payload.check(failures, "try_to_id", txn.try_to_id());
+    payload.check(failures, "try_amount", txn.try_amount());
+    payload.check(failures, "try_category", txn.try_category());
+}
+
+#[test]
+fn corpus_conforms() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut failures = vec![];
+    let mut payloads = 0;
+
+    let mut files = std::fs::read_dir(&corpus)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    files.sort();
+
+    for path in &files {
+        let file = path.file_name().unwrap().to_str().unwrap();
+        let check = match path.file_stem().unwrap().to_str().unwrap() {
+            "markets" => check_market,
+            "bets" => check_bet,
+            "users" => check_user,
+            "groups" => check_group,
+            "comments" => check_comment,
+            "txns" => check_txn,
+            other => panic!("{file}: no checks for {other:?}"),
+        };
+
+        let data: Vec<Value> = serde_json::from_str(&std::fs::read_to_string(path).unwrap())
+            .unwrap_or_else(|e| panic!("{file} is not a JSON array: {e}"));
+
+        for (index, data) in data.iter().enumerate() {
+            check(&Payload { file, index, data }, &mut failures);
+        }
+        payloads += data.len();
+    }
+
+    assert!(payloads > 0, "no payloads in {}", corpus.display());
+    assert!(
+        failures.is_empty(),
+        "{} of {payloads} payloads don't conform:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}